@@ -240,6 +240,24 @@ impl FileAnalyzer {
         Ok(self.results_as_vec())
     }
     
+    /// Analyze text that is already in memory, such as data read from the network
+    ///
+    /// Applies the same pattern and entropy checks as `analyze_file`, without
+    /// adding file metadata. Results accumulate across calls like
+    /// `analyze_file`'s; call `reset_results` between unrelated inputs.
+    ///
+    /// # Arguments
+    ///
+    /// * `content` - Text to analyze
+    ///
+    /// # Returns
+    ///
+    /// Analysis results organized by category
+    pub fn analyze_text(&mut self, content: &str) -> Result<Vec<(String, HashSet<String>)>> {
+        self.process_patterns(content)?;
+        Ok(self.results_as_vec())
+    }
+    
     /// Convert results hashmap to a vector of (category, values) tuples
     ///
     /// # Returns
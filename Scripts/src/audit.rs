@@ -0,0 +1,582 @@
+//! Tamper-evident audit trail of outbound probes
+//!
+//! Every probe the scanner sends is appended to a JSON-lines file: when it
+//! was sent, from and to which address, the protocol, TCP flags where they
+//! apply, and the length and SHA-256 of the payload. Each entry carries the
+//! hash of the entry before it and a hash over its own fields, so editing,
+//! reordering or deleting entries breaks the chain. A scan ends its session
+//! with a seal entry; a log that doesn't end in one was cut short.
+//!
+//! A log belongs to the scanner writing it: it travels in the scanner's
+//! `scope::Egress`, so scans running side by side keep separate trails.
+//! Connection-based probes are recorded by `AuditedStream` and
+//! `AuditedUdpSocket`, which `Egress::connect_tcp` / `connect_udp` hand out;
+//! raw-socket techniques call `Egress::record_probe` for each packet they
+//! transmit.
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::{TcpStream, UdpSocket};
+
+/// `prev_hash` of the first entry in a log
+const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// Protocol recorded for the entry that closes a session
+const SEAL_PROTOCOL: &str = "SEAL";
+
+/// One line of the audit log
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    /// Position in the log, starting at 0
+    pub seq: u64,
+    /// When the probe was sent
+    pub time: DateTime<Utc>,
+    /// Local address the probe was sent from
+    pub source: String,
+    /// Address the probe was sent to
+    pub destination: String,
+    /// TCP, UDP, ICMP, ... (SEAL for the end of a session)
+    pub protocol: String,
+    /// TCP flags or probe kind, where known
+    pub flags: Option<String>,
+    /// Payload length in bytes
+    pub payload_len: usize,
+    /// SHA-256 of the payload, hex encoded
+    pub payload_sha256: String,
+    /// Hash of the previous entry
+    pub prev_hash: String,
+    /// Hash over this entry's fields, `prev_hash` included
+    pub hash: String,
+}
+
+impl AuditEntry {
+    /// Compute the chain hash for this entry's fields
+    fn compute_hash(&self) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(format!(
+            "{}|{}|{}|{}|{}|{}|{}|{}|{}",
+            self.seq,
+            self.time.to_rfc3339_opts(SecondsFormat::Nanos, true),
+            self.source,
+            self.destination,
+            self.protocol,
+            self.flags.as_deref().unwrap_or(""),
+            self.payload_len,
+            self.payload_sha256,
+            self.prev_hash,
+        ));
+        format!("{:x}", hasher.finalize())
+    }
+
+    /// Whether this entry closes a session
+    pub fn is_seal(&self) -> bool {
+        self.protocol == SEAL_PROTOCOL
+    }
+}
+
+/// Problems found while verifying an audit log
+#[derive(Debug, thiserror::Error)]
+pub enum AuditError {
+    /// The log could not be read or written
+    #[error("Failed to access audit log: {0}")]
+    Io(#[from] io::Error),
+
+    /// A line isn't a valid entry
+    #[error("Line {line}: not a valid audit entry ({reason})")]
+    Malformed {
+        /// 1-based line number
+        line: usize,
+        /// Parser error message
+        reason: String,
+    },
+
+    /// Entries are missing, duplicated or out of order
+    #[error("Line {line}: expected entry {expected}, found entry {found}")]
+    Sequence {
+        /// 1-based line number
+        line: usize,
+        /// Sequence number that should be there
+        expected: u64,
+        /// Sequence number that is there
+        found: u64,
+    },
+
+    /// The entry doesn't point at the one before it
+    #[error("Line {line}: chain broken, previous hash does not match entry {}", .line - 1)]
+    BrokenChain {
+        /// 1-based line number
+        line: usize,
+    },
+
+    /// The entry's fields don't match its hash
+    #[error("Line {line}: entry has been modified (hash mismatch)")]
+    Modified {
+        /// 1-based line number
+        line: usize,
+    },
+
+    /// The log doesn't end with a seal entry
+    #[error("Log ends after {entries} entries without a seal (truncated, or the scan was interrupted)")]
+    Unsealed {
+        /// Entries that verified correctly
+        entries: u64,
+    },
+
+    /// The last hash differs from the one recorded when the scan finished
+    #[error("Log ends in hash {found}, expected {expected}")]
+    HeadMismatch {
+        /// Hash the caller expected
+        expected: String,
+        /// Hash the log ends in
+        found: String,
+    },
+}
+
+/// Result of a successful verification
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditSummary {
+    /// Entries in the log, seals included
+    pub entries: u64,
+    /// Probe entries (seals excluded)
+    pub probes: u64,
+    /// Sessions closed with a seal
+    pub sessions: u64,
+    /// Time of the first entry
+    pub first: Option<DateTime<Utc>>,
+    /// Time of the last entry
+    pub last: Option<DateTime<Utc>>,
+    /// Hash of the last entry
+    pub head: String,
+}
+
+/// Append-only, hash-chained probe log
+#[derive(Debug)]
+pub struct AuditLog {
+    path: PathBuf,
+    state: Mutex<ChainState>,
+}
+
+#[derive(Debug)]
+struct ChainState {
+    file: File,
+    next_seq: u64,
+    head: String,
+    failed: u64,
+    last_error: Option<String>,
+}
+
+impl AuditLog {
+    /// Open `path` for appending, continuing its chain if it already has entries
+    ///
+    /// An existing log is verified first; a broken chain is never extended.
+    /// A log left unsealed by an interrupted scan is continued as it is.
+    pub fn open(path: &Path) -> Result<Self, AuditError> {
+        let (next_seq, head) = if path.exists() {
+            match walk(path) {
+                Ok(summary) | Err((AuditError::Unsealed { .. }, Some(summary))) => (summary.entries, summary.head),
+                Err((e, _)) => return Err(e),
+            }
+        } else {
+            (0, GENESIS_HASH.to_string())
+        };
+
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            state: Mutex::new(ChainState { file, next_seq, head, failed: 0, last_error: None }),
+        })
+    }
+
+    /// File the log is written to
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Hash of the newest entry
+    pub fn head(&self) -> String {
+        self.state.lock().head.clone()
+    }
+
+    /// Entries that could not be written, and the last error, if any failed
+    pub fn failed_writes(&self) -> Option<(u64, String)> {
+        let state = self.state.lock();
+        state.last_error.clone().map(|error| (state.failed, error))
+    }
+
+    /// Append one probe
+    ///
+    /// A failed write is also remembered for `failed_writes`.
+    pub fn record(&self, source: &str, destination: &str, protocol: &str, flags: Option<&str>, payload: &[u8]) -> io::Result<()> {
+        let mut state = self.state.lock();
+        let written = Self::append(&mut state, source, destination, protocol, flags, payload);
+        if let Err(e) = &written {
+            state.failed += 1;
+            state.last_error = Some(e.to_string());
+        }
+        written
+    }
+
+    fn append(state: &mut ChainState, source: &str, destination: &str, protocol: &str, flags: Option<&str>, payload: &[u8]) -> io::Result<()> {
+        let mut entry = AuditEntry {
+            seq: state.next_seq,
+            time: Utc::now(),
+            source: source.to_string(),
+            destination: destination.to_string(),
+            protocol: protocol.to_string(),
+            flags: flags.map(str::to_string),
+            payload_len: payload.len(),
+            payload_sha256: format!("{:x}", Sha256::digest(payload)),
+            prev_hash: state.head.clone(),
+            hash: String::new(),
+        };
+        entry.hash = entry.compute_hash();
+
+        let mut line = serde_json::to_vec(&entry)?;
+        line.push(b'\n');
+        state.file.write_all(&line)?;
+        state.next_seq += 1;
+        state.head = entry.hash;
+        Ok(())
+    }
+
+    /// Close the session with a seal entry and flush the log to disk
+    ///
+    /// Returns the final hash, which is worth keeping somewhere the log
+    /// itself can't be edited (the report, a ticket) for `verify --head`.
+    pub fn seal(&self) -> io::Result<String> {
+        self.record("-", "-", SEAL_PROTOCOL, None, &[])?;
+        let state = self.state.lock();
+        state.file.sync_all()?;
+        Ok(state.head.clone())
+    }
+}
+
+/// Check every link of the chain in `path`
+///
+/// Fails on the first malformed, modified, missing or reordered entry, and
+/// when the log doesn't end with a seal. With `head`, the final hash must
+/// also match it, which catches a log that was cut and re-sealed.
+pub fn verify(path: &Path, head: Option<&str>) -> Result<AuditSummary, AuditError> {
+    let summary = walk(path).map_err(|(e, _)| e)?;
+    if let Some(expected) = head {
+        if !summary.head.eq_ignore_ascii_case(expected.trim()) {
+            return Err(AuditError::HeadMismatch {
+                expected: expected.trim().to_string(),
+                found: summary.head,
+            });
+        }
+    }
+    Ok(summary)
+}
+
+/// Walk the chain; on an unsealed log the summary of the valid part is returned too
+fn walk(path: &Path) -> Result<AuditSummary, (AuditError, Option<AuditSummary>)> {
+    let file = File::open(path).map_err(|e| (AuditError::Io(e), None))?;
+    let mut summary = AuditSummary {
+        entries: 0,
+        probes: 0,
+        sessions: 0,
+        first: None,
+        last: None,
+        head: GENESIS_HASH.to_string(),
+    };
+    let mut sealed = true;
+
+    for (index, line) in BufReader::new(file).lines().enumerate() {
+        let line_no = index + 1;
+        let line = line.map_err(|e| (AuditError::Io(e), None))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let entry: AuditEntry = serde_json::from_str(&line)
+            .map_err(|e| (AuditError::Malformed { line: line_no, reason: e.to_string() }, None))?;
+
+        if entry.seq != summary.entries {
+            return Err((AuditError::Sequence { line: line_no, expected: summary.entries, found: entry.seq }, None));
+        }
+        if entry.prev_hash != summary.head {
+            return Err((AuditError::BrokenChain { line: line_no }, None));
+        }
+        if entry.compute_hash() != entry.hash {
+            return Err((AuditError::Modified { line: line_no }, None));
+        }
+
+        summary.entries += 1;
+        if entry.is_seal() {
+            summary.sessions += 1;
+        } else {
+            summary.probes += 1;
+        }
+        sealed = entry.is_seal();
+        summary.first.get_or_insert(entry.time);
+        summary.last = Some(entry.time);
+        summary.head = entry.hash;
+    }
+
+    if sealed || summary.entries == 0 {
+        Ok(summary)
+    } else {
+        Err((AuditError::Unsealed { entries: summary.entries }, Some(summary)))
+    }
+}
+
+/// Record an outbound probe in `log`, if there is one
+///
+/// Failing to write the audit trail doesn't abort the probe; the log keeps
+/// count for `AuditLog::failed_writes`, and the gap shows up as an unsealed
+/// or broken log when it is verified.
+pub fn record_probe(log: Option<&AuditLog>, source: &str, destination: &str, protocol: &str, flags: Option<&str>, payload: &[u8]) {
+    if let Some(log) = log {
+        let _ = log.record(source, destination, protocol, flags, payload);
+    }
+}
+
+/// TCP connection whose outbound data is recorded in the audit log
+///
+/// Writes are recorded as they reach the socket, so for TLS connections the
+/// payload hash covers the encrypted records actually put on the wire.
+#[derive(Debug)]
+pub struct AuditedStream {
+    inner: TcpStream,
+    source: String,
+    destination: String,
+    log: Option<Arc<AuditLog>>,
+}
+
+impl AuditedStream {
+    /// Connect to `addr`, recording the connection attempt in `log` first
+    pub async fn connect(addr: SocketAddr, log: Option<Arc<AuditLog>>) -> io::Result<Self> {
+        let socket = if addr.is_ipv4() { tokio::net::TcpSocket::new_v4()? } else { tokio::net::TcpSocket::new_v6()? };
+        // Bind first so the attempt is logged with its real source port
+        socket.bind(if addr.is_ipv4() {
+            SocketAddr::from((std::net::Ipv4Addr::UNSPECIFIED, 0))
+        } else {
+            SocketAddr::from((std::net::Ipv6Addr::UNSPECIFIED, 0))
+        })?;
+        let source = socket.local_addr()?.to_string();
+        record_probe(log.as_deref(), &source, &addr.to_string(), "TCP", Some("SYN"), &[]);
+
+        let inner = socket.connect(addr).await?;
+        let source = inner.local_addr().map(|a| a.to_string()).unwrap_or(source);
+        Ok(Self { inner, source, destination: addr.to_string(), log })
+    }
+
+    /// Address of the remote end
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.inner.peer_addr()
+    }
+}
+
+impl AsyncRead for AuditedStream {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for AuditedStream {
+    fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        let result = Pin::new(&mut self.inner).poll_write(cx, buf);
+        if let Poll::Ready(Ok(written)) = &result {
+            record_probe(self.log.as_deref(), &self.source, &self.destination, "TCP", Some("PSH"), &buf[..*written]);
+        }
+        result
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let result = Pin::new(&mut self.inner).poll_shutdown(cx);
+        if let Poll::Ready(Ok(())) = &result {
+            record_probe(self.log.as_deref(), &self.source, &self.destination, "TCP", Some("FIN"), &[]);
+        }
+        result
+    }
+}
+
+/// Connected UDP socket whose datagrams are recorded in the audit log
+#[derive(Debug)]
+pub struct AuditedUdpSocket {
+    inner: UdpSocket,
+    source: String,
+    destination: String,
+    log: Option<Arc<AuditLog>>,
+}
+
+impl AuditedUdpSocket {
+    /// Bind an ephemeral port and connect it to `addr`, recording datagrams in `log`
+    pub async fn connect(addr: SocketAddr, log: Option<Arc<AuditLog>>) -> io::Result<Self> {
+        let inner = UdpSocket::bind(if addr.is_ipv6() { "[::]:0" } else { "0.0.0.0:0" }).await?;
+        inner.connect(addr).await?;
+        Ok(Self {
+            source: inner.local_addr()?.to_string(),
+            destination: addr.to_string(),
+            inner,
+            log,
+        })
+    }
+
+    /// Send one datagram to the connected address
+    pub async fn send(&self, payload: &[u8]) -> io::Result<usize> {
+        record_probe(self.log.as_deref(), &self.source, &self.destination, "UDP", None, payload);
+        self.inner.send(payload).await
+    }
+
+    /// Receive one datagram from the connected address
+    pub async fn recv(&self, buffer: &mut [u8]) -> io::Result<usize> {
+        self.inner.recv(buffer).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Write a sealed log of three probes and return its lines and final hash
+    fn sealed_log(path: &Path) -> (Vec<String>, String) {
+        let log = AuditLog::open(path).unwrap();
+        log.record("10.0.0.1:40000", "10.0.0.5:22", "TCP", Some("SYN"), &[]).unwrap();
+        log.record("10.0.0.1:40000", "10.0.0.5:22", "TCP", Some("PSH"), b"hello").unwrap();
+        log.record("10.0.0.1:40001", "10.0.0.5:53", "UDP", None, b"query").unwrap();
+        let head = log.seal().unwrap();
+        let lines = std::fs::read_to_string(path).unwrap().lines().map(str::to_string).collect();
+        (lines, head)
+    }
+
+    fn rewrite(path: &Path, lines: &[String]) {
+        std::fs::write(path, lines.join("\n") + "\n").unwrap();
+    }
+
+    #[test]
+    fn intact_log_verifies() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.jsonl");
+        let (_, head) = sealed_log(&path);
+
+        let summary = verify(&path, Some(&head)).unwrap();
+        assert_eq!(summary.entries, 4);
+        assert_eq!(summary.probes, 3);
+        assert_eq!(summary.sessions, 1);
+        assert_eq!(summary.head, head);
+    }
+
+    #[test]
+    fn modified_entry_is_detected() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.jsonl");
+        let (mut lines, _) = sealed_log(&path);
+        lines[1] = lines[1].replace("\"payload_len\":5", "\"payload_len\":6");
+        rewrite(&path, &lines);
+
+        assert!(matches!(verify(&path, None), Err(AuditError::Modified { line: 2 })));
+    }
+
+    #[test]
+    fn reordered_entries_are_detected() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.jsonl");
+        let (mut lines, _) = sealed_log(&path);
+        lines.swap(1, 2);
+        rewrite(&path, &lines);
+
+        assert!(matches!(verify(&path, None), Err(AuditError::Sequence { line: 2, expected: 1, found: 2 })));
+        // A broken chain is never extended
+        assert!(AuditLog::open(&path).is_err());
+    }
+
+    #[test]
+    fn deleted_entry_is_detected() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.jsonl");
+        let (mut lines, _) = sealed_log(&path);
+        lines.remove(1);
+        rewrite(&path, &lines);
+
+        assert!(matches!(verify(&path, None), Err(AuditError::Sequence { line: 2, .. })));
+    }
+
+    #[test]
+    fn truncated_log_is_unsealed() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.jsonl");
+        let (lines, _) = sealed_log(&path);
+        rewrite(&path, &lines[..2]);
+
+        assert!(matches!(verify(&path, None), Err(AuditError::Unsealed { entries: 2 })));
+        // An interrupted log is continued where it stopped
+        let log = AuditLog::open(&path).unwrap();
+        let head = log.seal().unwrap();
+        assert_eq!(verify(&path, Some(&head)).unwrap().entries, 3);
+    }
+
+    #[test]
+    fn truncated_and_resealed_log_fails_the_head_check() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.jsonl");
+        let (lines, head) = sealed_log(&path);
+        rewrite(&path, &lines[..2]);
+        AuditLog::open(&path).unwrap().seal().unwrap();
+
+        // The chain itself is valid again; only the recorded head gives it away
+        assert!(verify(&path, None).is_ok());
+        assert!(matches!(verify(&path, Some(&head)), Err(AuditError::HeadMismatch { .. })));
+    }
+
+    #[test]
+    fn failed_writes_are_counted() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.jsonl");
+        std::fs::write(&path, b"").unwrap();
+        // A handle opened for reading only fails every write
+        let log = AuditLog {
+            path: path.clone(),
+            state: Mutex::new(ChainState {
+                file: File::open(&path).unwrap(),
+                next_seq: 0,
+                head: GENESIS_HASH.to_string(),
+                failed: 0,
+                last_error: None,
+            }),
+        };
+        assert!(log.failed_writes().is_none());
+
+        record_probe(Some(&log), "a", "b", "TCP", Some("SYN"), &[]);
+        record_probe(Some(&log), "a", "b", "UDP", None, b"probe");
+        let (failed, error) = log.failed_writes().unwrap();
+        assert_eq!(failed, 2);
+        assert!(!error.is_empty());
+        // Nothing was chained, so the next good write still starts the log
+        assert_eq!(log.head(), GENESIS_HASH);
+    }
+
+    #[tokio::test]
+    async fn scanners_write_separate_logs() {
+        use crate::scope::Egress;
+        use tokio::io::AsyncWriteExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let first = Arc::new(AuditLog::open(&dir.path().join("first.jsonl")).unwrap());
+        let second = Arc::new(AuditLog::open(&dir.path().join("second.jsonl")).unwrap());
+        let mut stream = Egress::new(None, Some(first.clone())).connect_tcp("127.0.0.1", port).await.unwrap();
+        stream.write_all(b"ping").await.unwrap();
+        Egress::new(None, Some(second.clone())).record_probe("a", "b", "ICMP", Some("echo"), &[]);
+
+        assert_eq!(verify(first.path(), Some(&first.seal().unwrap())).unwrap().probes, 2);
+        assert_eq!(verify(second.path(), Some(&second.seal().unwrap())).unwrap().probes, 1);
+    }
+}
@@ -0,0 +1,249 @@
+//! Minimal BER/DER helpers
+//!
+//! Just enough ASN.1 to build and read SNMP, LDAP and CredSSP messages:
+//! definite-length TLVs, integers and object identifiers. Decoders return
+//! `None` on anything malformed rather than erroring, since the input is
+//! whatever a scanned host chose to send.
+
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+pub(crate) const TAG_BOOLEAN: u8 = 0x01;
+pub(crate) const TAG_INTEGER: u8 = 0x02;
+pub(crate) const TAG_OCTET_STRING: u8 = 0x04;
+pub(crate) const TAG_NULL: u8 = 0x05;
+pub(crate) const TAG_OID: u8 = 0x06;
+pub(crate) const TAG_ENUMERATED: u8 = 0x0a;
+pub(crate) const TAG_SEQUENCE: u8 = 0x30;
+pub(crate) const TAG_SET: u8 = 0x31;
+
+/// Largest element `read_element` accepts
+const MAX_ELEMENT: usize = 1 << 20;
+
+/// Encode tag, definite length and content
+pub(crate) fn tlv(tag: u8, content: &[u8]) -> Vec<u8> {
+    let mut out = vec![tag];
+    let len = content.len();
+    if len < 0x80 {
+        out.push(len as u8);
+    } else {
+        let bytes: Vec<u8> = len.to_be_bytes().iter().copied().skip_while(|b| *b == 0).collect();
+        out.push(0x80 | bytes.len() as u8);
+        out.extend(bytes);
+    }
+    out.extend_from_slice(content);
+    out
+}
+
+/// Split off one TLV, returning (tag, content, remainder)
+pub(crate) fn read_tlv(data: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let tag = *data.first()?;
+    let first = *data.get(1)? as usize;
+    let (len, header) = if first < 0x80 {
+        (first, 2)
+    } else {
+        let count = first & 0x7f;
+        if count == 0 || count > 4 {
+            return None;
+        }
+        let len = data.get(2..2 + count)?.iter().fold(0usize, |acc, b| (acc << 8) | *b as usize);
+        (len, 2 + count)
+    };
+    let content = data.get(header..header + len)?;
+    Some((tag, content, &data[header + len..]))
+}
+
+pub(crate) fn encode_integer(value: i64) -> Vec<u8> {
+    let bytes = value.to_be_bytes();
+    let mut start = 0;
+    // Drop redundant leading bytes while keeping the sign bit intact
+    while start < 7
+        && ((bytes[start] == 0x00 && bytes[start + 1] & 0x80 == 0)
+            || (bytes[start] == 0xff && bytes[start + 1] & 0x80 != 0))
+    {
+        start += 1;
+    }
+    bytes[start..].to_vec()
+}
+
+pub(crate) fn decode_integer(content: &[u8]) -> i64 {
+    let negative = content.first().is_some_and(|b| b & 0x80 != 0);
+    content.iter().take(8).fold(if negative { -1 } else { 0 }, |acc, b| (acc << 8) | *b as i64)
+}
+
+pub(crate) fn decode_unsigned(content: &[u8]) -> u64 {
+    content.iter().take(9).fold(0u64, |acc, b| (acc << 8) | *b as u64)
+}
+
+pub(crate) fn encode_oid(oid: &str) -> Result<Vec<u8>, anyhow::Error> {
+    let arcs: Vec<u32> = oid
+        .split('.')
+        .map(|arc| arc.parse().map_err(|_| anyhow::anyhow!("invalid OID '{}'", oid)))
+        .collect::<Result<_, _>>()?;
+    if arcs.len() < 2 {
+        return Err(anyhow::anyhow!("invalid OID '{}'", oid));
+    }
+
+    let mut out = Vec::new();
+    let mut push_arc = |arc: u32| {
+        let mut chunk = vec![(arc & 0x7f) as u8];
+        let mut rest = arc >> 7;
+        while rest > 0 {
+            chunk.push(0x80 | (rest & 0x7f) as u8);
+            rest >>= 7;
+        }
+        out.extend(chunk.iter().rev());
+    };
+    push_arc(arcs[0] * 40 + arcs[1]);
+    for &arc in &arcs[2..] {
+        push_arc(arc);
+    }
+    Ok(out)
+}
+
+pub(crate) fn decode_oid(content: &[u8]) -> Option<String> {
+    let mut arcs = Vec::new();
+    let mut value: u64 = 0;
+    for &byte in content {
+        value = (value << 7) | (byte & 0x7f) as u64;
+        if byte & 0x80 == 0 {
+            if arcs.is_empty() {
+                let first = (value / 40).min(2);
+                arcs.push(first);
+                arcs.push(value - first * 40);
+            } else {
+                arcs.push(value);
+            }
+            value = 0;
+        }
+    }
+    if arcs.is_empty() {
+        return None;
+    }
+    Some(arcs.iter().map(|a| a.to_string()).collect::<Vec<_>>().join("."))
+}
+
+/// Read one complete element (tag, length and content) from a stream
+pub(crate) async fn read_element<S: AsyncRead + Unpin>(stream: &mut S) -> Result<Vec<u8>, anyhow::Error> {
+    let mut header = [0u8; 2];
+    stream.read_exact(&mut header).await?;
+    let mut element = header.to_vec();
+
+    let len = if header[1] < 0x80 {
+        header[1] as usize
+    } else {
+        let count = (header[1] & 0x7f) as usize;
+        if count == 0 || count > 4 {
+            return Err(anyhow::anyhow!("unsupported BER length"));
+        }
+        let mut bytes = vec![0u8; count];
+        stream.read_exact(&mut bytes).await?;
+        element.extend_from_slice(&bytes);
+        bytes.iter().fold(0usize, |acc, b| (acc << 8) | *b as usize)
+    };
+    if len > MAX_ELEMENT {
+        return Err(anyhow::anyhow!("BER element too large ({} bytes)", len));
+    }
+
+    let start = element.len();
+    element.resize(start + len, 0);
+    stream.read_exact(&mut element[start..]).await?;
+    Ok(element)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tlv_lengths_round_trip() {
+        for len in [0usize, 1, 0x7f, 0x80, 0xff, 0x100, 70_000] {
+            let content = vec![0x5a; len];
+            let encoded = tlv(TAG_OCTET_STRING, &content);
+            let header = encoded.len() - len;
+            assert_eq!(header, match len {
+                0..=0x7f => 2,
+                0x80..=0xff => 3,
+                0x100..=0xffff => 4,
+                _ => 5,
+            }, "header for {} bytes", len);
+
+            let trailing = [encoded.clone(), vec![TAG_NULL, 0]].concat();
+            let (tag, decoded, rest) = read_tlv(&trailing).unwrap();
+            assert_eq!(tag, TAG_OCTET_STRING);
+            assert_eq!(decoded, &content[..]);
+            assert_eq!(rest, &[TAG_NULL, 0]);
+        }
+    }
+
+    #[test]
+    fn malformed_tlvs_are_rejected() {
+        assert!(read_tlv(&[]).is_none());
+        assert!(read_tlv(&[TAG_SEQUENCE]).is_none());
+        assert!(read_tlv(&[TAG_SEQUENCE, 0x05, 1, 2]).is_none(), "truncated content");
+        assert!(read_tlv(&[TAG_SEQUENCE, 0x82, 0x01]).is_none(), "truncated length");
+        assert!(read_tlv(&[TAG_SEQUENCE, 0x80, 0, 0]).is_none(), "indefinite length");
+        assert!(read_tlv(&[TAG_SEQUENCE, 0x85, 0, 0, 0, 0, 1, 0]).is_none(), "five length bytes");
+    }
+
+    #[test]
+    fn integers_use_minimal_twos_complement() {
+        let cases: &[(i64, &[u8])] = &[
+            (0, &[0x00]),
+            (127, &[0x7f]),
+            (128, &[0x00, 0x80]),
+            (256, &[0x01, 0x00]),
+            (-1, &[0xff]),
+            (-128, &[0x80]),
+            (-129, &[0xff, 0x7f]),
+            (i64::MAX, &[0x7f, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]),
+            (i64::MIN, &[0x80, 0, 0, 0, 0, 0, 0, 0]),
+        ];
+        for (value, bytes) in cases {
+            assert_eq!(encode_integer(*value), *bytes, "encoding {}", value);
+            assert_eq!(decode_integer(bytes), *value, "decoding {:02x?}", bytes);
+        }
+        assert_eq!(decode_integer(&[]), 0);
+    }
+
+    #[test]
+    fn unsigned_values_ignore_the_sign_bit() {
+        assert_eq!(decode_unsigned(&[0xff, 0xff, 0xff, 0xff]), u32::MAX as u64);
+        assert_eq!(decode_unsigned(&[0x00, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]), u64::MAX);
+        assert_eq!(decode_unsigned(&[]), 0);
+    }
+
+    #[test]
+    fn oids_round_trip() {
+        assert_eq!(encode_oid("1.3.6.1.2.1.1.1.0").unwrap(), [0x2b, 6, 1, 2, 1, 1, 1, 0]);
+        assert_eq!(encode_oid("1.2.840.113549").unwrap(), [0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d]);
+        for oid in ["1.3.6.1.4.1.311.2.2.10", "2.999.3", "0.0", "1.3.6.1.2.1.2.2.1.6.4294967295"] {
+            assert_eq!(decode_oid(&encode_oid(oid).unwrap()).as_deref(), Some(oid));
+        }
+
+        for invalid in ["", "1", "1.3.x", "1..3", "1.3.99999999999"] {
+            assert!(encode_oid(invalid).is_err(), "{:?} should be rejected", invalid);
+        }
+        assert!(decode_oid(&[]).is_none());
+    }
+
+    #[tokio::test]
+    async fn elements_are_read_whole() {
+        let first = tlv(TAG_SEQUENCE, &tlv(TAG_INTEGER, &encode_integer(7)));
+        let second = tlv(TAG_OCTET_STRING, &[0x41; 300]);
+        let stream = [first.clone(), second.clone()].concat();
+        let mut reader = &stream[..];
+        assert_eq!(read_element(&mut reader).await.unwrap(), first);
+        assert_eq!(read_element(&mut reader).await.unwrap(), second);
+        assert!(read_element(&mut reader).await.is_err(), "stream is exhausted");
+
+        let mut truncated = &second[..100];
+        assert!(read_element(&mut truncated).await.is_err());
+
+        let oversized = [TAG_SEQUENCE, 0x84, 0x7f, 0xff, 0xff, 0xff];
+        let error = read_element(&mut &oversized[..]).await.unwrap_err();
+        assert!(error.to_string().contains("too large"), "{}", error);
+
+        let indefinite = [TAG_SEQUENCE, 0x80, 0, 0];
+        assert!(read_element(&mut &indefinite[..]).await.is_err());
+    }
+}
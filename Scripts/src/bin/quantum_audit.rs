@@ -0,0 +1,60 @@
+//! Quantum Scanner audit log tool
+//!
+//! Checks the hash chain of an audit log written with `--audit-log`, so the
+//! record of what was sent to a client can be shown to be complete and
+//! unmodified. Exits non-zero if the log fails verification.
+
+use std::path::PathBuf;
+
+use clap::{Parser, Subcommand};
+
+use quantum_scanner::audit;
+
+#[derive(Parser)]
+#[clap(author, version, about = "Quantum Scanner audit log tool", long_about = None)]
+struct Args {
+    #[clap(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Verify that an audit log is intact: no entry modified, removed, reordered or cut off
+    VerifyAudit {
+        /// Audit log to check
+        path: PathBuf,
+
+        /// Final hash printed when the scan sealed the log; must match the last entry
+        #[clap(long)]
+        head: Option<String>,
+
+        /// Print the summary as JSON
+        #[clap(long)]
+        json: bool,
+    },
+}
+
+fn main() {
+    let args = Args::parse();
+
+    match args.command {
+        Command::VerifyAudit { path, head, json } => match audit::verify(&path, head.as_deref()) {
+            Ok(summary) => {
+                if json {
+                    println!("{}", serde_json::to_string_pretty(&summary).unwrap_or_default());
+                } else {
+                    println!("[+] {} verified: {} probes in {} sealed sessions",
+                        path.display(), summary.probes, summary.sessions);
+                    if let (Some(first), Some(last)) = (summary.first, summary.last) {
+                        println!("[+] First entry {}, last entry {}", first.to_rfc3339(), last.to_rfc3339());
+                    }
+                    println!("[+] Final hash {}", summary.head);
+                }
+            },
+            Err(e) => {
+                eprintln!("[!] {} FAILED verification: {}", path.display(), e);
+                std::process::exit(1);
+            }
+        },
+    }
+}
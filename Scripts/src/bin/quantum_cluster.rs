@@ -0,0 +1,209 @@
+//! Quantum Scanner distributed mode
+//!
+//! `coordinator` splits a scan over many hosts into work units and merges the
+//! results; `agent` connects to a coordinator and runs the units it is given.
+//! See `quantum_scanner::cluster` for the protocol and certificate setup.
+
+use std::future::Future;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use clap::{Parser, Subcommand};
+use tokio::sync::mpsc;
+
+use quantum_scanner::cluster::{self, Agent, AgentConfig, Coordinator, CoordinatorConfig, CoordinatorEvent, TlsFiles};
+use quantum_scanner::profiles::{self, ProfileSet};
+use quantum_scanner::ScanConfig;
+
+#[derive(Parser)]
+#[clap(author, version, about = "Quantum Scanner distributed mode", long_about = None)]
+struct Args {
+    #[clap(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Split a scan into work units, hand them to agents and merge the results
+    Coordinator {
+        /// Address agents connect to
+        #[clap(long, default_value = "127.0.0.1:8788")]
+        listen: SocketAddr,
+
+        /// CA certificate agents must be issued by (PEM)
+        #[clap(long)]
+        ca: PathBuf,
+
+        /// Coordinator certificate (PEM)
+        #[clap(long)]
+        cert: PathBuf,
+
+        /// Coordinator private key (PEM)
+        #[clap(long)]
+        key: PathBuf,
+
+        /// Hosts and IPv4 CIDR ranges to scan (comma-separated)
+        #[clap(long, value_delimiter = ',', required = true)]
+        targets: Vec<String>,
+
+        /// Scan settings as ScanConfig JSON (the target in it is ignored)
+        #[clap(long, conflicts_with = "profile")]
+        config: Option<PathBuf>,
+
+        /// Scan profile to use for the settings
+        #[clap(long)]
+        profile: Option<String>,
+
+        /// Profile file (defaults to the standard profile location)
+        #[clap(long)]
+        profile_file: Option<PathBuf>,
+
+        /// Ports per work unit
+        #[clap(long, default_value_t = 1000)]
+        ports_per_unit: usize,
+
+        /// Heartbeat interval asked of agents, in seconds
+        #[clap(long, default_value_t = 5)]
+        heartbeat: u64,
+
+        /// Seconds without a message after which an agent is considered dead
+        #[clap(long, default_value_t = 30)]
+        agent_timeout: u64,
+
+        /// Tries per unit before it is reported as failed
+        #[clap(long, default_value_t = 3)]
+        max_attempts: u32,
+
+        /// Write the merged results as JSON to this file
+        #[clap(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Connect to a coordinator and run the units it hands out
+    Agent {
+        /// Coordinator address (host:port)
+        #[clap(long)]
+        coordinator: String,
+
+        /// Name the coordinator certificate must be valid for (defaults to the coordinator host)
+        #[clap(long)]
+        server_name: Option<String>,
+
+        /// CA certificate the coordinator must be issued by (PEM)
+        #[clap(long)]
+        ca: PathBuf,
+
+        /// Agent certificate (PEM)
+        #[clap(long)]
+        cert: PathBuf,
+
+        /// Agent private key (PEM)
+        #[clap(long)]
+        key: PathBuf,
+
+        /// Name reported to the coordinator (defaults to the host name)
+        #[clap(long)]
+        name: Option<String>,
+
+        /// Units to run at the same time
+        #[clap(long, default_value_t = 1)]
+        slots: usize,
+    },
+}
+
+#[tokio::main]
+async fn main() -> Result<(), anyhow::Error> {
+    let args = Args::parse();
+
+    match args.command {
+        Command::Coordinator {
+            listen, ca, cert, key, targets, config, profile, profile_file,
+            ports_per_unit, heartbeat, agent_timeout, max_attempts, output,
+        } => {
+            let template: ScanConfig = match (config, profile) {
+                (Some(path), _) => serde_json::from_slice(&std::fs::read(&path)?)?,
+                (None, Some(name)) => {
+                    let path = profile_file.unwrap_or_else(profiles::default_profile_path);
+                    ProfileSet::load(&path)?.resolve(&name)?
+                }
+                (None, None) => anyhow::bail!("either --config or --profile is required"),
+            };
+
+            let units = cluster::plan_units(&template, &targets, ports_per_unit)?;
+            println!("[+] Split the scan into {} work units", units.len());
+            println!("[+] Waiting for agents on {}", listen);
+
+            let (events, progress) = mpsc::unbounded_channel();
+            let coordinator = Coordinator::new(CoordinatorConfig {
+                listen,
+                tls: TlsFiles { ca, cert, key },
+                heartbeat: Duration::from_secs(heartbeat),
+                agent_timeout: Duration::from_secs(agent_timeout),
+                max_attempts,
+                events: Some(events),
+            }, units);
+            let results = with_progress(coordinator.run(), progress, |event: CoordinatorEvent| {
+                if event.is_problem() {
+                    eprintln!("[coordinator] {}", event);
+                } else {
+                    println!("[coordinator] {}", event);
+                }
+            }).await?;
+
+            let open: usize = results.hosts.iter().map(|host| host.open_ports.len()).sum();
+            println!("[+] Scan complete: {} hosts, {} open ports, {} agents",
+                results.hosts.len(), open, results.agents.len());
+            for failure in &results.failed_units {
+                println!("[!] Unit {} ({} ports on {}) failed after {} attempts: {}",
+                    failure.unit, failure.ports.len(), failure.target, failure.attempts, failure.error);
+            }
+            if let Some(path) = output {
+                std::fs::write(&path, serde_json::to_vec_pretty(&results)?)?;
+                println!("[+] Results saved to {}", path.display());
+            }
+        }
+        Command::Agent { coordinator, server_name, ca, cert, key, name, slots } => {
+            let server_name = server_name.unwrap_or_else(|| {
+                coordinator.rsplit_once(':').map(|(host, _)| host).unwrap_or(&coordinator)
+                    .trim_start_matches('[').trim_end_matches(']').to_string()
+            });
+            let name = name
+                .or_else(|| std::env::var("HOSTNAME").ok())
+                .unwrap_or_else(|| format!("agent-{}", std::process::id()));
+
+            let (events, progress) = mpsc::unbounded_channel();
+            let agent = Agent::new(AgentConfig {
+                coordinator,
+                server_name,
+                tls: TlsFiles { ca, cert, key },
+                name,
+                slots,
+                events: Some(events),
+            });
+            with_progress(agent.run(), progress, |event| println!("[agent] {}", event)).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Await `task`, printing its progress events as they arrive
+async fn with_progress<T, E>(
+    task: impl Future<Output = T>,
+    mut progress: mpsc::UnboundedReceiver<E>,
+    print: impl Fn(E),
+) -> T {
+    tokio::pin!(task);
+    let output = loop {
+        tokio::select! {
+            output = &mut task => break output,
+            Some(event) = progress.recv() => print(event),
+        }
+    };
+    // Events sent just before the task finished
+    while let Ok(event) = progress.try_recv() {
+        print(event);
+    }
+    output
+}
@@ -0,0 +1,144 @@
+//! Quantum Scanner monitor
+//!
+//! Rescans a scope on a schedule and alerts when its attack surface changes:
+//! new open ports, changed service versions, certificates close to expiry and
+//! web security header regressions. See `quantum_scanner::monitor`.
+
+use std::path::PathBuf;
+use std::time::Duration;
+
+use clap::Parser;
+use tokio::sync::mpsc;
+
+use quantum_scanner::cluster;
+use quantum_scanner::monitor::{self, AlertSink, Monitor, MonitorConfig, MonitorEvent};
+use quantum_scanner::profiles::{self, ProfileSet};
+use quantum_scanner::{ScanConfig, ScopeConfig};
+
+#[derive(Parser)]
+#[clap(author, version, about = "Quantum Scanner attack surface monitor", long_about = None)]
+struct Args {
+    /// Hosts and IPv4 CIDR ranges to watch (comma-separated; defaults to the scope's include list)
+    #[clap(long, value_delimiter = ',')]
+    targets: Vec<String>,
+
+    /// Engagement scope file enforced on every run
+    #[clap(long)]
+    scope: Option<PathBuf>,
+
+    /// Scan settings as ScanConfig JSON (the target in it is ignored)
+    #[clap(long, conflicts_with = "profile")]
+    config: Option<PathBuf>,
+
+    /// Scan profile to use for the settings
+    #[clap(long)]
+    profile: Option<String>,
+
+    /// Profile file (defaults to the standard profile location)
+    #[clap(long)]
+    profile_file: Option<PathBuf>,
+
+    /// Time between the starts of two runs, e.g. 30m, 6h or 1d
+    #[clap(long, default_value = "24h", value_parser = monitor::parse_interval)]
+    interval: Duration,
+
+    /// Directory the runs are stored in
+    #[clap(long, default_value = "quantum_scanner_monitor")]
+    state_dir: PathBuf,
+
+    /// Alert when a certificate expires within this many days
+    #[clap(long, default_value_t = monitor::DEFAULT_CERT_EXPIRY_DAYS)]
+    cert_expiry_days: i64,
+
+    /// Hosts scanned at the same time
+    #[clap(long, default_value_t = 4)]
+    parallel: usize,
+
+    /// Stored runs to keep (0 = keep all)
+    #[clap(long, default_value_t = 30)]
+    keep_runs: usize,
+
+    /// Alert destination: webhook=URL, file=PATH, syslog or syslog=HOST:PORT (repeatable)
+    #[clap(long = "alert")]
+    alerts: Vec<AlertSink>,
+
+    /// Do a single run and exit
+    #[clap(long)]
+    once: bool,
+}
+
+#[tokio::main]
+async fn main() -> Result<(), anyhow::Error> {
+    let args = Args::parse();
+
+    let mut template: ScanConfig = match (args.config, args.profile) {
+        (Some(path), _) => serde_json::from_slice(&std::fs::read(&path)?)?,
+        (None, Some(name)) => {
+            let path = args.profile_file.unwrap_or_else(profiles::default_profile_path);
+            ProfileSet::load(&path)?.resolve(&name)?
+        }
+        (None, None) => anyhow::bail!("either --config or --profile is required"),
+    };
+
+    let mut targets = args.targets;
+    if let Some(path) = &args.scope {
+        let scope = ScopeConfig::load(path)?;
+        if targets.is_empty() {
+            for entry in &scope.include {
+                // Wildcard names and IPv6 ranges can't be enumerated
+                if entry.contains('*') || (entry.contains(':') && entry.contains('/')) {
+                    println!("[!] Not watching scope entry {} (list its hosts with --targets)", entry);
+                } else {
+                    targets.push(entry.clone());
+                }
+            }
+        }
+        template.scope = Some(scope);
+    }
+
+    let hosts = cluster::expand_targets(&targets)?;
+    if hosts.is_empty() {
+        anyhow::bail!("nothing to watch: give --targets or a --scope with an include list");
+    }
+    if args.alerts.is_empty() {
+        println!("[!] No --alert destination given; alerts are only printed and stored");
+    }
+
+    println!("[+] Watching {} hosts every {}s, runs stored in {}",
+        hosts.len(), args.interval.as_secs(), args.state_dir.display());
+    for sink in &args.alerts {
+        println!("[+] Alerts go to {}", sink);
+    }
+
+    let (events, mut progress) = mpsc::unbounded_channel::<MonitorEvent>();
+    let printer = tokio::spawn(async move {
+        while let Some(event) = progress.recv().await {
+            if event.is_problem() {
+                eprintln!("[monitor] {}", event);
+            } else {
+                println!("[monitor] {}", event);
+            }
+        }
+    });
+
+    let mut monitor = Monitor::new(MonitorConfig {
+        hosts,
+        template,
+        interval: args.interval,
+        state_dir: args.state_dir,
+        cert_expiry_days: args.cert_expiry_days,
+        parallel: args.parallel,
+        keep_runs: args.keep_runs,
+        sinks: args.alerts,
+        events: Some(events),
+    })?;
+    if let Some(previous) = monitor.previous() {
+        println!("[+] Comparing against stored run {}", previous.id);
+    }
+
+    let outcome = monitor.run(if args.once { Some(1) } else { None }).await;
+    // Dropping the monitor closes the channel once its last events are printed
+    drop(monitor);
+    let _ = printer.await;
+    outcome
+}
@@ -0,0 +1,71 @@
+//! Quantum Scanner daemon
+//!
+//! Runs the scan job API on a local port so several operators can share one
+//! scanning box. See `quantum_scanner::daemon` for the endpoints.
+
+use std::net::SocketAddr;
+use std::path::PathBuf;
+
+use clap::Parser;
+use tokio::sync::mpsc;
+
+use quantum_scanner::daemon::{self, Daemon, DaemonConfig};
+
+#[derive(Parser)]
+#[clap(author, version, about = "Quantum Scanner job daemon", long_about = None)]
+struct Args {
+    /// Address to listen on
+    #[clap(long, default_value = "127.0.0.1:8787")]
+    listen: SocketAddr,
+    
+    /// API token clients must send as a Bearer token (generated if omitted)
+    #[clap(long, env = "QUANTUM_SCANNER_TOKEN")]
+    token: Option<String>,
+    
+    /// Directory for the persistent job queue and results
+    #[clap(long)]
+    state_dir: Option<PathBuf>,
+    
+    /// Number of jobs to run at the same time
+    #[clap(long, default_value_t = 1)]
+    parallel_jobs: usize,
+}
+
+#[tokio::main]
+async fn main() -> Result<(), anyhow::Error> {
+    let args = Args::parse();
+    
+    let token = match args.token {
+        Some(token) if !token.is_empty() => token,
+        _ => {
+            let token = daemon::generate_token();
+            println!("[+] Generated API token: {}", token);
+            token
+        }
+    };
+    
+    if !args.listen.ip().is_loopback() {
+        println!("[!] Listening on non-loopback address {} - make sure the token stays secret", args.listen);
+    }
+    
+    let (events, mut reports) = mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        while let Some(event) = reports.recv().await {
+            eprintln!("[daemon] {}", event);
+        }
+    });
+    
+    let config = DaemonConfig {
+        listen: args.listen,
+        token,
+        state_dir: args.state_dir.unwrap_or_else(daemon::default_state_dir),
+        max_parallel_jobs: args.parallel_jobs,
+        events: Some(events),
+    };
+    
+    println!("[+] Quantum Scanner daemon listening on http://{}", config.listen);
+    println!("[+] Job state stored in {}", config.state_dir.display());
+    
+    let daemon = Daemon::new(config)?;
+    daemon.serve().await
+}
@@ -0,0 +1,1096 @@
+//! Distributed scanning with a coordinator and remote agents
+//!
+//! The coordinator splits a scan into work units, one target host and a slice
+//! of its ports each, and hands them out to agents. Agents run the normal scan
+//! engine on every unit they are given and send the `ScanResults` back, which
+//! the coordinator merges per host into one result set.
+//!
+//! Both directions are authenticated: coordinator and agents each present a
+//! certificate issued by the same CA, and each side refuses a peer whose
+//! certificate doesn't chain to it. Messages are length-prefixed JSON frames.
+//! Agents say how many units they run at once and send heartbeats; an agent
+//! that disconnects or stays silent for `agent_timeout` is treated as dead
+//! and its units are queued again, up to `max_attempts` tries per unit.
+//!
+//! A CA and certificates for a test on one machine can be made with openssl:
+//!
+//! ```text
+//! openssl req -x509 -newkey rsa:2048 -nodes -days 30 -subj "/CN=scan-ca" -keyout ca.key -out ca.pem
+//! openssl req -newkey rsa:2048 -nodes -subj "/CN=localhost" -keyout node.key -out node.csr
+//! openssl x509 -req -in node.csr -CA ca.pem -CAkey ca.key -CAcreateserial -days 30 \
+//!     -extfile <(printf "subjectAltName=DNS:localhost,IP:127.0.0.1") -out node.pem
+//! ```
+//!
+//! (one certificate per agent in real deployments, so agents show up under
+//! their own fingerprint).
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{mpsc, Notify};
+
+use crate::certificates;
+use crate::config::{ScanConfig, ScannerBuilder};
+use crate::error::ScanError;
+use crate::models::ScanResults;
+
+/// Largest frame accepted from a peer (results for a big port range included)
+const MAX_FRAME_SIZE: usize = 64 * 1024 * 1024;
+
+/// Most hosts a single CIDR target may expand to
+const MAX_CIDR_HOSTS: u32 = 65536;
+
+/// Certificate files for one end of the cluster connection
+#[derive(Debug, Clone)]
+pub struct TlsFiles {
+    /// CA certificate(s) that issued the peers' certificates (PEM)
+    pub ca: PathBuf,
+    /// This node's certificate chain (PEM)
+    pub cert: PathBuf,
+    /// This node's private key (PEM: PKCS#8, PKCS#1 or SEC1)
+    pub key: PathBuf,
+}
+
+impl TlsFiles {
+    fn read_pem(path: &Path) -> Result<Vec<(String, Vec<u8>)>, anyhow::Error> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("Failed to read {}: {}", path.display(), e))?;
+        Ok(certificates::from_pem(&text))
+    }
+
+    fn roots(&self) -> Result<rustls::RootCertStore, anyhow::Error> {
+        let mut roots = rustls::RootCertStore::empty();
+        for (label, der) in Self::read_pem(&self.ca)? {
+            if label == "CERTIFICATE" {
+                roots.add(&rustls::Certificate(der))?;
+            }
+        }
+        if roots.is_empty() {
+            anyhow::bail!("No CA certificate found in {}", self.ca.display());
+        }
+        Ok(roots)
+    }
+
+    fn identity(&self) -> Result<(Vec<rustls::Certificate>, rustls::PrivateKey), anyhow::Error> {
+        let chain: Vec<rustls::Certificate> = Self::read_pem(&self.cert)?.into_iter()
+            .filter(|(label, _)| label == "CERTIFICATE")
+            .map(|(_, der)| rustls::Certificate(der))
+            .collect();
+        if chain.is_empty() {
+            anyhow::bail!("No certificate found in {}", self.cert.display());
+        }
+        let key = Self::read_pem(&self.key)?.into_iter()
+            .find(|(label, _)| label.ends_with("PRIVATE KEY"))
+            .map(|(_, der)| rustls::PrivateKey(der))
+            .ok_or_else(|| anyhow::anyhow!("No private key found in {}", self.key.display()))?;
+        Ok((chain, key))
+    }
+
+    /// TLS settings for the coordinator: agents must present a certificate from the CA
+    pub fn server_config(&self) -> Result<rustls::ServerConfig, anyhow::Error> {
+        let verifier = rustls::server::AllowAnyAuthenticatedClient::new(self.roots()?).boxed();
+        let (chain, key) = self.identity()?;
+        Ok(rustls::ServerConfig::builder()
+            .with_safe_defaults()
+            .with_client_cert_verifier(verifier)
+            .with_single_cert(chain, key)?)
+    }
+
+    /// TLS settings for an agent: the coordinator must present a certificate from the CA
+    pub fn client_config(&self) -> Result<rustls::ClientConfig, anyhow::Error> {
+        let (chain, key) = self.identity()?;
+        Ok(rustls::ClientConfig::builder()
+            .with_safe_defaults()
+            .with_root_certificates(self.roots()?)
+            .with_client_auth_cert(chain, key)?)
+    }
+}
+
+/// A slice of the scan handed to one agent
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkUnit {
+    /// Unit identifier
+    pub id: u64,
+    /// Tries so far, including the current one
+    pub attempt: u32,
+    /// Complete scan configuration for the unit (one target, some ports)
+    pub config: ScanConfig,
+}
+
+/// Messages sent by agents
+///
+/// Externally tagged: an internally tagged enum would buffer the results and
+/// lose serde_json's support for the integer port keys in them.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum AgentMessage {
+    Hello { name: String, slots: usize },
+    Heartbeat { running: Vec<u64> },
+    Completed { unit: u64, results: Box<ScanResults> },
+    Failed { unit: u64, error: String },
+}
+
+/// Messages sent by the coordinator
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum CoordinatorMessage {
+    Welcome { agent: String, heartbeat_secs: u64 },
+    Assign { unit: Box<WorkUnit> },
+    Shutdown,
+}
+
+async fn write_frame<W: AsyncWrite + Unpin, T: Serialize>(writer: &mut W, message: &T) -> Result<(), anyhow::Error> {
+    let body = serde_json::to_vec(message)?;
+    writer.write_u32(body.len() as u32).await?;
+    writer.write_all(&body).await?;
+    writer.flush().await?;
+    Ok(())
+}
+
+async fn read_frame<R: AsyncRead + Unpin, T: DeserializeOwned>(reader: &mut R) -> Result<T, anyhow::Error> {
+    let len = reader.read_u32().await? as usize;
+    if len > MAX_FRAME_SIZE {
+        anyhow::bail!("frame of {} bytes exceeds the {} byte limit", len, MAX_FRAME_SIZE);
+    }
+    let mut body = vec![0u8; len];
+    reader.read_exact(&mut body).await?;
+    Ok(serde_json::from_slice(&body)?)
+}
+
+/// Expand targets into single hosts; IPv4 CIDR ranges become their host addresses
+pub fn expand_targets(targets: &[String]) -> Result<Vec<String>, ScanError> {
+    let mut hosts = Vec::new();
+    for target in targets.iter().map(|t| t.trim()).filter(|t| !t.is_empty()) {
+        let (addr, prefix) = match target.split_once('/') {
+            Some(split) => split,
+            None => {
+                hosts.push(target.to_string());
+                continue;
+            }
+        };
+        let invalid = || ScanError::InvalidConfig(format!("invalid target range '{}'", target));
+        let addr: Ipv4Addr = addr.parse().map_err(|_| invalid())?;
+        let prefix: u32 = prefix.parse().ok().filter(|p| *p <= 32).ok_or_else(invalid)?;
+
+        let size = 1u64 << (32 - prefix);
+        if size > MAX_CIDR_HOSTS as u64 {
+            return Err(ScanError::InvalidConfig(format!(
+                "target range '{}' has more than {} addresses", target, MAX_CIDR_HOSTS
+            )));
+        }
+        let network = u32::from(addr) & u32::MAX.checked_shl(32 - prefix).unwrap_or(0);
+        // Skip the network and broadcast addresses except for /31 and /32
+        let (first, last) = if size > 2 { (1, size - 2) } else { (0, size - 1) };
+        hosts.extend((first..=last).map(|offset| Ipv4Addr::from(network + offset as u32).to_string()));
+    }
+    let mut seen = HashSet::new();
+    hosts.retain(|host| seen.insert(host.clone()));
+    Ok(hosts)
+}
+
+/// Split a scan over `targets` into units of at most `ports_per_unit` ports
+pub fn plan_units(template: &ScanConfig, targets: &[String], ports_per_unit: usize) -> Result<Vec<WorkUnit>, ScanError> {
+    let hosts = expand_targets(targets)?;
+    if hosts.is_empty() {
+        return Err(ScanError::MissingTarget);
+    }
+    if ports_per_unit == 0 {
+        return Err(ScanError::InvalidConfig("ports per unit must be at least 1".to_string()));
+    }
+
+    let mut units = Vec::new();
+    for host in &hosts {
+        for ports in template.ports.chunks(ports_per_unit) {
+            let config = ScanConfig {
+                target: host.clone(),
+                ports: ports.to_vec(),
+                ..template.clone()
+            };
+            config.validate()?;
+            units.push(WorkUnit { id: units.len() as u64, attempt: 1, config });
+        }
+    }
+    if units.is_empty() {
+        return Err(ScanError::NoPorts);
+    }
+    Ok(units)
+}
+
+/// Coordinator settings
+#[derive(Debug, Clone)]
+pub struct CoordinatorConfig {
+    /// Address agents connect to
+    pub listen: SocketAddr,
+    /// Coordinator certificate, key and the CA agents must be issued by
+    pub tls: TlsFiles,
+    /// Interval agents are asked to send heartbeats at
+    pub heartbeat: Duration,
+    /// Silence after which an agent is considered dead
+    pub agent_timeout: Duration,
+    /// Tries per unit before it is reported as failed
+    pub max_attempts: u32,
+    /// Where progress is reported (dropped when `None`)
+    pub events: Option<mpsc::UnboundedSender<CoordinatorEvent>>,
+}
+
+impl Default for CoordinatorConfig {
+    fn default() -> Self {
+        Self {
+            listen: SocketAddr::from((Ipv4Addr::LOCALHOST, 8788)),
+            tls: TlsFiles {
+                ca: PathBuf::from("ca.pem"),
+                cert: PathBuf::from("coordinator.pem"),
+                key: PathBuf::from("coordinator.key"),
+            },
+            heartbeat: Duration::from_secs(5),
+            agent_timeout: Duration::from_secs(30),
+            max_attempts: 3,
+            events: None,
+        }
+    }
+}
+
+/// Progress of a distributed scan as seen by the coordinator
+#[derive(Debug, Clone)]
+pub enum CoordinatorEvent {
+    /// An agent passed the TLS handshake and introduced itself
+    AgentConnected {
+        /// Identifier given to the agent
+        agent: String,
+        /// Name the agent reported
+        name: String,
+        /// Agent address
+        peer: SocketAddr,
+        /// Units the agent runs at the same time
+        slots: usize,
+        /// Fingerprint of the agent certificate
+        fingerprint: String,
+    },
+    /// A connection ended with an error
+    AgentError {
+        /// Agent address
+        peer: SocketAddr,
+        /// What went wrong
+        error: String,
+    },
+    /// A unit was handed to an agent
+    UnitAssigned {
+        /// Unit identifier
+        unit: u64,
+        /// Ports in the unit
+        ports: usize,
+        /// Host the unit scans
+        target: String,
+        /// Attempt number
+        attempt: u32,
+        /// Agent running it
+        agent: String,
+    },
+    /// An agent sent a unit's results
+    UnitCompleted {
+        /// Unit identifier
+        unit: u64,
+        /// Agent that ran it
+        agent: String,
+        /// Units finished or given up on so far
+        finished: usize,
+        /// Units in the scan
+        total: usize,
+    },
+    /// An agent reported a unit as failed
+    UnitFailed {
+        /// Unit identifier
+        unit: u64,
+        /// Agent that ran it
+        agent: String,
+        /// Error reported by the agent
+        error: String,
+    },
+    /// An agent went away with units still running
+    AgentLost {
+        /// Agent identifier
+        agent: String,
+        /// Why it was dropped
+        reason: String,
+        /// Units put back in the queue
+        requeued: usize,
+    },
+}
+
+impl CoordinatorEvent {
+    /// Whether the event reports something going wrong
+    pub fn is_problem(&self) -> bool {
+        matches!(self, CoordinatorEvent::AgentError { .. } | CoordinatorEvent::UnitFailed { .. } | CoordinatorEvent::AgentLost { .. })
+    }
+}
+
+impl std::fmt::Display for CoordinatorEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CoordinatorEvent::AgentConnected { agent, name, peer, slots, fingerprint } => write!(f,
+                "agent {} ({}) connected from {} with {} slots, certificate {}", agent, name, peer, slots, fingerprint),
+            CoordinatorEvent::AgentError { peer, error } => write!(f, "agent {}: {}", peer, error),
+            CoordinatorEvent::UnitAssigned { unit, ports, target, attempt, agent } => write!(f,
+                "unit {} ({} ports on {}, attempt {}) -> {}", unit, ports, target, attempt, agent),
+            CoordinatorEvent::UnitCompleted { unit, agent, finished, total } => write!(f,
+                "unit {} completed by {} ({}/{})", unit, agent, finished, total),
+            CoordinatorEvent::UnitFailed { unit, agent, error } => write!(f, "unit {} failed on {}: {}", unit, agent, error),
+            CoordinatorEvent::AgentLost { agent, reason, requeued } => write!(f,
+                "{} lost ({}), re-queued {} units", agent, reason, requeued),
+        }
+    }
+}
+
+/// Health and throughput of one agent connection
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgentStatus {
+    /// Identifier assigned by the coordinator
+    pub id: String,
+    /// Name the agent announced
+    pub name: String,
+    /// Address the agent connected from
+    pub address: String,
+    /// SHA-256 fingerprint of the agent's certificate
+    pub fingerprint: String,
+    /// Units the agent runs at the same time
+    pub slots: usize,
+    /// When the agent connected
+    pub connected_at: chrono::DateTime<chrono::Utc>,
+    /// Last message received from the agent
+    pub last_seen: chrono::DateTime<chrono::Utc>,
+    /// Still connected and sending heartbeats
+    pub alive: bool,
+    /// Units the agent finished
+    pub completed: usize,
+    /// Units the agent reported as failed
+    pub failed: usize,
+    /// Units taken back from the agent when it was lost
+    pub requeued: usize,
+}
+
+/// A unit that ran out of attempts
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UnitFailure {
+    /// Unit identifier
+    pub unit: u64,
+    /// Host the unit scanned
+    pub target: String,
+    /// Ports the unit covered
+    pub ports: Vec<u16>,
+    /// Tries made
+    pub attempts: u32,
+    /// Error from the last try
+    pub error: String,
+}
+
+/// Merged outcome of a distributed scan
+#[derive(Debug, Clone, Serialize)]
+pub struct ClusterResults {
+    /// One merged result set per scanned host
+    pub hosts: Vec<ScanResults>,
+    /// Units that failed on every attempt
+    pub failed_units: Vec<UnitFailure>,
+    /// Every agent that took part
+    pub agents: Vec<AgentStatus>,
+    /// Number of units the scan was split into
+    pub units: usize,
+}
+
+/// Work queue and results shared by the agent connections
+#[derive(Default)]
+struct State {
+    pending: VecDeque<WorkUnit>,
+    running: HashMap<u64, (String, WorkUnit)>,
+    finished: BTreeSet<u64>,
+    failures: Vec<UnitFailure>,
+    hosts: BTreeMap<String, ScanResults>,
+    agents: BTreeMap<String, AgentStatus>,
+    total: usize,
+    next_agent: u64,
+}
+
+impl State {
+    fn is_done(&self) -> bool {
+        self.pending.is_empty() && self.running.is_empty()
+    }
+}
+
+/// Hands out work units to agents and merges what they send back
+pub struct Coordinator {
+    config: CoordinatorConfig,
+    state: Mutex<State>,
+    changed: Notify,
+}
+
+impl Coordinator {
+    /// Create a coordinator for the given units (see `plan_units`)
+    pub fn new(config: CoordinatorConfig, units: Vec<WorkUnit>) -> Arc<Self> {
+        let state = State {
+            total: units.len(),
+            pending: units.into(),
+            ..State::default()
+        };
+        Arc::new(Self { config, state: Mutex::new(state), changed: Notify::new() })
+    }
+
+    /// Current agent health
+    pub fn agents(&self) -> Vec<AgentStatus> {
+        self.state.lock().agents.values().cloned().collect()
+    }
+
+    /// Units finished or given up on, and the total
+    pub fn progress(&self) -> (usize, usize) {
+        let state = self.state.lock();
+        (state.finished.len(), state.total)
+    }
+
+    /// Accept agents until every unit has finished or failed, then return the merged results
+    pub async fn run(self: Arc<Self>) -> Result<ClusterResults, anyhow::Error> {
+        let acceptor = tokio_rustls::TlsAcceptor::from(Arc::new(self.config.tls.server_config()?));
+        let listener = TcpListener::bind(self.config.listen).await?;
+        let mut connections = tokio::task::JoinSet::new();
+
+        while !self.state.lock().is_done() {
+            tokio::select! {
+                accepted = listener.accept() => {
+                    let (stream, peer) = accepted?;
+                    let coordinator = self.clone();
+                    let acceptor = acceptor.clone();
+                    connections.spawn(async move {
+                        if let Err(e) = coordinator.handle_agent(acceptor, stream, peer).await {
+                            coordinator.report(CoordinatorEvent::AgentError { peer, error: e.to_string() });
+                        }
+                    });
+                }
+                // Re-check periodically in case a notification was missed
+                _ = tokio::time::timeout(Duration::from_secs(1), self.changed.notified()) => {}
+            }
+        }
+
+        // Give connected agents a moment to receive their shutdown message
+        self.changed.notify_waiters();
+        let _ = tokio::time::timeout(Duration::from_secs(5), async {
+            while connections.join_next().await.is_some() {}
+        }).await;
+
+        let state = self.state.lock();
+        Ok(ClusterResults {
+            hosts: state.hosts.values().cloned().collect(),
+            failed_units: state.failures.clone(),
+            agents: state.agents.values().cloned().collect(),
+            units: state.total,
+        })
+    }
+
+    /// Pass an event to whoever asked for them
+    fn report(&self, event: CoordinatorEvent) {
+        if let Some(events) = &self.config.events {
+            let _ = events.send(event);
+        }
+    }
+
+    async fn handle_agent(
+        self: &Arc<Self>,
+        acceptor: tokio_rustls::TlsAcceptor,
+        stream: TcpStream,
+        peer: SocketAddr,
+    ) -> Result<(), anyhow::Error> {
+        let tls = tokio::time::timeout(Duration::from_secs(10), acceptor.accept(stream)).await??;
+        let fingerprint = tls.get_ref().1.peer_certificates()
+            .and_then(|chain| chain.first())
+            .map(|cert| certificates::fingerprint(&cert.0))
+            .unwrap_or_default();
+        let (mut reader, mut writer) = tokio::io::split(tls);
+
+        // Read frames in their own task so waiting for one can be cancelled safely
+        let (tx, mut rx) = mpsc::channel::<Result<AgentMessage, anyhow::Error>>(16);
+        let reader_task = tokio::spawn(async move {
+            loop {
+                let message = read_frame::<_, AgentMessage>(&mut reader).await;
+                let failed = message.is_err();
+                if tx.send(message).await.is_err() || failed {
+                    break;
+                }
+            }
+        });
+
+        let (name, slots) = match tokio::time::timeout(self.config.agent_timeout, rx.recv()).await {
+            Ok(Some(Ok(AgentMessage::Hello { name, slots }))) => (name, slots.max(1)),
+            _ => {
+                reader_task.abort();
+                anyhow::bail!("did not introduce itself");
+            }
+        };
+        let id = self.register(&name, peer, &fingerprint, slots);
+        self.report(CoordinatorEvent::AgentConnected {
+            agent: id.clone(),
+            name,
+            peer,
+            slots,
+            fingerprint,
+        });
+
+        write_frame(&mut writer, &CoordinatorMessage::Welcome {
+            agent: id.clone(),
+            heartbeat_secs: self.config.heartbeat.as_secs().max(1),
+        }).await?;
+
+        let outcome = self.serve_agent(&id, slots, &mut rx, &mut writer).await;
+        reader_task.abort();
+        self.agent_gone(&id, outcome.as_ref().err().map(|e| e.to_string()));
+        outcome
+    }
+
+    fn register(&self, name: &str, peer: SocketAddr, fingerprint: &str, slots: usize) -> String {
+        let mut state = self.state.lock();
+        state.next_agent += 1;
+        let id = format!("agent-{}", state.next_agent);
+        let now = chrono::Utc::now();
+        state.agents.insert(id.clone(), AgentStatus {
+            id: id.clone(),
+            name: name.to_string(),
+            address: peer.to_string(),
+            fingerprint: fingerprint.to_string(),
+            slots,
+            connected_at: now,
+            last_seen: now,
+            alive: true,
+            completed: 0,
+            failed: 0,
+            requeued: 0,
+        });
+        id
+    }
+
+    /// Keep an agent busy until the scan is done or the agent is lost
+    async fn serve_agent<W: AsyncWrite + Unpin>(
+        &self,
+        id: &str,
+        slots: usize,
+        rx: &mut mpsc::Receiver<Result<AgentMessage, anyhow::Error>>,
+        writer: &mut W,
+    ) -> Result<(), anyhow::Error> {
+        let mut last_seen = Instant::now();
+        loop {
+            while let Some(unit) = self.take_unit(id, slots) {
+                self.report(CoordinatorEvent::UnitAssigned {
+                    unit: unit.id,
+                    ports: unit.config.ports.len(),
+                    target: unit.config.target.clone(),
+                    attempt: unit.attempt,
+                    agent: id.to_string(),
+                });
+                write_frame(writer, &CoordinatorMessage::Assign { unit: Box::new(unit) }).await?;
+            }
+            if self.state.lock().is_done() {
+                write_frame(writer, &CoordinatorMessage::Shutdown).await?;
+                return Ok(());
+            }
+
+            tokio::select! {
+                message = rx.recv() => match message {
+                    Some(Ok(message)) => {
+                        last_seen = Instant::now();
+                        self.handle_message(id, message);
+                    }
+                    Some(Err(e)) => anyhow::bail!("connection lost: {}", e),
+                    None => anyhow::bail!("connection closed"),
+                },
+                _ = tokio::time::sleep_until((last_seen + self.config.agent_timeout).into()) => {
+                    anyhow::bail!("no heartbeat for {} seconds", self.config.agent_timeout.as_secs());
+                }
+                _ = self.changed.notified() => {}
+            }
+        }
+    }
+
+    /// Move the next pending unit to `agent` if it has a free slot
+    fn take_unit(&self, agent: &str, slots: usize) -> Option<WorkUnit> {
+        let mut state = self.state.lock();
+        let busy = state.running.values().filter(|(owner, _)| owner == agent).count();
+        if busy >= slots {
+            return None;
+        }
+        let unit = state.pending.pop_front()?;
+        state.running.insert(unit.id, (agent.to_string(), unit.clone()));
+        Some(unit)
+    }
+
+    fn handle_message(&self, agent: &str, message: AgentMessage) {
+        let mut state = self.state.lock();
+        if let Some(status) = state.agents.get_mut(agent) {
+            status.last_seen = chrono::Utc::now();
+        }
+
+        match message {
+            AgentMessage::Hello { .. } | AgentMessage::Heartbeat { .. } => return,
+            AgentMessage::Completed { unit, results } => {
+                // A unit re-queued from a slow agent may still finish there first
+                if !state.finished.insert(unit) {
+                    return;
+                }
+                state.running.remove(&unit);
+                state.pending.retain(|pending| pending.id != unit);
+                if let Some(status) = state.agents.get_mut(agent) {
+                    status.completed += 1;
+                }
+                let results = *results;
+                match state.hosts.get_mut(&results.target) {
+                    Some(merged) => merge_results(merged, results),
+                    None => {
+                        state.hosts.insert(results.target.clone(), results);
+                    }
+                }
+                self.report(CoordinatorEvent::UnitCompleted {
+                    unit,
+                    agent: agent.to_string(),
+                    finished: state.finished.len(),
+                    total: state.total,
+                });
+            }
+            AgentMessage::Failed { unit, error } => {
+                let owned = matches!(state.running.get(&unit), Some((owner, _)) if owner == agent);
+                if !owned {
+                    return;
+                }
+                let (_, work) = state.running.remove(&unit).expect("unit is running");
+                if let Some(status) = state.agents.get_mut(agent) {
+                    status.failed += 1;
+                }
+                self.report(CoordinatorEvent::UnitFailed { unit, agent: agent.to_string(), error: error.clone() });
+                let max_attempts = self.config.max_attempts;
+                retry(&mut state, work, error, max_attempts);
+            }
+        }
+        drop(state);
+        self.changed.notify_waiters();
+    }
+
+    /// Mark an agent dead and put its running units back in the queue
+    fn agent_gone(&self, agent: &str, error: Option<String>) {
+        let mut state = self.state.lock();
+        let lost: Vec<u64> = state.running.iter()
+            .filter(|(_, (owner, _))| owner == agent)
+            .map(|(id, _)| *id)
+            .collect();
+        let reason = error.unwrap_or_else(|| "agent disconnected".to_string());
+        for id in &lost {
+            if let Some((_, unit)) = state.running.remove(id) {
+                let max_attempts = self.config.max_attempts;
+                retry(&mut state, unit, format!("{} lost: {}", agent, reason), max_attempts);
+            }
+        }
+        if let Some(status) = state.agents.get_mut(agent) {
+            status.alive = false;
+            status.requeued += lost.len();
+        }
+        if !lost.is_empty() {
+            self.report(CoordinatorEvent::AgentLost { agent: agent.to_string(), reason, requeued: lost.len() });
+        }
+        drop(state);
+        self.changed.notify_waiters();
+    }
+}
+
+/// Queue a unit for another try, or record it as failed
+fn retry(state: &mut State, mut unit: WorkUnit, error: String, max_attempts: u32) {
+    if unit.attempt >= max_attempts {
+        state.failures.push(UnitFailure {
+            unit: unit.id,
+            target: unit.config.target.clone(),
+            ports: unit.config.ports.clone(),
+            attempts: unit.attempt,
+            error,
+        });
+        state.finished.insert(unit.id);
+    } else {
+        unit.attempt += 1;
+        state.pending.push_front(unit);
+    }
+}
+
+/// Fold one unit's results into the results already held for its host
+fn merge_results(merged: &mut ScanResults, results: ScanResults) {
+    let mut open: Vec<u16> = merged.open_ports.iter().chain(results.open_ports.iter()).copied().collect();
+    open.sort_unstable();
+    open.dedup();
+    merged.results.extend(results.results);
+    merged.open_ports = open.into_iter().collect();
+}
+
+/// Agent settings
+#[derive(Debug, Clone)]
+pub struct AgentConfig {
+    /// Coordinator address (host:port)
+    pub coordinator: String,
+    /// Name the coordinator's certificate must be valid for
+    pub server_name: String,
+    /// Agent certificate, key and the CA the coordinator must be issued by
+    pub tls: TlsFiles,
+    /// Name reported to the coordinator
+    pub name: String,
+    /// Units to run at the same time
+    pub slots: usize,
+    /// Where progress is reported (dropped when `None`)
+    pub events: Option<mpsc::UnboundedSender<AgentEvent>>,
+}
+
+/// Progress of an agent
+#[derive(Debug, Clone)]
+pub enum AgentEvent {
+    /// The coordinator accepted the agent
+    Connected {
+        /// Coordinator address
+        coordinator: String,
+        /// Identifier the coordinator gave the agent
+        agent: String,
+    },
+    /// The coordinator handed out a unit
+    UnitReceived {
+        /// Unit identifier
+        unit: u64,
+        /// Ports in the unit
+        ports: usize,
+        /// Host the unit scans
+        target: String,
+        /// Attempt number
+        attempt: u32,
+    },
+    /// The coordinator ended the scan
+    Finished {
+        /// Units completed by this agent
+        completed: usize,
+    },
+}
+
+impl std::fmt::Display for AgentEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AgentEvent::Connected { coordinator, agent } => write!(f, "connected to {} as {}", coordinator, agent),
+            AgentEvent::UnitReceived { unit, ports, target, attempt } => write!(f,
+                "unit {}: {} ports on {} (attempt {})", unit, ports, target, attempt),
+            AgentEvent::Finished { completed } => write!(f,
+                "coordinator finished the scan; {} units completed here", completed),
+        }
+    }
+}
+
+/// Runs units handed out by a coordinator on the local scan engine
+pub struct Agent {
+    config: AgentConfig,
+}
+
+impl Agent {
+    /// Create an agent
+    pub fn new(config: AgentConfig) -> Self {
+        Self { config }
+    }
+
+    /// Pass an event to whoever asked for them
+    fn report(&self, event: AgentEvent) {
+        if let Some(events) = &self.config.events {
+            let _ = events.send(event);
+        }
+    }
+
+    /// Connect, work until the coordinator says the scan is done, and return the units completed
+    pub async fn run(&self) -> Result<usize, anyhow::Error> {
+        let connector = tokio_rustls::TlsConnector::from(Arc::new(self.config.tls.client_config()?));
+        let server_name = match self.config.server_name.parse::<IpAddr>() {
+            Ok(ip) => rustls::ServerName::IpAddress(ip),
+            Err(_) => rustls::ServerName::try_from(self.config.server_name.as_str())
+                .map_err(|_| anyhow::anyhow!("Invalid server name: {}", self.config.server_name))?,
+        };
+        // Talking to the coordinator isn't a probe, so this bypasses the scope guard and audit log
+        let stream = TcpStream::connect(&self.config.coordinator).await?;
+        let tls = connector.connect(server_name, stream).await?;
+        let (mut reader, mut writer) = tokio::io::split(tls);
+
+        // All writes go through one task so heartbeats and results don't interleave
+        let (out, mut outgoing) = mpsc::channel::<AgentMessage>(64);
+        let mut writer_task = AbortOnDrop(tokio::spawn(async move {
+            while let Some(message) = outgoing.recv().await {
+                write_frame(&mut writer, &message).await?;
+            }
+            Ok::<_, anyhow::Error>(())
+        }));
+
+        // Read frames in their own task so waiting for one can be cancelled safely
+        let (tx, mut incoming) = mpsc::channel::<Result<CoordinatorMessage, anyhow::Error>>(16);
+        let _reader_task = AbortOnDrop(tokio::spawn(async move {
+            loop {
+                let message = read_frame::<_, CoordinatorMessage>(&mut reader).await;
+                let failed = message.is_err();
+                if tx.send(message).await.is_err() || failed {
+                    break;
+                }
+            }
+        }));
+
+        let slots = self.config.slots.max(1);
+        out.send(AgentMessage::Hello { name: self.config.name.clone(), slots }).await?;
+        let heartbeat = match incoming.recv().await {
+            Some(Ok(CoordinatorMessage::Welcome { agent, heartbeat_secs })) => {
+                self.report(AgentEvent::Connected { coordinator: self.config.coordinator.clone(), agent });
+                Duration::from_secs(heartbeat_secs.max(1))
+            }
+            Some(Ok(other)) => anyhow::bail!("unexpected first message from coordinator: {:?}", other),
+            Some(Err(e)) => return Err(e),
+            None => anyhow::bail!("coordinator closed the connection"),
+        };
+
+        let running: Arc<Mutex<BTreeSet<u64>>> = Arc::new(Mutex::new(BTreeSet::new()));
+        let heartbeat_task = {
+            let out = out.clone();
+            let running = running.clone();
+            AbortOnDrop(tokio::spawn(async move {
+                let mut interval = tokio::time::interval(heartbeat);
+                loop {
+                    interval.tick().await;
+                    let running = running.lock().iter().copied().collect();
+                    if out.send(AgentMessage::Heartbeat { running }).await.is_err() {
+                        break;
+                    }
+                }
+            }))
+        };
+
+        // Dropping the set on an early return aborts any scans still running
+        let mut units = tokio::task::JoinSet::new();
+        let mut completed = 0;
+        loop {
+            let message = tokio::select! {
+                message = incoming.recv() => message,
+                Some(finished) = units.join_next(), if !units.is_empty() => {
+                    if matches!(finished, Ok(true)) {
+                        completed += 1;
+                    }
+                    continue;
+                }
+            };
+            match message {
+                Some(Ok(CoordinatorMessage::Assign { unit })) => {
+                    self.report(AgentEvent::UnitReceived {
+                        unit: unit.id,
+                        ports: unit.config.ports.len(),
+                        target: unit.config.target.clone(),
+                        attempt: unit.attempt,
+                    });
+                    running.lock().insert(unit.id);
+                    let out = out.clone();
+                    let running = running.clone();
+                    units.spawn(async move {
+                        let id = unit.id;
+                        // Its own task, so a panicking scan is reported instead of hanging the unit
+                        let mut scan = AbortOnDrop(tokio::spawn(run_unit(*unit)));
+                        let message = match (&mut scan.0).await {
+                            Ok(Ok(results)) => AgentMessage::Completed { unit: id, results: Box::new(results) },
+                            Ok(Err(e)) => AgentMessage::Failed { unit: id, error: e.to_string() },
+                            Err(e) => AgentMessage::Failed { unit: id, error: format!("scan task aborted: {}", e) },
+                        };
+                        let ok = matches!(message, AgentMessage::Completed { .. });
+                        running.lock().remove(&id);
+                        let _ = out.send(message).await;
+                        ok
+                    });
+                }
+                Some(Ok(CoordinatorMessage::Shutdown)) => break,
+                Some(Ok(CoordinatorMessage::Welcome { .. })) => {}
+                Some(Err(e)) => return Err(anyhow::anyhow!("lost connection to coordinator: {}", e)),
+                None => anyhow::bail!("lost connection to coordinator"),
+            }
+        }
+
+        // Units whose results went out before the shutdown still count
+        while let Some(finished) = units.join_next().await {
+            if matches!(finished, Ok(true)) {
+                completed += 1;
+            }
+        }
+        drop(heartbeat_task);
+        drop(out);
+        let _ = (&mut writer_task.0).await;
+        self.report(AgentEvent::Finished { completed });
+        Ok(completed)
+    }
+}
+
+/// Aborts a task when dropped, so an agent that stops (or is dropped) takes
+/// its connection and scans down with it
+struct AbortOnDrop<T>(tokio::task::JoinHandle<T>);
+
+impl<T> Drop for AbortOnDrop<T> {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+/// Run one unit on the local engine
+async fn run_unit(unit: WorkUnit) -> Result<ScanResults, anyhow::Error> {
+    let mut scanner = ScannerBuilder::from_config(unit.config).build().await?;
+    Ok(scanner.run().await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Issue a CA plus one certificate per node (valid for localhost and 127.0.0.1)
+    fn issue_certificates(dir: &Path, nodes: &[&str]) -> HashMap<String, TlsFiles> {
+        let mut ca_params = rcgen::CertificateParams::new(Vec::new());
+        ca_params.is_ca = rcgen::IsCa::Ca(rcgen::BasicConstraints::Unconstrained);
+        ca_params.distinguished_name.push(rcgen::DnType::CommonName, "scan-ca");
+        let ca = rcgen::Certificate::from_params(ca_params).unwrap();
+        let ca_path = dir.join("ca.pem");
+        std::fs::write(&ca_path, ca.serialize_pem().unwrap()).unwrap();
+
+        let mut files = HashMap::new();
+        for node in nodes {
+            let mut params = rcgen::CertificateParams::new(vec!["localhost".to_string()]);
+            params.subject_alt_names.push(rcgen::SanType::IpAddress(IpAddr::V4(Ipv4Addr::LOCALHOST)));
+            params.distinguished_name.push(rcgen::DnType::CommonName, *node);
+            let cert = rcgen::Certificate::from_params(params).unwrap();
+            let cert_path = dir.join(format!("{}.pem", node));
+            let key_path = dir.join(format!("{}.key", node));
+            std::fs::write(&cert_path, cert.serialize_pem_with_signer(&ca).unwrap()).unwrap();
+            std::fs::write(&key_path, cert.serialize_private_key_pem()).unwrap();
+            files.insert(node.to_string(), TlsFiles { ca: ca_path.clone(), cert: cert_path, key: key_path });
+        }
+        files
+    }
+
+    #[test]
+    fn units_cover_every_port_of_every_host() {
+        let template = ScanConfig { ports: (1..=10).collect(), ..ScanConfig::default() };
+        let units = plan_units(&template, &["127.0.0.1".to_string(), "10.0.0.0/31".to_string()], 4).unwrap();
+        assert_eq!(units.len(), 9);
+        assert!(units.iter().enumerate().all(|(i, unit)| unit.id == i as u64 && unit.attempt == 1));
+        let ports: Vec<u16> = units.iter().filter(|u| u.config.target == "10.0.0.1").flat_map(|u| u.config.ports.clone()).collect();
+        assert_eq!(ports, (1..=10).collect::<Vec<u16>>());
+
+        assert!(plan_units(&template, &["10.0.0.0/8".to_string()], 4).is_err());
+        assert!(plan_units(&template, &["127.0.0.1".to_string()], 0).is_err());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn lost_agent_units_are_requeued_and_results_merged() {
+        let dir = tempfile::tempdir().unwrap();
+        let tls = issue_certificates(dir.path(), &["coordinator", "doomed", "worker-1", "worker-2"]);
+
+        // An open port whose service never speaks keeps a unit busy for the banner timeout
+        let silent = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let silent_port = silent.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let mut held = Vec::new();
+            while let Ok((stream, _)) = silent.accept().await {
+                held.push(stream);
+            }
+        });
+        let open = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let open_port = open.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = open.accept().await {
+                let _ = stream.write_all(b"SSH-2.0-OpenSSH_9.6\r\n").await;
+            }
+        });
+        let closed_port = {
+            let probe = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+            probe.local_addr().unwrap().port()
+        };
+
+        let template = ScanConfig {
+            ports: vec![silent_port, open_port, closed_port],
+            timeout: 1.0,
+            timeout_connect: 1.0,
+            timeout_banner: 2.0,
+            log_file: dir.path().join("scan.log"),
+            ..ScanConfig::default()
+        };
+        let units = plan_units(&template, &["127.0.0.1".to_string()], 1).unwrap();
+
+        let listen = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        let (events, mut reports) = mpsc::unbounded_channel();
+        let coordinator = Coordinator::new(CoordinatorConfig {
+            listen,
+            tls: tls["coordinator"].clone(),
+            heartbeat: Duration::from_secs(1),
+            agent_timeout: Duration::from_secs(5),
+            max_attempts: 3,
+            events: Some(events),
+        }, units);
+        let run = tokio::spawn(coordinator.clone().run());
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        let agent = |name: &str| {
+            Agent::new(AgentConfig {
+                coordinator: listen.to_string(),
+                server_name: "localhost".to_string(),
+                tls: tls[name].clone(),
+                name: name.to_string(),
+                slots: 1,
+                events: None,
+            })
+        };
+
+        // The first agent takes unit 0 (the silent port) and dies while scanning it
+        let doomed = agent("doomed");
+        let doomed = tokio::spawn(async move { doomed.run().await });
+        tokio::time::timeout(Duration::from_secs(10), async {
+            while !coordinator.state.lock().running.contains_key(&0) {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        }).await.expect("unit 0 was never handed out");
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        doomed.abort();
+
+        let workers: Vec<_> = ["worker-1", "worker-2"].into_iter()
+            .map(|name| {
+                let worker = agent(name);
+                tokio::spawn(async move { worker.run().await })
+            })
+            .collect();
+
+        let results = tokio::time::timeout(Duration::from_secs(60), run).await
+            .expect("scan did not finish").unwrap().unwrap();
+        let mut completed = 0;
+        for worker in workers {
+            completed += worker.await.unwrap().unwrap();
+        }
+
+        assert_eq!(results.units, 3);
+        assert!(results.failed_units.is_empty(), "{:?}", results.failed_units);
+        assert_eq!(completed, 3);
+        let lost = results.agents.iter().find(|a| a.name == "doomed").unwrap();
+        assert_eq!(lost.requeued, 1);
+        assert_eq!(lost.completed, 0);
+        assert!(!lost.alive);
+
+        assert_eq!(results.hosts.len(), 1);
+        let host = &results.hosts[0];
+        let mut expected = vec![silent_port, open_port];
+        expected.sort_unstable();
+        assert_eq!(host.open_ports, expected);
+        assert!(host.results.contains_key(&silent_port));
+        assert!(host.results.contains_key(&open_port));
+
+        let mut completions = 0;
+        let mut requeued = 0;
+        while let Ok(event) = reports.try_recv() {
+            match event {
+                CoordinatorEvent::UnitCompleted { .. } => completions += 1,
+                CoordinatorEvent::AgentLost { requeued: units, .. } => requeued += units,
+                _ => {}
+            }
+        }
+        assert_eq!(completions, 3);
+        assert_eq!(requeued, 1);
+    }
+}
@@ -0,0 +1,489 @@
+//! Scan configuration and scanner builder
+//!
+//! `ScanConfig` holds every setting that controls a scan as plain data, so it can
+//! be built in code, deserialized from JSON or filled from CLI flags.
+//! `ScannerBuilder` wraps a config together with the runtime-only pieces
+//! (loggers, packet transport) and produces a ready-to-run `QuantumScanner`.
+
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::dns::DnsConfig;
+use crate::error::{Result, ScanError};
+use crate::http_discovery::HttpDiscoveryConfig;
+use crate::models::ScanType;
+use crate::packet::{PacketTransport, ProbeOptions};
+use crate::scanner::QuantumScanner;
+use crate::scope::ScopeConfig;
+use crate::snmp::SnmpConfig;
+use crate::utils;
+
+/// Settings for fragmented SYN scans
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FragmentConfig {
+    /// Minimum fragment size in bytes
+    pub min_size: u16,
+    /// Maximum fragment size in bytes
+    pub max_size: u16,
+    /// Minimum delay between fragments in seconds
+    pub min_delay: f64,
+    /// Maximum delay between fragments in seconds
+    pub max_delay: f64,
+    /// Timeout waiting for a response to fragmented probes in seconds
+    pub timeout: u64,
+    /// Minimum size of the first fragment in bytes
+    pub first_min_size: u16,
+    /// Split every probe into exactly two fragments
+    pub two_frags: bool,
+}
+
+impl Default for FragmentConfig {
+    fn default() -> Self {
+        Self {
+            min_size: 64,
+            max_size: 128,
+            min_delay: 0.01,
+            max_delay: 0.1,
+            timeout: 10,
+            first_min_size: 64,
+            two_frags: false,
+        }
+    }
+}
+
+/// Complete configuration for a scan
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScanConfig {
+    /// Target hostname or IP address
+    pub target: String,
+    /// Ports to scan
+    pub ports: Vec<u16>,
+    /// Scan techniques to use
+    pub scan_types: Vec<ScanType>,
+    /// Maximum number of concurrent probes
+    pub concurrency: usize,
+    /// Maximum probes per second (0 = unlimited)
+    pub rate: usize,
+    /// Enable basic evasion techniques
+    pub evasion: bool,
+    /// Enable enhanced evasion (OS mimicry, TTL jitter)
+    pub enhanced_evasion: bool,
+    /// Operating system to mimic with enhanced evasion
+    pub mimic_os: String,
+    /// TTL jitter range for enhanced evasion
+    pub ttl_jitter: u8,
+    /// Protocol variant for enhanced evasion
+    pub protocol_variant: Option<String>,
+    /// Verbose output
+    pub verbose: bool,
+    /// Scan over IPv6
+    pub ipv6: bool,
+    /// Produce JSON output
+    pub json_output: bool,
+    /// Probe timeout in seconds
+    pub timeout: f64,
+    /// Connect timeout in seconds
+    pub timeout_connect: f64,
+    /// Banner grab timeout in seconds
+    pub timeout_banner: f64,
+    /// Protocol to mimic in MIMIC scans
+    pub mimic_protocol: String,
+    /// Fragmentation settings
+    pub fragmentation: FragmentConfig,
+    /// Log file path
+    pub log_file: PathBuf,
+    /// HTTP content/vhost discovery (disabled when `None`)
+    pub http_discovery: Option<HttpDiscoveryConfig>,
+    /// Run read-only default-configuration checks
+    pub default_checks: bool,
+    /// SNMP community guessing and MIB reads on UDP results (disabled when `None`)
+    pub snmp: Option<SnmpConfig>,
+    /// DNS server inspection on port 53 (zones, recursion test, AXFR)
+    pub dns: DnsConfig,
+    /// Redirects followed from a web server's first response (0 = don't follow)
+    pub max_redirects: usize,
+    /// Follow redirects to other hosts even without a scope (a scope always allows them)
+    pub cross_host_redirects: bool,
+    /// Audit HTTP methods (OPTIONS, TRACE, PUT/DELETE, PROPFIND) on web servers
+    pub http_methods: bool,
+    /// Engagement scope enforced on every connection (unrestricted when `None`)
+    pub scope: Option<ScopeConfig>,
+}
+
+impl Default for ScanConfig {
+    fn default() -> Self {
+        Self {
+            target: String::new(),
+            ports: Vec::new(),
+            scan_types: vec![ScanType::Syn],
+            concurrency: 100,
+            rate: 0,
+            evasion: false,
+            enhanced_evasion: false,
+            mimic_os: "random".to_string(),
+            ttl_jitter: 2,
+            protocol_variant: None,
+            verbose: false,
+            ipv6: false,
+            json_output: false,
+            timeout: 5.0,
+            timeout_connect: 3.0,
+            timeout_banner: 3.0,
+            mimic_protocol: "HTTP".to_string(),
+            fragmentation: FragmentConfig::default(),
+            log_file: PathBuf::from("scanner.log"),
+            http_discovery: None,
+            default_checks: false,
+            snmp: None,
+            dns: DnsConfig::default(),
+            max_redirects: 5,
+            cross_host_redirects: false,
+            http_methods: false,
+            scope: None,
+        }
+    }
+}
+
+impl ScanConfig {
+    /// Check the configuration for missing or inconsistent values
+    pub fn validate(&self) -> Result<()> {
+        if self.target.trim().is_empty() {
+            return Err(ScanError::MissingTarget);
+        }
+        if self.ports.is_empty() {
+            return Err(ScanError::NoPorts);
+        }
+        if self.ports.contains(&0) {
+            return Err(ScanError::InvalidConfig("port 0 is not scannable".to_string()));
+        }
+        if self.scan_types.is_empty() {
+            return Err(ScanError::NoScanTypes);
+        }
+        if self.concurrency == 0 {
+            return Err(ScanError::InvalidConfig("concurrency must be at least 1".to_string()));
+        }
+        for (name, value) in [
+            ("timeout", self.timeout),
+            ("timeout_connect", self.timeout_connect),
+            ("timeout_banner", self.timeout_banner),
+        ] {
+            if value.is_nan() || value <= 0.0 {
+                return Err(ScanError::InvalidConfig(format!("{} must be positive", name)));
+            }
+        }
+
+        let frag = &self.fragmentation;
+        if frag.min_size > frag.max_size {
+            return Err(ScanError::InvalidConfig(format!(
+                "fragment min size {} exceeds max size {}",
+                frag.min_size, frag.max_size
+            )));
+        }
+        if frag.min_delay > frag.max_delay {
+            return Err(ScanError::InvalidConfig(format!(
+                "fragment min delay {} exceeds max delay {}",
+                frag.min_delay, frag.max_delay
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+/// Builder for `QuantumScanner`
+///
+/// ```no_run
+/// # async fn example() -> Result<(), quantum_scanner::ScanError> {
+/// use quantum_scanner::{ScannerBuilder, ScanType};
+///
+/// let mut scanner = ScannerBuilder::new("192.168.1.10")
+///     .ports(vec![22, 80, 443])
+///     .scan_types(vec![ScanType::Syn])
+///     .concurrency(50)
+///     .build()
+///     .await?;
+///
+/// let results = scanner.run().await?;
+/// for port in &results.open_ports {
+///     println!("{} is open", port);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Default)]
+pub struct ScannerBuilder {
+    config: ScanConfig,
+    memory_log: Option<Arc<utils::MemoryLogBuffer>>,
+    enhanced_logger: Option<Arc<utils::EnhancedLogger>>,
+    packet_transport: Option<Arc<dyn PacketTransport>>,
+    shared_scope: Option<Arc<crate::scope::Scope>>,
+    audit_log: Option<Arc<crate::audit::AuditLog>>,
+}
+
+impl ScannerBuilder {
+    /// Start a builder for the given target with default settings
+    pub fn new(target: &str) -> Self {
+        Self::from_config(ScanConfig {
+            target: target.to_string(),
+            ..ScanConfig::default()
+        })
+    }
+
+    /// Start a builder from an existing configuration
+    pub fn from_config(config: ScanConfig) -> Self {
+        Self {
+            config,
+            memory_log: None,
+            enhanced_logger: None,
+            packet_transport: None,
+            shared_scope: None,
+            audit_log: None,
+        }
+    }
+
+    /// Ports to scan
+    pub fn ports(mut self, ports: Vec<u16>) -> Self {
+        self.config.ports = ports;
+        self
+    }
+
+    /// Scan techniques to use
+    pub fn scan_types(mut self, scan_types: Vec<ScanType>) -> Self {
+        self.config.scan_types = scan_types;
+        self
+    }
+
+    /// Maximum number of concurrent probes
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.config.concurrency = concurrency;
+        self
+    }
+
+    /// Maximum probes per second (0 = unlimited)
+    pub fn rate(mut self, rate: usize) -> Self {
+        self.config.rate = rate;
+        self
+    }
+
+    /// Probe, connect and banner timeouts in seconds
+    pub fn timeouts(mut self, probe: f64, connect: f64, banner: f64) -> Self {
+        self.config.timeout = probe;
+        self.config.timeout_connect = connect;
+        self.config.timeout_banner = banner;
+        self
+    }
+
+    /// Enable basic evasion techniques
+    pub fn evasion(mut self, enabled: bool) -> Self {
+        self.config.evasion = enabled;
+        self
+    }
+
+    /// Enable enhanced evasion with OS mimicry and TTL jitter
+    pub fn enhanced_evasion(mut self, mimic_os: &str, ttl_jitter: u8, protocol_variant: Option<&str>) -> Self {
+        self.config.enhanced_evasion = true;
+        self.config.mimic_os = mimic_os.to_string();
+        self.config.ttl_jitter = ttl_jitter;
+        self.config.protocol_variant = protocol_variant.map(str::to_string);
+        self
+    }
+
+    /// Protocol to mimic in MIMIC scans
+    pub fn mimic_protocol(mut self, protocol: &str) -> Self {
+        self.config.mimic_protocol = protocol.to_string();
+        self
+    }
+
+    /// Fragmentation settings for FRAG scans
+    pub fn fragmentation(mut self, fragmentation: FragmentConfig) -> Self {
+        self.config.fragmentation = fragmentation;
+        self
+    }
+
+    /// Scan over IPv6
+    pub fn ipv6(mut self, enabled: bool) -> Self {
+        self.config.ipv6 = enabled;
+        self
+    }
+
+    /// Verbose output
+    pub fn verbose(mut self, enabled: bool) -> Self {
+        self.config.verbose = enabled;
+        self
+    }
+
+    /// Log file path
+    pub fn log_file(mut self, path: PathBuf) -> Self {
+        self.config.log_file = path;
+        self
+    }
+
+    /// Enable HTTP content and virtual-host discovery
+    pub fn http_discovery(mut self, discovery: HttpDiscoveryConfig) -> Self {
+        self.config.http_discovery = Some(discovery);
+        self
+    }
+
+    /// Enable read-only default-configuration checks
+    pub fn default_checks(mut self, enabled: bool) -> Self {
+        self.config.default_checks = enabled;
+        self
+    }
+
+    /// Enable SNMP probing of UDP ports
+    pub fn snmp(mut self, snmp: SnmpConfig) -> Self {
+        self.config.snmp = Some(snmp);
+        self
+    }
+
+    /// DNS inspection settings
+    pub fn dns(mut self, dns: DnsConfig) -> Self {
+        self.config.dns = dns;
+        self
+    }
+
+    /// Maximum number of HTTP redirects to follow (0 disables following)
+    pub fn max_redirects(mut self, max: usize) -> Self {
+        self.config.max_redirects = max;
+        self
+    }
+
+    /// Follow redirects to other hosts when no scope is set
+    pub fn cross_host_redirects(mut self, enabled: bool) -> Self {
+        self.config.cross_host_redirects = enabled;
+        self
+    }
+
+    /// Enable the HTTP method and WebDAV audit (sends PUT and DELETE)
+    pub fn http_methods(mut self, enabled: bool) -> Self {
+        self.config.http_methods = enabled;
+        self
+    }
+
+    /// Restrict every connection and probe to an engagement scope
+    pub fn scope(mut self, scope: ScopeConfig) -> Self {
+        self.config.scope = Some(scope);
+        self
+    }
+
+    /// Attach an in-memory log buffer
+    pub fn memory_log(mut self, log: Arc<utils::MemoryLogBuffer>) -> Self {
+        self.memory_log = Some(log);
+        self
+    }
+
+    /// Attach an enhanced logger for packet capture
+    pub fn enhanced_logger(mut self, logger: Arc<utils::EnhancedLogger>) -> Self {
+        self.enhanced_logger = Some(logger);
+        self
+    }
+
+    /// Send and receive raw probes through `transport`
+    ///
+    /// Pass a `simnet::SimulatedNetwork` to run the raw scan types without
+    /// root, or a `packet::RawSocketTransport` bound to a chosen address.
+    pub fn packet_transport(mut self, transport: Arc<dyn PacketTransport>) -> Self {
+        self.packet_transport = Some(transport);
+        self
+    }
+
+    /// Enforce an already-built scope instead of one made from the config
+    ///
+    /// Scanners given the same scope share its refusal record, so a caller
+    /// scanning several hosts under one scope gets a single list back.
+    pub fn shared_scope(mut self, scope: Arc<crate::scope::Scope>) -> Self {
+        self.shared_scope = Some(scope);
+        self
+    }
+
+    /// Record every probe the scanner sends in `log`
+    pub fn audit_log(mut self, log: Arc<crate::audit::AuditLog>) -> Self {
+        self.audit_log = Some(log);
+        self
+    }
+
+    /// Access the configuration built so far
+    pub fn config(&self) -> &ScanConfig {
+        &self.config
+    }
+
+    /// Validate the configuration and create the scanner
+    pub async fn build(self) -> Result<QuantumScanner> {
+        let config = self.config;
+        config.validate()?;
+
+        // Check the target against the scope before the engine exists, so
+        // an out-of-scope target never gets as far as a lookup
+        let scope = match (self.shared_scope, config.scope.clone()) {
+            (Some(scope), _) => Some(scope),
+            (None, Some(scope)) => Some(Arc::new(crate::scope::Scope::new(scope, self.enhanced_logger.clone())?)),
+            (None, None) => None,
+        };
+        if let Some(scope) = &scope {
+            scope.check_target(&config.target).await?;
+        }
+
+        let mut scanner = QuantumScanner::new(
+            &config.target,
+            config.ports.clone(),
+            config.scan_types.clone(),
+            config.concurrency,
+            config.rate,
+            config.evasion || config.enhanced_evasion,
+            config.verbose,
+            config.ipv6,
+            config.timeout,
+            config.timeout_connect,
+            config.timeout_banner,
+            &config.mimic_protocol,
+            config.fragmentation.clone(),
+        ).await?;
+
+        if let Some(scope) = scope {
+            scanner.set_scope(scope);
+        }
+        if let Some(log) = self.audit_log {
+            scanner.set_audit_log(log);
+        }
+        if config.enhanced_evasion {
+            scanner.set_enhanced_evasion(true, &config.mimic_os, config.ttl_jitter);
+            scanner.set_protocol_variant(config.protocol_variant.as_deref());
+        }
+
+        if let Some(discovery) = config.http_discovery.clone() {
+            scanner.set_http_discovery(discovery);
+        }
+        scanner.set_default_checks(config.default_checks);
+        if let Some(snmp) = config.snmp.clone() {
+            scanner.set_snmp(snmp);
+        }
+        scanner.set_dns(config.dns.clone());
+        scanner.set_max_redirects(config.max_redirects);
+        scanner.set_cross_host_redirects(config.cross_host_redirects);
+        scanner.set_http_methods(config.http_methods);
+        scanner.set_control(Arc::new(crate::control::ScanControl::new(config.rate)));
+
+        if let Some(transport) = self.packet_transport {
+            let options = ProbeOptions {
+                timeout: std::time::Duration::from_secs_f64(config.timeout.max(0.0)),
+                fragmentation: config.fragmentation.clone(),
+                mimic_payload: crate::packet::mimic_payload(&config.mimic_protocol).to_vec(),
+                ..ProbeOptions::default()
+            };
+            scanner.set_packet_transport(transport, options);
+        }
+
+        if let Some(log) = self.memory_log {
+            scanner.set_memory_log(log);
+        }
+        if let Some(logger) = self.enhanced_logger {
+            scanner.set_enhanced_logger(logger);
+        }
+
+        Ok(scanner)
+    }
+}
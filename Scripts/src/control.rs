@@ -0,0 +1,194 @@
+//! Runtime control of a running scan
+//!
+//! `ScanControl` is shared between the scan engine and whatever is driving it
+//! (the dashboard, the daemon). Every probe passes `checkpoint` on its way out
+//! through the scanner's `Egress`, which is where pausing, rate limiting and
+//! host skipping take effect. The same handle carries the probe, timeout and
+//! retransmit counters shown to operators.
+
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+use tokio::sync::Notify;
+
+/// Snapshot of the scan counters
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ScanCounters {
+    /// Probes sent so far
+    pub probes: u64,
+    /// Probes that got no answer
+    pub timeouts: u64,
+    /// Probes that had to be re-sent
+    pub retransmits: u64,
+}
+
+/// Shared pause / rate / skip state for a scan
+#[derive(Debug)]
+pub struct ScanControl {
+    paused: AtomicBool,
+    cancelled: AtomicBool,
+    rate: AtomicUsize,
+    next_slot: Mutex<Instant>,
+    skipped_hosts: Mutex<HashSet<String>>,
+    resumed: Notify,
+    probes: AtomicU64,
+    timeouts: AtomicU64,
+    retransmits: AtomicU64,
+}
+
+impl Default for ScanControl {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+impl ScanControl {
+    /// Create a control handle with the given rate limit (0 = unlimited)
+    pub fn new(rate: usize) -> Self {
+        Self {
+            paused: AtomicBool::new(false),
+            cancelled: AtomicBool::new(false),
+            rate: AtomicUsize::new(rate),
+            next_slot: Mutex::new(Instant::now()),
+            skipped_hosts: Mutex::new(HashSet::new()),
+            resumed: Notify::new(),
+            probes: AtomicU64::new(0),
+            timeouts: AtomicU64::new(0),
+            retransmits: AtomicU64::new(0),
+        }
+    }
+
+    /// Pause the scan at the next checkpoint
+    pub fn pause(&self) {
+        self.paused.store(true, Ordering::SeqCst);
+    }
+
+    /// Resume a paused scan
+    pub fn resume(&self) {
+        self.paused.store(false, Ordering::SeqCst);
+        self.resumed.notify_waiters();
+    }
+
+    /// Whether the scan is currently paused
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::SeqCst)
+    }
+
+    /// Stop the scan at the next checkpoint
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+        self.resume();
+    }
+
+    /// Whether the scan has been cancelled
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Current rate limit in probes per second (0 = unlimited)
+    pub fn rate(&self) -> usize {
+        self.rate.load(Ordering::SeqCst)
+    }
+
+    /// Set the rate limit in probes per second (0 = unlimited)
+    pub fn set_rate(&self, rate: usize) {
+        self.rate.store(rate, Ordering::SeqCst);
+    }
+
+    /// Raise the rate limit by roughly 25% (no-op when unlimited)
+    pub fn increase_rate(&self) {
+        let rate = self.rate();
+        if rate > 0 {
+            self.set_rate(rate + (rate / 4).max(10));
+        }
+    }
+
+    /// Lower the rate limit by roughly 25%, starting from 1000 pps if unlimited
+    pub fn decrease_rate(&self) {
+        let rate = match self.rate() {
+            0 => 1000,
+            rate => rate,
+        };
+        self.set_rate((rate - (rate / 4).max(10).min(rate - 1)).max(1));
+    }
+
+    /// Skip all remaining work for a host
+    pub fn skip_host(&self, host: &str) {
+        self.skipped_hosts.lock().insert(host.to_string());
+    }
+
+    /// Whether a host has been skipped
+    pub fn is_skipped(&self, host: &str) -> bool {
+        self.skipped_hosts.lock().contains(host)
+    }
+
+    /// Count a probe that was sent
+    pub fn record_probe(&self) {
+        self.probes.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Count a probe that timed out
+    pub fn record_timeout(&self) {
+        self.timeouts.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Count a probe that was retransmitted
+    pub fn record_retransmit(&self) {
+        self.retransmits.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Read the current counters
+    pub fn counters(&self) -> ScanCounters {
+        ScanCounters {
+            probes: self.probes.load(Ordering::Relaxed),
+            timeouts: self.timeouts.load(Ordering::Relaxed),
+            retransmits: self.retransmits.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Wait while the scan is paused, without taking a rate-limit slot
+    ///
+    /// Returns `false` if the host was skipped or the scan cancelled.
+    pub async fn wait_resumed(&self, host: &str) -> bool {
+        loop {
+            if self.is_cancelled() || self.is_skipped(host) {
+                return false;
+            }
+            if !self.is_paused() {
+                return true;
+            }
+            // Re-check periodically in case the resume notification raced us
+            let _ = tokio::time::timeout(Duration::from_millis(250), self.resumed.notified()).await;
+        }
+    }
+
+    /// Gate a probe to `host`
+    ///
+    /// Waits while the scan is paused and then for the next rate-limit slot.
+    /// Returns `false` if the host was skipped or the scan cancelled, in which
+    /// case the probe must not be sent. Scope is the scanner's concern: one
+    /// control handle may be shared by scanners with different scopes.
+    pub async fn checkpoint(&self, host: &str) -> bool {
+        if !self.wait_resumed(host).await {
+            return false;
+        }
+
+        let rate = self.rate();
+        if rate > 0 {
+            let interval = Duration::from_secs_f64(1.0 / rate as f64);
+            let slot = {
+                let mut next = self.next_slot.lock();
+                let now = Instant::now();
+                let slot = (*next).max(now);
+                *next = slot + interval;
+                slot
+            };
+            tokio::time::sleep_until(slot.into()).await;
+        }
+
+        self.record_probe();
+        !self.is_cancelled() && !self.is_skipped(host)
+    }
+}
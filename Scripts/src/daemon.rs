@@ -0,0 +1,1061 @@
+//! Scan daemon with a local HTTP/JSON job API
+//!
+//! Jobs are submitted as a `JobRequest` and run one at a time (or a few, see
+//! `max_parallel_jobs`) from a queue that is persisted under `state_dir`, so a
+//! restart picks up where it left off. Every request must carry the API token as
+//! `Authorization: Bearer <token>`.
+//!
+//! A job request only carries scan settings; anything that touches the daemon's
+//! filesystem (the log file) is decided by the daemon and kept under `state_dir`.
+//!
+//! Endpoints:
+//!
+//! * `POST   /jobs`              submit a job (body: `JobRequest` JSON)
+//! * `GET    /jobs`              list jobs
+//! * `GET    /jobs/{id}`         job status
+//! * `DELETE /jobs/{id}`         cancel a queued or running job
+//! * `GET    /jobs/{id}/results` final `ScanResults`
+//! * `GET    /jobs/{id}/events`  live progress as Server-Sent Events
+//!
+//! The HTTP layer is deliberately small and hand-rolled, like the scanner's
+//! client side; it only needs to serve a handful of teammates on a jump box.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::StreamExt;
+use parking_lot::Mutex;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{broadcast, mpsc, Notify};
+
+use crate::config::{FragmentConfig, ScanConfig, ScannerBuilder};
+use crate::control::ScanControl;
+use crate::dns::DnsConfig;
+use crate::events::ScanEvent;
+use crate::http_discovery::HttpDiscoveryConfig;
+use crate::models::{ScanResults, ScanType};
+use crate::scope::ScopeConfig;
+use crate::snmp::SnmpConfig;
+
+/// Largest request body we accept (a job request is a few KB at most)
+const MAX_BODY_SIZE: usize = 1024 * 1024;
+
+/// Events buffered per job for slow SSE clients
+const EVENT_BUFFER: usize = 1024;
+
+/// Daemon settings
+#[derive(Debug, Clone)]
+pub struct DaemonConfig {
+    /// Address to listen on
+    pub listen: SocketAddr,
+    /// Bearer token required on every request
+    pub token: String,
+    /// Directory holding the persisted job queue and results
+    pub state_dir: PathBuf,
+    /// Number of jobs allowed to run at the same time
+    pub max_parallel_jobs: usize,
+    /// Where problems the daemon recovers from are reported (dropped when `None`)
+    pub events: Option<mpsc::UnboundedSender<DaemonEvent>>,
+}
+
+/// Something the daemon handled without stopping
+#[derive(Debug, Clone)]
+pub enum DaemonEvent {
+    /// Serving an API request failed
+    RequestFailed {
+        /// Client address
+        peer: SocketAddr,
+        /// What went wrong
+        error: String,
+    },
+    /// A persisted job file could not be read and was skipped
+    UnreadableJobFile {
+        /// The skipped file
+        path: PathBuf,
+    },
+    /// A job's state could not be written to disk
+    PersistFailed {
+        /// Job identifier
+        job: String,
+        /// What went wrong
+        error: String,
+    },
+}
+
+impl std::fmt::Display for DaemonEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DaemonEvent::RequestFailed { peer, error } => write!(f, "request from {} failed: {}", peer, error),
+            DaemonEvent::UnreadableJobFile { path } => write!(f, "ignoring unreadable job file {}", path.display()),
+            DaemonEvent::PersistFailed { job, error } => write!(f, "failed to persist job {}: {}", job, error),
+        }
+    }
+}
+
+/// Lifecycle state of a job
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    /// Waiting in the queue
+    Queued,
+    /// Currently scanning
+    Running,
+    /// Finished successfully; results are available
+    Completed,
+    /// Scan returned an error
+    Failed,
+    /// Cancelled by a user
+    Cancelled,
+}
+
+impl JobStatus {
+    fn is_terminal(self) -> bool {
+        matches!(self, JobStatus::Completed | JobStatus::Failed | JobStatus::Cancelled)
+    }
+}
+
+/// Scan settings accepted from API clients
+///
+/// A whitelist of `ScanConfig` fields; unknown fields (including `log_file`)
+/// are rejected rather than silently ignored.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct JobRequest {
+    /// Target hostname or IP address
+    pub target: String,
+    /// Ports to scan
+    pub ports: Vec<u16>,
+    /// Scan techniques to use
+    pub scan_types: Vec<ScanType>,
+    /// Maximum number of concurrent probes
+    pub concurrency: usize,
+    /// Maximum probes per second (0 = unlimited)
+    pub rate: usize,
+    /// Enable basic evasion techniques
+    pub evasion: bool,
+    /// Enable enhanced evasion (OS mimicry, TTL jitter)
+    pub enhanced_evasion: bool,
+    /// Operating system to mimic with enhanced evasion
+    pub mimic_os: String,
+    /// TTL jitter range for enhanced evasion
+    pub ttl_jitter: u8,
+    /// Protocol variant for enhanced evasion
+    pub protocol_variant: Option<String>,
+    /// Scan over IPv6
+    pub ipv6: bool,
+    /// Probe timeout in seconds
+    pub timeout: f64,
+    /// Connect timeout in seconds
+    pub timeout_connect: f64,
+    /// Banner grab timeout in seconds
+    pub timeout_banner: f64,
+    /// Protocol to mimic in MIMIC scans
+    pub mimic_protocol: String,
+    /// Fragmentation settings
+    pub fragmentation: FragmentConfig,
+    /// HTTP content/vhost discovery (disabled when `None`)
+    pub http_discovery: Option<HttpDiscoveryConfig>,
+    /// Run read-only default-configuration checks
+    pub default_checks: bool,
+    /// SNMP community guessing and MIB reads on UDP results (disabled when `None`)
+    pub snmp: Option<SnmpConfig>,
+    /// DNS server inspection on port 53
+    pub dns: DnsConfig,
+    /// Redirects followed from a web server's first response
+    pub max_redirects: usize,
+    /// Follow redirects to other hosts without a scope
+    pub cross_host_redirects: bool,
+    /// Audit HTTP methods on web servers
+    pub http_methods: bool,
+    /// Engagement scope enforced on every connection
+    pub scope: Option<ScopeConfig>,
+}
+
+impl Default for JobRequest {
+    fn default() -> Self {
+        ScanConfig::default().into()
+    }
+}
+
+impl From<ScanConfig> for JobRequest {
+    fn from(config: ScanConfig) -> Self {
+        Self {
+            target: config.target,
+            ports: config.ports,
+            scan_types: config.scan_types,
+            concurrency: config.concurrency,
+            rate: config.rate,
+            evasion: config.evasion,
+            enhanced_evasion: config.enhanced_evasion,
+            mimic_os: config.mimic_os,
+            ttl_jitter: config.ttl_jitter,
+            protocol_variant: config.protocol_variant,
+            ipv6: config.ipv6,
+            timeout: config.timeout,
+            timeout_connect: config.timeout_connect,
+            timeout_banner: config.timeout_banner,
+            mimic_protocol: config.mimic_protocol,
+            fragmentation: config.fragmentation,
+            http_discovery: config.http_discovery,
+            default_checks: config.default_checks,
+            snmp: config.snmp,
+            dns: config.dns,
+            max_redirects: config.max_redirects,
+            cross_host_redirects: config.cross_host_redirects,
+            http_methods: config.http_methods,
+            scope: config.scope,
+        }
+    }
+}
+
+impl JobRequest {
+    /// Turn the request into a full scan configuration with daemon defaults
+    pub fn into_config(self) -> ScanConfig {
+        ScanConfig {
+            target: self.target,
+            ports: self.ports,
+            scan_types: self.scan_types,
+            concurrency: self.concurrency,
+            rate: self.rate,
+            evasion: self.evasion,
+            enhanced_evasion: self.enhanced_evasion,
+            mimic_os: self.mimic_os,
+            ttl_jitter: self.ttl_jitter,
+            protocol_variant: self.protocol_variant,
+            ipv6: self.ipv6,
+            timeout: self.timeout,
+            timeout_connect: self.timeout_connect,
+            timeout_banner: self.timeout_banner,
+            mimic_protocol: self.mimic_protocol,
+            fragmentation: self.fragmentation,
+            http_discovery: self.http_discovery,
+            default_checks: self.default_checks,
+            snmp: self.snmp,
+            dns: self.dns,
+            max_redirects: self.max_redirects,
+            cross_host_redirects: self.cross_host_redirects,
+            http_methods: self.http_methods,
+            scope: self.scope,
+            ..ScanConfig::default()
+        }
+    }
+}
+
+/// A scan job as persisted and reported by the API
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Job {
+    /// Job identifier
+    pub id: String,
+    /// Current state
+    pub status: JobStatus,
+    /// Scan configuration for this job
+    pub config: ScanConfig,
+    /// When the job was submitted
+    pub submitted_at: chrono::DateTime<chrono::Utc>,
+    /// When the scan started
+    pub started_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// When the scan ended
+    pub finished_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// Error message for failed jobs
+    pub error: Option<String>,
+    /// Number of open ports found (once completed)
+    pub open_ports: Option<usize>,
+}
+
+/// In-memory bookkeeping for a job
+struct JobEntry {
+    job: Job,
+    control: Option<Arc<ScanControl>>,
+    events: broadcast::Sender<ScanEvent>,
+}
+
+/// Shared daemon state
+pub struct Daemon {
+    config: DaemonConfig,
+    jobs: Mutex<BTreeMap<String, JobEntry>>,
+    queue: Mutex<VecDeque<String>>,
+    wakeup: Notify,
+}
+
+impl Daemon {
+    /// Create the daemon and reload any persisted jobs
+    pub fn new(config: DaemonConfig) -> Result<Arc<Self>, anyhow::Error> {
+        std::fs::create_dir_all(config.state_dir.join("jobs"))?;
+
+        let daemon = Arc::new(Self {
+            config,
+            jobs: Mutex::new(BTreeMap::new()),
+            queue: Mutex::new(VecDeque::new()),
+            wakeup: Notify::new(),
+        });
+        daemon.load_jobs()?;
+        Ok(daemon)
+    }
+
+    /// Run the API server and job workers until the process exits
+    pub async fn serve(self: Arc<Self>) -> Result<(), anyhow::Error> {
+        let listener = TcpListener::bind(self.config.listen).await?;
+
+        for _ in 0..self.config.max_parallel_jobs.max(1) {
+            let daemon = self.clone();
+            tokio::spawn(async move { daemon.worker().await });
+        }
+
+        loop {
+            let (stream, peer) = listener.accept().await?;
+            let daemon = self.clone();
+            tokio::spawn(async move {
+                if let Err(e) = daemon.handle_connection(stream).await {
+                    daemon.report(DaemonEvent::RequestFailed { peer, error: e.to_string() });
+                }
+            });
+        }
+    }
+
+    /// Pass an event to whoever asked for them
+    fn report(&self, event: DaemonEvent) {
+        if let Some(events) = &self.config.events {
+            let _ = events.send(event);
+        }
+    }
+
+    fn job_path(&self, id: &str) -> PathBuf {
+        self.config.state_dir.join("jobs").join(format!("{}.json", id))
+    }
+
+    fn log_path(&self, id: &str) -> PathBuf {
+        self.config.state_dir.join("jobs").join(format!("{}.log", id))
+    }
+
+    fn results_path(&self, id: &str) -> PathBuf {
+        self.config.state_dir.join("jobs").join(format!("{}.results.json", id))
+    }
+
+    /// Reload persisted jobs; anything that was running is queued again
+    fn load_jobs(&self) -> Result<(), anyhow::Error> {
+        let mut loaded = Vec::new();
+        for entry in std::fs::read_dir(self.config.state_dir.join("jobs"))? {
+            let path = entry?.path();
+            let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+            if !name.ends_with(".json") || name.ends_with(".results.json") {
+                continue;
+            }
+            let mut job: Job = match std::fs::read(&path).map(|b| serde_json::from_slice(&b)) {
+                Ok(Ok(job)) => job,
+                _ => {
+                    self.report(DaemonEvent::UnreadableJobFile { path });
+                    continue;
+                }
+            };
+            if job.status == JobStatus::Running {
+                job.status = JobStatus::Queued;
+                job.started_at = None;
+            }
+            loaded.push(job);
+        }
+
+        loaded.sort_by_key(|job| job.submitted_at);
+        let mut jobs = self.jobs.lock();
+        let mut queue = self.queue.lock();
+        for job in loaded {
+            if job.status == JobStatus::Queued {
+                queue.push_back(job.id.clone());
+            }
+            let (events, _) = broadcast::channel(EVENT_BUFFER);
+            jobs.insert(job.id.clone(), JobEntry { job, control: None, events });
+        }
+        Ok(())
+    }
+
+    fn persist(&self, job: &Job) {
+        let write = serde_json::to_vec_pretty(job)
+            .map_err(anyhow::Error::from)
+            .and_then(|data| Ok(std::fs::write(self.job_path(&job.id), data)?));
+        if let Err(e) = write {
+            self.report(DaemonEvent::PersistFailed { job: job.id.clone(), error: e.to_string() });
+        }
+    }
+
+    /// Update a job under the lock and persist the result
+    fn update_job<F: FnOnce(&mut JobEntry)>(&self, id: &str, f: F) -> Option<Job> {
+        let job = {
+            let mut jobs = self.jobs.lock();
+            let entry = jobs.get_mut(id)?;
+            f(entry);
+            entry.job.clone()
+        };
+        self.persist(&job);
+        Some(job)
+    }
+
+    /// Queue a new job
+    ///
+    /// The job's log file is always placed under `state_dir`, whatever the
+    /// config says.
+    pub fn submit(&self, mut config: ScanConfig) -> Result<Job, crate::error::ScanError> {
+        config.validate()?;
+
+        let id: String = (0..8).map(|_| format!("{:02x}", rand::thread_rng().gen::<u8>())).collect();
+        config.log_file = self.log_path(&id);
+        let job = Job {
+            id: id.clone(),
+            status: JobStatus::Queued,
+            config,
+            submitted_at: chrono::Utc::now(),
+            started_at: None,
+            finished_at: None,
+            error: None,
+            open_ports: None,
+        };
+        self.persist(&job);
+
+        let (events, _) = broadcast::channel(EVENT_BUFFER);
+        self.jobs.lock().insert(id.clone(), JobEntry { job: job.clone(), control: None, events });
+        self.queue.lock().push_back(id);
+        self.wakeup.notify_one();
+
+        Ok(job)
+    }
+
+    /// Cancel a queued or running job
+    pub fn cancel(&self, id: &str) -> Option<Job> {
+        self.queue.lock().retain(|queued| queued != id);
+        self.update_job(id, |entry| {
+            if entry.job.status.is_terminal() {
+                return;
+            }
+            if let Some(control) = &entry.control {
+                control.cancel();
+            }
+            entry.job.status = JobStatus::Cancelled;
+            entry.job.finished_at = Some(chrono::Utc::now());
+        })
+    }
+
+    /// Take queued jobs one at a time and run them
+    async fn worker(self: Arc<Self>) {
+        loop {
+            let next = self.queue.lock().pop_front();
+            let id = match next {
+                Some(id) => id,
+                None => {
+                    // Poll occasionally as well, in case a notification was missed
+                    let _ = tokio::time::timeout(Duration::from_secs(5), self.wakeup.notified()).await;
+                    continue;
+                }
+            };
+            // Run each job in its own task so a panicking scan can't take the worker down
+            let daemon = self.clone();
+            let job_id = id.clone();
+            if let Err(e) = tokio::spawn(async move { daemon.run_job(&job_id).await }).await {
+                self.update_job(&id, |entry| {
+                    entry.control = None;
+                    entry.job.status = JobStatus::Failed;
+                    entry.job.error = Some(format!("scan task aborted: {}", e));
+                    entry.job.finished_at = Some(chrono::Utc::now());
+                });
+            }
+        }
+    }
+
+    async fn run_job(&self, id: &str) {
+        let (config, events_tx) = {
+            let jobs = self.jobs.lock();
+            match jobs.get(id) {
+                Some(entry) if entry.job.status == JobStatus::Queued => (entry.job.config.clone(), entry.events.clone()),
+                _ => return,
+            }
+        };
+
+        let mut scanner = match ScannerBuilder::from_config(config).build().await {
+            Ok(scanner) => scanner,
+            Err(e) => {
+                self.update_job(id, |entry| {
+                    entry.job.status = JobStatus::Failed;
+                    entry.job.error = Some(e.to_string());
+                    entry.job.finished_at = Some(chrono::Utc::now());
+                });
+                return;
+            }
+        };
+
+        let control = scanner.control();
+        let scope = scanner.scope();
+        self.update_job(id, |entry| {
+            entry.job.status = JobStatus::Running;
+            entry.job.started_at = Some(chrono::Utc::now());
+            entry.control = Some(control.clone());
+        });
+
+        // Forward scanner events to SSE subscribers
+        let mut stream = scanner.subscribe();
+        let forward = tokio::spawn(async move {
+            while let Some(event) = stream.next().await {
+                let _ = events_tx.send(event);
+            }
+        });
+
+        let outcome = scanner.run().await;
+        let _ = forward.await;
+
+        match outcome {
+            Ok(results) => {
+                let saved = self.save_results(id, &results, scope.as_deref());
+                self.update_job(id, |entry| {
+                    entry.control = None;
+                    entry.job.finished_at = Some(chrono::Utc::now());
+                    entry.job.open_ports = Some(results.open_ports.len());
+                    if entry.job.status == JobStatus::Cancelled {
+                        return;
+                    }
+                    match &saved {
+                        Ok(()) => entry.job.status = JobStatus::Completed,
+                        Err(e) => {
+                            entry.job.status = JobStatus::Failed;
+                            entry.job.error = Some(format!("failed to save results: {}", e));
+                        }
+                    }
+                });
+            }
+            Err(e) => {
+                self.update_job(id, |entry| {
+                    entry.control = None;
+                    entry.job.finished_at = Some(chrono::Utc::now());
+                    if entry.job.status != JobStatus::Cancelled {
+                        entry.job.status = JobStatus::Failed;
+                        entry.job.error = Some(e.to_string());
+                    }
+                });
+            }
+        }
+    }
+
+    fn save_results(&self, id: &str, results: &ScanResults, scope: Option<&crate::scope::Scope>) -> Result<(), anyhow::Error> {
+        let path = self.results_path(id);
+        std::fs::write(&path, serde_json::to_vec(results)?)?;
+        if let Some(scope) = scope {
+            crate::scope::embed_in_results(scope, &path, true)?;
+        }
+        Ok(())
+    }
+
+    /// Compare the presented token in constant time
+    fn authorized(&self, request: &Request) -> bool {
+        let presented = request
+            .header("Authorization")
+            .and_then(|v| v.strip_prefix("Bearer "))
+            .unwrap_or("")
+            .as_bytes();
+        let expected = self.config.token.as_bytes();
+
+        let mut diff = presented.len() ^ expected.len();
+        for (i, byte) in expected.iter().enumerate() {
+            diff |= (*byte ^ presented.get(i).copied().unwrap_or(0)) as usize;
+        }
+        diff == 0 && !expected.is_empty()
+    }
+
+    async fn handle_connection(&self, mut stream: TcpStream) -> Result<(), anyhow::Error> {
+        let request = match read_request(&mut stream).await? {
+            Some(request) => request,
+            None => return Ok(()),
+        };
+
+        if !self.authorized(&request) {
+            return write_json(&mut stream, 401, &serde_json::json!({"error": "missing or invalid API token"})).await;
+        }
+
+        let segments: Vec<&str> = request.path.trim_matches('/').split('/').filter(|s| !s.is_empty()).collect();
+
+        match (request.method.as_str(), segments.as_slice()) {
+            ("POST", ["jobs"]) => {
+                let job: JobRequest = match serde_json::from_slice(&request.body) {
+                    Ok(job) => job,
+                    Err(e) => {
+                        return write_json(&mut stream, 400, &serde_json::json!({"error": format!("invalid job request: {}", e)})).await;
+                    }
+                };
+                match self.submit(job.into_config()) {
+                    Ok(job) => write_json(&mut stream, 201, &job).await,
+                    Err(e) => write_json(&mut stream, 400, &serde_json::json!({"error": e.to_string()})).await,
+                }
+            }
+            ("GET", ["jobs"]) => {
+                let jobs: Vec<Job> = self.jobs.lock().values().map(|e| e.job.clone()).collect();
+                write_json(&mut stream, 200, &jobs).await
+            }
+            ("GET", ["jobs", id]) => {
+                let job = self.jobs.lock().get(*id).map(|e| e.job.clone());
+                match job {
+                    Some(job) => write_json(&mut stream, 200, &job).await,
+                    None => write_not_found(&mut stream).await,
+                }
+            }
+            ("DELETE", ["jobs", id]) | ("POST", ["jobs", id, "cancel"]) => match self.cancel(id) {
+                Some(job) => write_json(&mut stream, 200, &job).await,
+                None => write_not_found(&mut stream).await,
+            },
+            ("GET", ["jobs", id, "results"]) => {
+                let status = self.jobs.lock().get(*id).map(|e| e.job.status);
+                match status {
+                    Some(JobStatus::Completed) => match std::fs::read(self.results_path(id)) {
+                        Ok(body) => write_response(&mut stream, 200, "application/json", &body).await,
+                        Err(e) => write_json(&mut stream, 500, &serde_json::json!({"error": e.to_string()})).await,
+                    },
+                    Some(status) => {
+                        write_json(&mut stream, 409, &serde_json::json!({"error": "job has no results", "status": status})).await
+                    }
+                    None => write_not_found(&mut stream).await,
+                }
+            }
+            ("GET", ["jobs", id, "events"]) => self.stream_events(stream, id).await,
+            _ => write_not_found(&mut stream).await,
+        }
+    }
+
+    /// Serve a job's progress as Server-Sent Events until it finishes
+    async fn stream_events(&self, mut stream: TcpStream, id: &str) -> Result<(), anyhow::Error> {
+        let found = self.jobs.lock().get(id).map(|entry| (entry.events.subscribe(), entry.job.clone()));
+        let (mut receiver, job) = match found {
+            Some(found) => found,
+            None => return write_not_found(&mut stream).await,
+        };
+
+        stream
+            .write_all(b"HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\nConnection: close\r\n\r\n")
+            .await?;
+        write_sse(&mut stream, "status", &serde_json::to_string(&job)?).await?;
+
+        if job.status.is_terminal() {
+            return write_sse(&mut stream, "end", "{}").await;
+        }
+
+        loop {
+            match tokio::time::timeout(Duration::from_secs(15), receiver.recv()).await {
+                Ok(Ok(event)) => {
+                    let name = match serde_json::to_value(&event)?.get("event").and_then(|v| v.as_str()) {
+                        Some(name) => name.to_string(),
+                        None => "scan".to_string(),
+                    };
+                    write_sse(&mut stream, &name, &serde_json::to_string(&event)?).await?;
+                }
+                Ok(Err(broadcast::error::RecvError::Lagged(skipped))) => {
+                    write_sse(&mut stream, "lagged", &format!("{{\"skipped\":{}}}", skipped)).await?;
+                }
+                Ok(Err(broadcast::error::RecvError::Closed)) => break,
+                Err(_) => {
+                    // Keep-alive comment, and stop once the job is over
+                    stream.write_all(b": keep-alive\n\n").await?;
+                    let done = self.jobs.lock().get(id).is_none_or(|e| e.job.status.is_terminal());
+                    if done {
+                        break;
+                    }
+                }
+            }
+        }
+
+        let job = self.jobs.lock().get(id).map(|e| e.job.clone());
+        if let Some(job) = job {
+            write_sse(&mut stream, "status", &serde_json::to_string(&job)?).await?;
+        }
+        write_sse(&mut stream, "end", "{}").await
+    }
+}
+
+/// A parsed API request
+struct Request {
+    method: String,
+    path: String,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl Request {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+/// Read one HTTP/1.1 request from the socket
+async fn read_request(stream: &mut TcpStream) -> Result<Option<Request>, anyhow::Error> {
+    let mut data = Vec::new();
+    let mut buffer = [0u8; 4096];
+
+    let header_end = loop {
+        let n = tokio::time::timeout(Duration::from_secs(10), stream.read(&mut buffer)).await??;
+        if n == 0 {
+            return Ok(None);
+        }
+        data.extend_from_slice(&buffer[..n]);
+        if let Some(pos) = data.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos;
+        }
+        if data.len() > 64 * 1024 {
+            return Err(anyhow::anyhow!("request headers too large"));
+        }
+    };
+
+    let head = String::from_utf8_lossy(&data[..header_end]).to_string();
+    let mut lines = head.lines();
+    let mut request_line = lines.next().unwrap_or("").split_whitespace();
+    let method = request_line.next().unwrap_or("").to_uppercase();
+    let path = request_line.next().unwrap_or("/").split('?').next().unwrap_or("/").to_string();
+
+    let headers: Vec<(String, String)> = lines
+        .filter_map(|line| {
+            let (name, value) = line.split_once(':')?;
+            Some((name.trim().to_string(), value.trim().to_string()))
+        })
+        .collect();
+
+    let content_length = headers
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case("Content-Length"))
+        .and_then(|(_, v)| v.parse::<usize>().ok())
+        .unwrap_or(0);
+    if content_length > MAX_BODY_SIZE {
+        return Err(anyhow::anyhow!("request body too large"));
+    }
+
+    let mut body = data[header_end + 4..].to_vec();
+    while body.len() < content_length {
+        let n = tokio::time::timeout(Duration::from_secs(10), stream.read(&mut buffer)).await??;
+        if n == 0 {
+            break;
+        }
+        body.extend_from_slice(&buffer[..n]);
+    }
+    body.truncate(content_length);
+
+    Ok(Some(Request { method, path, headers, body }))
+}
+
+fn reason_phrase(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        201 => "Created",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        404 => "Not Found",
+        409 => "Conflict",
+        _ => "Internal Server Error",
+    }
+}
+
+async fn write_response(stream: &mut TcpStream, status: u16, content_type: &str, body: &[u8]) -> Result<(), anyhow::Error> {
+    let mut header = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n",
+        status,
+        reason_phrase(status),
+        content_type,
+        body.len()
+    );
+    if status == 401 {
+        header.push_str("WWW-Authenticate: Bearer\r\n");
+    }
+    header.push_str("\r\n");
+
+    stream.write_all(header.as_bytes()).await?;
+    stream.write_all(body).await?;
+    stream.flush().await?;
+    Ok(())
+}
+
+async fn write_json<T: Serialize>(stream: &mut TcpStream, status: u16, value: &T) -> Result<(), anyhow::Error> {
+    let body = serde_json::to_vec_pretty(value)?;
+    write_response(stream, status, "application/json", &body).await
+}
+
+async fn write_not_found(stream: &mut TcpStream) -> Result<(), anyhow::Error> {
+    write_json(stream, 404, &serde_json::json!({"error": "not found"})).await
+}
+
+async fn write_sse(stream: &mut TcpStream, event: &str, data: &str) -> Result<(), anyhow::Error> {
+    stream.write_all(format!("event: {}\ndata: {}\n\n", event, data).as_bytes()).await?;
+    stream.flush().await?;
+    Ok(())
+}
+
+/// Generate a random API token
+pub fn generate_token() -> String {
+    (0..24).map(|_| format!("{:02x}", rand::thread_rng().gen::<u8>())).collect()
+}
+
+/// Default location for daemon state
+pub fn default_state_dir() -> PathBuf {
+    Path::new(".").join("quantum_scanner_jobs")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOKEN: &str = "0123456789abcdef";
+
+    fn new_daemon(dir: &Path) -> Arc<Daemon> {
+        Daemon::new(DaemonConfig {
+            listen: "127.0.0.1:0".parse().unwrap(),
+            token: TOKEN.to_string(),
+            state_dir: dir.to_path_buf(),
+            max_parallel_jobs: 1,
+            events: None,
+        })
+        .unwrap()
+    }
+
+    fn scan_config() -> ScanConfig {
+        ScanConfig {
+            target: "127.0.0.1".to_string(),
+            ports: vec![22, 80],
+            scan_types: vec![ScanType::Ssl],
+            ..ScanConfig::default()
+        }
+    }
+
+    /// Serve API connections without starting any workers, so jobs stay queued
+    async fn serve_api(daemon: Arc<Daemon>) -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                let daemon = daemon.clone();
+                tokio::spawn(async move { daemon.handle_connection(stream).await });
+            }
+        });
+        addr
+    }
+
+    async fn call(addr: SocketAddr, method: &str, path: &str, token: Option<&str>, body: &str) -> (u16, serde_json::Value) {
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let auth = token.map(|t| format!("Authorization: Bearer {}\r\n", t)).unwrap_or_default();
+        let request = format!(
+            "{} {} HTTP/1.1\r\nHost: localhost\r\n{}Content-Length: {}\r\n\r\n{}",
+            method,
+            path,
+            auth,
+            body.len(),
+            body
+        );
+        stream.write_all(request.as_bytes()).await.unwrap();
+
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).await.unwrap();
+        let response = String::from_utf8(response).unwrap();
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        let status = head.split_whitespace().nth(1).unwrap().parse().unwrap();
+        (status, serde_json::from_str(body).unwrap())
+    }
+
+    fn request_with(auth: Option<&str>) -> Request {
+        Request {
+            method: "GET".to_string(),
+            path: "/jobs".to_string(),
+            headers: auth.map(|v| vec![("authorization".to_string(), v.to_string())]).unwrap_or_default(),
+            body: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn token_must_match_exactly() {
+        let dir = tempfile::tempdir().unwrap();
+        let daemon = new_daemon(dir.path());
+
+        assert!(daemon.authorized(&request_with(Some(&format!("Bearer {}", TOKEN)))));
+        assert!(!daemon.authorized(&request_with(None)));
+        assert!(!daemon.authorized(&request_with(Some(TOKEN))));
+        assert!(!daemon.authorized(&request_with(Some("Bearer 0123456789abcdeF"))));
+        assert!(!daemon.authorized(&request_with(Some("Bearer 0123456789abcde"))));
+        assert!(!daemon.authorized(&request_with(Some(&format!("Bearer {}0", TOKEN)))));
+
+        // An empty configured token never authorizes anything
+        let empty = Daemon::new(DaemonConfig { token: String::new(), ..daemon.config.clone() }).unwrap();
+        assert!(!empty.authorized(&request_with(Some("Bearer "))));
+        assert!(!empty.authorized(&request_with(None)));
+    }
+
+    #[tokio::test]
+    async fn api_rejects_requests_without_the_token() {
+        let dir = tempfile::tempdir().unwrap();
+        let addr = serve_api(new_daemon(dir.path())).await;
+
+        let (status, body) = call(addr, "GET", "/jobs", None, "").await;
+        assert_eq!(status, 401);
+        assert!(body["error"].is_string());
+        let (status, _) = call(addr, "GET", "/jobs", Some("wrong"), "").await;
+        assert_eq!(status, 401);
+        let (status, _) = call(addr, "POST", "/jobs", Some("wrong"), r#"{"target":"127.0.0.1","ports":[80]}"#).await;
+        assert_eq!(status, 401);
+
+        let (status, body) = call(addr, "GET", "/jobs", Some(TOKEN), "").await;
+        assert_eq!(status, 200);
+        assert_eq!(body, serde_json::json!([]));
+    }
+
+    #[tokio::test]
+    async fn job_requests_are_whitelisted() {
+        let dir = tempfile::tempdir().unwrap();
+        let daemon = new_daemon(dir.path());
+        let addr = serve_api(daemon.clone()).await;
+
+        for body in [
+            r#"{"target":"127.0.0.1","ports":[80],"log_file":"/etc/cron.d/x"}"#,
+            r#"{"target":"127.0.0.1","ports":[80],"verbose":true}"#,
+            r#"{"target":"127.0.0.1","ports":[80],"no_such_field":1}"#,
+        ] {
+            let (status, reply) = call(addr, "POST", "/jobs", Some(TOKEN), body).await;
+            assert_eq!(status, 400, "{}", body);
+            assert!(reply["error"].as_str().unwrap().contains("unknown field"), "{}", reply);
+        }
+        assert!(daemon.jobs.lock().is_empty());
+
+        let (status, _) = call(addr, "POST", "/jobs", Some(TOKEN), r#"{"target":"127.0.0.1","ports":[]}"#).await;
+        assert_eq!(status, 400);
+
+        let (status, job) = call(
+            addr,
+            "POST",
+            "/jobs",
+            Some(TOKEN),
+            r#"{"target":"127.0.0.1","ports":[22,80],"scan_types":["Ssl"],"rate":50}"#,
+        )
+        .await;
+        assert_eq!(status, 201);
+        let job: Job = serde_json::from_value(job).unwrap();
+        assert_eq!(job.status, JobStatus::Queued);
+        assert_eq!(job.config.ports, vec![22, 80]);
+        assert_eq!(job.config.rate, 50);
+        assert_eq!(job.config.log_file, dir.path().join("jobs").join(format!("{}.log", job.id)));
+    }
+
+    #[test]
+    fn submitted_log_file_is_confined_to_state_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let daemon = new_daemon(dir.path());
+
+        let job = daemon
+            .submit(ScanConfig { log_file: PathBuf::from("/tmp/elsewhere.log"), ..scan_config() })
+            .unwrap();
+        assert!(job.config.log_file.starts_with(dir.path()));
+    }
+
+    #[test]
+    fn queue_survives_a_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let (first, second, done) = {
+            let daemon = new_daemon(dir.path());
+            let first = daemon.submit(scan_config()).unwrap();
+            let second = daemon.submit(scan_config()).unwrap();
+            let done = daemon.submit(scan_config()).unwrap();
+
+            // Simulate a crash with the first job mid-scan and the last one finished
+            daemon.queue.lock().retain(|id| *id != first.id && *id != done.id);
+            daemon.update_job(&first.id, |entry| {
+                entry.job.status = JobStatus::Running;
+                entry.job.started_at = Some(chrono::Utc::now());
+            });
+            daemon.update_job(&done.id, |entry| entry.job.status = JobStatus::Completed);
+            (first, second, done)
+        };
+
+        let daemon = new_daemon(dir.path());
+        let queue: Vec<String> = daemon.queue.lock().iter().cloned().collect();
+        assert_eq!(queue, vec![first.id.clone(), second.id.clone()]);
+
+        let jobs = daemon.jobs.lock();
+        assert_eq!(jobs.len(), 3);
+        let requeued = &jobs[&first.id].job;
+        assert_eq!(requeued.status, JobStatus::Queued);
+        assert!(requeued.started_at.is_none());
+        assert_eq!(requeued.config.ports, first.config.ports);
+        assert_eq!(jobs[&second.id].job.status, JobStatus::Queued);
+        assert_eq!(jobs[&done.id].job.status, JobStatus::Completed);
+    }
+
+    #[test]
+    fn unreadable_job_files_are_reported() {
+        let dir = tempfile::tempdir().unwrap();
+        let job = new_daemon(dir.path()).submit(scan_config()).unwrap();
+        let broken = dir.path().join("jobs").join("broken.json");
+        std::fs::write(&broken, b"{not json").unwrap();
+
+        let (events, mut reports) = mpsc::unbounded_channel();
+        let daemon = Daemon::new(DaemonConfig {
+            listen: "127.0.0.1:0".parse().unwrap(),
+            token: TOKEN.to_string(),
+            state_dir: dir.path().to_path_buf(),
+            max_parallel_jobs: 1,
+            events: Some(events),
+        })
+        .unwrap();
+        assert!(daemon.jobs.lock().contains_key(&job.id));
+
+        match reports.try_recv() {
+            Ok(DaemonEvent::UnreadableJobFile { path }) => assert_eq!(path, broken),
+            other => panic!("unexpected report {:?}", other),
+        }
+        assert!(reports.try_recv().is_err());
+    }
+
+    #[test]
+    fn cancel_dequeues_and_stops_jobs() {
+        let dir = tempfile::tempdir().unwrap();
+        let daemon = new_daemon(dir.path());
+        let queued = daemon.submit(scan_config()).unwrap();
+        let running = daemon.submit(scan_config()).unwrap();
+
+        let control = Arc::new(ScanControl::new(0));
+        daemon.queue.lock().retain(|id| *id != running.id);
+        daemon.update_job(&running.id, |entry| {
+            entry.job.status = JobStatus::Running;
+            entry.control = Some(control.clone());
+        });
+
+        let cancelled = daemon.cancel(&queued.id).unwrap();
+        assert_eq!(cancelled.status, JobStatus::Cancelled);
+        assert!(cancelled.finished_at.is_some());
+        assert!(daemon.queue.lock().is_empty());
+
+        assert_eq!(daemon.cancel(&running.id).unwrap().status, JobStatus::Cancelled);
+        assert!(control.is_cancelled());
+
+        // Terminal jobs are left as they are
+        daemon.update_job(&queued.id, |entry| entry.job.status = JobStatus::Completed);
+        assert_eq!(daemon.cancel(&queued.id).unwrap().status, JobStatus::Completed);
+        assert!(daemon.cancel("no-such-job").is_none());
+
+        // The cancellation is persisted
+        drop(daemon);
+        let daemon = new_daemon(dir.path());
+        assert!(daemon.queue.lock().is_empty());
+        assert_eq!(daemon.jobs.lock()[&running.id].job.status, JobStatus::Cancelled);
+    }
+
+    #[tokio::test]
+    async fn api_cancels_jobs() {
+        let dir = tempfile::tempdir().unwrap();
+        let daemon = new_daemon(dir.path());
+        let job = daemon.submit(scan_config()).unwrap();
+        let addr = serve_api(daemon.clone()).await;
+
+        let (status, body) = call(addr, "DELETE", &format!("/jobs/{}", job.id), Some(TOKEN), "").await;
+        assert_eq!(status, 200);
+        assert_eq!(body["status"], "cancelled");
+        assert!(daemon.queue.lock().is_empty());
+
+        let (status, _) = call(addr, "DELETE", "/jobs/unknown", Some(TOKEN), "").await;
+        assert_eq!(status, 404);
+        let (status, body) = call(addr, "GET", &format!("/jobs/{}/results", job.id), Some(TOKEN), "").await;
+        assert_eq!(status, 409);
+        assert_eq!(body["status"], "cancelled");
+    }
+}
@@ -0,0 +1,406 @@
+//! Interactive terminal dashboard for running scans
+//!
+//! Renders live progress from the scan event stream with ratatui: per-host
+//! progress, the open-port table, probe rate and counters from `ScanControl`,
+//! and a scrolling view of the scanner's log buffer. Keys drive the same
+//! `ScanControl` handle the engine checks before each probe.
+
+use std::collections::btree_map::Entry;
+use std::collections::{BTreeMap, HashSet};
+use std::io;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use crossterm::execute;
+use crossterm::terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen};
+use futures::StreamExt;
+use ratatui::backend::CrosstermBackend;
+use ratatui::layout::{Constraint, Direction, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, Gauge, List, ListItem, ListState, Paragraph, Row, Table};
+use ratatui::{Frame, Terminal};
+use tokio::sync::mpsc;
+
+use crate::control::{ScanControl, ScanCounters};
+use crate::events::{ScanEventKind, ScanEventStream};
+use crate::models::PortStatus;
+use crate::utils;
+
+/// How often the screen is redrawn
+const TICK: Duration = Duration::from_millis(250);
+
+/// Where the dashboard reads log lines from
+#[derive(Clone, Default)]
+pub struct LogSource {
+    /// In-memory log buffer
+    pub memory_log: Option<Arc<utils::MemoryLogBuffer>>,
+    /// Enhanced logger (preferred when both are set)
+    pub enhanced_logger: Option<Arc<utils::EnhancedLogger>>,
+}
+
+impl LogSource {
+    fn lines(&self) -> Vec<String> {
+        let text = if let Some(logger) = &self.enhanced_logger {
+            logger.buffer().format_logs(false)
+        } else if let Some(log) = &self.memory_log {
+            log.format_logs(false)
+        } else {
+            return Vec::new();
+        };
+        text.lines().map(str::to_string).collect()
+    }
+}
+
+/// Progress of one host
+#[derive(Debug, Default)]
+struct HostProgress {
+    ports_done: HashSet<u16>,
+    open_ports: usize,
+    up: bool,
+    finished: bool,
+}
+
+/// One row of the open-port table
+#[derive(Debug, Default)]
+struct OpenPortRow {
+    service: Option<String>,
+    version: Option<String>,
+    tls: Option<String>,
+}
+
+/// Dashboard state
+struct App {
+    total_ports: usize,
+    hosts: BTreeMap<String, HostProgress>,
+    open_ports: BTreeMap<(String, u16), OpenPortRow>,
+    host_state: ListState,
+    log_scroll: usize,
+    last_counters: ScanCounters,
+    last_sample: Instant,
+    probe_rate: f64,
+    scan_done: bool,
+}
+
+impl App {
+    fn new(total_ports: usize) -> Self {
+        Self {
+            total_ports,
+            hosts: BTreeMap::new(),
+            open_ports: BTreeMap::new(),
+            host_state: ListState::default(),
+            log_scroll: 0,
+            last_counters: ScanCounters::default(),
+            last_sample: Instant::now(),
+            probe_rate: 0.0,
+            scan_done: false,
+        }
+    }
+
+    fn selected_host(&self) -> Option<String> {
+        self.host_state
+            .selected()
+            .and_then(|i| self.hosts.keys().nth(i).cloned())
+    }
+
+    fn select_next_host(&mut self, step: isize) {
+        if self.hosts.is_empty() {
+            return;
+        }
+        let count = self.hosts.len() as isize;
+        let current = self.host_state.selected().unwrap_or(0) as isize;
+        self.host_state.select(Some((current + step).rem_euclid(count) as usize));
+    }
+
+    fn apply(&mut self, target: &str, kind: ScanEventKind) {
+        let host = self.hosts.entry(target.to_string()).or_default();
+        if self.host_state.selected().is_none() {
+            self.host_state.select(Some(0));
+        }
+
+        match kind {
+            ScanEventKind::HostUp { .. } => host.up = true,
+            ScanEventKind::PortState { port, status, .. } => {
+                host.ports_done.insert(port);
+                if status == PortStatus::Open {
+                    if let Entry::Vacant(entry) = self.open_ports.entry((target.to_string(), port)) {
+                        host.open_ports += 1;
+                        entry.insert(OpenPortRow::default());
+                    }
+                }
+            }
+            ScanEventKind::ServiceIdentified { port, service, version } => {
+                let row = self.open_ports.entry((target.to_string(), port)).or_default();
+                row.service = Some(service);
+                row.version = version;
+            }
+            ScanEventKind::TlsInfo { port, info } => {
+                let row = self.open_ports.entry((target.to_string(), port)).or_default();
+                row.tls = Some(format!(
+                    "{} {}",
+                    info.protocol_version.as_deref().unwrap_or("TLS"),
+                    info.cert_cn.as_deref().unwrap_or("")
+                ));
+            }
+            ScanEventKind::ScanFinished { .. } => host.finished = true,
+        }
+    }
+
+    /// Update the probe rate from the control counters
+    fn sample(&mut self, counters: ScanCounters) {
+        let elapsed = self.last_sample.elapsed().as_secs_f64();
+        if elapsed >= 1.0 {
+            let sent = counters.probes.saturating_sub(self.last_counters.probes);
+            self.probe_rate = sent as f64 / elapsed;
+            self.last_counters = counters;
+            self.last_sample = Instant::now();
+        }
+    }
+}
+
+/// Input from the key reader thread
+enum Input {
+    Key(KeyCode),
+}
+
+/// Read keys on a blocking thread and forward them to the async loop
+fn spawn_key_reader(tx: mpsc::UnboundedSender<Input>) {
+    std::thread::spawn(move || loop {
+        match event::poll(TICK) {
+            Ok(true) => {
+                if let Ok(Event::Key(key)) = event::read() {
+                    if key.kind == KeyEventKind::Press && tx.send(Input::Key(key.code)).is_err() {
+                        break;
+                    }
+                }
+            }
+            Ok(false) => {
+                if tx.is_closed() {
+                    break;
+                }
+            }
+            Err(_) => break,
+        }
+    });
+}
+
+/// Run the dashboard until the user quits
+///
+/// Returns once `q`/`Esc` is pressed. The scan keeps running in the background
+/// if the dashboard is closed early.
+pub async fn run_dashboard(
+    mut events: ScanEventStream,
+    control: Arc<ScanControl>,
+    logs: LogSource,
+    total_ports: usize,
+) -> Result<(), anyhow::Error> {
+    enable_raw_mode()?;
+    let mut stdout = io::stdout();
+    execute!(stdout, EnterAlternateScreen)?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(stdout))?;
+
+    let result = event_loop(&mut terminal, &mut events, &control, &logs, total_ports).await;
+
+    // Always restore the terminal, even if drawing failed
+    disable_raw_mode()?;
+    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+    terminal.show_cursor()?;
+
+    result
+}
+
+async fn event_loop(
+    terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
+    events: &mut ScanEventStream,
+    control: &ScanControl,
+    logs: &LogSource,
+    total_ports: usize,
+) -> Result<(), anyhow::Error> {
+    let mut app = App::new(total_ports);
+    let (key_tx, mut key_rx) = mpsc::unbounded_channel();
+    spawn_key_reader(key_tx);
+
+    let mut tick = tokio::time::interval(TICK);
+
+    loop {
+        tokio::select! {
+            event = events.next(), if !app.scan_done => match event {
+                Some(event) => app.apply(&event.target, event.kind),
+                None => app.scan_done = true,
+            },
+            Some(Input::Key(code)) = key_rx.recv() => match code {
+                KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                KeyCode::Char('p') => control.pause(),
+                KeyCode::Char('r') => control.resume(),
+                KeyCode::Char(' ') => {
+                    if control.is_paused() { control.resume() } else { control.pause() }
+                }
+                KeyCode::Char('+') | KeyCode::Char('=') => control.increase_rate(),
+                KeyCode::Char('-') => control.decrease_rate(),
+                KeyCode::Char('s') => {
+                    if let Some(host) = app.selected_host() {
+                        control.skip_host(&host);
+                    }
+                }
+                KeyCode::Up => app.select_next_host(-1),
+                KeyCode::Down => app.select_next_host(1),
+                KeyCode::PageUp => app.log_scroll = app.log_scroll.saturating_add(10),
+                KeyCode::PageDown => app.log_scroll = app.log_scroll.saturating_sub(10),
+                KeyCode::End => app.log_scroll = 0,
+                _ => {}
+            },
+            _ = tick.tick() => {
+                app.sample(control.counters());
+                let log_lines = logs.lines();
+                terminal.draw(|frame| draw(frame, &mut app, control, &log_lines))?;
+            }
+        }
+    }
+}
+
+fn draw(frame: &mut Frame, app: &mut App, control: &ScanControl, log_lines: &[String]) {
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(3),
+            Constraint::Length(app.hosts.len().clamp(1, 6) as u16 + 2),
+            Constraint::Min(6),
+            Constraint::Length(10),
+            Constraint::Length(1),
+        ])
+        .split(frame.size());
+
+    draw_status(frame, rows[0], app, control);
+    draw_hosts(frame, rows[1], app, control);
+    draw_ports(frame, rows[2], app);
+    draw_logs(frame, rows[3], app, log_lines);
+
+    let help = Paragraph::new(
+        "p pause  r resume  space toggle  +/- rate  s skip host  ↑/↓ select host  PgUp/PgDn logs  q quit",
+    )
+    .style(Style::default().fg(Color::DarkGray));
+    frame.render_widget(help, rows[4]);
+}
+
+fn draw_status(frame: &mut Frame, area: Rect, app: &App, control: &ScanControl) {
+    let counters = control.counters();
+    let state = if app.scan_done {
+        Span::styled("FINISHED", Style::default().fg(Color::Green).add_modifier(Modifier::BOLD))
+    } else if control.is_paused() {
+        Span::styled("PAUSED", Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD))
+    } else {
+        Span::styled("RUNNING", Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD))
+    };
+    let limit = match control.rate() {
+        0 => "unlimited".to_string(),
+        rate => format!("{} pps", rate),
+    };
+
+    let line = Line::from(vec![
+        state,
+        Span::raw(format!(
+            "  rate {:.0} pps (limit {})  probes {}  timeouts {}  retransmits {}  open {}",
+            app.probe_rate,
+            limit,
+            counters.probes,
+            counters.timeouts,
+            counters.retransmits,
+            app.open_ports.len()
+        )),
+    ]);
+
+    let status = Paragraph::new(line).block(Block::default().borders(Borders::ALL).title(" Quantum Scanner "));
+    frame.render_widget(status, area);
+}
+
+fn draw_hosts(frame: &mut Frame, area: Rect, app: &mut App, control: &ScanControl) {
+    let block = Block::default().borders(Borders::ALL).title(" Hosts ");
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+
+    if app.hosts.is_empty() {
+        frame.render_widget(Paragraph::new("Waiting for first response..."), inner);
+        return;
+    }
+
+    let selected = app.host_state.selected().unwrap_or(0);
+    for (i, (host, progress)) in app.hosts.iter().enumerate().take(inner.height as usize) {
+        let row = Rect { y: inner.y + i as u16, height: 1, ..inner };
+        let done = progress.ports_done.len();
+        let ratio = if progress.finished || app.total_ports == 0 {
+            1.0
+        } else {
+            (done as f64 / app.total_ports as f64).min(1.0)
+        };
+
+        let mut label = format!(
+            "{} {}  {}/{} ports  {} open",
+            if i == selected { ">" } else { " " },
+            host,
+            done,
+            app.total_ports,
+            progress.open_ports
+        );
+        if control.is_skipped(host) {
+            label.push_str("  [skipped]");
+        } else if !progress.up {
+            label.push_str("  [no response yet]");
+        }
+
+        let gauge = Gauge::default()
+            .gauge_style(Style::default().fg(if control.is_skipped(host) { Color::DarkGray } else { Color::Blue }))
+            .ratio(ratio)
+            .label(label);
+        frame.render_widget(gauge, row);
+    }
+}
+
+fn draw_ports(frame: &mut Frame, area: Rect, app: &App) {
+    let header = Row::new(vec!["Host", "Port", "Service", "Version", "TLS"])
+        .style(Style::default().add_modifier(Modifier::BOLD));
+
+    let rows = app.open_ports.iter().map(|((host, port), row)| {
+        Row::new(vec![
+            host.clone(),
+            port.to_string(),
+            row.service.clone().unwrap_or_default(),
+            row.version.clone().unwrap_or_default(),
+            row.tls.clone().unwrap_or_default(),
+        ])
+    });
+
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Length(18),
+            Constraint::Length(6),
+            Constraint::Length(14),
+            Constraint::Percentage(40),
+            Constraint::Percentage(40),
+        ],
+    )
+    .header(header)
+    .block(Block::default().borders(Borders::ALL).title(" Open ports "));
+    frame.render_widget(table, area);
+}
+
+fn draw_logs(frame: &mut Frame, area: Rect, app: &mut App, log_lines: &[String]) {
+    let visible = area.height.saturating_sub(2) as usize;
+    let max_scroll = log_lines.len().saturating_sub(visible);
+    app.log_scroll = app.log_scroll.min(max_scroll);
+
+    let end = log_lines.len() - app.log_scroll;
+    let start = end.saturating_sub(visible);
+    let items: Vec<ListItem> = log_lines[start..end]
+        .iter()
+        .map(|line| ListItem::new(line.as_str()))
+        .collect();
+
+    let title = if app.log_scroll > 0 {
+        format!(" Log (scrolled back {} lines) ", app.log_scroll)
+    } else {
+        " Log ".to_string()
+    };
+    frame.render_widget(List::new(items).block(Block::default().borders(Borders::ALL).title(title)), area);
+}
@@ -0,0 +1,897 @@
+//! Database handshake probes
+//!
+//! Each probe speaks just enough of a database's wire protocol to read what
+//! the server volunteers before authentication: the MySQL greeting, the
+//! PostgreSQL SSL answer and startup reply, the MSSQL PRELOGIN response, Redis
+//! `INFO server` and MongoDB `hello`/`buildInfo`. No credentials are sent and
+//! no data is read or changed.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+use crate::audit::AuditedStream;
+use crate::default_checks::{bson, info_field, mongo_command};
+use crate::models::{DatabaseInfo, ServiceIdentity};
+use crate::scope::Egress;
+use crate::service_identity;
+use crate::utils;
+
+/// What a database probe learned
+#[derive(Debug, Clone)]
+pub struct DatabaseProbeResult {
+    /// Handshake details
+    pub info: DatabaseInfo,
+    /// Product identity derived from the handshake
+    pub identity: Option<ServiceIdentity>,
+}
+
+/// Database protocols we can handshake with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Engine {
+    MySql,
+    PostgreSql,
+    MsSql,
+    Redis,
+    MongoDb,
+}
+
+impl Engine {
+    /// Pick a probe from the detected service name, falling back to the port
+    fn select(port: u16, service: Option<&str>) -> Option<Self> {
+        match service.map(|s| s.trim_start_matches("ssl/").to_lowercase()).as_deref() {
+            Some("mysql") => return Some(Self::MySql),
+            Some("postgresql") | Some("postgres") => return Some(Self::PostgreSql),
+            Some("mssql") | Some("ms-sql-s") => return Some(Self::MsSql),
+            Some("redis") => return Some(Self::Redis),
+            Some("mongodb") => return Some(Self::MongoDb),
+            _ => {}
+        }
+
+        match port {
+            3306 => Some(Self::MySql),
+            5432 => Some(Self::PostgreSql),
+            1433 => Some(Self::MsSql),
+            6379 => Some(Self::Redis),
+            27017 | 27018 => Some(Self::MongoDb),
+            _ => None,
+        }
+    }
+}
+
+/// Runs the pre-authentication database probes
+pub struct DatabaseProbes {
+    timeout: Duration,
+    egress: Egress,
+    logger: Option<Arc<utils::EnhancedLogger>>,
+}
+
+impl DatabaseProbes {
+    /// Create a prober with the given per-operation timeout
+    pub fn new(timeout: Duration, egress: Egress, logger: Option<Arc<utils::EnhancedLogger>>) -> Self {
+        Self { timeout, egress, logger }
+    }
+
+    fn log(&self, level: &str, message: &str) {
+        if let Some(logger) = &self.logger {
+            logger.log(level, message);
+        }
+    }
+
+    /// Run the probe appropriate for this port, if any
+    pub async fn run(&self, target: &str, port: u16, service: Option<&str>) -> Option<DatabaseProbeResult> {
+        let engine = Engine::select(port, service)?;
+
+        let result = match engine {
+            Engine::MySql => self.probe_mysql(target, port).await,
+            Engine::PostgreSql => self.probe_postgresql(target, port).await,
+            Engine::MsSql => self.probe_mssql(target, port).await,
+            Engine::Redis => self.probe_redis(target, port).await,
+            Engine::MongoDb => self.probe_mongodb(target, port).await,
+        };
+
+        match result {
+            Ok(Some(found)) => {
+                self.log("INFO", &format!(
+                    "{}:{} {} {}",
+                    target,
+                    port,
+                    found.info.engine,
+                    found.info.version.as_deref().unwrap_or("(version hidden)")
+                ));
+                Some(found)
+            }
+            Ok(None) => None,
+            Err(e) => {
+                self.log("DEBUG", &format!(
+                    "Database probe {:?} on {}:{} failed: {}",
+                    engine, target, port, e
+                ));
+                None
+            }
+        }
+    }
+
+    async fn connect(&self, target: &str, port: u16) -> Result<AuditedStream, anyhow::Error> {
+        Ok(tokio::time::timeout(self.timeout, self.egress.connect_tcp(target, port)).await??)
+    }
+
+    async fn read_exact(&self, stream: &mut AuditedStream, buffer: &mut [u8]) -> Result<(), anyhow::Error> {
+        tokio::time::timeout(self.timeout, stream.read_exact(buffer)).await??;
+        Ok(())
+    }
+
+    /// MySQL/MariaDB: read the initial handshake packet
+    async fn probe_mysql(&self, target: &str, port: u16) -> Result<Option<DatabaseProbeResult>, anyhow::Error> {
+        let mut stream = self.connect(target, port).await?;
+
+        let mut header = [0u8; 4];
+        self.read_exact(&mut stream, &mut header).await?;
+        let length = u32::from_le_bytes([header[0], header[1], header[2], 0]) as usize;
+        if length == 0 || length > 64 * 1024 {
+            return Ok(None);
+        }
+        let mut payload = vec![0u8; length];
+        self.read_exact(&mut stream, &mut payload).await?;
+
+        Ok(parse_mysql_handshake(&payload))
+    }
+
+    /// PostgreSQL: SSLRequest, then a startup message for a non-existent role
+    async fn probe_postgresql(&self, target: &str, port: u16) -> Result<Option<DatabaseProbeResult>, anyhow::Error> {
+        const SSL_REQUEST_CODE: i32 = 80877103;
+        const PROTOCOL_3_0: i32 = 196608;
+
+        let mut info = DatabaseInfo {
+            engine: "postgresql".to_string(),
+            ..Default::default()
+        };
+
+        // SSLRequest: the server answers a single 'S' or 'N'
+        let mut stream = self.connect(target, port).await?;
+        let mut request = 8i32.to_be_bytes().to_vec();
+        request.extend_from_slice(&SSL_REQUEST_CODE.to_be_bytes());
+        stream.write_all(&request).await?;
+        let mut answer = [0u8; 1];
+        self.read_exact(&mut stream, &mut answer).await?;
+        info.encryption = match answer[0] {
+            b'S' => Some("supported".to_string()),
+            b'N' => Some("not supported".to_string()),
+            _ => return Ok(None),
+        };
+        drop(stream);
+
+        // Startup: the reply is either an authentication request or an error
+        let mut stream = self.connect(target, port).await?;
+        let mut body = PROTOCOL_3_0.to_be_bytes().to_vec();
+        for (key, value) in [("user", "quantum_scanner"), ("database", "quantum_scanner"), ("application_name", "quantum_scanner")] {
+            body.extend_from_slice(key.as_bytes());
+            body.push(0);
+            body.extend_from_slice(value.as_bytes());
+            body.push(0);
+        }
+        body.push(0);
+        let mut startup = ((body.len() + 4) as i32).to_be_bytes().to_vec();
+        startup.extend_from_slice(&body);
+        stream.write_all(&startup).await?;
+
+        let mut header = [0u8; 5];
+        self.read_exact(&mut stream, &mut header).await?;
+        let length = i32::from_be_bytes([header[1], header[2], header[3], header[4]]) as usize;
+        if !(4..=64 * 1024).contains(&length) {
+            return Ok(None);
+        }
+        let mut message = vec![0u8; length - 4];
+        self.read_exact(&mut stream, &mut message).await?;
+
+        match header[0] {
+            b'R' => {
+                let code = message.get(..4).map(|b| i32::from_be_bytes([b[0], b[1], b[2], b[3]]));
+                let methods: Vec<String> = match code {
+                    Some(0) => vec!["trust".to_string()],
+                    Some(3) => vec!["password".to_string()],
+                    Some(5) => vec!["md5".to_string()],
+                    Some(7) => vec!["gss".to_string()],
+                    Some(9) => vec!["sspi".to_string()],
+                    Some(10) => message[4..]
+                        .split(|&b| b == 0)
+                        .filter(|m| !m.is_empty())
+                        .map(|m| String::from_utf8_lossy(m).to_string())
+                        .collect(),
+                    Some(other) => vec![format!("auth code {}", other)],
+                    None => Vec::new(),
+                };
+                info.authentication_required = Some(code != Some(0));
+                info.auth_methods = methods;
+                let _ = stream.write_all(&[b'X', 0, 0, 0, 4]).await;
+            }
+            b'E' => {
+                let fields = parse_postgres_error(&message);
+                if let Some(code) = fields.get(&'C') {
+                    info.details.insert("error_code".to_string(), code.clone());
+                }
+                if let Some(text) = fields.get(&'M') {
+                    info.details.insert("error".to_string(), text.clone());
+                }
+                // Source file, line and routine differ between releases
+                if let (Some(file), Some(line)) = (fields.get(&'F'), fields.get(&'L')) {
+                    let routine = fields.get(&'R').map(|r| format!(" ({})", r)).unwrap_or_default();
+                    info.details.insert("error_source".to_string(), format!("{}:{}{}", file, line, routine));
+                }
+                // 28000 (invalid authorization) and 28P01 (bad password) mean auth is enforced
+                if fields.get(&'C').is_some_and(|c| c.starts_with("28")) {
+                    info.authentication_required = Some(true);
+                }
+            }
+            _ => return Ok(None),
+        }
+
+        Ok(Some(DatabaseProbeResult {
+            identity: Some(ServiceIdentity {
+                product: "PostgreSQL DB".to_string(),
+                cpe: Some("cpe:/a:postgresql:postgresql".to_string()),
+                ..Default::default()
+            }),
+            info,
+        }))
+    }
+
+    /// MSSQL: TDS PRELOGIN exchange
+    async fn probe_mssql(&self, target: &str, port: u16) -> Result<Option<DatabaseProbeResult>, anyhow::Error> {
+        let mut stream = self.connect(target, port).await?;
+        stream.write_all(&tds_prelogin()).await?;
+
+        let mut header = [0u8; 8];
+        self.read_exact(&mut stream, &mut header).await?;
+        let length = u16::from_be_bytes([header[2], header[3]]) as usize;
+        if header[0] != 0x04 || length <= 8 {
+            return Ok(None);
+        }
+        let mut payload = vec![0u8; length - 8];
+        self.read_exact(&mut stream, &mut payload).await?;
+
+        Ok(parse_prelogin_response(&payload))
+    }
+
+    /// Redis: INFO server
+    async fn probe_redis(&self, target: &str, port: u16) -> Result<Option<DatabaseProbeResult>, anyhow::Error> {
+        let mut stream = self.connect(target, port).await?;
+        stream.write_all(b"*2\r\n$4\r\nINFO\r\n$6\r\nserver\r\n").await?;
+
+        let mut response = Vec::new();
+        let mut buffer = [0u8; 4096];
+        loop {
+            let n = tokio::time::timeout(self.timeout, stream.read(&mut buffer)).await??;
+            if n == 0 {
+                break;
+            }
+            response.extend_from_slice(&buffer[..n]);
+            if resp_complete(&response) || response.len() > 64 * 1024 {
+                break;
+            }
+        }
+
+        let text = String::from_utf8_lossy(&response);
+        let mut info = DatabaseInfo {
+            engine: "redis".to_string(),
+            ..Default::default()
+        };
+
+        if text.starts_with("-NOAUTH") || text.starts_with("-WRONGPASS") {
+            info.authentication_required = Some(true);
+            info.details.insert("error".to_string(), text.lines().next().unwrap_or("").trim_start_matches('-').to_string());
+            return Ok(Some(DatabaseProbeResult {
+                identity: Some(redis_identity(None, None)),
+                info,
+            }));
+        }
+        if text.starts_with("-DENIED") {
+            info.details.insert("protected_mode".to_string(), "yes".to_string());
+            return Ok(Some(DatabaseProbeResult {
+                identity: Some(redis_identity(None, None)),
+                info,
+            }));
+        }
+        if !text.starts_with('$') {
+            return Ok(None);
+        }
+
+        info.authentication_required = Some(false);
+        info.version = info_field(&text, "redis_version").map(str::to_string);
+        for key in ["redis_mode", "os", "arch_bits", "tcp_port", "uptime_in_days", "executable", "config_file"] {
+            if let Some(value) = info_field(&text, key).filter(|v| !v.is_empty()) {
+                info.details.insert(key.to_string(), value.to_string());
+            }
+        }
+
+        Ok(Some(DatabaseProbeResult {
+            identity: Some(redis_identity(info.version.as_deref(), info.details.get("os").map(String::as_str))),
+            info,
+        }))
+    }
+
+    /// MongoDB: hello (isMaster on older servers), then buildInfo
+    async fn probe_mongodb(&self, target: &str, port: u16) -> Result<Option<DatabaseProbeResult>, anyhow::Error> {
+        let mut stream = self.connect(target, port).await?;
+
+        let mut hello = mongo_command(
+            &mut stream,
+            &bson::Document::new().int32("hello", 1).string("$db", "admin"),
+            self.timeout,
+        ).await?;
+        if bson::find_number(&hello, "ok") != Some(1.0) {
+            hello = mongo_command(
+                &mut stream,
+                &bson::Document::new().int32("isMaster", 1).string("$db", "admin"),
+                self.timeout,
+            ).await?;
+        }
+        let fields = match bson::parse(&hello) {
+            Some(fields) => fields,
+            None => return Ok(None),
+        };
+
+        let mut info = DatabaseInfo {
+            engine: "mongodb".to_string(),
+            ..Default::default()
+        };
+
+        let max_wire = bson::find_number(&hello, "maxWireVersion");
+        if let Some(wire) = max_wire {
+            info.details.insert("max_wire_version".to_string(), (wire as i64).to_string());
+        }
+        let primary = top_level(&fields, "isWritablePrimary").or_else(|| top_level(&fields, "ismaster"));
+        if let Some(bson::Value::Bool(primary)) = primary {
+            info.details.insert("writable_primary".to_string(), primary.to_string());
+        }
+        if let Some(bson::Value::String(set)) = top_level(&fields, "setName") {
+            info.details.insert("replica_set".to_string(), set.clone());
+        }
+        if let Some(bson::Value::String(msg)) = top_level(&fields, "msg") {
+            if msg == "isdbgrid" {
+                info.details.insert("role".to_string(), "mongos router".to_string());
+            }
+        }
+
+        // buildInfo is allowed before authentication and carries the exact version
+        let build_info = mongo_command(
+            &mut stream,
+            &bson::Document::new().int32("buildInfo", 1).string("$db", "admin"),
+            self.timeout,
+        ).await.ok();
+        if let Some(build) = build_info.as_deref().and_then(bson::parse) {
+            if let Some(bson::Value::String(version)) = top_level(&build, "version") {
+                info.version = Some(version.clone());
+            }
+            for key in ["gitVersion", "allocator", "javascriptEngine"] {
+                if let Some(bson::Value::String(value)) = top_level(&build, key) {
+                    info.details.insert(key.to_string(), value.clone());
+                }
+            }
+            if let Some(bson::Value::Array(modules)) = top_level(&build, "modules") {
+                if modules.iter().any(|m| *m == bson::Value::String("enterprise".to_string())) {
+                    info.details.insert("edition".to_string(), "enterprise".to_string());
+                }
+            }
+        }
+
+        // Fall back to the release series implied by the wire version
+        let version = info.version.clone().or_else(|| {
+            max_wire.and_then(|w| mongo_series(w as i64)).map(|series| format!("{}.x", series))
+        });
+        Ok(Some(DatabaseProbeResult {
+            identity: Some(ServiceIdentity {
+                product: "MongoDB".to_string(),
+                cpe: Some(match &info.version {
+                    Some(v) => format!("cpe:/a:mongodb:mongodb:{}", v),
+                    None => "cpe:/a:mongodb:mongodb".to_string(),
+                }),
+                version,
+                ..Default::default()
+            }),
+            info,
+        }))
+    }
+}
+
+/// Parse a MySQL initial handshake (protocol 10) or error packet
+fn parse_mysql_handshake(payload: &[u8]) -> Option<DatabaseProbeResult> {
+    const CAPABILITIES: &[(u32, &str)] = &[
+        (0x0000_0020, "COMPRESS"),
+        (0x0000_0200, "PROTOCOL_41"),
+        (0x0000_0800, "SSL"),
+        (0x0000_8000, "SECURE_CONNECTION"),
+        (0x0001_0000, "MULTI_STATEMENTS"),
+        (0x0008_0000, "PLUGIN_AUTH"),
+        (0x0010_0000, "CONNECT_ATTRS"),
+        (0x0040_0000, "CAN_HANDLE_EXPIRED_PASSWORDS"),
+        (0x0100_0000, "SESSION_TRACK"),
+        (0x0400_0000, "OPTIONAL_RESULTSET_METADATA"),
+    ];
+
+    let mut info = DatabaseInfo {
+        engine: "mysql".to_string(),
+        ..Default::default()
+    };
+
+    match payload.first()? {
+        0xff => {
+            // Error packet: code, optional "#SQLSTATE", message
+            let code = u16::from_le_bytes([*payload.get(1)?, *payload.get(2)?]);
+            let mut message = payload.get(3..).unwrap_or_default();
+            if message.first() == Some(&b'#') {
+                message = message.get(6..).unwrap_or_default();
+            }
+            info.details.insert("error_code".to_string(), code.to_string());
+            info.details.insert("error".to_string(), String::from_utf8_lossy(message).to_string());
+            return Some(DatabaseProbeResult {
+                identity: Some(ServiceIdentity {
+                    product: "MySQL".to_string(),
+                    extra_info: Some("unauthorized".to_string()),
+                    cpe: Some("cpe:/a:mysql:mysql".to_string()),
+                    ..Default::default()
+                }),
+                info,
+            });
+        }
+        0x0a => {}
+        _ => return None,
+    }
+
+    let version_end = payload[1..].iter().position(|&b| b == 0)? + 1;
+    let raw_version = String::from_utf8_lossy(&payload[1..version_end]).to_string();
+    let mut pos = version_end + 1;
+
+    let connection_id = u32::from_le_bytes(payload.get(pos..pos + 4)?.try_into().ok()?);
+    pos += 4 + 8 + 1; // connection id, auth-plugin-data part 1, filler
+    let lower = u16::from_le_bytes(payload.get(pos..pos + 2)?.try_into().ok()?) as u32;
+    pos += 2;
+
+    let mut capabilities = lower;
+    let mut auth_plugin = None;
+    if payload.len() >= pos + 16 {
+        let charset = payload[pos];
+        let status = u16::from_le_bytes([payload[pos + 1], payload[pos + 2]]);
+        let upper = u16::from_le_bytes([payload[pos + 3], payload[pos + 4]]) as u32;
+        let auth_data_len = payload[pos + 5] as usize;
+        capabilities |= upper << 16;
+        info.details.insert("charset".to_string(), charset.to_string());
+        info.details.insert("status".to_string(), format!("0x{:04x}", status));
+        pos += 16; // charset, status, upper flags, auth data length, 10 reserved
+
+        if capabilities & 0x0008_0000 != 0 {
+            pos += auth_data_len.saturating_sub(8).max(13);
+            if let Some(rest) = payload.get(pos..) {
+                let end = rest.iter().position(|&b| b == 0).unwrap_or(rest.len());
+                auth_plugin = Some(String::from_utf8_lossy(&rest[..end]).to_string()).filter(|p| !p.is_empty());
+            }
+        }
+    }
+
+    let flags: Vec<&str> = CAPABILITIES.iter()
+        .filter(|(bit, _)| capabilities & bit != 0)
+        .map(|(_, name)| *name)
+        .collect();
+    info.details.insert("connection_id".to_string(), connection_id.to_string());
+    info.details.insert("capabilities".to_string(), format!("0x{:08x} ({})", capabilities, flags.join(", ")));
+    info.encryption = Some(if capabilities & 0x0800 != 0 { "supported" } else { "not supported" }.to_string());
+    info.auth_methods = auth_plugin.into_iter().collect();
+
+    let identity = service_identity::identity_from_mysql_version(&raw_version);
+    info.version = identity.version.clone();
+    info.details.insert("server_version".to_string(), raw_version);
+
+    Some(DatabaseProbeResult {
+        identity: Some(identity),
+        info,
+    })
+}
+
+/// Fields of a PostgreSQL ErrorResponse, keyed by field type
+fn parse_postgres_error(message: &[u8]) -> BTreeMap<char, String> {
+    message.split(|&b| b == 0)
+        .filter(|field| field.len() > 1)
+        .map(|field| (field[0] as char, String::from_utf8_lossy(&field[1..]).to_string()))
+        .collect()
+}
+
+/// Build a TDS PRELOGIN packet offering encryption but not requiring it
+fn tds_prelogin() -> Vec<u8> {
+    // (token, data): VERSION, ENCRYPTION (ENCRYPT_OFF), INSTOPT, THREADID, MARS
+    let options: [(u8, Vec<u8>); 5] = [
+        (0x00, vec![0, 0, 0, 0, 0, 0]),
+        (0x01, vec![0x00]),
+        (0x02, vec![0x00]),
+        (0x03, vec![0, 0, 0, 0]),
+        (0x04, vec![0x00]),
+    ];
+
+    let table_len = options.len() * 5 + 1;
+    let mut table = Vec::new();
+    let mut data = Vec::new();
+    for (token, value) in &options {
+        table.push(*token);
+        table.extend_from_slice(&((table_len + data.len()) as u16).to_be_bytes());
+        table.extend_from_slice(&(value.len() as u16).to_be_bytes());
+        data.extend_from_slice(value);
+    }
+    table.push(0xff);
+
+    let length = 8 + table.len() + data.len();
+    let mut packet = vec![0x12, 0x01];
+    packet.extend_from_slice(&(length as u16).to_be_bytes());
+    packet.extend_from_slice(&[0, 0, 1, 0]); // SPID, packet id, window
+    packet.extend_from_slice(&table);
+    packet.extend_from_slice(&data);
+    packet
+}
+
+/// Parse the server's PRELOGIN response
+fn parse_prelogin_response(payload: &[u8]) -> Option<DatabaseProbeResult> {
+    let mut info = DatabaseInfo {
+        engine: "mssql".to_string(),
+        ..Default::default()
+    };
+    let mut identity = None;
+
+    let mut pos = 0;
+    while let Some(&token) = payload.get(pos) {
+        if token == 0xff {
+            break;
+        }
+        let option = payload.get(pos + 1..pos + 5)?;
+        let offset = u16::from_be_bytes([option[0], option[1]]) as usize;
+        let length = u16::from_be_bytes([option[2], option[3]]) as usize;
+        let value = payload.get(offset..offset + length)?;
+
+        match token {
+            0x00 if length >= 6 => {
+                let build = u16::from_be_bytes([value[2], value[3]]);
+                let subbuild = u16::from_le_bytes([value[4], value[5]]);
+                let version = format!("{}.{}.{}", value[0], value[1], build);
+                info.details.insert("subbuild".to_string(), subbuild.to_string());
+                let release = mssql_release(value[0], value[1]);
+                identity = Some(ServiceIdentity {
+                    product: match release {
+                        Some(release) => format!("Microsoft SQL Server {}", release),
+                        None => "Microsoft SQL Server".to_string(),
+                    },
+                    version: Some(version.clone()),
+                    cpe: Some(match release {
+                        Some(release) => format!("cpe:/a:microsoft:sql_server:{}", release.to_lowercase().replace(' ', "_")),
+                        None => "cpe:/a:microsoft:sql_server".to_string(),
+                    }),
+                    ..Default::default()
+                });
+                info.version = Some(version);
+            }
+            0x01 if length >= 1 => {
+                info.encryption = Some(match value[0] {
+                    0x00 => "supported (off)",
+                    0x01 => "on",
+                    0x02 => "not supported",
+                    0x03 => "required",
+                    _ => "unknown",
+                }.to_string());
+            }
+            0x02 if length > 1 => {
+                let name = String::from_utf8_lossy(value).trim_end_matches('\0').to_string();
+                if !name.is_empty() {
+                    info.details.insert("instance".to_string(), name);
+                }
+            }
+            0x04 if length >= 1 => {
+                info.details.insert("mars".to_string(), (value[0] == 1).to_string());
+            }
+            _ => {}
+        }
+        pos += 5;
+    }
+
+    info.version.as_ref()?;
+    Some(DatabaseProbeResult { info, identity })
+}
+
+/// Marketing release name for an MSSQL major/minor version
+fn mssql_release(major: u8, minor: u8) -> Option<&'static str> {
+    Some(match (major, minor) {
+        (8, _) => "2000",
+        (9, _) => "2005",
+        (10, 50) => "2008 R2",
+        (10, _) => "2008",
+        (11, _) => "2012",
+        (12, _) => "2014",
+        (13, _) => "2016",
+        (14, _) => "2017",
+        (15, _) => "2019",
+        (16, _) => "2022",
+        _ => return None,
+    })
+}
+
+/// Whether a RESP reply has been received in full
+fn resp_complete(response: &[u8]) -> bool {
+    match response.first() {
+        Some(b'$') => {
+            let header_end = match response.windows(2).position(|w| w == b"\r\n") {
+                Some(end) => end,
+                None => return false,
+            };
+            let length: i64 = String::from_utf8_lossy(&response[1..header_end]).parse().unwrap_or(-1);
+            length < 0 || response.len() >= header_end + 2 + length as usize + 2
+        }
+        Some(_) => response.ends_with(b"\r\n"),
+        None => false,
+    }
+}
+
+fn redis_identity(version: Option<&str>, os: Option<&str>) -> ServiceIdentity {
+    ServiceIdentity {
+        product: "Redis key-value store".to_string(),
+        version: version.map(str::to_string),
+        os_hint: os.and_then(|os| os.split_whitespace().next()).map(str::to_string),
+        cpe: Some(match version {
+            Some(v) => format!("cpe:/a:redislabs:redis:{}", v),
+            None => "cpe:/a:redislabs:redis".to_string(),
+        }),
+        ..Default::default()
+    }
+}
+
+/// Look up a top-level field of a parsed BSON document
+fn top_level<'a>(fields: &'a [(String, bson::Value)], name: &str) -> Option<&'a bson::Value> {
+    fields.iter().find(|(key, _)| key == name).map(|(_, value)| value)
+}
+
+/// MongoDB release series for a maxWireVersion
+fn mongo_series(max_wire_version: i64) -> Option<&'static str> {
+    Some(match max_wire_version {
+        6 => "3.6",
+        7 => "4.0",
+        8 => "4.2",
+        9 => "4.4",
+        13 => "5.0",
+        17 => "6.0",
+        21 => "7.0",
+        25 => "8.0",
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Answer the n-th connection's first request with `replies[n]`
+    async fn fake_server(replies: Vec<Vec<u8>>) -> u16 {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            for reply in replies {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut buffer = [0u8; 1024];
+                let _ = stream.read(&mut buffer).await;
+                let _ = stream.write_all(&reply).await;
+                // Hold the connection until the client is done with it
+                let _ = stream.read(&mut buffer).await;
+            }
+        });
+        port
+    }
+
+    async fn probe(port: u16, service: &str) -> Option<DatabaseProbeResult> {
+        DatabaseProbes::new(Duration::from_secs(2), Egress::default(), None)
+            .run("127.0.0.1", port, Some(service))
+            .await
+    }
+
+    /// MySQL protocol 10 handshake with the given capability halves
+    fn mysql_greeting(version: &str, lower: u16, upper: u16, plugin: &str) -> Vec<u8> {
+        let mut payload = vec![0x0a];
+        payload.extend_from_slice(version.as_bytes());
+        payload.push(0);
+        payload.extend_from_slice(&42u32.to_le_bytes());
+        payload.extend_from_slice(b"abcdefgh");
+        payload.push(0);
+        payload.extend_from_slice(&lower.to_le_bytes());
+        payload.push(0xff); // charset
+        payload.extend_from_slice(&0x0002u16.to_le_bytes());
+        payload.extend_from_slice(&upper.to_le_bytes());
+        payload.push(21);
+        payload.extend_from_slice(&[0; 10]);
+        payload.extend_from_slice(b"ijklmnopqrst\0");
+        payload.extend_from_slice(plugin.as_bytes());
+        payload.push(0);
+        payload
+    }
+
+    /// TDS PRELOGIN option table followed by the option data
+    fn prelogin(options: &[(u8, &[u8])]) -> Vec<u8> {
+        let table_len = options.len() * 5 + 1;
+        let mut table = Vec::new();
+        let mut data = Vec::new();
+        for (token, value) in options {
+            table.push(*token);
+            table.extend_from_slice(&((table_len + data.len()) as u16).to_be_bytes());
+            table.extend_from_slice(&(value.len() as u16).to_be_bytes());
+            data.extend_from_slice(value);
+        }
+        table.push(0xff);
+        table.extend_from_slice(&data);
+        table
+    }
+
+    #[test]
+    fn engines_follow_service_then_port() {
+        assert_eq!(Engine::select(3307, Some("mysql")), Some(Engine::MySql));
+        assert_eq!(Engine::select(9999, Some("ssl/postgres")), Some(Engine::PostgreSql));
+        assert_eq!(Engine::select(1433, None), Some(Engine::MsSql));
+        assert_eq!(Engine::select(6379, Some("unknown")), Some(Engine::Redis));
+        assert_eq!(Engine::select(27018, None), Some(Engine::MongoDb));
+        assert_eq!(Engine::select(8080, Some("http")), None);
+    }
+
+    #[test]
+    fn mysql_greeting_is_parsed() {
+        let greeting = mysql_greeting("8.0.36-0ubuntu0.22.04.1", 0x8a00, 0x0008, "caching_sha2_password");
+        let found = parse_mysql_handshake(&greeting).unwrap();
+        let info = &found.info;
+        assert_eq!(info.version.as_deref(), Some("8.0.36"));
+        assert_eq!(info.encryption.as_deref(), Some("supported"));
+        assert_eq!(info.auth_methods, ["caching_sha2_password"]);
+        assert_eq!(info.details["connection_id"], "42");
+        assert_eq!(info.details["server_version"], "8.0.36-0ubuntu0.22.04.1");
+        assert_eq!(info.details["capabilities"], "0x00088a00 (PROTOCOL_41, SSL, SECURE_CONNECTION, PLUGIN_AUTH)");
+        assert_eq!(found.identity.unwrap().product, "MySQL");
+
+        // Without SSL or PLUGIN_AUTH
+        let plain = parse_mysql_handshake(&mysql_greeting("5.5.5-10.6.12-MariaDB", 0x8200, 0, "")).unwrap();
+        assert_eq!(plain.info.encryption.as_deref(), Some("not supported"));
+        assert!(plain.info.auth_methods.is_empty());
+        assert_eq!(plain.identity.unwrap().product, "MariaDB");
+
+        assert!(parse_mysql_handshake(&greeting[..12]).is_none());
+        assert!(parse_mysql_handshake(b"\x09junk").is_none());
+        assert!(parse_mysql_handshake(b"").is_none());
+    }
+
+    #[test]
+    fn mysql_errors_are_reported() {
+        let mut packet = vec![0xff];
+        packet.extend_from_slice(&1130u16.to_le_bytes());
+        packet.extend_from_slice(b"Host '10.0.0.5' is not allowed to connect to this MySQL server");
+        let found = parse_mysql_handshake(&packet).unwrap();
+        assert_eq!(found.info.details["error_code"], "1130");
+        assert!(found.info.details["error"].starts_with("Host '10.0.0.5'"));
+        assert_eq!(found.identity.unwrap().extra_info.as_deref(), Some("unauthorized"));
+
+        let mut packet = vec![0xff];
+        packet.extend_from_slice(&1045u16.to_le_bytes());
+        packet.extend_from_slice(b"#28000Access denied");
+        assert_eq!(parse_mysql_handshake(&packet).unwrap().info.details["error"], "Access denied");
+    }
+
+    #[test]
+    fn prelogin_request_is_well_formed() {
+        let packet = tds_prelogin();
+        assert_eq!(&packet[..2], [0x12, 0x01]);
+        assert_eq!(u16::from_be_bytes([packet[2], packet[3]]) as usize, packet.len());
+        // Five options of five bytes, then the terminator
+        assert_eq!(packet[8 + 25], 0xff);
+        // The option table points at data inside the packet
+        let parsed = parse_prelogin_response(&packet[8..]).unwrap();
+        assert_eq!(parsed.info.version.as_deref(), Some("0.0.0"));
+        assert_eq!(parsed.info.encryption.as_deref(), Some("supported (off)"));
+    }
+
+    #[test]
+    fn prelogin_response_is_parsed() {
+        let payload = prelogin(&[
+            (0x00, &[15, 0, 0x07, 0xd0, 0x00, 0x00]),
+            (0x01, &[0x02]),
+            (0x02, b"SQLEXPRESS\0"),
+            (0x04, &[0x01]),
+        ]);
+        let found = parse_prelogin_response(&payload).unwrap();
+        assert_eq!(found.info.version.as_deref(), Some("15.0.2000"));
+        assert_eq!(found.info.encryption.as_deref(), Some("not supported"));
+        assert_eq!(found.info.details["instance"], "SQLEXPRESS");
+        assert_eq!(found.info.details["mars"], "true");
+        let identity = found.identity.unwrap();
+        assert_eq!(identity.product, "Microsoft SQL Server 2019");
+        assert_eq!(identity.cpe.as_deref(), Some("cpe:/a:microsoft:sql_server:2019"));
+
+        let r2 = parse_prelogin_response(&prelogin(&[(0x00, &[10, 50, 0x06, 0x40, 0, 0])])).unwrap();
+        assert_eq!(r2.identity.unwrap().cpe.as_deref(), Some("cpe:/a:microsoft:sql_server:2008_r2"));
+
+        // No version, or an option pointing past the end
+        assert!(parse_prelogin_response(&prelogin(&[(0x01, &[0x03])])).is_none());
+        let mut truncated = prelogin(&[(0x00, &[15, 0, 0x07, 0xd0, 0, 0])]);
+        truncated.truncate(8);
+        assert!(parse_prelogin_response(&truncated).is_none());
+    }
+
+    #[test]
+    fn resp_replies_are_framed() {
+        assert!(resp_complete(b"$5\r\nhello\r\n"));
+        assert!(!resp_complete(b"$5\r\nhel"));
+        assert!(!resp_complete(b"$5"));
+        assert!(resp_complete(b"$-1\r\n"));
+        assert!(resp_complete(b"-NOAUTH Authentication required.\r\n"));
+        assert!(!resp_complete(b"-NOAUTH"));
+        assert!(!resp_complete(b""));
+    }
+
+    #[test]
+    fn postgres_error_fields() {
+        let fields = parse_postgres_error(b"SFATAL\0C28000\0Mno pg_hba.conf entry\0Fauth.c\0L543\0\0");
+        assert_eq!(fields[&'C'], "28000");
+        assert_eq!(fields[&'M'], "no pg_hba.conf entry");
+        assert_eq!(fields[&'L'], "543");
+        assert_eq!(fields.len(), 5);
+    }
+
+    #[tokio::test]
+    async fn postgres_handshake() {
+        // SSLRequest refused, then SCRAM authentication requested
+        let mut auth = vec![b'R'];
+        let methods = b"SCRAM-SHA-256\0SCRAM-SHA-256-PLUS\0\0";
+        auth.extend_from_slice(&((8 + methods.len()) as i32).to_be_bytes());
+        auth.extend_from_slice(&10i32.to_be_bytes());
+        auth.extend_from_slice(methods);
+        let port = fake_server(vec![b"N".to_vec(), auth]).await;
+
+        let found = probe(port, "postgresql").await.unwrap();
+        assert_eq!(found.info.encryption.as_deref(), Some("not supported"));
+        assert_eq!(found.info.auth_methods, ["SCRAM-SHA-256", "SCRAM-SHA-256-PLUS"]);
+        assert_eq!(found.info.authentication_required, Some(true));
+
+        // SSL offered, then rejected by pg_hba.conf
+        let fields = b"SFATAL\0C28000\0Mno pg_hba.conf entry\0Fauth.c\0L543\0RClientAuthentication\0\0";
+        let mut error = vec![b'E'];
+        error.extend_from_slice(&((4 + fields.len()) as i32).to_be_bytes());
+        error.extend_from_slice(fields);
+        let port = fake_server(vec![b"S".to_vec(), error]).await;
+
+        let found = probe(port, "postgresql").await.unwrap();
+        assert_eq!(found.info.encryption.as_deref(), Some("supported"));
+        assert_eq!(found.info.authentication_required, Some(true));
+        assert_eq!(found.info.details["error_source"], "auth.c:543 (ClientAuthentication)");
+
+        // Something that isn't PostgreSQL
+        let port = fake_server(vec![b"HTTP/1.1 400 Bad Request\r\n\r\n".to_vec()]).await;
+        assert!(probe(port, "postgresql").await.is_none());
+    }
+
+    #[tokio::test]
+    async fn redis_info_and_auth() {
+        let info = "# Server\r\nredis_version:7.2.4\r\nredis_mode:standalone\r\nos:Linux 6.1.0 x86_64\r\ntcp_port:6379\r\n";
+        let reply = format!("${}\r\n{}\r\n", info.len(), info);
+        let port = fake_server(vec![reply.into_bytes()]).await;
+
+        let found = probe(port, "redis").await.unwrap();
+        assert_eq!(found.info.authentication_required, Some(false));
+        assert_eq!(found.info.version.as_deref(), Some("7.2.4"));
+        assert_eq!(found.info.details["redis_mode"], "standalone");
+        let identity = found.identity.unwrap();
+        assert_eq!(identity.os_hint.as_deref(), Some("Linux"));
+        assert_eq!(identity.cpe.as_deref(), Some("cpe:/a:redislabs:redis:7.2.4"));
+
+        let port = fake_server(vec![b"-NOAUTH Authentication required.\r\n".to_vec()]).await;
+        let found = probe(port, "redis").await.unwrap();
+        assert_eq!(found.info.authentication_required, Some(true));
+        assert_eq!(found.info.details["error"], "NOAUTH Authentication required.");
+
+        let port = fake_server(vec![b"-DENIED Redis is running in protected mode\r\n".to_vec()]).await;
+        assert_eq!(probe(port, "redis").await.unwrap().info.details["protected_mode"], "yes");
+    }
+
+    #[test]
+    fn mongo_wire_versions_map_to_series() {
+        assert_eq!(mongo_series(17), Some("6.0"));
+        assert_eq!(mongo_series(25), Some("8.0"));
+        assert_eq!(mongo_series(3), None);
+    }
+}
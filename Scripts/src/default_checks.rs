@@ -0,0 +1,754 @@
+//! Safe default-configuration checks for common services
+//!
+//! Every check here is strictly read-only: it logs in anonymously or issues an
+//! informational request (INFO, stats, version, list) and never writes, deletes
+//! or changes server state. A finding is only reported when the server actually
+//! answers the request, not merely because the port is open.
+
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+use crate::audit::AuditedStream;
+use crate::http_client::{self, HttpRequest};
+use crate::scope::Egress;
+use crate::utils;
+
+/// A confirmed anonymous / unauthenticated access finding
+#[derive(Debug, Clone)]
+pub struct DefaultCheckFinding {
+    /// Service the finding applies to (e.g. "redis")
+    pub service: &'static str,
+    /// Short description of the exposure
+    pub summary: String,
+    /// Evidence taken from the server's response
+    pub evidence: String,
+}
+
+impl fmt::Display for DefaultCheckFinding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}] {} ({})", self.service, self.summary, self.evidence)
+    }
+}
+
+/// Services we know how to check
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CheckTarget {
+    Ftp,
+    Redis,
+    MongoDb,
+    Elasticsearch,
+    Memcached,
+    DockerApi,
+    Kubelet { tls: bool },
+}
+
+impl CheckTarget {
+    /// Pick a check from the detected service name, falling back to the port
+    fn select(port: u16, service: Option<&str>) -> Option<Self> {
+        match service.map(|s| s.to_lowercase()).as_deref() {
+            Some("ftp") => return Some(Self::Ftp),
+            Some("redis") => return Some(Self::Redis),
+            Some("mongodb") => return Some(Self::MongoDb),
+            Some("elasticsearch") => return Some(Self::Elasticsearch),
+            Some("memcached") => return Some(Self::Memcached),
+            _ => {}
+        }
+
+        match port {
+            21 => Some(Self::Ftp),
+            6379 => Some(Self::Redis),
+            27017 | 27018 => Some(Self::MongoDb),
+            9200 => Some(Self::Elasticsearch),
+            11211 => Some(Self::Memcached),
+            2375 => Some(Self::DockerApi),
+            10250 => Some(Self::Kubelet { tls: true }),
+            10255 => Some(Self::Kubelet { tls: false }),
+            _ => None,
+        }
+    }
+}
+
+/// Runs the non-destructive default-configuration checks
+pub struct DefaultChecks {
+    timeout: Duration,
+    egress: Egress,
+    logger: Option<Arc<utils::EnhancedLogger>>,
+}
+
+impl DefaultChecks {
+    /// Create a checker with the given per-operation timeout
+    pub fn new(timeout: Duration, egress: Egress, logger: Option<Arc<utils::EnhancedLogger>>) -> Self {
+        Self { timeout, egress, logger }
+    }
+
+    fn log(&self, level: &str, message: &str) {
+        if let Some(logger) = &self.logger {
+            logger.log(level, message);
+        }
+    }
+
+    /// Run the check appropriate for this port, if any
+    pub async fn run(&self, target: &str, port: u16, service: Option<&str>) -> Option<DefaultCheckFinding> {
+        let check = CheckTarget::select(port, service)?;
+
+        let result = match check {
+            CheckTarget::Ftp => self.check_ftp(target, port).await,
+            CheckTarget::Redis => self.check_redis(target, port).await,
+            CheckTarget::MongoDb => self.check_mongodb(target, port).await,
+            CheckTarget::Elasticsearch => self.check_elasticsearch(target, port).await,
+            CheckTarget::Memcached => self.check_memcached(target, port).await,
+            CheckTarget::DockerApi => self.check_docker(target, port).await,
+            CheckTarget::Kubelet { tls } => self.check_kubelet(target, port, tls).await,
+        };
+
+        match result {
+            Ok(Some(finding)) => {
+                self.log("WARN", &format!("{}:{} {}", target, port, finding));
+                Some(finding)
+            }
+            Ok(None) => None,
+            Err(e) => {
+                self.log("DEBUG", &format!(
+                    "Default-config check {:?} on {}:{} failed: {}",
+                    check, target, port, e
+                ));
+                None
+            }
+        }
+    }
+
+    async fn connect(&self, target: &str, port: u16) -> Result<AuditedStream, anyhow::Error> {
+        Ok(tokio::time::timeout(self.timeout, self.egress.connect_tcp(target, port)).await??)
+    }
+
+    /// Read whatever the server sends within the timeout
+    async fn read_some(&self, stream: &mut AuditedStream) -> Result<Vec<u8>, anyhow::Error> {
+        let mut buffer = vec![0u8; 8192];
+        let n = tokio::time::timeout(self.timeout, stream.read(&mut buffer)).await??;
+        buffer.truncate(n);
+        Ok(buffer)
+    }
+
+    /// Read a (possibly multi-line) FTP reply and return its final line
+    async fn read_ftp_reply(&self, stream: &mut AuditedStream) -> Result<String, anyhow::Error> {
+        let mut reply = String::new();
+        loop {
+            let chunk = self.read_some(stream).await?;
+            if chunk.is_empty() {
+                break;
+            }
+            reply.push_str(&String::from_utf8_lossy(&chunk));
+            if ftp_reply_complete(&reply) {
+                break;
+            }
+        }
+        Ok(reply.lines().last().unwrap_or("").trim().to_string())
+    }
+
+    /// Anonymous FTP: USER anonymous / PASS, then QUIT
+    async fn check_ftp(&self, target: &str, port: u16) -> Result<Option<DefaultCheckFinding>, anyhow::Error> {
+        let mut stream = self.connect(target, port).await?;
+
+        let greeting = self.read_ftp_reply(&mut stream).await?;
+        if !greeting.starts_with("220") {
+            return Ok(None);
+        }
+
+        stream.write_all(b"USER anonymous\r\n").await?;
+        let reply = self.read_ftp_reply(&mut stream).await?;
+
+        let login = if reply.starts_with("230") {
+            reply
+        } else if reply.starts_with("331") {
+            stream.write_all(b"PASS anonymous@example.com\r\n").await?;
+            self.read_ftp_reply(&mut stream).await?
+        } else {
+            return Ok(None);
+        };
+
+        let _ = stream.write_all(b"QUIT\r\n").await;
+
+        if login.starts_with("230") {
+            Ok(Some(DefaultCheckFinding {
+                service: "ftp",
+                summary: "Anonymous FTP login allowed".to_string(),
+                evidence: login,
+            }))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Unauthenticated Redis: INFO server
+    async fn check_redis(&self, target: &str, port: u16) -> Result<Option<DefaultCheckFinding>, anyhow::Error> {
+        let mut stream = self.connect(target, port).await?;
+        stream.write_all(b"*2\r\n$4\r\nINFO\r\n$6\r\nserver\r\n").await?;
+
+        let mut response = Vec::new();
+        while let Ok(chunk) = self.read_some(&mut stream).await {
+            if chunk.is_empty() {
+                break;
+            }
+            response.extend_from_slice(&chunk);
+            if response.windows(14).any(|w| w == b"redis_version:") && response.ends_with(b"\r\n") {
+                break;
+            }
+        }
+
+        let text = String::from_utf8_lossy(&response);
+        if !text.starts_with('$') {
+            // "-NOAUTH" / "-ERR" means authentication is enforced
+            return Ok(None);
+        }
+
+        let version = info_field(&text, "redis_version").unwrap_or("unknown");
+        let mode = info_field(&text, "redis_mode").unwrap_or("unknown");
+        Ok(Some(DefaultCheckFinding {
+            service: "redis",
+            summary: "Redis accepts unauthenticated commands".to_string(),
+            evidence: format!("INFO server returned redis_version={} redis_mode={}", version, mode),
+        }))
+    }
+
+    /// MongoDB without auth: listDatabases succeeds
+    async fn check_mongodb(&self, target: &str, port: u16) -> Result<Option<DefaultCheckFinding>, anyhow::Error> {
+        let mut stream = self.connect(target, port).await?;
+
+        let command = bson::Document::new()
+            .int32("listDatabases", 1)
+            .boolean("nameOnly", true)
+            .string("$db", "admin");
+        let reply = mongo_command(&mut stream, &command, self.timeout).await?;
+
+        let ok = bson::find_number(&reply, "ok").unwrap_or(0.0);
+        if ok != 1.0 {
+            return Ok(None);
+        }
+
+        let names = bson::find_strings(&reply, "name");
+        Ok(Some(DefaultCheckFinding {
+            service: "mongodb",
+            summary: "MongoDB allows unauthenticated access".to_string(),
+            evidence: format!("listDatabases returned {} databases: {}", names.len(), names.join(", ")),
+        }))
+    }
+
+    /// Open Elasticsearch: cluster info and index listing over HTTP
+    async fn check_elasticsearch(&self, target: &str, port: u16) -> Result<Option<DefaultCheckFinding>, anyhow::Error> {
+        let response = http_client::send_request(target, port, false, &HttpRequest::get("/"), self.timeout, &self.egress).await?;
+        if response.status_code != 200 {
+            return Ok(None);
+        }
+
+        let info: serde_json::Value = match serde_json::from_slice(&response.body) {
+            Ok(v) => v,
+            Err(_) => return Ok(None),
+        };
+        let cluster = match info.get("cluster_name").and_then(|v| v.as_str()) {
+            Some(name) => name.to_string(),
+            None => return Ok(None),
+        };
+        let version = info
+            .pointer("/version/number")
+            .and_then(|v| v.as_str())
+            .unwrap_or("unknown")
+            .to_string();
+
+        let indices = http_client::send_request(target, port, false, &HttpRequest::get("/_cat/indices?format=json"), self.timeout, &self.egress)
+            .await
+            .ok()
+            .filter(|r| r.status_code == 200)
+            .and_then(|r| serde_json::from_slice::<Vec<serde_json::Value>>(&r.body).ok())
+            .map(|v| v.len());
+
+        Ok(Some(DefaultCheckFinding {
+            service: "elasticsearch",
+            summary: "Elasticsearch API accessible without authentication".to_string(),
+            evidence: format!(
+                "cluster_name={} version={} indices={}",
+                cluster,
+                version,
+                indices.map(|n| n.to_string()).unwrap_or_else(|| "unknown".to_string())
+            ),
+        }))
+    }
+
+    /// Memcached: stats
+    async fn check_memcached(&self, target: &str, port: u16) -> Result<Option<DefaultCheckFinding>, anyhow::Error> {
+        let mut stream = self.connect(target, port).await?;
+        stream.write_all(b"stats\r\n").await?;
+
+        let mut response = Vec::new();
+        while let Ok(chunk) = self.read_some(&mut stream).await {
+            if chunk.is_empty() {
+                break;
+            }
+            response.extend_from_slice(&chunk);
+            if response.ends_with(b"END\r\n") {
+                break;
+            }
+        }
+
+        let text = String::from_utf8_lossy(&response);
+        if !text.starts_with("STAT ") {
+            return Ok(None);
+        }
+
+        let stat = |name: &str| {
+            text.lines()
+                .find_map(|l| l.strip_prefix(&format!("STAT {} ", name)))
+                .unwrap_or("unknown")
+                .trim()
+                .to_string()
+        };
+
+        Ok(Some(DefaultCheckFinding {
+            service: "memcached",
+            summary: "Memcached stats available without authentication".to_string(),
+            evidence: format!("version={} curr_items={}", stat("version"), stat("curr_items")),
+        }))
+    }
+
+    /// Docker Engine API on plain TCP: GET /version
+    async fn check_docker(&self, target: &str, port: u16) -> Result<Option<DefaultCheckFinding>, anyhow::Error> {
+        let response = http_client::send_request(target, port, false, &HttpRequest::get("/version"), self.timeout, &self.egress).await?;
+        if response.status_code != 200 {
+            return Ok(None);
+        }
+
+        let info: serde_json::Value = match serde_json::from_slice(&response.body) {
+            Ok(v) => v,
+            Err(_) => return Ok(None),
+        };
+        let api_version = match info.get("ApiVersion").and_then(|v| v.as_str()) {
+            Some(v) => v.to_string(),
+            None => return Ok(None),
+        };
+
+        Ok(Some(DefaultCheckFinding {
+            service: "docker",
+            summary: "Docker Engine API exposed without TLS or authentication".to_string(),
+            evidence: format!(
+                "Version={} ApiVersion={} Os={}",
+                info.get("Version").and_then(|v| v.as_str()).unwrap_or("unknown"),
+                api_version,
+                info.get("Os").and_then(|v| v.as_str()).unwrap_or("unknown")
+            ),
+        }))
+    }
+
+    /// Kubelet: GET /pods without credentials
+    async fn check_kubelet(&self, target: &str, port: u16, tls: bool) -> Result<Option<DefaultCheckFinding>, anyhow::Error> {
+        let response = http_client::send_request(target, port, tls, &HttpRequest::get("/pods"), self.timeout, &self.egress).await?;
+        if response.status_code != 200 {
+            return Ok(None);
+        }
+
+        let pods: serde_json::Value = match serde_json::from_slice(&response.body) {
+            Ok(v) => v,
+            Err(_) => return Ok(None),
+        };
+        let count = match pods.get("items").and_then(|v| v.as_array()) {
+            Some(items) => items.len(),
+            None => return Ok(None),
+        };
+
+        Ok(Some(DefaultCheckFinding {
+            service: "kubelet",
+            summary: if tls {
+                "Kubelet API allows anonymous access".to_string()
+            } else {
+                "Kubelet read-only port exposed".to_string()
+            },
+            evidence: format!("GET /pods returned {} pods", count),
+        }))
+    }
+}
+
+/// Whether `reply` holds a complete FTP reply
+///
+/// A reply is complete once a full line reads "NNN " (not "NNN-", which
+/// continues a multi-line reply).
+fn ftp_reply_complete(reply: &str) -> bool {
+    let last = reply.lines().last().is_some_and(|line| {
+        line.len() >= 4 && line.as_bytes()[..3].iter().all(u8::is_ascii_digit) && line.as_bytes()[3] == b' '
+    });
+    last && reply.ends_with('\n')
+}
+
+/// Get a `key:value` field from a Redis INFO reply
+pub(crate) fn info_field<'a>(info: &'a str, key: &str) -> Option<&'a str> {
+    info.lines()
+        .find_map(|line| line.strip_prefix(key)?.strip_prefix(':'))
+        .map(str::trim)
+}
+
+/// Send a command over the MongoDB OP_MSG wire protocol and return the reply body
+pub(crate) async fn mongo_command(
+    stream: &mut AuditedStream,
+    command: &bson::Document,
+    timeout: Duration,
+) -> Result<Vec<u8>, anyhow::Error> {
+    const OP_MSG: i32 = 2013;
+
+    let body = command.to_bytes();
+    let length = 16 + 4 + 1 + body.len();
+
+    let mut message = Vec::with_capacity(length);
+    message.extend_from_slice(&(length as i32).to_le_bytes());
+    message.extend_from_slice(&1i32.to_le_bytes()); // requestID
+    message.extend_from_slice(&0i32.to_le_bytes()); // responseTo
+    message.extend_from_slice(&OP_MSG.to_le_bytes());
+    message.extend_from_slice(&0u32.to_le_bytes()); // flagBits
+    message.push(0); // section kind 0: body
+    message.extend_from_slice(&body);
+
+    stream.write_all(&message).await?;
+
+    let mut header = [0u8; 16];
+    tokio::time::timeout(timeout, stream.read_exact(&mut header)).await??;
+    let total = i32::from_le_bytes([header[0], header[1], header[2], header[3]]) as usize;
+    if !(21..=16 * 1024 * 1024).contains(&total) {
+        return Err(anyhow::anyhow!("Invalid MongoDB reply length {}", total));
+    }
+
+    let mut rest = vec![0u8; total - 16];
+    tokio::time::timeout(timeout, stream.read_exact(&mut rest)).await??;
+
+    // flagBits (4) + section kind (1), then the BSON document
+    Ok(rest[5..].to_vec())
+}
+
+/// Just enough BSON to build simple commands and read their replies
+pub(crate) mod bson {
+    /// A flat BSON document built from scalar fields
+    #[derive(Debug, Default, Clone)]
+    pub struct Document {
+        elements: Vec<u8>,
+    }
+
+    impl Document {
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        fn key(&mut self, kind: u8, name: &str) {
+            self.elements.push(kind);
+            self.elements.extend_from_slice(name.as_bytes());
+            self.elements.push(0);
+        }
+
+        /// Add an int32 field
+        pub fn int32(mut self, name: &str, value: i32) -> Self {
+            self.key(0x10, name);
+            self.elements.extend_from_slice(&value.to_le_bytes());
+            self
+        }
+
+        /// Add a boolean field
+        pub fn boolean(mut self, name: &str, value: bool) -> Self {
+            self.key(0x08, name);
+            self.elements.push(value as u8);
+            self
+        }
+
+        /// Add a UTF-8 string field
+        pub fn string(mut self, name: &str, value: &str) -> Self {
+            self.key(0x02, name);
+            self.elements.extend_from_slice(&((value.len() + 1) as i32).to_le_bytes());
+            self.elements.extend_from_slice(value.as_bytes());
+            self.elements.push(0);
+            self
+        }
+
+        /// Encode the document
+        pub fn to_bytes(&self) -> Vec<u8> {
+            let length = 4 + self.elements.len() + 1;
+            let mut out = Vec::with_capacity(length);
+            out.extend_from_slice(&(length as i32).to_le_bytes());
+            out.extend_from_slice(&self.elements);
+            out.push(0);
+            out
+        }
+    }
+
+    /// A decoded BSON value (only the types we care about are kept)
+    #[derive(Debug, Clone, PartialEq)]
+    pub enum Value {
+        Double(f64),
+        String(String),
+        Document(Vec<(String, Value)>),
+        Array(Vec<Value>),
+        Bool(bool),
+        Int32(i32),
+        Int64(i64),
+        Other,
+    }
+
+    fn read_i32(data: &[u8], pos: usize) -> Option<i32> {
+        Some(i32::from_le_bytes(data.get(pos..pos.checked_add(4)?)?.try_into().ok()?))
+    }
+
+    /// A length prefix; negative lengths are malformed
+    fn read_len(data: &[u8], pos: usize) -> Option<usize> {
+        usize::try_from(read_i32(data, pos)?).ok()
+    }
+
+    fn read_cstring(data: &[u8], pos: usize) -> Option<(String, usize)> {
+        let end = data.get(pos..)?.iter().position(|&b| b == 0)? + pos;
+        Some((String::from_utf8_lossy(&data[pos..end]).to_string(), end + 1))
+    }
+
+    /// Parse a BSON document into key/value pairs
+    ///
+    /// Returns `None` for anything malformed: a length prefix shorter than
+    /// an empty document or longer than the data, a missing terminator, or
+    /// an element running past the end of its document.
+    pub fn parse(data: &[u8]) -> Option<Vec<(String, Value)>> {
+        let length = read_len(data, 0)?;
+        if length < 5 {
+            return None;
+        }
+        let data = data.get(..length)?;
+        if data[length - 1] != 0 {
+            return None;
+        }
+        let end = length - 1;
+        let mut pos = 4;
+        let mut fields = Vec::new();
+
+        while pos < end {
+            let kind = data[pos];
+            let (name, next) = read_cstring(data, pos + 1)?;
+            pos = next;
+
+            let (value, size) = match kind {
+                0x01 => (Value::Double(f64::from_le_bytes(data.get(pos..pos + 8)?.try_into().ok()?)), 8),
+                0x02 | 0x0D | 0x0E => {
+                    let len = read_len(data, pos)?;
+                    let start = pos.checked_add(4)?;
+                    let s = data.get(start..start.checked_add(len.checked_sub(1)?)?)?;
+                    (Value::String(String::from_utf8_lossy(s).to_string()), len.checked_add(4)?)
+                }
+                0x03 | 0x04 => {
+                    let len = read_len(data, pos)?;
+                    let inner = parse(data.get(pos..pos.checked_add(len)?)?)?;
+                    let value = if kind == 0x04 {
+                        Value::Array(inner.into_iter().map(|(_, v)| v).collect())
+                    } else {
+                        Value::Document(inner)
+                    };
+                    (value, len)
+                }
+                0x05 => (Value::Other, read_len(data, pos)?.checked_add(5)?),
+                0x07 => (Value::Other, 12),
+                0x08 => (Value::Bool(*data.get(pos)? != 0), 1),
+                0x09 | 0x11 | 0x12 => {
+                    let v = i64::from_le_bytes(data.get(pos..pos + 8)?.try_into().ok()?);
+                    (if kind == 0x12 { Value::Int64(v) } else { Value::Other }, 8)
+                }
+                0x0A | 0x06 | 0x7F | 0xFF => (Value::Other, 0),
+                0x10 => (Value::Int32(read_i32(data, pos)?), 4),
+                0x13 => (Value::Other, 16),
+                _ => return None,
+            };
+
+            fields.push((name, value));
+            pos = pos.checked_add(size)?;
+        }
+
+        // The last element must end exactly at the terminator
+        (pos == end).then_some(fields)
+    }
+
+    /// Find a numeric top-level field regardless of its BSON number type
+    pub fn find_number(document: &[u8], name: &str) -> Option<f64> {
+        parse(document)?.into_iter().find(|(k, _)| k == name).and_then(|(_, v)| match v {
+            Value::Double(d) => Some(d),
+            Value::Int32(i) => Some(i as f64),
+            Value::Int64(i) => Some(i as f64),
+            _ => None,
+        })
+    }
+
+    /// Collect every string value stored under `name`, at any depth
+    pub fn find_strings(document: &[u8], name: &str) -> Vec<String> {
+        fn walk(fields: &[(String, Value)], name: &str, out: &mut Vec<String>) {
+            for (key, value) in fields {
+                match value {
+                    Value::String(s) if key == name => out.push(s.clone()),
+                    Value::Document(inner) => walk(inner, name, out),
+                    Value::Array(items) => {
+                        for item in items {
+                            if let Value::Document(inner) = item {
+                                walk(inner, name, out);
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        let mut out = Vec::new();
+        if let Some(fields) = parse(document) {
+            walk(&fields, name, &mut out);
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    /// Serve one connection: send `greeting`, then answer each read with the next reply
+    async fn serve(greeting: &'static [u8], replies: &[&[u8]]) -> u16 {
+        let replies: Vec<Vec<u8>> = replies.iter().map(|reply| reply.to_vec()).collect();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            socket.write_all(greeting).await.unwrap();
+            let mut buffer = [0u8; 1024];
+            for reply in replies {
+                if socket.read(&mut buffer).await.unwrap_or(0) == 0 {
+                    return;
+                }
+                socket.write_all(&reply).await.unwrap();
+            }
+        });
+        port
+    }
+
+    fn checks() -> DefaultChecks {
+        DefaultChecks::new(Duration::from_secs(2), Egress::default(), None)
+    }
+
+    #[test]
+    fn bson_round_trips_scalar_fields() {
+        let bytes = bson::Document::new().int32("hello", 1).boolean("nameOnly", true).string("$db", "admin").to_bytes();
+        assert_eq!(bson::parse(&bytes).unwrap(), vec![
+            ("hello".to_string(), bson::Value::Int32(1)),
+            ("nameOnly".to_string(), bson::Value::Bool(true)),
+            ("$db".to_string(), bson::Value::String("admin".to_string())),
+        ]);
+        assert_eq!(bson::find_number(&bytes, "hello"), Some(1.0));
+        assert_eq!(bson::parse(&bson::Document::new().to_bytes()).unwrap(), vec![]);
+    }
+
+    #[test]
+    fn bson_finds_strings_in_nested_documents() {
+        // { ok: 1.0, databases: [ { name: "admin" }, { name: "local" } ] }
+        let entry = |name| bson::Document::new().string("name", name).to_bytes();
+        let mut array = Vec::new();
+        for (index, doc) in [entry("admin"), entry("local")].iter().enumerate() {
+            array.push(0x03);
+            array.extend_from_slice(index.to_string().as_bytes());
+            array.push(0);
+            array.extend_from_slice(doc);
+        }
+        let mut elements = vec![0x01];
+        elements.extend_from_slice(b"ok\0");
+        elements.extend_from_slice(&1.0f64.to_le_bytes());
+        elements.push(0x04);
+        elements.extend_from_slice(b"databases\0");
+        elements.extend_from_slice(&((array.len() + 5) as i32).to_le_bytes());
+        elements.extend_from_slice(&array);
+        elements.push(0);
+        let mut document = ((elements.len() + 5) as i32).to_le_bytes().to_vec();
+        document.extend_from_slice(&elements);
+        document.push(0);
+
+        assert_eq!(bson::find_number(&document, "ok"), Some(1.0));
+        assert_eq!(bson::find_strings(&document, "name"), ["admin", "local"]);
+    }
+
+    #[test]
+    fn bson_rejects_malformed_documents() {
+        let valid = bson::Document::new().string("a", "b").to_bytes();
+
+        // Declared lengths below the 5-byte minimum, negative or past the data
+        for length in [0i32, 4, -1, valid.len() as i32 + 1] {
+            let mut bytes = valid.clone();
+            bytes[..4].copy_from_slice(&length.to_le_bytes());
+            assert!(bson::parse(&bytes).is_none(), "length {}", length);
+        }
+        assert!(bson::parse(&[5, 0, 0]).is_none());
+
+        // Missing terminator
+        let mut bytes = valid.clone();
+        *bytes.last_mut().unwrap() = 1;
+        assert!(bson::parse(&bytes).is_none());
+
+        // String lengths that are zero, huge or run past the document
+        for length in [0i32, i32::MAX, -5, 100] {
+            let mut bytes = valid.clone();
+            bytes[7..11].copy_from_slice(&length.to_le_bytes());
+            assert!(bson::parse(&bytes).is_none(), "string length {}", length);
+        }
+
+        // A fixed-size element cut short by the terminator
+        let mut bytes = bson::Document::new().int32("n", 7).to_bytes();
+        bytes.remove(bytes.len() - 2);
+        bytes[0] -= 1;
+        assert!(bson::parse(&bytes).is_none());
+    }
+
+    #[test]
+    fn ftp_replies_end_on_a_final_status_line() {
+        assert!(ftp_reply_complete("220 ready\r\n"));
+        assert!(ftp_reply_complete("220-Welcome\r\n220-to the server\r\n220 ready\r\n"));
+        assert!(!ftp_reply_complete("220-Welcome\r\n"));
+        assert!(!ftp_reply_complete("220 rea"));
+        assert!(!ftp_reply_complete("hello\r\n"));
+        assert!(!ftp_reply_complete(""));
+    }
+
+    #[test]
+    fn redis_info_fields() {
+        let info = "$60\r\n# Server\r\nredis_version:7.2.4\r\nredis_mode:standalone\r\nos:Linux\r\n";
+        assert_eq!(info_field(info, "redis_version"), Some("7.2.4"));
+        assert_eq!(info_field(info, "redis_mode"), Some("standalone"));
+        assert_eq!(info_field(info, "redis"), None);
+    }
+
+    #[tokio::test]
+    async fn anonymous_ftp_login_is_reported() {
+        let port = serve(b"220-Welcome\r\n220 FTP ready\r\n", &[
+            b"331 Please specify the password.\r\n",
+            b"230 Login successful.\r\n",
+        ]).await;
+        let finding = checks().check_ftp("127.0.0.1", port).await.unwrap().unwrap();
+        assert_eq!(finding.evidence, "230 Login successful.");
+
+        let port = serve(b"220 FTP ready\r\n", &[b"530 Anonymous access denied.\r\n"]).await;
+        assert!(checks().check_ftp("127.0.0.1", port).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn redis_without_auth_is_reported() {
+        let port = serve(b"", &[b"$56\r\n# Server\r\nredis_version:7.2.4\r\nredis_mode:standalone\r\n"]).await;
+        let finding = checks().check_redis("127.0.0.1", port).await.unwrap().unwrap();
+        assert_eq!(finding.evidence, "INFO server returned redis_version=7.2.4 redis_mode=standalone");
+
+        let port = serve(b"", &[b"-NOAUTH Authentication required.\r\n"]).await;
+        assert!(checks().check_redis("127.0.0.1", port).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn mongo_replies_with_bad_lengths_are_rejected() {
+        let command = bson::Document::new().int32("hello", 1);
+        for length in [4i32, -1] {
+            let mut reply = length.to_le_bytes().to_vec();
+            reply.extend_from_slice(&[0; 12]);
+            let port = serve(b"", &[&reply]).await;
+            let mut stream = Egress::default().connect_tcp("127.0.0.1", port).await.unwrap();
+            assert!(mongo_command(&mut stream, &command, Duration::from_secs(2)).await.is_err());
+        }
+    }
+}
@@ -0,0 +1,808 @@
+//! DNS server inspection
+//!
+//! Runs against port 53 once it answers. Three kinds of question are asked:
+//!
+//! * CHAOS-class TXT queries for `version.bind` and `hostname.bind`, which many
+//!   servers answer with their software version and host name
+//! * a recursive query for a name the server shouldn't be authoritative for;
+//!   an answer with the RA bit set means it is an open resolver
+//! * per zone: a DNSKEY query with the DO bit (DNSSEC) and an AXFR over TCP
+//!
+//! Zones come from the configuration and from certificate SANs seen during the
+//! scan. Simple queries go over UDP and fall back to TCP when the answer is
+//! truncated or UDP gets nothing back; AXFR always uses TCP.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+use crate::audit::AuditedStream;
+use crate::models::{DnsInfo, DnsRecord, DnsZoneInfo};
+use crate::scope::Egress;
+use crate::utils;
+
+const CLASS_IN: u16 = 1;
+const CLASS_CH: u16 = 3;
+const TYPE_A: u16 = 1;
+const TYPE_NS: u16 = 2;
+const TYPE_CNAME: u16 = 5;
+const TYPE_SOA: u16 = 6;
+const TYPE_PTR: u16 = 12;
+const TYPE_MX: u16 = 15;
+const TYPE_TXT: u16 = 16;
+const TYPE_AAAA: u16 = 28;
+const TYPE_SRV: u16 = 33;
+const TYPE_OPT: u16 = 41;
+const TYPE_DS: u16 = 43;
+const TYPE_RRSIG: u16 = 46;
+const TYPE_NSEC: u16 = 47;
+const TYPE_DNSKEY: u16 = 48;
+const TYPE_AXFR: u16 = 252;
+
+/// Stop reading a zone transfer after this many records
+const MAX_AXFR_RECORDS: usize = 50_000;
+
+/// Settings for the DNS stage
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DnsConfig {
+    /// Zones to check for DNSSEC and zone transfers
+    pub domains: Vec<String>,
+    /// Also check zones derived from certificate SANs seen during the scan
+    pub domains_from_certificates: bool,
+    /// External name used for the recursion test
+    pub recursion_name: String,
+    /// Attempt AXFR for each zone
+    pub axfr: bool,
+}
+
+impl Default for DnsConfig {
+    fn default() -> Self {
+        Self {
+            domains: Vec::new(),
+            domains_from_certificates: true,
+            recursion_name: "www.example.com".to_string(),
+            axfr: true,
+        }
+    }
+}
+
+/// Zone worth checking for a certificate SAN
+///
+/// Wildcards name their zone directly (`*.corp.example.com` gives
+/// `corp.example.com`); host names are reduced to their parent, so
+/// `www.example.com` gives `example.com` and `example.com` stays as is.
+/// The SAN comes from the target, so anything but letter-digit-hyphen
+/// labels is refused.
+pub fn zone_from_san(san: &str) -> Option<String> {
+    if san.starts_with("IP:") {
+        return None;
+    }
+    let wildcard = san.starts_with("*.");
+    let name = san.trim_start_matches("*.").trim_end_matches('.').to_lowercase();
+    let labels: Vec<&str> = name.split('.').collect();
+    if labels.len() < 2 || !labels.iter().all(|l| is_ldh_label(l)) {
+        return None;
+    }
+    if labels.len() > 2 && !wildcard {
+        Some(labels[1..].join("."))
+    } else {
+        Some(name)
+    }
+}
+
+/// Letters, digits and inner hyphens, at most 63 characters
+fn is_ldh_label(label: &str) -> bool {
+    !label.is_empty()
+        && label.len() <= 63
+        && !label.starts_with('-')
+        && !label.ends_with('-')
+        && label.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-')
+}
+
+/// File name for an exported zone, `<target>_<port>_<zone>.zone`
+///
+/// Zone names can come from certificates the target presented, so every
+/// character that could leave the export directory or trip up a file system
+/// is replaced with `_`.
+pub fn zone_file_name(target: &str, port: u16, zone: &str) -> String {
+    let safe = |part: &str| -> String {
+        part.chars()
+            .map(|c| if c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_') { c } else { '_' })
+            .collect::<String>()
+            .replace("..", "__")
+    };
+    format!("{}_{}_{}.zone", safe(target), port, safe(zone))
+}
+
+/// Runs the DNS queries against one server
+pub struct DnsProbe {
+    config: Arc<DnsConfig>,
+    timeout: Duration,
+    egress: Egress,
+    logger: Option<Arc<utils::EnhancedLogger>>,
+}
+
+impl DnsProbe {
+    /// Create a prober with the given per-query timeout
+    pub fn new(config: Arc<DnsConfig>, timeout: Duration, egress: Egress, logger: Option<Arc<utils::EnhancedLogger>>) -> Self {
+        Self { config, timeout, egress, logger }
+    }
+
+    fn log(&self, level: &str, message: &str) {
+        if let Some(logger) = &self.logger {
+            logger.log(level, message);
+        }
+    }
+
+    /// Server-level checks (CHAOS names, recursion) plus the given zones
+    ///
+    /// Returns `None` if the server never answered a query.
+    pub async fn run(&self, target: &str, port: u16, zones: &[String]) -> Option<DnsInfo> {
+        let mut info = DnsInfo::default();
+        let mut answered = false;
+
+        for (name, slot) in [("version.bind", &mut info.version_bind), ("hostname.bind", &mut info.hostname_bind)] {
+            match self.query(target, port, &Question::new(name, TYPE_TXT, CLASS_CH)).await {
+                Ok(message) => {
+                    answered = true;
+                    *slot = message.answers.iter()
+                        .find(|r| r.record_type == "TXT")
+                        .map(|r| r.data.trim_matches('"').to_string())
+                        .filter(|v| !v.is_empty());
+                }
+                Err(e) => self.log("DEBUG", &format!("{} query to {}:{} failed: {}", name, target, port, e)),
+            }
+        }
+
+        let question = Question { recursion_desired: true, ..Question::new(&self.config.recursion_name, TYPE_A, CLASS_IN) };
+        match self.query(target, port, &question).await {
+            Ok(message) => {
+                answered = true;
+                info.recursion = Some(message.recursion_available && message.rcode == 0 && !message.answers.is_empty());
+            }
+            Err(e) => self.log("DEBUG", &format!("Recursion test against {}:{} failed: {}", target, port, e)),
+        }
+
+        if !answered {
+            return None;
+        }
+
+        for zone in zones {
+            info.zones.push(self.check_zone(target, port, zone).await);
+        }
+        Some(info)
+    }
+
+    /// DNSSEC and AXFR checks for one zone
+    pub async fn check_zone(&self, target: &str, port: u16, zone: &str) -> DnsZoneInfo {
+        let mut info = DnsZoneInfo {
+            domain: zone.to_string(),
+            ..Default::default()
+        };
+
+        let question = Question { dnssec_ok: true, ..Question::new(zone, TYPE_DNSKEY, CLASS_IN) };
+        match self.query(target, port, &question).await {
+            Ok(message) => {
+                info.dnssec = Some(message.answers.iter().any(|r| r.record_type == "DNSKEY" || r.record_type == "RRSIG"));
+            }
+            Err(e) => self.log("DEBUG", &format!("DNSKEY query for {} to {}:{} failed: {}", zone, target, port, e)),
+        }
+
+        if self.config.axfr {
+            match self.axfr(target, port, zone).await {
+                Ok(records) => {
+                    self.log("INFO", &format!(
+                        "AXFR of {} from {}:{} returned {} records", zone, target, port, records.len()
+                    ));
+                    info.axfr_allowed = Some(true);
+                    info.records = records;
+                }
+                Err(e) => {
+                    self.log("DEBUG", &format!("AXFR of {} from {}:{} refused: {}", zone, target, port, e));
+                    info.axfr_allowed = Some(false);
+                }
+            }
+        }
+        info
+    }
+
+    /// Send a single query, over UDP first and TCP if that fails or truncates
+    async fn query(&self, target: &str, port: u16, question: &Question<'_>) -> Result<Message, anyhow::Error> {
+        let id = rand::random::<u16>();
+        let query = build_query(id, question)?;
+
+        let udp = async {
+            let socket = self.egress.connect_udp(target, port).await?;
+            socket.send(&query).await?;
+            let mut buffer = vec![0u8; 4096];
+            loop {
+                let n = socket.recv(&mut buffer).await?;
+                if let Some(message) = parse_message(&buffer[..n]) {
+                    if message.id == id {
+                        return Ok::<_, anyhow::Error>(message);
+                    }
+                }
+            }
+        };
+
+        match tokio::time::timeout(self.timeout, udp).await {
+            Ok(Ok(message)) if !message.truncated => return Ok(message),
+            _ => {}
+        }
+
+        let mut stream = tokio::time::timeout(self.timeout, self.egress.connect_tcp(target, port)).await??;
+        stream.write_all(&tcp_frame(&query)).await?;
+        let response = tokio::time::timeout(self.timeout, read_tcp_message(&mut stream)).await??;
+        parse_message(&response).ok_or_else(|| anyhow::anyhow!("malformed response"))
+    }
+
+    /// Request a zone transfer and collect the records
+    async fn axfr(&self, target: &str, port: u16, zone: &str) -> Result<Vec<DnsRecord>, anyhow::Error> {
+        let id = rand::random::<u16>();
+        let query = build_query(id, &Question::new(zone, TYPE_AXFR, CLASS_IN))?;
+        let mut stream = tokio::time::timeout(self.timeout, self.egress.connect_tcp(target, port)).await??;
+        stream.write_all(&tcp_frame(&query)).await?;
+
+        // The transfer is bracketed by the zone's SOA record
+        let mut records = Vec::new();
+        let mut soa_seen = 0;
+        while soa_seen < 2 && records.len() < MAX_AXFR_RECORDS {
+            let response = tokio::time::timeout(self.timeout, read_tcp_message(&mut stream)).await??;
+            let message = parse_message(&response).ok_or_else(|| anyhow::anyhow!("malformed response"))?;
+            if message.rcode != 0 {
+                return Err(anyhow::anyhow!("rcode {}", rcode_name(message.rcode)));
+            }
+            if message.answers.is_empty() {
+                return Err(anyhow::anyhow!("empty answer"));
+            }
+            for record in message.answers {
+                if record.record_type == "SOA" {
+                    soa_seen += 1;
+                    if soa_seen == 2 {
+                        break;
+                    }
+                }
+                records.push(record);
+            }
+        }
+
+        if records.first().is_none_or(|r| r.record_type != "SOA") {
+            return Err(anyhow::anyhow!("transfer did not start with SOA"));
+        }
+        Ok(records)
+    }
+}
+
+/// Render AXFR records as a zone file in master-file format
+pub fn zone_file(zone: &DnsZoneInfo) -> String {
+    let mut out = format!("; AXFR of {}\n", zone.domain);
+    for record in &zone.records {
+        out.push_str(&format!("{}\t{}\tIN\t{}\t{}\n", record.name, record.ttl, record.record_type, record.data));
+    }
+    out
+}
+
+/// A parsed DNS message (only what the probes need)
+#[derive(Debug, Clone)]
+struct Message {
+    id: u16,
+    truncated: bool,
+    recursion_available: bool,
+    rcode: u8,
+    answers: Vec<DnsRecord>,
+}
+
+/// The single question a probe asks
+#[derive(Debug, Clone, Copy)]
+struct Question<'a> {
+    name: &'a str,
+    qtype: u16,
+    qclass: u16,
+    /// Set RD, asking the server to recurse
+    recursion_desired: bool,
+    /// Add an EDNS0 OPT record with the DO bit
+    dnssec_ok: bool,
+}
+
+impl<'a> Question<'a> {
+    fn new(name: &'a str, qtype: u16, qclass: u16) -> Self {
+        Self { name, qtype, qclass, recursion_desired: false, dnssec_ok: false }
+    }
+}
+
+fn build_query(id: u16, question: &Question<'_>) -> Result<Vec<u8>, anyhow::Error> {
+    let Question { name, qtype, qclass, recursion_desired, dnssec_ok } = *question;
+    let mut query = Vec::with_capacity(64);
+    query.extend_from_slice(&id.to_be_bytes());
+    query.extend_from_slice(&(if recursion_desired { 0x0100u16 } else { 0 }).to_be_bytes());
+    query.extend_from_slice(&1u16.to_be_bytes()); // QDCOUNT
+    query.extend_from_slice(&[0, 0, 0, 0]); // ANCOUNT, NSCOUNT
+    query.extend_from_slice(&(if dnssec_ok { 1u16 } else { 0 }).to_be_bytes()); // ARCOUNT
+
+    for label in name.trim_end_matches('.').split('.') {
+        if label.is_empty() || label.len() > 63 {
+            return Err(anyhow::anyhow!("invalid name '{}'", name));
+        }
+        query.push(label.len() as u8);
+        query.extend_from_slice(label.as_bytes());
+    }
+    query.push(0);
+    query.extend_from_slice(&qtype.to_be_bytes());
+    query.extend_from_slice(&qclass.to_be_bytes());
+
+    if dnssec_ok {
+        // EDNS0 OPT: root name, 4096-byte payload, DO bit set
+        query.push(0);
+        query.extend_from_slice(&TYPE_OPT.to_be_bytes());
+        query.extend_from_slice(&4096u16.to_be_bytes());
+        query.extend_from_slice(&[0, 0, 0x80, 0x00]);
+        query.extend_from_slice(&0u16.to_be_bytes());
+    }
+    Ok(query)
+}
+
+fn tcp_frame(message: &[u8]) -> Vec<u8> {
+    let mut framed = (message.len() as u16).to_be_bytes().to_vec();
+    framed.extend_from_slice(message);
+    framed
+}
+
+async fn read_tcp_message(stream: &mut AuditedStream) -> Result<Vec<u8>, anyhow::Error> {
+    let mut length = [0u8; 2];
+    stream.read_exact(&mut length).await?;
+    let mut message = vec![0u8; u16::from_be_bytes(length) as usize];
+    stream.read_exact(&mut message).await?;
+    Ok(message)
+}
+
+fn parse_message(data: &[u8]) -> Option<Message> {
+    let flags = read_u16(data, 2)?;
+    if flags & 0x8000 == 0 {
+        return None; // not a response
+    }
+    let questions = read_u16(data, 4)?;
+    let answers = read_u16(data, 6)?;
+
+    let mut pos = 12;
+    for _ in 0..questions {
+        let (_, next) = read_name(data, pos)?;
+        pos = next + 4;
+    }
+
+    let mut records = Vec::with_capacity(answers as usize);
+    for _ in 0..answers {
+        let (record, next) = read_record(data, pos)?;
+        pos = next;
+        records.extend(record);
+    }
+
+    Some(Message {
+        id: read_u16(data, 0)?,
+        truncated: flags & 0x0200 != 0,
+        recursion_available: flags & 0x0080 != 0,
+        rcode: (flags & 0x000f) as u8,
+        answers: records,
+    })
+}
+
+/// Read one resource record; OPT pseudo-records are skipped
+fn read_record(data: &[u8], pos: usize) -> Option<(Option<DnsRecord>, usize)> {
+    let (name, pos) = read_name(data, pos)?;
+    let rtype = read_u16(data, pos)?;
+    let ttl = read_u32(data, pos + 4)?;
+    let length = read_u16(data, pos + 8)? as usize;
+    let start = pos + 10;
+    let rdata = data.get(start..start + length)?;
+    let end = start + length;
+
+    if rtype == TYPE_OPT {
+        return Some((None, end));
+    }
+
+    let name_at = |offset: usize| read_name(data, start + offset).map(|(n, _)| n);
+    let rendered = match rtype {
+        TYPE_A if length == 4 => Some(format!("{}.{}.{}.{}", rdata[0], rdata[1], rdata[2], rdata[3])),
+        TYPE_AAAA if length == 16 => {
+            let octets: [u8; 16] = rdata.try_into().ok()?;
+            Some(std::net::Ipv6Addr::from(octets).to_string())
+        }
+        TYPE_NS | TYPE_CNAME | TYPE_PTR => name_at(0),
+        TYPE_MX => Some(format!("{} {}", read_u16(rdata, 0)?, name_at(2)?)),
+        TYPE_SRV => Some(format!(
+            "{} {} {} {}",
+            read_u16(rdata, 0)?, read_u16(rdata, 2)?, read_u16(rdata, 4)?, name_at(6)?
+        )),
+        TYPE_SOA => {
+            let (mname, next) = read_name(data, start)?;
+            let (rname, next) = read_name(data, next)?;
+            let numbers: Vec<String> = (0..5).filter_map(|i| read_u32(data, next + i * 4)).map(|n| n.to_string()).collect();
+            Some(format!("{} {} {}", mname, rname, numbers.join(" ")))
+        }
+        TYPE_TXT => {
+            let mut strings = Vec::new();
+            let mut i = 0;
+            while let Some(&len) = rdata.get(i) {
+                let text = rdata.get(i + 1..i + 1 + len as usize)?;
+                strings.push(format!("\"{}\"", String::from_utf8_lossy(text).replace('"', "\\\"")));
+                i += 1 + len as usize;
+            }
+            Some(strings.join(" "))
+        }
+        _ => None,
+    };
+
+    let data_text = rendered.unwrap_or_else(|| {
+        // RFC 3597 generic form for everything else
+        format!("\\# {} {}", length, rdata.iter().map(|b| format!("{:02x}", b)).collect::<String>())
+    });
+
+    Some((
+        Some(DnsRecord {
+            name,
+            record_type: type_name(rtype),
+            ttl,
+            data: data_text,
+        }),
+        end,
+    ))
+}
+
+/// Read a possibly compressed domain name, returning it and the offset after it
+fn read_name(data: &[u8], mut pos: usize) -> Option<(String, usize)> {
+    let mut labels = Vec::new();
+    let mut end = None;
+    let mut jumps = 0;
+
+    loop {
+        let len = *data.get(pos)? as usize;
+        if len == 0 {
+            end.get_or_insert(pos + 1);
+            break;
+        }
+        if len & 0xc0 == 0xc0 {
+            let pointer = (read_u16(data, pos)? & 0x3fff) as usize;
+            end.get_or_insert(pos + 2);
+            jumps += 1;
+            if jumps > 32 {
+                return None;
+            }
+            pos = pointer;
+            continue;
+        }
+        let label = data.get(pos + 1..pos + 1 + len)?;
+        labels.push(String::from_utf8_lossy(label).into_owned());
+        pos += 1 + len;
+    }
+
+    let name = if labels.is_empty() { ".".to_string() } else { format!("{}.", labels.join(".")) };
+    Some((name, end?))
+}
+
+fn read_u16(data: &[u8], pos: usize) -> Option<u16> {
+    Some(u16::from_be_bytes([*data.get(pos)?, *data.get(pos + 1)?]))
+}
+
+fn read_u32(data: &[u8], pos: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(pos..pos + 4)?.try_into().ok()?))
+}
+
+fn type_name(rtype: u16) -> String {
+    match rtype {
+        TYPE_A => "A".to_string(),
+        TYPE_NS => "NS".to_string(),
+        TYPE_CNAME => "CNAME".to_string(),
+        TYPE_SOA => "SOA".to_string(),
+        TYPE_PTR => "PTR".to_string(),
+        TYPE_MX => "MX".to_string(),
+        TYPE_TXT => "TXT".to_string(),
+        TYPE_AAAA => "AAAA".to_string(),
+        TYPE_SRV => "SRV".to_string(),
+        TYPE_DS => "DS".to_string(),
+        TYPE_RRSIG => "RRSIG".to_string(),
+        TYPE_NSEC => "NSEC".to_string(),
+        TYPE_DNSKEY => "DNSKEY".to_string(),
+        other => format!("TYPE{}", other),
+    }
+}
+
+fn rcode_name(rcode: u8) -> &'static str {
+    match rcode {
+        1 => "FORMERR",
+        2 => "SERVFAIL",
+        3 => "NXDOMAIN",
+        4 => "NOTIMP",
+        5 => "REFUSED",
+        9 => "NOTAUTH",
+        _ => "error",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::{TcpListener, UdpSocket};
+
+    fn name(name: &str) -> Vec<u8> {
+        let mut out = Vec::new();
+        for label in name.trim_end_matches('.').split('.').filter(|l| !l.is_empty()) {
+            out.push(label.len() as u8);
+            out.extend_from_slice(label.as_bytes());
+        }
+        out.push(0);
+        out
+    }
+
+    fn rr(owner: &[u8], rtype: u16, ttl: u32, rdata: &[u8]) -> Vec<u8> {
+        [
+            owner,
+            &rtype.to_be_bytes()[..],
+            &CLASS_IN.to_be_bytes(),
+            &ttl.to_be_bytes(),
+            &(rdata.len() as u16).to_be_bytes(),
+            rdata,
+        ]
+        .concat()
+    }
+
+    /// Answer `query` with the given header flags and records
+    fn reply(query: &[u8], flags: u16, answers: &[Vec<u8>]) -> Vec<u8> {
+        let (_, end) = read_name(query, 12).unwrap();
+        let mut out = query[..2].to_vec();
+        out.extend_from_slice(&(0x8000 | flags).to_be_bytes());
+        out.extend_from_slice(&1u16.to_be_bytes());
+        out.extend_from_slice(&(answers.len() as u16).to_be_bytes());
+        out.extend_from_slice(&[0, 0, 0, 0]);
+        out.extend_from_slice(&query[12..end + 4]);
+        out.extend(answers.concat());
+        out
+    }
+
+    /// (name, type, class, additional count) of a query
+    fn question(query: &[u8]) -> (String, u16, u16, u16) {
+        let (qname, end) = read_name(query, 12).unwrap();
+        (qname, read_u16(query, end).unwrap(), read_u16(query, end + 2).unwrap(), read_u16(query, 10).unwrap())
+    }
+
+    fn soa(zone: &str) -> Vec<u8> {
+        let rdata = [name(&format!("ns1.{}", zone)), name(&format!("hostmaster.{}", zone)), [
+            2026101901u32, 7200, 900, 1209600, 300,
+        ].iter().flat_map(|n| n.to_be_bytes()).collect()].concat();
+        rr(&name(zone), TYPE_SOA, 3600, &rdata)
+    }
+
+    fn txt(text: &str) -> Vec<u8> {
+        [&[text.len() as u8][..], text.as_bytes()].concat()
+    }
+
+    /// Server answering `(query, over_tcp)` with a list of messages
+    async fn dns_server(answer: fn(&[u8], bool) -> Vec<Vec<u8>>) -> u16 {
+        let (listener, socket) = loop {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let port = listener.local_addr().unwrap().port();
+            if let Ok(socket) = UdpSocket::bind(("127.0.0.1", port)).await {
+                break (listener, socket);
+            }
+        };
+        let port = socket.local_addr().unwrap().port();
+
+        tokio::spawn(async move {
+            let mut buffer = vec![0u8; 512];
+            loop {
+                let (n, peer) = socket.recv_from(&mut buffer).await.unwrap();
+                for message in answer(&buffer[..n], false) {
+                    socket.send_to(&message, peer).await.unwrap();
+                }
+            }
+        });
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                tokio::spawn(async move {
+                    loop {
+                        let mut length = [0u8; 2];
+                        if stream.read_exact(&mut length).await.is_err() {
+                            break;
+                        }
+                        let mut query = vec![0u8; u16::from_be_bytes(length) as usize];
+                        stream.read_exact(&mut query).await.unwrap();
+                        for message in answer(&query, true) {
+                            stream.write_all(&tcp_frame(&message)).await.unwrap();
+                        }
+                    }
+                });
+            }
+        });
+        port
+    }
+
+    #[test]
+    fn zones_from_certificate_names() {
+        let too_long = format!("{}.example.com", "a".repeat(64));
+        let cases = [
+            ("www.example.com", Some("example.com")),
+            ("example.com", Some("example.com")),
+            ("*.corp.example.com", Some("corp.example.com")),
+            ("*.example.com", Some("example.com")),
+            ("Mail.Example.COM.", Some("example.com")),
+            ("localhost", None),
+            ("IP:10.0.0.1", None),
+            ("bad..example.com", None),
+            ("../../etc/cron.d", None),
+            ("evil/..%2f.example.com", None),
+            ("back\\slash.example.com", None),
+            ("*./tmp.example.com", None),
+            ("-dash.example.com", None),
+            ("under_score.example.com", None),
+            (&too_long, None),
+        ];
+        for (san, zone) in cases {
+            assert_eq!(zone_from_san(san).as_deref(), zone, "{}", san);
+        }
+    }
+
+    #[test]
+    fn zone_file_names_stay_in_the_directory() {
+        assert_eq!(zone_file_name("10.0.0.53", 53, "example.com"), "10.0.0.53_53_example.com.zone");
+        assert_eq!(zone_file_name("::1", 53, "example.com"), "__1_53_example.com.zone");
+        assert_eq!(zone_file_name("ns1", 53, "../../etc/passwd"), "ns1_53_______etc_passwd.zone");
+        assert_eq!(zone_file_name("ns1", 53, "a\\..\\b"), "ns1_53_a____b.zone");
+        assert!(!zone_file_name("..", 53, "..").contains(".."));
+    }
+
+    #[test]
+    fn queries_are_well_formed() {
+        let query = build_query(0x1234, &Question::new("version.bind", TYPE_TXT, CLASS_CH)).unwrap();
+        assert_eq!(&query[..12], &[0x12, 0x34, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(question(&query), ("version.bind.".to_string(), TYPE_TXT, CLASS_CH, 0));
+        assert_eq!(query.len(), 12 + name("version.bind").len() + 4);
+
+        let recursive = Question { recursion_desired: true, ..Question::new("www.example.com.", TYPE_A, CLASS_IN) };
+        let query = build_query(1, &recursive).unwrap();
+        assert_eq!(read_u16(&query, 2), Some(0x0100));
+        assert_eq!(question(&query).0, "www.example.com.");
+
+        let dnssec = Question { dnssec_ok: true, ..Question::new("example.com", TYPE_DNSKEY, CLASS_IN) };
+        let query = build_query(1, &dnssec).unwrap();
+        assert_eq!(read_u16(&query, 10), Some(1));
+        assert_eq!(&query[query.len() - 11..], &[0, 0, 41, 0x10, 0, 0, 0, 0x80, 0, 0, 0]);
+
+        for invalid in ["", "a..b", &format!("{}.com", "x".repeat(64))] {
+            assert!(build_query(1, &Question::new(invalid, TYPE_A, CLASS_IN)).is_err(), "{:?}", invalid);
+        }
+        assert_eq!(tcp_frame(&[7, 8, 9]), [0, 3, 7, 8, 9]);
+    }
+
+    #[test]
+    fn records_are_rendered_in_presentation_format() {
+        let query = build_query(9, &Question::new("example.com", TYPE_A, CLASS_IN)).unwrap();
+        let at_question = [0xc0, 0x0c];
+        let answers = [
+            rr(&at_question, TYPE_A, 300, &[192, 0, 2, 10]),
+            rr(&at_question, TYPE_AAAA, 300, &"2001:db8::1".parse::<std::net::Ipv6Addr>().unwrap().octets()),
+            rr(&at_question, TYPE_NS, 86400, &[&[3][..], b"ns1", &at_question].concat()),
+            rr(&at_question, TYPE_MX, 300, &[&[0, 10][..], &name("mail.example.com")].concat()),
+            rr(&name("_ldap._tcp.example.com"), TYPE_SRV, 60, &[&[0, 0, 0, 100, 1, 0x85][..], &name("dc1.example.com")].concat()),
+            soa("example.com"),
+            rr(&at_question, TYPE_TXT, 300, &[txt("v=spf1 -all"), txt("say \"hi\"")].concat()),
+            rr(&at_question, 65, 300, &[0x00, 0x01, 0x00]),
+            rr(&name(""), TYPE_OPT, 0, &[]),
+            rr(&at_question, TYPE_A, 300, &[1, 2, 3]),
+        ];
+        let data = reply(&query, 0x0080, &answers);
+        let message = parse_message(&data).unwrap();
+        assert_eq!(message.id, 9);
+        assert!(message.recursion_available);
+        assert!(!message.truncated);
+
+        let rendered: Vec<String> = message.answers.iter()
+            .map(|r| format!("{} {} {} {}", r.name, r.ttl, r.record_type, r.data))
+            .collect();
+        assert_eq!(rendered, [
+            "example.com. 300 A 192.0.2.10",
+            "example.com. 300 AAAA 2001:db8::1",
+            "example.com. 86400 NS ns1.example.com.",
+            "example.com. 300 MX 10 mail.example.com.",
+            "_ldap._tcp.example.com. 60 SRV 0 100 389 dc1.example.com.",
+            "example.com. 3600 SOA ns1.example.com. hostmaster.example.com. 2026101901 7200 900 1209600 300",
+            "example.com. 300 TXT \"v=spf1 -all\" \"say \\\"hi\\\"\"",
+            "example.com. 300 TYPE65 \\# 3 000100",
+            "example.com. 300 A \\# 3 010203",
+        ]);
+    }
+
+    #[test]
+    fn malformed_messages_are_rejected() {
+        let query = build_query(1, &Question::new("example.com", TYPE_A, CLASS_IN)).unwrap();
+        assert!(parse_message(&query).is_none(), "queries are not responses");
+        assert!(parse_message(&[0, 1, 0x80]).is_none());
+
+        let data = reply(&query, 0, &[rr(&[0xc0, 0x0c], TYPE_A, 300, &[192, 0, 2, 10])]);
+        assert!(parse_message(&data[..data.len() - 2]).is_none(), "truncated rdata");
+
+        let looping = [&data[..12], &[0xc0, 0x0c][..]].concat();
+        assert!(read_name(&looping, 12).is_none(), "pointer loop");
+        assert!(read_name(&[3, b'c', b'o'], 0).is_none());
+        assert_eq!(read_name(&[0], 0), Some((".".to_string(), 1)));
+
+        let flags = parse_message(&reply(&query, 0x0200 | 0x0005, &[])).unwrap();
+        assert!(flags.truncated);
+        assert_eq!(rcode_name(flags.rcode), "REFUSED");
+        assert_eq!(type_name(TYPE_DNSKEY), "DNSKEY");
+        assert_eq!(type_name(999), "TYPE999");
+    }
+
+    #[test]
+    fn zone_files_list_every_record() {
+        let zone = DnsZoneInfo {
+            domain: "example.com".into(),
+            records: vec![DnsRecord {
+                name: "www.example.com.".into(),
+                record_type: "A".into(),
+                ttl: 60,
+                data: "192.0.2.1".into(),
+            }],
+            ..Default::default()
+        };
+        assert_eq!(zone_file(&zone), "; AXFR of example.com\nwww.example.com.\t60\tIN\tA\t192.0.2.1\n");
+    }
+
+    fn answer(query: &[u8], tcp: bool) -> Vec<Vec<u8>> {
+        let (qname, qtype, qclass, additional) = question(query);
+        let owner = name(&qname);
+        match (qname.as_str(), qtype) {
+            ("version.bind.", TYPE_TXT) if qclass == CLASS_CH => {
+                vec![reply(query, 0, &[rr(&owner, TYPE_TXT, 0, &txt("9.18.24"))])]
+            }
+            // Too big for UDP, so only answered over TCP
+            ("hostname.bind.", TYPE_TXT) if !tcp => vec![reply(query, 0x0200, &[])],
+            ("hostname.bind.", TYPE_TXT) => vec![reply(query, 0, &[rr(&owner, TYPE_TXT, 0, &txt("ns1.lab"))])],
+            ("www.example.com.", TYPE_A) if read_u16(query, 2) == Some(0x0100) => {
+                vec![reply(query, 0x0180, &[rr(&owner, TYPE_A, 60, &[93, 184, 215, 14])])]
+            }
+            ("signed.test.", TYPE_DNSKEY) if additional == 1 => {
+                vec![reply(query, 0, &[rr(&owner, TYPE_DNSKEY, 3600, &[1, 1, 3, 13, 0xaa])])]
+            }
+            ("signed.test.", TYPE_AXFR) if tcp => vec![
+                reply(query, 0, &[soa("signed.test"), rr(&name("www.signed.test"), TYPE_A, 60, &[10, 0, 0, 5])]),
+                reply(query, 0, &[rr(&owner, TYPE_MX, 60, &[&[0, 5][..], &name("mx.signed.test")].concat()), soa("signed.test")]),
+            ],
+            ("locked.test.", TYPE_AXFR) => vec![reply(query, 5, &[])],
+            _ => vec![reply(query, 3, &[])],
+        }
+    }
+
+    #[tokio::test]
+    async fn server_checks_and_zone_transfers() {
+        let port = dns_server(answer).await;
+        let probe = DnsProbe::new(Arc::new(DnsConfig::default()), Duration::from_millis(500), Egress::default(), None);
+        let info = probe.run("127.0.0.1", port, &["signed.test".into(), "locked.test".into()]).await.unwrap();
+
+        assert_eq!(info.version_bind.as_deref(), Some("9.18.24"));
+        assert_eq!(info.hostname_bind.as_deref(), Some("ns1.lab"), "truncated answers are retried over TCP");
+        assert_eq!(info.recursion, Some(true));
+
+        let signed = &info.zones[0];
+        assert_eq!(signed.dnssec, Some(true));
+        assert_eq!(signed.axfr_allowed, Some(true));
+        let records: Vec<&str> = signed.records.iter().map(|r| r.record_type.as_str()).collect();
+        assert_eq!(records, ["SOA", "A", "MX"], "the closing SOA ends the transfer");
+
+        let locked = &info.zones[1];
+        assert_eq!(locked.dnssec, Some(false));
+        assert_eq!(locked.axfr_allowed, Some(false));
+        assert!(locked.records.is_empty());
+    }
+
+    #[tokio::test]
+    async fn silent_servers_yield_nothing() {
+        fn ignore(_: &[u8], _: bool) -> Vec<Vec<u8>> {
+            Vec::new()
+        }
+        let port = dns_server(ignore).await;
+        let probe = DnsProbe::new(Arc::new(DnsConfig::default()), Duration::from_millis(100), Egress::default(), None);
+        assert!(probe.run("127.0.0.1", port, &["example.com".into()]).await.is_none());
+    }
+}
@@ -0,0 +1,70 @@
+//! Error types for the scanner library
+//!
+//! Library entry points return `ScanError` so that embedding tools can react to
+//! specific failures (bad configuration, missing privileges) instead of
+//! matching on error strings.
+
+use std::path::PathBuf;
+
+/// Errors returned by the scanner library
+#[derive(Debug, thiserror::Error)]
+pub enum ScanError {
+    /// No target host was configured
+    #[error("No scan target specified")]
+    MissingTarget,
+
+    /// The port list is empty
+    #[error("No ports to scan")]
+    NoPorts,
+
+    /// No scan techniques were selected
+    #[error("No scan types selected")]
+    NoScanTypes,
+
+    /// A configuration value is out of range or inconsistent
+    #[error("Invalid configuration: {0}")]
+    InvalidConfig(String),
+
+    /// The target could not be resolved to an address
+    #[error("Failed to resolve target {target}: {reason}")]
+    Resolve {
+        /// Target as given in the configuration
+        target: String,
+        /// Resolver error message
+        reason: String,
+    },
+
+    /// The target lies outside the engagement scope
+    #[error("Target {target} is out of scope: {reason}")]
+    OutOfScope {
+        /// Target as given in the configuration
+        target: String,
+        /// Why the scope refuses it
+        reason: String,
+    },
+
+    /// A raw-socket scan type was requested without the needed privileges
+    #[error("Scan type {0} requires root privileges")]
+    PermissionDenied(String),
+
+    /// A wordlist, log or output file could not be read or written
+    #[error("Failed to access {path}: {source}")]
+    File {
+        /// File that caused the error
+        path: PathBuf,
+        /// Underlying I/O error
+        #[source]
+        source: std::io::Error,
+    },
+
+    /// Any other I/O failure
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    /// Failure inside the scan engine
+    #[error("Scan failed: {0}")]
+    Engine(#[from] anyhow::Error),
+}
+
+/// Result type used throughout the scanner library
+pub type Result<T> = std::result::Result<T, ScanError>;
@@ -0,0 +1,197 @@
+//! Live scan events
+//!
+//! The scanner publishes what it learns as it happens so callers don't have to
+//! wait for `run` to return. Subscribe with `QuantumScanner::subscribe` before
+//! starting the scan; the returned `ScanEventStream` is an async `Stream` that
+//! ends once the scan has finished.
+
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures::{Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio::sync::mpsc;
+
+use crate::models::{PortStatus, ScanType, SslInfo};
+
+/// What happened
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum ScanEventKind {
+    /// First response received from the target
+    HostUp {
+        /// Resolved target address
+        ip: String,
+    },
+    /// A scan technique determined the state of a port
+    PortState {
+        /// Port number
+        port: u16,
+        /// Technique that produced the state
+        scan_type: ScanType,
+        /// Port state
+        status: PortStatus,
+    },
+    /// A service was identified on an open port
+    ServiceIdentified {
+        /// Port number
+        port: u16,
+        /// Service name
+        service: String,
+        /// Service version, if known
+        version: Option<String>,
+    },
+    /// TLS handshake details for a port are available
+    TlsInfo {
+        /// Port number
+        port: u16,
+        /// Certificate and connection details
+        info: Box<SslInfo>,
+    },
+    /// The scan is complete
+    ScanFinished {
+        /// All open ports
+        open_ports: Vec<u16>,
+        /// Total scan duration in milliseconds
+        duration_ms: u64,
+    },
+}
+
+/// A timestamped scan event
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScanEvent {
+    /// When the event was produced
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+    /// Target the event belongs to
+    pub target: String,
+    /// Event details
+    #[serde(flatten)]
+    pub kind: ScanEventKind,
+}
+
+impl ScanEvent {
+    /// Create an event stamped with the current time
+    pub fn new(target: &str, kind: ScanEventKind) -> Self {
+        Self {
+            timestamp: chrono::Utc::now(),
+            target: target.to_string(),
+            kind,
+        }
+    }
+}
+
+/// Fan-out of events to every subscriber
+///
+/// Channels are unbounded so a slow consumer never stalls the scan itself.
+#[derive(Debug, Default)]
+pub struct EventBus {
+    subscribers: Vec<mpsc::UnboundedSender<ScanEvent>>,
+}
+
+impl EventBus {
+    /// Register a new subscriber
+    pub fn subscribe(&mut self) -> ScanEventStream {
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.subscribers.push(tx);
+        ScanEventStream { receiver: rx }
+    }
+
+    /// Whether anybody is listening
+    pub fn has_subscribers(&self) -> bool {
+        !self.subscribers.is_empty()
+    }
+
+    /// Send an event to all live subscribers, dropping any that went away
+    pub fn emit(&mut self, event: ScanEvent) {
+        self.subscribers.retain(|tx| tx.send(event.clone()).is_ok());
+    }
+
+    /// Drop all senders so subscriber streams terminate
+    pub fn close(&mut self) {
+        self.subscribers.clear();
+    }
+}
+
+/// Async stream of `ScanEvent`s for one subscriber
+#[derive(Debug)]
+pub struct ScanEventStream {
+    receiver: mpsc::UnboundedReceiver<ScanEvent>,
+}
+
+impl Stream for ScanEventStream {
+    type Item = ScanEvent;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.receiver.poll_recv(cx)
+    }
+}
+
+/// Write every event from `stream` to `writer` as newline-delimited JSON
+///
+/// Each line is flushed immediately so downstream tools see results live.
+/// Returns the number of events written.
+pub async fn write_ndjson<W>(mut stream: ScanEventStream, mut writer: W) -> Result<usize, anyhow::Error>
+where
+    W: AsyncWrite + Unpin,
+{
+    let mut count = 0;
+    while let Some(event) = stream.next().await {
+        let mut line = serde_json::to_vec(&event)?;
+        line.push(b'\n');
+        writer.write_all(&line).await?;
+        writer.flush().await?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn ndjson_has_one_object_per_line() {
+        let mut bus = EventBus::default();
+        let stream = bus.subscribe();
+        let events = [
+            ScanEventKind::HostUp { ip: "192.0.2.1".to_string() },
+            ScanEventKind::PortState { port: 22, scan_type: ScanType::Syn, status: PortStatus::Open },
+            // Banners and certificates carry newlines of their own
+            ScanEventKind::ServiceIdentified { port: 22, service: "ssh".to_string(), version: Some("OpenSSH 9.6\r\nextra".to_string()) },
+            ScanEventKind::TlsInfo {
+                port: 443,
+                info: Box::new(SslInfo { cert_cn: Some("line one\nline two".to_string()), ..SslInfo::default() }),
+            },
+            ScanEventKind::ScanFinished { open_ports: vec![22, 443], duration_ms: 1200 },
+        ];
+        for kind in events.clone() {
+            bus.emit(ScanEvent::new("example.test", kind));
+        }
+        bus.close();
+
+        let mut output = Vec::new();
+        let written = write_ndjson(stream, &mut output).await.unwrap();
+        assert_eq!(written, events.len());
+
+        let text = String::from_utf8(output).unwrap();
+        assert!(text.ends_with('\n'));
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), events.len());
+        let names: Vec<String> = lines.iter()
+            .map(|line| {
+                let value: serde_json::Value = serde_json::from_str(line).unwrap();
+                assert!(value.is_object(), "{}", line);
+                assert_eq!(value["target"], "example.test");
+                value["event"].as_str().unwrap().to_string()
+            })
+            .collect();
+        assert_eq!(names, ["host_up", "port_state", "service_identified", "tls_info", "scan_finished"]);
+
+        let tls: ScanEvent = serde_json::from_str(lines[3]).unwrap();
+        match tls.kind {
+            ScanEventKind::TlsInfo { info, .. } => assert_eq!(info.cert_cn.as_deref(), Some("line one\nline two")),
+            other => panic!("unexpected event {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,431 @@
+//! Firewall and filtering classification
+//!
+//! Combines what the probes of a scan saw into a verdict per port and per
+//! host: unfiltered, stateful firewall, stateless ACL or rate-limited. The
+//! signals are:
+//!
+//! - SYN against ACK (and window) results. A filter that drops SYNs but lets
+//!   ACKs through to be reset matches packets on their flags alone; one that
+//!   drops ACKs outside a connection, including to ports it lets SYNs into,
+//!   tracks state.
+//! - ICMP "administratively prohibited" errors, and which device sent them.
+//! - The TTL of RSTs against the TTL of SYN/ACKs from the same host. A
+//!   firewall resetting on the host's behalf sits at a different hop count or
+//!   starts from a different initial TTL.
+//! - Replies that only came after retries, which point at rate limiting when
+//!   they are common across the host.
+//!
+//! A verdict is only given when the evidence supports one, and it lists the
+//! observations it rests on. TTL, ICMP and retry signals come from the
+//! `probe_observations` recorded for every probe. Connect probes never see
+//! the packets, so they only add retries (told from how long the answer
+//! took); the SYN/ACK/window comparison works from `tcp_states` alone.
+
+use std::collections::{BTreeMap, HashMap};
+use std::net::Ipv4Addr;
+
+use crate::models::{FilteringClass, FilteringVerdict, PortResult, PortStatus, ProbeObservation, ScanType};
+use crate::packet::{Reply, TCP_ACK, TCP_RST, TCP_SYN};
+
+/// ICMP unreachable codes for "administratively prohibited" (network, host, communication)
+const ADMIN_PROHIBITED: &[u8] = &[9, 10, 13];
+
+/// Replies that needed retries before rate limiting is considered
+const RATE_LIMIT_MIN_RETRIED: usize = 3;
+
+/// Share of answered probes that needed retries before rate limiting is considered
+const RATE_LIMIT_MIN_SHARE: f64 = 0.2;
+
+/// Most ports listed per class in a host verdict
+const MAX_LISTED_PORTS: usize = 10;
+
+/// SYN-based scan types, in order of preference when several ran
+const SYN_TYPES: &[ScanType] = &[ScanType::Syn, ScanType::Mimic, ScanType::Frag, ScanType::TlsEcho];
+
+/// Classify every port in `results`, store the verdicts and return the host's
+///
+/// `target` is the scanned address; ICMP errors from any other address came
+/// from a device in the path.
+pub fn annotate(target: &str, results: &mut HashMap<u16, PortResult>) -> Option<FilteringVerdict> {
+    let target = target.parse::<Ipv4Addr>().ok();
+    let host = HostEvidence::gather(results);
+    for result in results.values_mut() {
+        result.filtering = classify_port(target, &host, result);
+    }
+    host_verdict(results)
+}
+
+/// Verdict for a host from the verdicts already stored on its ports
+///
+/// The host takes the most common class among its filtered ports, since
+/// ports a filter lets through look unfiltered whatever the filter is, or
+/// rate-limited when enough of its replies needed retries.
+pub fn host_verdict(results: &HashMap<u16, PortResult>) -> Option<FilteringVerdict> {
+    let mut by_class: BTreeMap<FilteringClass, Vec<u16>> = BTreeMap::new();
+    for (port, result) in results {
+        if let Some(verdict) = &result.filtering {
+            by_class.entry(verdict.class).or_default().push(*port);
+        }
+    }
+    if by_class.is_empty() {
+        return None;
+    }
+
+    let host = HostEvidence::gather(results);
+    let class = if host.rate_limited.is_some() {
+        FilteringClass::RateLimited
+    } else {
+        by_class.iter()
+            .filter(|(class, _)| **class != FilteringClass::Unfiltered)
+            .max_by_key(|(class, ports)| (ports.len(), std::cmp::Reverse(**class)))
+            .map(|(class, _)| *class)
+            .unwrap_or(FilteringClass::Unfiltered)
+    };
+
+    let mut reasons = Vec::new();
+    if let Some(reason) = &host.rate_limited {
+        reasons.push(reason.clone());
+    }
+    for (class, ports) in &mut by_class {
+        ports.sort_unstable();
+        let mut listed: Vec<String> = ports.iter().take(MAX_LISTED_PORTS).map(u16::to_string).collect();
+        if ports.len() > MAX_LISTED_PORTS {
+            listed.push(format!("and {} more", ports.len() - MAX_LISTED_PORTS));
+        }
+        reasons.push(format!("{} port(s) {}: {}", ports.len(), class, listed.join(", ")));
+    }
+    if let Some(ttl) = host.syn_ack_ttl {
+        reasons.push(format!("SYN/ACKs from the host arrive with TTL {}", ttl));
+    }
+    Some(FilteringVerdict { class, reasons })
+}
+
+/// What the whole host's replies say, used as context for each port
+struct HostEvidence {
+    /// Most common TTL of SYN/ACK replies
+    syn_ack_ttl: Option<u8>,
+    /// Why the host looks rate-limited, if it does
+    rate_limited: Option<String>,
+}
+
+impl HostEvidence {
+    fn gather(results: &HashMap<u16, PortResult>) -> Self {
+        let observations = || results.values().flat_map(|result| result.probe_observations.iter());
+
+        let mut ttls: BTreeMap<u8, usize> = BTreeMap::new();
+        for observation in observations() {
+            if let Some(Reply::Tcp { flags, ttl, .. }) = observation.reply {
+                if flags & (TCP_SYN | TCP_ACK) == TCP_SYN | TCP_ACK {
+                    *ttls.entry(ttl).or_default() += 1;
+                }
+            }
+        }
+        let syn_ack_ttl = ttls.into_iter().max_by_key(|(_, count)| *count).map(|(ttl, _)| ttl);
+
+        let answered = observations().filter(|o| was_answered(o)).count();
+        let retried = observations().filter(|o| was_answered(o) && o.attempts > 1).count();
+        let rate_limited = (retried >= RATE_LIMIT_MIN_RETRIED && retried as f64 >= answered as f64 * RATE_LIMIT_MIN_SHARE)
+            .then(|| format!(
+                "{} of {} answered probes only got a reply after retries: replies are being dropped under load",
+                retried, answered
+            ));
+
+        Self { syn_ack_ttl, rate_limited }
+    }
+}
+
+/// Classify one port
+fn classify_port(target: Option<Ipv4Addr>, host: &HostEvidence, result: &PortResult) -> Option<FilteringVerdict> {
+    let mut reasons = Vec::new();
+
+    let retried: Vec<&ProbeObservation> = result.probe_observations.iter()
+        .filter(|o| was_answered(o) && o.attempts > 1)
+        .collect();
+    for observation in &retried {
+        reasons.push(format!(
+            "{:?} probe was answered on attempt {} after the earlier ones got nothing",
+            observation.scan_type, observation.attempts
+        ));
+    }
+    if !retried.is_empty() {
+        if let Some(reason) = &host.rate_limited {
+            reasons.push(format!("across the host, {}", reason));
+            return Some(FilteringVerdict { class: FilteringClass::RateLimited, reasons });
+        }
+    }
+
+    let syn = SYN_TYPES.iter()
+        .find_map(|scan_type| result.tcp_states.get(scan_type).map(|status| (*scan_type, *status)));
+    let ack = result.tcp_states.get(&ScanType::Ack).copied();
+    let window = result.tcp_states.get(&ScanType::Window).copied();
+
+    // An ACK (or window) probe that drew a RST reached the host
+    let ack_passed = ack == Some(PortStatus::Unfiltered) || matches!(window, Some(PortStatus::Open | PortStatus::Closed));
+    let ack_blocked = !ack_passed && (ack == Some(PortStatus::Filtered) || window == Some(PortStatus::Filtered));
+    if ack_passed {
+        reasons.push(match ack {
+            Some(PortStatus::Unfiltered) => "ACK probe reached the host and was reset".to_string(),
+            _ => "window probe (an ACK) reached the host and was reset".to_string(),
+        });
+    } else if ack_blocked {
+        reasons.push("ACK probe outside any connection got no reply".to_string());
+    }
+
+    let prohibited = result.probe_observations.iter().find_map(|o| match o.reply {
+        Some(Reply::Icmp { code, from, .. }) if ADMIN_PROHIBITED.contains(&code) => Some((o.scan_type, code, from)),
+        _ => None,
+    });
+    if let Some((scan_type, code, from)) = prohibited {
+        reasons.push(format!("{:?} probe was rejected with ICMP administratively prohibited (code {}) from {}", scan_type, code, from));
+    }
+
+    let class = match syn {
+        Some((scan_type, status @ (PortStatus::Open | PortStatus::Closed))) => {
+            reasons.insert(0, format!("{:?} probe was answered ({})", scan_type, status_name(status)));
+            let rst_ttl = result.probe_observations.iter().find_map(|o| match o.reply {
+                Some(Reply::Tcp { flags, ttl, .. }) if flags & TCP_RST != 0 && SYN_TYPES.contains(&o.scan_type) => Some(ttl),
+                _ => None,
+            });
+            let forged = match (rst_ttl, host.syn_ack_ttl) {
+                (Some(rst), Some(syn_ack)) if rst != syn_ack => {
+                    reasons.push(format!(
+                        "RST arrived with TTL {} but SYN/ACKs from the host arrive with TTL {}: a device in front of the host sent it",
+                        rst, syn_ack
+                    ));
+                    true
+                }
+                (Some(rst), Some(_)) => {
+                    reasons.push(format!("RST arrived with the host's own TTL {}", rst));
+                    false
+                }
+                _ => false,
+            };
+
+            if ack_blocked {
+                reasons.push("the SYN got through but the ACK did not: packets outside a tracked connection are dropped".to_string());
+                FilteringClass::StatefulFirewall
+            } else if forged && ack_passed {
+                reasons.push("resets are forged for closed ports while ACKs pass unchecked: a per-packet rule".to_string());
+                FilteringClass::StatelessAcl
+            } else if forged {
+                reasons.push("answering for the host with resets is a stateful firewall's reject action".to_string());
+                FilteringClass::StatefulFirewall
+            } else {
+                FilteringClass::Unfiltered
+            }
+        }
+        Some((scan_type, PortStatus::Filtered)) if ack_passed => {
+            reasons.insert(0, format!("{:?} probe got no reply", scan_type));
+            reasons.push("only connection attempts are blocked, so the filter matches TCP flags without tracking state".to_string());
+            FilteringClass::StatelessAcl
+        }
+        Some((scan_type, PortStatus::Filtered)) if ack_blocked => {
+            reasons.insert(0, format!("{:?} probe got no reply", scan_type));
+            reasons.push("new connections and packets outside a connection are both dropped, as a stateful firewall does".to_string());
+            FilteringClass::StatefulFirewall
+        }
+        _ if ack_passed => FilteringClass::Unfiltered,
+        _ => match prohibited {
+            // Routers enforcing access lists reject with ICMP; a host rejecting
+            // for itself runs its own (connection-tracking) firewall
+            Some((_, _, from)) if Some(from) == target => {
+                reasons.push("the host itself sent the rejection, so it runs its own firewall".to_string());
+                FilteringClass::StatefulFirewall
+            }
+            Some(_) => {
+                reasons.push("a device in the path sent the rejection, as routers enforcing access lists do".to_string());
+                FilteringClass::StatelessAcl
+            }
+            None => return None,
+        },
+    };
+
+    Some(FilteringVerdict { class, reasons })
+}
+
+/// Whether anything came back for a probe: a raw reply, or a connect that
+/// was accepted or refused
+fn was_answered(observation: &ProbeObservation) -> bool {
+    observation.reply.is_some() || matches!(observation.status, PortStatus::Open | PortStatus::Closed)
+}
+
+fn status_name(status: PortStatus) -> &'static str {
+    match status {
+        PortStatus::Open => "open",
+        PortStatus::Closed => "closed",
+        _ => "filtered",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TARGET: &str = "10.0.0.5";
+
+    fn syn_ack(ttl: u8) -> Option<Reply> {
+        Some(Reply::Tcp { flags: TCP_SYN | TCP_ACK, window: 64240, ttl })
+    }
+
+    fn rst(ttl: u8) -> Option<Reply> {
+        Some(Reply::Tcp { flags: TCP_RST | TCP_ACK, window: 0, ttl })
+    }
+
+    fn prohibited(code: u8, from: Ipv4Addr) -> Option<Reply> {
+        Some(Reply::Icmp { code, from, ttl: 61 })
+    }
+
+    /// A port result from (technique, state, reply, attempts) per probe
+    fn port(probes: &[(ScanType, PortStatus, Option<Reply>, u32)]) -> PortResult {
+        let mut result = PortResult::default();
+        for (scan_type, status, reply, attempts) in probes {
+            result.tcp_states.insert(*scan_type, *status);
+            result.probe_observations.push(ProbeObservation {
+                scan_type: *scan_type,
+                status: *status,
+                reply: *reply,
+                attempts: *attempts,
+            });
+        }
+        result
+    }
+
+    fn verdict(results: &HashMap<u16, PortResult>, port: u16) -> &FilteringVerdict {
+        results[&port].filtering.as_ref().expect("port has a verdict")
+    }
+
+    #[test]
+    fn host_answering_for_itself_is_unfiltered() {
+        let mut results = HashMap::from([
+            (22, port(&[(ScanType::Syn, PortStatus::Open, syn_ack(60), 1), (ScanType::Ack, PortStatus::Unfiltered, rst(60), 1)])),
+            (23, port(&[(ScanType::Syn, PortStatus::Closed, rst(60), 1), (ScanType::Ack, PortStatus::Unfiltered, rst(60), 1)])),
+        ]);
+        let host = annotate(TARGET, &mut results).unwrap();
+
+        let closed = verdict(&results, 23);
+        assert_eq!(closed.class, FilteringClass::Unfiltered);
+        assert_eq!(closed.reasons, [
+            "Syn probe was answered (closed)",
+            "ACK probe reached the host and was reset",
+            "RST arrived with the host's own TTL 60",
+        ]);
+        assert_eq!(verdict(&results, 22).class, FilteringClass::Unfiltered);
+        assert_eq!(host.class, FilteringClass::Unfiltered);
+        assert_eq!(host.reasons, ["2 port(s) unfiltered: 22, 23", "SYN/ACKs from the host arrive with TTL 60"]);
+    }
+
+    #[test]
+    fn dropped_acks_mean_a_stateful_firewall() {
+        let mut results = HashMap::from([
+            (22, port(&[(ScanType::Syn, PortStatus::Open, syn_ack(60), 1), (ScanType::Ack, PortStatus::Filtered, None, 2)])),
+            (23, port(&[(ScanType::Syn, PortStatus::Filtered, None, 2), (ScanType::Ack, PortStatus::Filtered, None, 2)])),
+        ]);
+        let host = annotate(TARGET, &mut results).unwrap();
+
+        let open = verdict(&results, 22);
+        assert_eq!(open.class, FilteringClass::StatefulFirewall);
+        assert_eq!(open.reasons.last().unwrap(),
+            "the SYN got through but the ACK did not: packets outside a tracked connection are dropped");
+        let blocked = verdict(&results, 23);
+        assert_eq!(blocked.class, FilteringClass::StatefulFirewall);
+        assert_eq!(blocked.reasons, [
+            "Syn probe got no reply",
+            "ACK probe outside any connection got no reply",
+            "new connections and packets outside a connection are both dropped, as a stateful firewall does",
+        ]);
+        assert_eq!(host.class, FilteringClass::StatefulFirewall);
+    }
+
+    #[test]
+    fn forged_resets_are_told_apart_by_ttl() {
+        let mut results = HashMap::from([
+            (22, port(&[(ScanType::Syn, PortStatus::Open, syn_ack(60), 1)])),
+            (23, port(&[(ScanType::Syn, PortStatus::Closed, rst(250), 1)])),
+            (24, port(&[(ScanType::Syn, PortStatus::Closed, rst(250), 1), (ScanType::Window, PortStatus::Closed, rst(60), 1)])),
+        ]);
+        annotate(TARGET, &mut results).unwrap();
+
+        let reset = verdict(&results, 23);
+        assert_eq!(reset.class, FilteringClass::StatefulFirewall);
+        assert_eq!(reset.reasons, [
+            "Syn probe was answered (closed)",
+            "RST arrived with TTL 250 but SYN/ACKs from the host arrive with TTL 60: a device in front of the host sent it",
+            "answering for the host with resets is a stateful firewall's reject action",
+        ]);
+        let acl = verdict(&results, 24);
+        assert_eq!(acl.class, FilteringClass::StatelessAcl);
+        assert_eq!(acl.reasons.last().unwrap(),
+            "resets are forged for closed ports while ACKs pass unchecked: a per-packet rule");
+    }
+
+    #[test]
+    fn blocked_syns_with_passing_acks_mean_a_stateless_acl() {
+        let mut results = HashMap::from([
+            (22, port(&[(ScanType::Syn, PortStatus::Open, syn_ack(60), 1), (ScanType::Window, PortStatus::Closed, rst(60), 1)])),
+            (23, port(&[(ScanType::Syn, PortStatus::Filtered, None, 2), (ScanType::Window, PortStatus::Closed, rst(60), 1)])),
+            (24, port(&[(ScanType::Syn, PortStatus::Filtered, None, 2), (ScanType::Window, PortStatus::Closed, rst(60), 1)])),
+        ]);
+        let host = annotate(TARGET, &mut results).unwrap();
+
+        let blocked = verdict(&results, 23);
+        assert_eq!(blocked.class, FilteringClass::StatelessAcl);
+        assert_eq!(blocked.reasons, [
+            "Syn probe got no reply",
+            "window probe (an ACK) reached the host and was reset",
+            "only connection attempts are blocked, so the filter matches TCP flags without tracking state",
+        ]);
+        assert_eq!(verdict(&results, 22).class, FilteringClass::Unfiltered);
+        assert_eq!(host.class, FilteringClass::StatelessAcl);
+        assert!(host.reasons.contains(&"2 port(s) stateless ACL: 23, 24".to_string()));
+    }
+
+    #[test]
+    fn icmp_rejections_are_attributed_to_their_sender() {
+        let router = Ipv4Addr::new(10, 0, 0, 254);
+        let mut results = HashMap::from([
+            (23, port(&[(ScanType::Syn, PortStatus::Filtered, prohibited(13, router), 1)])),
+            (24, port(&[(ScanType::Syn, PortStatus::Filtered, prohibited(10, TARGET.parse().unwrap()), 1)])),
+            (25, port(&[(ScanType::Syn, PortStatus::Filtered, None, 2)])),
+        ]);
+        annotate(TARGET, &mut results).unwrap();
+
+        let acl = verdict(&results, 23);
+        assert_eq!(acl.class, FilteringClass::StatelessAcl);
+        assert_eq!(acl.reasons, [
+            "Syn probe was rejected with ICMP administratively prohibited (code 13) from 10.0.0.254",
+            "a device in the path sent the rejection, as routers enforcing access lists do",
+        ]);
+        let host_firewall = verdict(&results, 24);
+        assert_eq!(host_firewall.class, FilteringClass::StatefulFirewall);
+        assert_eq!(host_firewall.reasons.last().unwrap(), "the host itself sent the rejection, so it runs its own firewall");
+        // Silence on its own proves nothing
+        assert!(results[&25].filtering.is_none());
+    }
+
+    #[test]
+    fn retried_connects_across_the_host_mean_rate_limiting() {
+        // Connect probes: no reply recorded, the retries come from the connect time
+        let mut results: HashMap<u16, PortResult> = (80..84)
+            .map(|p| (p, port(&[(ScanType::Syn, PortStatus::Open, None, 2)])))
+            .collect();
+        results.insert(84, port(&[(ScanType::Syn, PortStatus::Closed, None, 1)]));
+        let host = annotate(TARGET, &mut results).unwrap();
+
+        let limited = verdict(&results, 80);
+        assert_eq!(limited.class, FilteringClass::RateLimited);
+        assert_eq!(limited.reasons, [
+            "Syn probe was answered on attempt 2 after the earlier ones got nothing",
+            "across the host, 4 of 5 answered probes only got a reply after retries: replies are being dropped under load",
+        ]);
+        assert_eq!(verdict(&results, 84).class, FilteringClass::Unfiltered);
+        assert_eq!(host.class, FilteringClass::RateLimited);
+        assert_eq!(host.reasons[0], "4 of 5 answered probes only got a reply after retries: replies are being dropped under load");
+
+        // A couple of slow answers are not enough
+        let mut results: HashMap<u16, PortResult> = (80..90)
+            .map(|p| (p, port(&[(ScanType::Syn, PortStatus::Open, None, if p < 82 { 2 } else { 1 })])))
+            .collect();
+        let host = annotate(TARGET, &mut results).unwrap();
+        assert_eq!(host.class, FilteringClass::Unfiltered);
+    }
+}
@@ -0,0 +1,539 @@
+//! HTTP content and virtual-host discovery
+//!
+//! Optional follow-up stage that runs once `grab_http_banner` has confirmed a
+//! web server. Paths are brute-forced from a wordlist (with optional extensions)
+//! and filtered by status code. Before that, a couple of random paths are
+//! requested to fingerprint wildcard / soft-404 behaviour so that catch-all
+//! servers don't flood the results. Virtual hosts are found the same way by
+//! varying the Host header and, on TLS ports, the SNI value.
+
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::stream::{self, StreamExt};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::http_client::{self, HttpRequest, HttpResponse};
+use crate::models::{DiscoveredPath, HttpInfo, VirtualHost};
+use crate::scope::Egress;
+use crate::utils;
+
+/// Status codes reported when no explicit match list is given
+const DEFAULT_MATCH_STATUS: &[u16] = &[200, 204, 301, 302, 307, 308, 401, 403, 405, 500];
+
+/// Small built-in wordlist used when no wordlist file is supplied
+const DEFAULT_WORDLIST: &[&str] = &[
+    "admin", "login", "api", "backup", "config", "console", "dashboard", "debug",
+    "dev", "docs", "manager", "phpmyadmin", "server-status", "status", "swagger",
+    "test", "uploads", ".git/HEAD", ".env", "robots.txt", "sitemap.xml",
+];
+
+/// Slack allowed when comparing body lengths against the soft-404 baseline
+const LENGTH_TOLERANCE: usize = 16;
+
+/// Settings for the content and virtual-host discovery stage
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HttpDiscoveryConfig {
+    /// Words to try as paths
+    pub wordlist: Vec<String>,
+    /// Extensions appended to each word (without the leading dot)
+    pub extensions: Vec<String>,
+    /// Only report these status codes (empty = default set)
+    pub match_status: Vec<u16>,
+    /// Never report these status codes
+    pub filter_status: Vec<u16>,
+    /// Names to try as virtual hosts (empty = skip vhost discovery)
+    pub vhost_wordlist: Vec<String>,
+    /// Domain appended to vhost words that aren't already fully qualified
+    pub vhost_domain: Option<String>,
+    /// Number of requests in flight at once
+    pub concurrency: usize,
+}
+
+impl Default for HttpDiscoveryConfig {
+    fn default() -> Self {
+        Self {
+            wordlist: DEFAULT_WORDLIST.iter().map(|w| w.to_string()).collect(),
+            extensions: Vec::new(),
+            match_status: Vec::new(),
+            filter_status: vec![404],
+            vhost_wordlist: Vec::new(),
+            vhost_domain: None,
+            concurrency: 10,
+        }
+    }
+}
+
+impl HttpDiscoveryConfig {
+    /// Whether a status code should be reported
+    fn status_wanted(&self, status: u16) -> bool {
+        if self.filter_status.contains(&status) {
+            return false;
+        }
+        if self.match_status.is_empty() {
+            DEFAULT_MATCH_STATUS.contains(&status)
+        } else {
+            self.match_status.contains(&status)
+        }
+    }
+
+    /// Expand the wordlist with the configured extensions
+    fn candidate_paths(&self) -> Vec<String> {
+        let mut paths = Vec::new();
+        for word in &self.wordlist {
+            let word = word.trim_start_matches('/');
+            paths.push(format!("/{}", word));
+            for ext in &self.extensions {
+                paths.push(format!("/{}.{}", word, ext.trim_start_matches('.')));
+            }
+        }
+        paths
+    }
+}
+
+/// Load a newline-separated wordlist, skipping blanks and `#` comments
+pub fn load_wordlist(path: &Path) -> Result<Vec<String>, anyhow::Error> {
+    let content = std::fs::read_to_string(path)?;
+    Ok(content
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .map(str::to_string)
+        .collect())
+}
+
+/// Response fingerprint used for wildcard / soft-404 comparison
+#[derive(Debug, Clone)]
+struct Fingerprint {
+    status: u16,
+    /// Body length minus the length of the reflected input
+    adjusted_length: usize,
+    title: Option<String>,
+}
+
+impl Fingerprint {
+    fn new(response: &HttpResponse, reflected: &str) -> Self {
+        Self {
+            status: response.status_code,
+            adjusted_length: response.body.len().saturating_sub(reflected.len()),
+            title: response.title(),
+        }
+    }
+
+    /// Whether two responses are indistinguishable for discovery purposes
+    fn matches(&self, other: &Fingerprint) -> bool {
+        self.status == other.status
+            && self.title == other.title
+            && self.adjusted_length.abs_diff(other.adjusted_length) <= LENGTH_TOLERANCE
+    }
+}
+
+/// Generate a random token that is very unlikely to exist on the server
+pub(crate) fn random_token(len: usize) -> String {
+    rand::thread_rng()
+        .sample_iter(&rand::distributions::Alphanumeric)
+        .take(len)
+        .map(char::from)
+        .collect::<String>()
+        .to_lowercase()
+}
+
+/// Runs discovery against one web port
+pub struct HttpDiscovery {
+    target: String,
+    port: u16,
+    use_tls: bool,
+    config: Arc<HttpDiscoveryConfig>,
+    timeout: Duration,
+    egress: Egress,
+    logger: Option<Arc<utils::EnhancedLogger>>,
+}
+
+impl HttpDiscovery {
+    /// Create a discovery runner for `target:port`
+    ///
+    /// `timeout` bounds every step of each request (connect, TLS handshake and
+    /// each read), so a server that stalls costs at most a few timeouts per
+    /// request; the scanner passes its banner timeout, as for the other
+    /// follow-up probes.
+    pub fn new(
+        target: &str,
+        port: u16,
+        use_tls: bool,
+        config: Arc<HttpDiscoveryConfig>,
+        timeout: Duration,
+        egress: Egress,
+        logger: Option<Arc<utils::EnhancedLogger>>,
+    ) -> Self {
+        Self {
+            target: target.to_string(),
+            port,
+            use_tls,
+            config,
+            timeout,
+            egress,
+            logger,
+        }
+    }
+
+    fn log(&self, level: &str, message: &str) {
+        if let Some(logger) = &self.logger {
+            logger.log(level, message);
+        }
+    }
+
+    async fn send(&self, request: HttpRequest) -> Option<HttpResponse> {
+        match http_client::send_request(&self.target, self.port, self.use_tls, &request, self.timeout, &self.egress).await {
+            Ok(response) => Some(response),
+            Err(e) => {
+                self.log("DEBUG", &format!(
+                    "Discovery request {} to {}:{} failed: {}",
+                    request.path, self.target, self.port, e
+                ));
+                None
+            }
+        }
+    }
+
+    /// Run path and vhost discovery and attach the results to `http_info`
+    ///
+    /// `extra_vhosts` lets the caller add names learned elsewhere (e.g. from
+    /// certificate SANs) to the vhost candidates.
+    pub async fn run(&self, http_info: &mut HttpInfo, extra_vhosts: &[String]) {
+        let (paths, wildcard) = self.discover_paths().await;
+        http_info.discovered_paths = paths;
+        http_info.wildcard_response = Some(wildcard);
+
+        if !self.config.vhost_wordlist.is_empty() || !extra_vhosts.is_empty() {
+            http_info.virtual_hosts = self.discover_vhosts(extra_vhosts).await;
+        }
+    }
+
+    /// Fingerprint how the server answers for paths that can't exist
+    async fn path_baseline(&self) -> Vec<Fingerprint> {
+        let probes = [
+            format!("/{}", random_token(16)),
+            format!("/{}.php", random_token(12)),
+            format!("/{}/", random_token(10)),
+        ];
+
+        let mut baseline = Vec::new();
+        for path in &probes {
+            if let Some(response) = self.send(HttpRequest::get(path)).await {
+                baseline.push(Fingerprint::new(&response, path));
+            }
+        }
+        baseline
+    }
+
+    /// Brute-force paths from the wordlist
+    ///
+    /// Returns the discovered paths and whether wildcard behaviour was detected.
+    pub async fn discover_paths(&self) -> (Vec<DiscoveredPath>, bool) {
+        let baseline = self.path_baseline().await;
+        let wildcard = baseline.iter().any(|fp| fp.status != 404);
+
+        if wildcard {
+            self.log("INFO", &format!(
+                "Wildcard/soft-404 responses detected on {}:{} - filtering by response fingerprint",
+                self.target, self.port
+            ));
+        }
+
+        let candidates = self.config.candidate_paths();
+        let baseline = &baseline;
+
+        let mut found: Vec<DiscoveredPath> = stream::iter(candidates)
+            .map(|path| async move {
+                let response = self.send(HttpRequest::get(&path)).await?;
+                if !self.config.status_wanted(response.status_code) {
+                    return None;
+                }
+
+                let fingerprint = Fingerprint::new(&response, &path);
+                if baseline.iter().any(|fp| fp.matches(&fingerprint)) {
+                    return None;
+                }
+
+                Some(DiscoveredPath {
+                    path,
+                    status_code: response.status_code,
+                    content_length: response.content_length(),
+                    redirect: response.header("Location").map(str::to_string),
+                })
+            })
+            .buffer_unordered(self.config.concurrency.max(1))
+            .filter_map(|result| async move { result })
+            .collect()
+            .await;
+
+        found.sort_by(|a, b| a.path.cmp(&b.path));
+
+        for entry in &found {
+            self.log("INFO", &format!(
+                "Discovered {} on {}:{} [{}] ({} bytes)",
+                entry.path, self.target, self.port, entry.status_code, entry.content_length
+            ));
+        }
+
+        (found, wildcard)
+    }
+
+    /// Build a request that presents `hostname` as Host header and, on TLS, SNI
+    fn vhost_request(&self, hostname: &str) -> HttpRequest {
+        let mut request = HttpRequest::get("/");
+        request.host_header = Some(hostname.to_string());
+        if self.use_tls {
+            request.sni = Some(hostname.to_string());
+        }
+        request
+    }
+
+    /// Turn a vhost word into a fully qualified candidate name
+    fn qualify(&self, word: &str) -> Option<String> {
+        if word.contains('.') {
+            return Some(word.to_string());
+        }
+        self.config
+            .vhost_domain
+            .as_ref()
+            .map(|domain| format!("{}.{}", word, domain.trim_start_matches('.')))
+    }
+
+    /// Find virtual hosts that answer differently from an unknown name
+    pub async fn discover_vhosts(&self, extra_vhosts: &[String]) -> Vec<VirtualHost> {
+        let bogus = match &self.config.vhost_domain {
+            Some(domain) => format!("{}.{}", random_token(12), domain),
+            None => format!("{}.invalid", random_token(12)),
+        };
+
+        // The default vhost and an unknown name are what every miss looks like
+        let mut baseline = Vec::new();
+        for name in [bogus.as_str(), self.target.as_str()] {
+            if let Some(response) = self.send(self.vhost_request(name)).await {
+                baseline.push(Fingerprint::new(&response, name));
+            }
+        }
+
+        if baseline.is_empty() {
+            return Vec::new();
+        }
+
+        let mut candidates: Vec<String> = self
+            .config
+            .vhost_wordlist
+            .iter()
+            .filter_map(|w| self.qualify(w))
+            .chain(extra_vhosts.iter().filter(|h| !h.starts_with("*.") && !h.starts_with("IP:")).cloned())
+            .collect();
+        candidates.sort();
+        candidates.dedup();
+
+        let baseline = &baseline;
+        let mut found: Vec<VirtualHost> = stream::iter(candidates)
+            .map(|hostname| async move {
+                let response = self.send(self.vhost_request(&hostname)).await?;
+                let fingerprint = Fingerprint::new(&response, &hostname);
+                if baseline.iter().any(|fp| fp.matches(&fingerprint)) {
+                    return None;
+                }
+
+                Some(VirtualHost {
+                    status_code: response.status_code,
+                    content_length: response.content_length(),
+                    title: response.title(),
+                    via_sni: self.use_tls,
+                    hostname,
+                })
+            })
+            .buffer_unordered(self.config.concurrency.max(1))
+            .filter_map(|result| async move { result })
+            .collect()
+            .await;
+
+        found.sort_by(|a, b| a.hostname.cmp(&b.hostname));
+
+        for vhost in &found {
+            self.log("INFO", &format!(
+                "Virtual host {} on {}:{} [{}] {}",
+                vhost.hostname,
+                self.target,
+                self.port,
+                vhost.status_code,
+                vhost.title.as_deref().unwrap_or("")
+            ));
+        }
+
+        found
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    /// Serve one response per connection from `route(path, host)`
+    async fn web_server(route: fn(&str, &str) -> (u16, String)) -> u16 {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                tokio::spawn(async move {
+                    let mut buffer = [0u8; 2048];
+                    let n = stream.read(&mut buffer).await.unwrap_or(0);
+                    let request = String::from_utf8_lossy(&buffer[..n]).to_string();
+                    let path = request.split_whitespace().nth(1).unwrap_or("/").to_string();
+                    let host = request
+                        .lines()
+                        .find_map(|line| line.strip_prefix("Host: "))
+                        .unwrap_or("")
+                        .to_string();
+                    let (status, body) = route(&path, &host);
+                    let response = format!(
+                        "HTTP/1.1 {} X\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                        status,
+                        body.len(),
+                        body
+                    );
+                    let _ = stream.write_all(response.as_bytes()).await;
+                });
+            }
+        });
+        port
+    }
+
+    fn discovery(port: u16, config: HttpDiscoveryConfig, timeout: Duration) -> HttpDiscovery {
+        HttpDiscovery::new("127.0.0.1", port, false, Arc::new(config), timeout, Egress::default(), None)
+    }
+
+    #[test]
+    fn status_filters() {
+        let config = HttpDiscoveryConfig::default();
+        assert!(config.status_wanted(200));
+        assert!(config.status_wanted(403));
+        assert!(!config.status_wanted(404));
+        assert!(!config.status_wanted(418));
+
+        let config = HttpDiscoveryConfig { match_status: vec![200, 404], filter_status: vec![404], ..Default::default() };
+        assert!(config.status_wanted(200));
+        assert!(!config.status_wanted(404));
+        assert!(!config.status_wanted(403));
+    }
+
+    #[test]
+    fn wordlist_expands_with_extensions() {
+        let config = HttpDiscoveryConfig {
+            wordlist: vec!["admin".to_string(), "/backup".to_string()],
+            extensions: vec!["php".to_string(), ".bak".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(config.candidate_paths(), [
+            "/admin", "/admin.php", "/admin.bak", "/backup", "/backup.php", "/backup.bak",
+        ]);
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("words.txt");
+        std::fs::write(&path, "# comment\nadmin\n\n  login  \n#skip\n").unwrap();
+        assert_eq!(load_wordlist(&path).unwrap(), ["admin", "login"]);
+        assert!(load_wordlist(&dir.path().join("missing.txt")).is_err());
+    }
+
+    #[test]
+    fn fingerprints_tolerate_reflected_input() {
+        let response = |status: u16, body: &str| HttpResponse {
+            status_code: status,
+            body: body.as_bytes().to_vec(),
+            ..HttpResponse::default()
+        };
+        let miss = Fingerprint::new(&response(200, "<title>Home</title> not found: /abcdefgh"), "/abcdefgh");
+        let other_miss = Fingerprint::new(&response(200, "<title>Home</title> not found: /admin"), "/admin");
+        assert!(miss.matches(&other_miss));
+
+        let page = Fingerprint::new(&response(200, "<title>Admin</title> not found: /admin"), "/admin");
+        assert!(!miss.matches(&page));
+        let longer = Fingerprint::new(&response(200, &format!("<title>Home</title>{}", "x".repeat(100))), "/a");
+        assert!(!miss.matches(&longer));
+        let status = Fingerprint::new(&response(403, "<title>Home</title> not found: /admin"), "/admin");
+        assert!(!miss.matches(&status));
+    }
+
+    #[test]
+    fn vhost_words_are_qualified() {
+        let plain = discovery(80, HttpDiscoveryConfig::default(), Duration::from_secs(1));
+        assert_eq!(plain.qualify("dev"), None);
+        assert_eq!(plain.qualify("dev.example.com").as_deref(), Some("dev.example.com"));
+
+        let config = HttpDiscoveryConfig { vhost_domain: Some(".example.com".to_string()), ..Default::default() };
+        let qualified = discovery(80, config, Duration::from_secs(1));
+        assert_eq!(qualified.qualify("dev").as_deref(), Some("dev.example.com"));
+    }
+
+    #[tokio::test]
+    async fn paths_are_found_past_a_soft_404() {
+        // Every unknown path answers 200 with the path echoed back
+        let port = web_server(|path, _| match path {
+            "/admin" => (200, "<title>Admin console</title>".to_string()),
+            "/backup.zip" => (403, "forbidden".to_string()),
+            _ => (200, format!("<title>Shop</title>nothing at {}", path)),
+        })
+        .await;
+        let config = HttpDiscoveryConfig {
+            wordlist: vec!["admin".to_string(), "login".to_string(), "backup".to_string()],
+            extensions: vec!["zip".to_string()],
+            ..Default::default()
+        };
+
+        let (found, wildcard) = discovery(port, config, Duration::from_secs(2)).discover_paths().await;
+        assert!(wildcard);
+        let found: Vec<(&str, u16)> = found.iter().map(|p| (p.path.as_str(), p.status_code)).collect();
+        assert_eq!(found, [("/admin", 200), ("/backup.zip", 403)]);
+    }
+
+    #[tokio::test]
+    async fn virtual_hosts_differ_from_the_default() {
+        let port = web_server(|path, host| match (path, host) {
+            ("/", "intranet.example.com") => (200, "<title>Intranet</title>".to_string()),
+            ("/", _) => (200, "<title>Default</title>".to_string()),
+            _ => (404, "not found".to_string()),
+        })
+        .await;
+        let config = HttpDiscoveryConfig {
+            vhost_wordlist: vec!["intranet".to_string(), "www".to_string()],
+            vhost_domain: Some("example.com".to_string()),
+            ..Default::default()
+        };
+
+        let mut info = HttpInfo::default();
+        discovery(port, config, Duration::from_secs(2)).run(&mut info, &["*.example.com".to_string()]).await;
+        assert_eq!(info.wildcard_response, Some(false));
+        assert_eq!(info.virtual_hosts.len(), 1);
+        assert_eq!(info.virtual_hosts[0].hostname, "intranet.example.com");
+        assert_eq!(info.virtual_hosts[0].title.as_deref(), Some("Intranet"));
+        assert!(!info.virtual_hosts[0].via_sni);
+    }
+
+    #[tokio::test]
+    async fn stalled_servers_are_bounded_by_the_timeout() {
+        // Accepts connections but never answers
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let mut open = Vec::new();
+            while let Ok((stream, _)) = listener.accept().await {
+                open.push(stream);
+            }
+        });
+        let config = HttpDiscoveryConfig { wordlist: vec!["admin".to_string(), "login".to_string()], ..Default::default() };
+
+        let started = std::time::Instant::now();
+        let (found, wildcard) = discovery(port, config, Duration::from_millis(200)).discover_paths().await;
+        assert!(found.is_empty());
+        assert!(!wildcard);
+        // Three baseline probes one after another, then the candidates in parallel
+        assert!(started.elapsed() < Duration::from_secs(2), "{:?}", started.elapsed());
+    }
+}
@@ -0,0 +1,424 @@
+//! HTTP method and WebDAV audit
+//!
+//! Compares what a web server advertises (OPTIONS: Allow, Public and DAV
+//! headers) with what it actually does when asked. TRACE is sent with a
+//! marker header to detect cross-site tracing, PUT and DELETE are tried
+//! against a random path that can't collide with real content, and PROPFIND
+//! checks for WebDAV. A file created by PUT is deleted again straight away.
+//! This is the only web module that writes to the server, so it is opt-in.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::http_client::{self, HttpRequest, HttpResponse};
+use crate::http_discovery::random_token;
+use crate::models::{Finding, HttpMethods, Severity};
+use crate::scope::Egress;
+use crate::utils;
+
+/// Header used to spot a TRACE echo
+const TRACE_MARKER_HEADER: &str = "X-Quantum-Trace";
+
+/// Minimal PROPFIND body asking for the resource type only
+const PROPFIND_BODY: &str = "<?xml version=\"1.0\" encoding=\"utf-8\"?>\
+<propfind xmlns=\"DAV:\"><prop><resourcetype/></prop></propfind>";
+
+/// Outcome of the audit for one port
+#[derive(Debug, Clone)]
+pub struct MethodAuditResult {
+    /// Advertised and accepted methods
+    pub methods: HttpMethods,
+    /// Graded findings for risky methods that were accepted
+    pub findings: Vec<Finding>,
+}
+
+/// Runs the HTTP verb audit against one web server
+pub struct HttpMethodAudit {
+    target: String,
+    port: u16,
+    tls: bool,
+    timeout: Duration,
+    egress: Egress,
+    logger: Option<Arc<utils::EnhancedLogger>>,
+}
+
+impl HttpMethodAudit {
+    /// Create an audit for `target:port`, over TLS when `tls` is set
+    pub fn new(target: &str, port: u16, tls: bool, timeout: Duration, egress: Egress, logger: Option<Arc<utils::EnhancedLogger>>) -> Self {
+        Self {
+            target: target.to_string(),
+            port,
+            tls,
+            timeout,
+            egress,
+            logger,
+        }
+    }
+
+    fn log(&self, level: &str, message: &str) {
+        if let Some(logger) = &self.logger {
+            logger.log(level, message);
+        }
+    }
+
+    async fn send(&self, request: &HttpRequest) -> Option<HttpResponse> {
+        match http_client::send_request(&self.target, self.port, self.tls, request, self.timeout, &self.egress).await {
+            Ok(response) => Some(response),
+            Err(e) => {
+                self.log("DEBUG", &format!(
+                    "{} {} on {}:{} failed: {}", request.method, request.path, self.target, self.port, e
+                ));
+                None
+            }
+        }
+    }
+
+    /// Run OPTIONS, TRACE, PUT/DELETE and PROPFIND and collect the results
+    pub async fn run(&self) -> Option<MethodAuditResult> {
+        let service = if self.tls { "https" } else { "http" };
+        let mut methods = HttpMethods::default();
+        let mut findings = Vec::new();
+        let mut add = |severity, title: &str, evidence: String| {
+            findings.push(Finding {
+                severity,
+                service: service.to_string(),
+                title: title.to_string(),
+                evidence: Some(evidence),
+            });
+        };
+
+        // What the server claims to support
+        let options = self.send(&HttpRequest::new("OPTIONS", "/")).await?;
+        for name in ["Allow", "Public"] {
+            for value in options.header_values(name) {
+                for method in value.split(',').map(|m| m.trim().to_uppercase()).filter(|m| !m.is_empty()) {
+                    if !methods.advertised.contains(&method) {
+                        methods.advertised.push(method);
+                    }
+                }
+            }
+        }
+        methods.dav = options.header("DAV").map(|d| d.to_string());
+        if is_success(options.status_code) {
+            methods.accepted.push("OPTIONS".to_string());
+        }
+
+        // TRACE: does the server reflect our request, headers included?
+        let marker = random_token(16);
+        let mut trace = HttpRequest::new("TRACE", "/");
+        trace.headers.push((TRACE_MARKER_HEADER.to_string(), marker.clone()));
+        if let Some(response) = self.send(&trace).await {
+            let echoed = String::from_utf8_lossy(&response.body).contains(&marker);
+            if is_success(response.status_code) && echoed {
+                methods.accepted.push("TRACE".to_string());
+                add(Severity::Medium, "TRACE method enabled (cross-site tracing)",
+                    format!("TRACE / returned {} echoing the request headers", response.status_code));
+            }
+        }
+
+        // PUT a file that can't exist, confirm it was stored, then remove it
+        let path = format!("/{}.txt", random_token(12));
+        let mut put = HttpRequest::new("PUT", &path);
+        put.headers.push(("Content-Type".to_string(), "text/plain".to_string()));
+        put.body = Some(marker.clone().into_bytes());
+        let put_status = self.send(&put).await.map(|r| r.status_code);
+        let stored = match put_status {
+            Some(status) if is_success(status) => {
+                let response = self.send(&HttpRequest::get(&path)).await;
+                response.map(|r| String::from_utf8_lossy(&r.body).contains(&marker)).unwrap_or(false)
+            }
+            _ => false,
+        };
+        if stored {
+            methods.accepted.push("PUT".to_string());
+        }
+
+        // DELETE the uploaded file, or a path that doesn't exist if PUT failed
+        if let Some(response) = self.send(&HttpRequest::new("DELETE", &path)).await {
+            if is_success(response.status_code) {
+                methods.accepted.push("DELETE".to_string());
+            }
+        }
+        let removed = if stored {
+            let check = self.send(&HttpRequest::get(&path)).await;
+            check.map(|r| !String::from_utf8_lossy(&r.body).contains(&marker)).unwrap_or(false)
+        } else {
+            true
+        };
+
+        if stored {
+            let cleanup = if removed { "removed again with DELETE" } else { "COULD NOT BE REMOVED" };
+            add(Severity::High, "PUT creates files on the server",
+                format!("PUT {} returned {}, file readable afterwards, {}", path, put_status.unwrap_or_default(), cleanup));
+            self.log("WARN", &format!(
+                "PUT {} succeeded on {}:{} ({})", path, self.target, self.port, cleanup
+            ));
+        }
+        if methods.accepted.iter().any(|m| m == "DELETE") {
+            let (severity, title) = if stored {
+                (Severity::High, "DELETE removes files from the server")
+            } else {
+                (Severity::Medium, "DELETE accepted for a non-existent path")
+            };
+            add(severity, title, format!("DELETE {}", path));
+        }
+
+        // PROPFIND: a 207 Multi-Status answer means WebDAV is live
+        let mut propfind = HttpRequest::new("PROPFIND", "/");
+        propfind.headers.push(("Depth".to_string(), "0".to_string()));
+        propfind.headers.push(("Content-Type".to_string(), "application/xml".to_string()));
+        propfind.body = Some(PROPFIND_BODY.as_bytes().to_vec());
+        if let Some(response) = self.send(&propfind).await {
+            if response.status_code == 207 {
+                methods.accepted.push("PROPFIND".to_string());
+                methods.webdav = true;
+                add(Severity::Medium, "WebDAV enabled (PROPFIND answered)",
+                    format!("PROPFIND / returned 207 Multi-Status{}",
+                        methods.dav.as_deref().map(|d| format!(", DAV: {}", d)).unwrap_or_default()));
+            }
+        }
+
+        // Advertised risky methods that turned out to be refused
+        methods.refused = ["TRACE", "PUT", "DELETE", "PROPFIND"].iter()
+            .filter(|m| methods.advertised.iter().any(|a| a == *m))
+            .filter(|m| !methods.accepted.iter().any(|a| a == *m))
+            .map(|m| m.to_string())
+            .collect();
+
+        self.log("INFO", &format!(
+            "HTTP methods on {}:{} - advertised: [{}], accepted: [{}]",
+            self.target, self.port, methods.advertised.join(", "), methods.accepted.join(", ")
+        ));
+
+        Some(MethodAuditResult { methods, findings })
+    }
+}
+
+fn is_success(status: u16) -> bool {
+    (200..300).contains(&status)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    #[derive(Clone, Copy, PartialEq)]
+    enum Trace {
+        Refused,
+        /// 200 without reflecting the request
+        Blank,
+        Echo,
+    }
+
+    /// How the fake server treats each method
+    #[derive(Clone, Copy)]
+    struct Behaviour {
+        allow: &'static str,
+        dav: Option<&'static str>,
+        trace: Trace,
+        put: bool,
+        delete: bool,
+        webdav: bool,
+    }
+
+    /// Read one request (head and Content-Length body)
+    async fn read_request(stream: &mut tokio::net::TcpStream) -> (String, Vec<u8>) {
+        let mut data = Vec::new();
+        let mut buffer = [0u8; 2048];
+        loop {
+            let n = stream.read(&mut buffer).await.unwrap();
+            data.extend_from_slice(&buffer[..n]);
+            if let Some(end) = data.windows(4).position(|w| w == b"\r\n\r\n") {
+                let head = String::from_utf8_lossy(&data[..end]).to_string();
+                let length: usize = head.lines()
+                    .find_map(|l| l.to_lowercase().strip_prefix("content-length:").map(|v| v.trim().parse().unwrap()))
+                    .unwrap_or(0);
+                while data.len() < end + 4 + length {
+                    let n = stream.read(&mut buffer).await.unwrap();
+                    data.extend_from_slice(&buffer[..n]);
+                }
+                return (head, data[end + 4..].to_vec());
+            }
+            if n == 0 {
+                return (String::from_utf8_lossy(&data).to_string(), Vec::new());
+            }
+        }
+    }
+
+    /// Web server storing PUT bodies in `files`
+    async fn web_server(behaviour: Behaviour, files: Arc<Mutex<HashMap<String, Vec<u8>>>>) -> u16 {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let files = files.clone();
+                tokio::spawn(async move {
+                    let (head, body) = read_request(&mut stream).await;
+                    let mut words = head.split_whitespace();
+                    let (method, path) = (words.next().unwrap_or("").to_string(), words.next().unwrap_or("/").to_string());
+
+                    let mut headers = String::new();
+                    let (status, content) = match method.as_str() {
+                        "OPTIONS" => {
+                            headers.push_str(&format!("Allow: {}\r\n", behaviour.allow));
+                            if let Some(dav) = behaviour.dav {
+                                headers.push_str(&format!("DAV: {}\r\nPublic: PROPFIND, mkcol\r\n", dav));
+                            }
+                            (200, Vec::new())
+                        }
+                        "TRACE" => match behaviour.trace {
+                            Trace::Refused => (405, Vec::new()),
+                            Trace::Blank => (200, Vec::new()),
+                            Trace::Echo => (200, head.clone().into_bytes()),
+                        },
+                        "PUT" if behaviour.put => {
+                            files.lock().unwrap().insert(path, body);
+                            (201, Vec::new())
+                        }
+                        "DELETE" if behaviour.delete => {
+                            files.lock().unwrap().remove(&path);
+                            (204, Vec::new())
+                        }
+                        "GET" => match files.lock().unwrap().get(&path) {
+                            Some(stored) => (200, stored.clone()),
+                            None => (404, b"not found".to_vec()),
+                        },
+                        "PROPFIND" if behaviour.webdav => {
+                            assert!(head.contains("Depth: 0"));
+                            (207, b"<multistatus xmlns=\"DAV:\"/>".to_vec())
+                        }
+                        _ => (405, Vec::new()),
+                    };
+                    let response = [
+                        format!("HTTP/1.1 {} X\r\n{}Content-Length: {}\r\nConnection: close\r\n\r\n", status, headers, content.len()).into_bytes(),
+                        content,
+                    ]
+                    .concat();
+                    let _ = stream.write_all(&response).await;
+                });
+            }
+        });
+        port
+    }
+
+    async fn audit(behaviour: Behaviour) -> (MethodAuditResult, HashMap<String, Vec<u8>>) {
+        let files = Arc::new(Mutex::new(HashMap::new()));
+        let port = web_server(behaviour, files.clone()).await;
+        let result = HttpMethodAudit::new("127.0.0.1", port, false, Duration::from_secs(2), Egress::default(), None)
+            .run()
+            .await
+            .unwrap();
+        let files = files.lock().unwrap().clone();
+        (result, files)
+    }
+
+    fn titles(findings: &[Finding]) -> Vec<String> {
+        findings.iter().map(|f| format!("{:?}: {}", f.severity, f.title)).collect()
+    }
+
+    #[tokio::test]
+    async fn advertised_methods_are_verified() {
+        let (result, files) = audit(Behaviour {
+            allow: "GET, HEAD, OPTIONS, trace, PUT",
+            dav: None,
+            trace: Trace::Refused,
+            put: false,
+            delete: false,
+            webdav: false,
+        }).await;
+
+        assert_eq!(result.methods.advertised, ["GET", "HEAD", "OPTIONS", "TRACE", "PUT"]);
+        assert_eq!(result.methods.accepted, ["OPTIONS"]);
+        assert_eq!(result.methods.refused, ["TRACE", "PUT"]);
+        assert_eq!(result.methods.dav, None);
+        assert!(!result.methods.webdav);
+        assert!(result.findings.is_empty());
+        assert!(files.is_empty());
+    }
+
+    #[tokio::test]
+    async fn writable_webdav_server() {
+        let (result, files) = audit(Behaviour {
+            allow: "OPTIONS, GET, PUT, DELETE, PROPFIND, TRACE",
+            dav: Some("1, 2"),
+            trace: Trace::Echo,
+            put: true,
+            delete: true,
+            webdav: true,
+        }).await;
+
+        assert_eq!(result.methods.advertised, ["OPTIONS", "GET", "PUT", "DELETE", "PROPFIND", "TRACE", "MKCOL"]);
+        assert_eq!(result.methods.accepted, ["OPTIONS", "TRACE", "PUT", "DELETE", "PROPFIND"]);
+        assert!(result.methods.refused.is_empty());
+        assert_eq!(result.methods.dav.as_deref(), Some("1, 2"));
+        assert!(result.methods.webdav);
+        assert_eq!(titles(&result.findings), [
+            "Medium: TRACE method enabled (cross-site tracing)",
+            "High: PUT creates files on the server",
+            "High: DELETE removes files from the server",
+            "Medium: WebDAV enabled (PROPFIND answered)",
+        ]);
+
+        let put = result.findings[1].evidence.as_deref().unwrap();
+        assert!(put.starts_with("PUT /") && put.ends_with(".txt returned 201, file readable afterwards, removed again with DELETE"), "{}", put);
+        assert_eq!(result.findings[3].evidence.as_deref(), Some("PROPFIND / returned 207 Multi-Status, DAV: 1, 2"));
+        assert!(files.is_empty(), "the uploaded file is removed");
+        assert!(result.findings.iter().all(|f| f.service == "http"));
+    }
+
+    #[tokio::test]
+    async fn files_that_cannot_be_removed_are_reported() {
+        let (result, files) = audit(Behaviour {
+            allow: "GET, PUT, DELETE",
+            dav: None,
+            trace: Trace::Blank,
+            put: true,
+            delete: false,
+            webdav: false,
+        }).await;
+
+        assert_eq!(result.methods.accepted, ["OPTIONS", "PUT"]);
+        assert_eq!(result.methods.refused, ["DELETE"]);
+        assert_eq!(titles(&result.findings), ["High: PUT creates files on the server"]);
+        assert!(result.findings[0].evidence.as_deref().unwrap().ends_with("COULD NOT BE REMOVED"));
+        assert_eq!(files.len(), 1);
+        let (path, body) = files.iter().next().unwrap();
+        assert!(path.ends_with(".txt"));
+        assert_eq!(body.len(), 16, "the body is the random marker");
+    }
+
+    #[tokio::test]
+    async fn delete_without_put() {
+        let (result, _) = audit(Behaviour {
+            allow: "",
+            dav: None,
+            trace: Trace::Blank,
+            put: false,
+            delete: true,
+            webdav: false,
+        }).await;
+
+        assert!(result.methods.advertised.is_empty());
+        assert_eq!(result.methods.accepted, ["OPTIONS", "DELETE"], "a TRACE that doesn't echo is not counted");
+        assert_eq!(titles(&result.findings), ["Medium: DELETE accepted for a non-existent path"]);
+    }
+
+    #[tokio::test]
+    async fn unreachable_servers_are_skipped() {
+        let port = {
+            let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+            listener.local_addr().unwrap().port()
+        };
+        let audit = HttpMethodAudit::new("127.0.0.1", port, false, Duration::from_millis(500), Egress::default(), None);
+        assert!(audit.run().await.is_none());
+    }
+
+    #[test]
+    fn success_is_any_2xx() {
+        assert!(is_success(200) && is_success(204) && is_success(299));
+        assert!(!is_success(199) && !is_success(301) && !is_success(405));
+    }
+}
@@ -0,0 +1,545 @@
+//! HTTP security header and cookie grading
+//!
+//! Passive evaluation of the response already stored in `HttpInfo`; nothing
+//! here sends a request. Each weakness becomes a `Finding` graded Info to High
+//! so it can be pasted straight into a report. Browser-only protections (CSP,
+//! framing, Referrer-Policy, Permissions-Policy) are only judged on HTML
+//! responses, HSTS only over TLS, and every Set-Cookie is checked on its own.
+//! Redirect chains recorded by the banner grab are checked for loops,
+//! downgrades to cleartext and hops to other hosts.
+
+use crate::models::{Finding, HttpInfo, Severity};
+
+/// Minimum HSTS max-age we accept without comment (180 days)
+const HSTS_MIN_MAX_AGE: u64 = 180 * 24 * 60 * 60;
+
+/// Cookie name fragments that suggest a session or authentication token
+const SESSION_COOKIE_HINTS: &[&str] = &["sess", "sid", "auth", "token", "jwt", "login"];
+
+/// Grade the security headers and cookies of a stored response
+///
+/// `host` is the name or address the response was requested from; it is
+/// used to judge how far a cookie's Domain attribute reaches.
+pub fn grade(info: &HttpInfo, host: &str, tls: bool) -> Vec<Finding> {
+    let mut grader = Grader {
+        service: if tls { "https" } else { "http" },
+        findings: Vec::new(),
+    };
+
+    if tls {
+        grader.check_hsts(header(info, "strict-transport-security"));
+    }
+
+    let is_html = info.content_type.as_deref()
+        .map(|ct| ct.to_lowercase().contains("html"))
+        .unwrap_or(true);
+    if is_html {
+        let csp = header(info, "content-security-policy").map(parse_csp);
+        grader.check_csp(csp.as_deref(), header(info, "content-security-policy-report-only").is_some());
+        grader.check_framing(header(info, "x-frame-options"), csp.as_deref());
+        grader.check_referrer_policy(header(info, "referrer-policy"));
+        grader.check_permissions_policy(header(info, "permissions-policy"), header(info, "feature-policy"));
+    }
+
+    for cookie in &info.cookies {
+        grader.check_cookie(cookie, host, tls);
+    }
+
+    grader.check_redirects(info);
+
+    grader.findings
+}
+
+struct Grader {
+    service: &'static str,
+    findings: Vec<Finding>,
+}
+
+impl Grader {
+    fn add(&mut self, severity: Severity, title: &str, evidence: Option<String>) {
+        self.findings.push(Finding {
+            severity,
+            service: self.service.to_string(),
+            title: title.to_string(),
+            evidence,
+        });
+    }
+
+    fn check_hsts(&mut self, value: Option<&str>) {
+        let value = match value {
+            Some(v) => v,
+            None => {
+                self.add(Severity::Medium, "Strict-Transport-Security header missing", None);
+                return;
+            }
+        };
+
+        let directives: Vec<String> = value.split(';').map(|d| d.trim().to_lowercase()).collect();
+        let max_age = directives.iter()
+            .find_map(|d| d.strip_prefix("max-age="))
+            .and_then(|v| v.trim_matches('"').parse::<u64>().ok());
+        let evidence = Some(truncate(value));
+
+        match max_age {
+            None => self.add(Severity::Medium, "HSTS header has no valid max-age", evidence.clone()),
+            Some(0) => self.add(Severity::Medium, "HSTS disabled with max-age=0", evidence.clone()),
+            Some(age) if age < HSTS_MIN_MAX_AGE => {
+                self.add(Severity::Low, "HSTS max-age shorter than 180 days", evidence.clone())
+            }
+            Some(_) => {}
+        }
+
+        if !directives.iter().any(|d| d == "includesubdomains") {
+            self.add(Severity::Low, "HSTS does not cover subdomains (includeSubDomains missing)", evidence);
+        }
+    }
+
+    fn check_csp(&mut self, csp: Option<&[(String, Vec<String>)]>, report_only: bool) {
+        let csp = match csp {
+            Some(csp) => csp,
+            None if report_only => {
+                self.add(Severity::Low, "Content-Security-Policy is only deployed in report-only mode", None);
+                return;
+            }
+            None => {
+                self.add(Severity::Medium, "Content-Security-Policy header missing", None);
+                return;
+            }
+        };
+
+        let script_src = directive(csp, "script-src").or_else(|| directive(csp, "default-src"));
+        match script_src {
+            None => self.add(Severity::Medium, "CSP does not restrict scripts (no script-src or default-src)", None),
+            Some(sources) => {
+                // Nonces and hashes make browsers ignore 'unsafe-inline'
+                let has_nonce_or_hash = sources.iter()
+                    .any(|s| s.starts_with("'nonce-") || s.starts_with("'sha"));
+                if sources.iter().any(|s| s == "'unsafe-inline'") && !has_nonce_or_hash {
+                    self.add(Severity::Medium, "CSP allows inline scripts ('unsafe-inline')", Some(source_list("script-src", sources)));
+                }
+                if sources.iter().any(|s| s == "'unsafe-eval'") {
+                    self.add(Severity::Low, "CSP allows eval() ('unsafe-eval')", Some(source_list("script-src", sources)));
+                }
+            }
+        }
+
+        for name in ["default-src", "script-src", "object-src"] {
+            if let Some(sources) = directive(csp, name) {
+                if let Some(wildcard) = sources.iter().find(|s| is_wildcard_source(s)) {
+                    let title = format!("CSP {} allows any source ({})", name, wildcard);
+                    self.add(Severity::Medium, &title, Some(source_list(name, sources)));
+                }
+            }
+        }
+
+        if directive(csp, "object-src").is_none() && directive(csp, "default-src").is_none() {
+            self.add(Severity::Low, "CSP has no object-src (plugins can load from anywhere)", None);
+        }
+    }
+
+    fn check_framing(&mut self, x_frame_options: Option<&str>, csp: Option<&[(String, Vec<String>)]>) {
+        let frame_ancestors = csp.and_then(|csp| directive(csp, "frame-ancestors"));
+        let xfo = x_frame_options.map(|v| v.trim().to_uppercase());
+
+        if let Some(sources) = frame_ancestors {
+            if sources.iter().any(|s| is_wildcard_source(s)) {
+                self.add(Severity::Medium, "CSP frame-ancestors allows framing by any site", Some(source_list("frame-ancestors", sources)));
+            }
+
+            // frame-ancestors wins in every current browser; point out contradictions
+            let consistent = match xfo.as_deref() {
+                None => true,
+                Some("DENY") => sources == ["'none'"],
+                Some("SAMEORIGIN") => sources == ["'self'"],
+                Some(_) => false,
+            };
+            if !consistent {
+                let evidence = format!("X-Frame-Options: {}; {}", xfo.unwrap_or_default(), source_list("frame-ancestors", sources));
+                self.add(Severity::Info, "X-Frame-Options and CSP frame-ancestors disagree (browsers follow frame-ancestors)", Some(evidence));
+            }
+            return;
+        }
+
+        match xfo.as_deref() {
+            None => self.add(Severity::Medium, "No clickjacking protection (X-Frame-Options and CSP frame-ancestors missing)", None),
+            Some("DENY") | Some("SAMEORIGIN") => {}
+            Some(other) if other.starts_with("ALLOW-FROM") => {
+                self.add(Severity::Medium, "X-Frame-Options ALLOW-FROM is ignored by current browsers", Some(truncate(other)))
+            }
+            Some(other) => self.add(Severity::Medium, "X-Frame-Options has an invalid value", Some(truncate(other))),
+        }
+    }
+
+    fn check_referrer_policy(&mut self, value: Option<&str>) {
+        // Browsers use the last policy they understand
+        let policy = value.and_then(|v| {
+            v.split(',').map(|p| p.trim().to_lowercase()).rfind(|p| !p.is_empty())
+        });
+
+        match policy.as_deref() {
+            None => self.add(Severity::Info, "Referrer-Policy header missing", None),
+            Some("unsafe-url") => {
+                self.add(Severity::Medium, "Referrer-Policy sends full URLs to other sites", Some("unsafe-url".to_string()))
+            }
+            Some("no-referrer-when-downgrade") => {
+                self.add(Severity::Low, "Referrer-Policy sends full URLs cross-origin over HTTPS", Some("no-referrer-when-downgrade".to_string()))
+            }
+            Some(_) => {}
+        }
+    }
+
+    fn check_permissions_policy(&mut self, permissions_policy: Option<&str>, feature_policy: Option<&str>) {
+        match (permissions_policy, feature_policy) {
+            (Some(_), _) => {}
+            (None, Some(value)) => {
+                self.add(Severity::Info, "Only the deprecated Feature-Policy header is set (Permissions-Policy missing)", Some(truncate(value)))
+            }
+            (None, None) => self.add(Severity::Info, "Permissions-Policy header missing", None),
+        }
+    }
+
+    fn check_redirects(&mut self, info: &HttpInfo) {
+        if info.redirect_loop {
+            let chain: Vec<&str> = info.redirects.iter().map(|hop| hop.url.as_str()).collect();
+            self.add(Severity::Low, "Redirect loop", Some(truncate(&chain.join(" -> "))));
+        }
+
+        for hop in &info.redirects {
+            let evidence = Some(format!("{} {} -> {}", hop.status_code, hop.url, hop.location));
+            if hop.url.starts_with("https://") && hop.location.starts_with("http://") {
+                self.add(Severity::Medium, "Redirect from HTTPS to cleartext HTTP", evidence.clone());
+            }
+            if hop.cross_host {
+                self.add(Severity::Info, "Redirects to another host", evidence);
+            }
+        }
+    }
+
+    fn check_cookie(&mut self, set_cookie: &str, host: &str, tls: bool) {
+        let mut parts = set_cookie.split(';');
+        let name = parts.next()
+            .and_then(|pair| pair.split('=').next())
+            .map(|n| n.trim().to_string())
+            .unwrap_or_default();
+        if name.is_empty() {
+            return;
+        }
+
+        let mut secure = false;
+        let mut http_only = false;
+        let mut same_site = None;
+        let mut domain = None;
+        for attribute in parts {
+            let (key, value) = match attribute.split_once('=') {
+                Some((k, v)) => (k.trim().to_lowercase(), Some(v.trim().to_string())),
+                None => (attribute.trim().to_lowercase(), None),
+            };
+            match key.as_str() {
+                "secure" => secure = true,
+                "httponly" => http_only = true,
+                "samesite" => same_site = value.map(|v| v.to_lowercase()),
+                "domain" => domain = value.map(|v| v.trim_start_matches('.').to_lowercase()),
+                _ => {}
+            }
+        }
+
+        let lower_name = name.to_lowercase();
+        let is_session = SESSION_COOKIE_HINTS.iter().any(|hint| lower_name.contains(hint));
+        let evidence = Some(format!("cookie {}", name));
+
+        if !secure {
+            if tls {
+                self.add(Severity::Medium, "Cookie set without the Secure flag", evidence.clone());
+            } else {
+                self.add(Severity::Low, "Cookie set over cleartext HTTP without the Secure flag", evidence.clone());
+            }
+        }
+
+        if !http_only {
+            let severity = if is_session { Severity::Medium } else { Severity::Low };
+            self.add(severity, "Cookie set without the HttpOnly flag", evidence.clone());
+        }
+
+        match same_site.as_deref() {
+            None => self.add(Severity::Low, "Cookie set without a SameSite attribute", evidence.clone()),
+            Some("none") if !secure => {
+                self.add(Severity::Low, "Cookie uses SameSite=None without Secure and is rejected by browsers", evidence.clone())
+            }
+            Some("none") => self.add(Severity::Info, "Cookie is sent on cross-site requests (SameSite=None)", evidence.clone()),
+            _ => {}
+        }
+
+        if let Some(domain) = domain {
+            let host = host.trim_end_matches('.').to_lowercase();
+            let labels = domain.split('.').filter(|l| !l.is_empty()).count();
+            if labels < 2 {
+                let title = format!("Cookie Domain covers a whole top-level domain ({})", domain);
+                self.add(Severity::High, &title, evidence);
+            } else if domain != host {
+                let title = format!("Cookie Domain {} shares the cookie with every subdomain", domain);
+                self.add(Severity::Low, &title, evidence);
+            }
+        }
+    }
+}
+
+/// Look up a header in the lowercased header map
+fn header<'a>(info: &'a HttpInfo, name: &str) -> Option<&'a str> {
+    info.headers.iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case(name))
+        .map(|(_, v)| v.as_str())
+}
+
+/// Split a policy into (directive, sources) pairs, keeping the first of any duplicates
+fn parse_csp(value: &str) -> Vec<(String, Vec<String>)> {
+    let mut directives: Vec<(String, Vec<String>)> = Vec::new();
+    for part in value.split(';') {
+        let mut tokens = part.split_whitespace();
+        let name = match tokens.next() {
+            Some(name) => name.to_lowercase(),
+            None => continue,
+        };
+        if directives.iter().any(|(existing, _)| *existing == name) {
+            continue;
+        }
+        directives.push((name, tokens.map(|t| t.to_lowercase()).collect()));
+    }
+    directives
+}
+
+fn directive<'a>(csp: &'a [(String, Vec<String>)], name: &str) -> Option<&'a [String]> {
+    csp.iter().find(|(n, _)| n == name).map(|(_, sources)| sources.as_slice())
+}
+
+/// Sources that match any origin: "*", bare schemes and data:
+fn is_wildcard_source(source: &str) -> bool {
+    matches!(source, "*" | "http:" | "https:" | "data:" | "blob:" | "filesystem:")
+}
+
+fn source_list(name: &str, sources: &[String]) -> String {
+    truncate(&format!("{} {}", name, sources.join(" ")))
+}
+
+fn truncate(value: &str) -> String {
+    const MAX: usize = 120;
+    if value.chars().count() > MAX {
+        format!("{}...", value.chars().take(MAX).collect::<String>())
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::RedirectHop;
+
+    /// Headers that pass every check
+    const HARDENED: &[(&str, &str)] = &[
+        ("strict-transport-security", "max-age=31536000; includeSubDomains; preload"),
+        ("content-security-policy", "default-src 'self'; script-src 'self' 'nonce-r4nd0m' 'unsafe-inline'; object-src 'none'; frame-ancestors 'none'"),
+        ("x-frame-options", "DENY"),
+        ("referrer-policy", "strict-origin-when-cross-origin"),
+        ("permissions-policy", "camera=(), microphone=()"),
+    ];
+
+    fn html(overrides: &[(&str, Option<&str>)]) -> HttpInfo {
+        let mut headers: std::collections::HashMap<String, String> =
+            HARDENED.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        for (name, value) in overrides {
+            match value {
+                Some(value) => headers.insert(name.to_string(), value.to_string()),
+                None => headers.remove(*name),
+            };
+        }
+        HttpInfo {
+            content_type: Some("text/html; charset=utf-8".to_string()),
+            headers,
+            ..Default::default()
+        }
+    }
+
+    fn titles(findings: &[Finding]) -> Vec<String> {
+        findings.iter().map(|f| format!("{:?}: {}", f.severity, f.title)).collect()
+    }
+
+    fn graded(overrides: &[(&str, Option<&str>)]) -> Vec<String> {
+        titles(&grade(&html(overrides), "www.example.com", true))
+    }
+
+    #[test]
+    fn hardened_responses_pass() {
+        assert!(graded(&[]).is_empty(), "{:?}", graded(&[]));
+
+        let findings = grade(&html(&[]), "www.example.com", false);
+        assert!(findings.is_empty(), "HSTS is only judged over TLS");
+    }
+
+    #[test]
+    fn missing_headers() {
+        let bare = HttpInfo { content_type: Some("text/html".into()), ..Default::default() };
+        let findings = grade(&bare, "www.example.com", true);
+        assert_eq!(titles(&findings), [
+            "Medium: Strict-Transport-Security header missing",
+            "Medium: Content-Security-Policy header missing",
+            "Medium: No clickjacking protection (X-Frame-Options and CSP frame-ancestors missing)",
+            "Info: Referrer-Policy header missing",
+            "Info: Permissions-Policy header missing",
+        ]);
+        assert!(findings.iter().all(|f| f.service == "https"));
+
+        let api = HttpInfo { content_type: Some("application/json".into()), ..Default::default() };
+        assert!(grade(&api, "api.example.com", false).is_empty(), "browser protections are only judged on HTML");
+        let unknown = HttpInfo::default();
+        assert_eq!(grade(&unknown, "www.example.com", false)[0].service, "http");
+        assert_eq!(grade(&unknown, "www.example.com", false).len(), 4, "responses without a content type count as HTML");
+    }
+
+    #[test]
+    fn hsts_lifetime_and_scope() {
+        let hsts = |value: &str| graded(&[("strict-transport-security", Some(value))]);
+        assert!(hsts("max-age=\"15552000\"; includesubdomains").is_empty());
+        assert_eq!(hsts("max-age=0; includeSubDomains"), ["Medium: HSTS disabled with max-age=0"]);
+        assert_eq!(hsts("max-age=86400; includeSubDomains"), ["Low: HSTS max-age shorter than 180 days"]);
+        assert_eq!(hsts("includeSubDomains; max-age=soon"), ["Medium: HSTS header has no valid max-age"]);
+        assert_eq!(hsts("max-age=63072000"), ["Low: HSTS does not cover subdomains (includeSubDomains missing)"]);
+    }
+
+    #[test]
+    fn csp_weaknesses() {
+        let csp = |value: &str| graded(&[("content-security-policy", Some(value))]);
+        assert_eq!(csp("default-src 'self'; script-src 'self' 'unsafe-inline' 'unsafe-eval'"), [
+            "Medium: CSP allows inline scripts ('unsafe-inline')",
+            "Low: CSP allows eval() ('unsafe-eval')",
+        ]);
+        assert_eq!(csp("default-src 'self'; script-src 'self' 'sha256-abc=' 'unsafe-inline'"), Vec::<String>::new());
+        assert_eq!(csp("img-src *; object-src 'none'"), ["Medium: CSP does not restrict scripts (no script-src or default-src)"]);
+        assert_eq!(csp("Script-Src https: 'self'; script-src 'none'"), [
+            "Medium: CSP script-src allows any source (https:)",
+            "Low: CSP has no object-src (plugins can load from anywhere)",
+        ], "directive names are case-insensitive and the first duplicate wins");
+        assert_eq!(csp("default-src *"), ["Medium: CSP default-src allows any source (*)"]);
+
+        let findings = grade(&html(&[("content-security-policy", None), ("content-security-policy-report-only", Some("default-src 'self'"))]), "www.example.com", true);
+        assert_eq!(titles(&findings), ["Low: Content-Security-Policy is only deployed in report-only mode"]);
+
+        let inline = grade(&html(&[("content-security-policy", Some("script-src 'unsafe-inline'; object-src 'none'"))]), "www.example.com", true);
+        assert_eq!(inline[0].evidence.as_deref(), Some("script-src 'unsafe-inline'"));
+    }
+
+    #[test]
+    fn framing_protection() {
+        let no_ancestors = Some("default-src 'self'");
+        let framing = |xfo: Option<&str>, csp: Option<&str>| {
+            graded(&[("x-frame-options", xfo), ("content-security-policy", csp.or(no_ancestors))])
+        };
+        assert!(framing(Some("sameorigin"), None).is_empty());
+        assert_eq!(framing(None, None), ["Medium: No clickjacking protection (X-Frame-Options and CSP frame-ancestors missing)"]);
+        assert_eq!(framing(Some("ALLOW-FROM https://partner.example"), None), ["Medium: X-Frame-Options ALLOW-FROM is ignored by current browsers"]);
+        assert_eq!(framing(Some("ALLOWALL"), None), ["Medium: X-Frame-Options has an invalid value"]);
+
+        assert!(framing(None, Some("default-src 'self'; frame-ancestors 'self'")).is_empty());
+        assert!(framing(Some("SAMEORIGIN"), Some("default-src 'self'; frame-ancestors 'self'")).is_empty());
+        assert_eq!(framing(Some("DENY"), Some("default-src 'self'; frame-ancestors 'self'")), [
+            "Info: X-Frame-Options and CSP frame-ancestors disagree (browsers follow frame-ancestors)",
+        ]);
+        assert_eq!(framing(None, Some("default-src 'self'; frame-ancestors *")), [
+            "Medium: CSP frame-ancestors allows framing by any site",
+        ]);
+    }
+
+    #[test]
+    fn referrer_and_permissions_policies() {
+        let referrer = |value: &str| graded(&[("referrer-policy", Some(value))]);
+        assert_eq!(referrer("unsafe-url"), ["Medium: Referrer-Policy sends full URLs to other sites"]);
+        assert_eq!(referrer("no-referrer, No-Referrer-When-Downgrade"), ["Low: Referrer-Policy sends full URLs cross-origin over HTTPS"]);
+        assert!(referrer("unsafe-url, same-origin,").is_empty(), "the last policy wins");
+
+        assert_eq!(
+            graded(&[("permissions-policy", None), ("feature-policy", Some("camera 'none'"))]),
+            ["Info: Only the deprecated Feature-Policy header is set (Permissions-Policy missing)"]
+        );
+    }
+
+    #[test]
+    fn cookie_flags() {
+        let cookies = |cookies: &[&str], host: &str, tls: bool| {
+            let info = HttpInfo { cookies: cookies.iter().map(|c| c.to_string()).collect(), ..html(&[]) };
+            titles(&grade(&info, host, tls))
+        };
+
+        assert!(cookies(&["SESSIONID=abc; Path=/; Secure; HttpOnly; SameSite=Lax"], "www.example.com", true).is_empty());
+        assert_eq!(cookies(&["PHPSESSID=abc; path=/"], "www.example.com", true), [
+            "Medium: Cookie set without the Secure flag",
+            "Medium: Cookie set without the HttpOnly flag",
+            "Low: Cookie set without a SameSite attribute",
+        ]);
+        assert_eq!(cookies(&["theme=dark; SameSite=Strict"], "www.example.com", false), [
+            "Low: Cookie set over cleartext HTTP without the Secure flag",
+            "Low: Cookie set without the HttpOnly flag",
+        ]);
+        assert_eq!(cookies(&["tracker=1; HttpOnly; SameSite=None"], "www.example.com", true), [
+            "Medium: Cookie set without the Secure flag",
+            "Low: Cookie uses SameSite=None without Secure and is rejected by browsers",
+        ]);
+        assert_eq!(cookies(&["embed=1; Secure; HttpOnly; samesite=none"], "www.example.com", true), [
+            "Info: Cookie is sent on cross-site requests (SameSite=None)",
+        ]);
+        assert!(cookies(&["=orphan; Secure", "; HttpOnly"], "www.example.com", true).is_empty(), "nameless cookies are skipped");
+    }
+
+    #[test]
+    fn cookie_domains() {
+        let domain = |domain: &str, host: &str| {
+            let cookie = format!("sid=1; Secure; HttpOnly; SameSite=Lax; Domain={}", domain);
+            let info = HttpInfo { cookies: vec![cookie], ..html(&[]) };
+            grade(&info, host, true)
+        };
+
+        assert!(domain("WWW.example.com", "www.example.com.").is_empty());
+        let findings = domain(".example.com", "www.example.com");
+        assert_eq!(titles(&findings), ["Low: Cookie Domain example.com shares the cookie with every subdomain"]);
+        assert_eq!(findings[0].evidence.as_deref(), Some("cookie sid"));
+        assert_eq!(titles(&domain(".com", "www.example.com")), ["High: Cookie Domain covers a whole top-level domain (com)"]);
+    }
+
+    #[test]
+    fn redirect_chains() {
+        let hop = |url: &str, location: &str, cross_host: bool| RedirectHop {
+            url: url.to_string(),
+            status_code: 302,
+            location: location.to_string(),
+            cross_host,
+        };
+        let info = HttpInfo {
+            redirects: vec![
+                hop("https://www.example.com/", "http://www.example.com/login", false),
+                hop("http://www.example.com/login", "https://sso.example.net/auth", true),
+                hop("https://sso.example.net/auth", "https://www.example.com/", true),
+            ],
+            redirect_loop: true,
+            ..html(&[])
+        };
+        let findings = grade(&info, "www.example.com", true);
+        assert_eq!(titles(&findings), [
+            "Low: Redirect loop",
+            "Medium: Redirect from HTTPS to cleartext HTTP",
+            "Info: Redirects to another host",
+            "Info: Redirects to another host",
+        ]);
+        assert_eq!(
+            findings[0].evidence.as_deref(),
+            Some("https://www.example.com/ -> http://www.example.com/login -> https://sso.example.net/auth")
+        );
+        assert_eq!(findings[1].evidence.as_deref(), Some("302 https://www.example.com/ -> http://www.example.com/login"));
+    }
+
+    #[test]
+    fn long_evidence_is_truncated() {
+        let value = format!("max-age=60; {}", "x".repeat(200));
+        let findings = grade(&html(&[("strict-transport-security", Some(&value))]), "www.example.com", true);
+        let evidence = findings[0].evidence.as_deref().unwrap();
+        assert_eq!(evidence.chars().count(), 123);
+        assert!(evidence.ends_with("..."));
+    }
+}
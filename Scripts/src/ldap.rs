@@ -0,0 +1,609 @@
+//! LDAP rootDSE and anonymous access probe
+//!
+//! Reads the rootDSE (the empty-DN entry every LDAPv3 server publishes without
+//! authentication) for naming contexts, SASL mechanisms, supported versions and,
+//! on Active Directory, the domain and forest functionality levels. Then it
+//! performs an explicit anonymous simple bind and a one-level search under the
+//! default naming context. Any entries coming back mean the directory can be
+//! enumerated without credentials.
+//!
+//! LDAPS ports (636, 3269) are wrapped in TLS without certificate validation.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
+
+use crate::ber::{
+    decode_integer, read_element, read_tlv, tlv, TAG_BOOLEAN, TAG_ENUMERATED, TAG_INTEGER, TAG_OCTET_STRING,
+    TAG_SEQUENCE, TAG_SET,
+};
+use crate::models::LdapInfo;
+use crate::scope::Egress;
+use crate::utils;
+
+const OP_BIND_REQUEST: u8 = 0x60;
+const OP_BIND_RESPONSE: u8 = 0x61;
+const OP_UNBIND_REQUEST: u8 = 0x42;
+const OP_SEARCH_REQUEST: u8 = 0x63;
+const OP_SEARCH_ENTRY: u8 = 0x64;
+const OP_SEARCH_DONE: u8 = 0x65;
+const OP_SEARCH_REFERENCE: u8 = 0x73;
+/// Context tag for the "present" filter, e.g. `(objectClass=*)`
+const FILTER_PRESENT: u8 = 0x87;
+/// Context tag for simple authentication in a BindRequest
+const AUTH_SIMPLE: u8 = 0x80;
+
+const SCOPE_BASE: u8 = 0;
+const SCOPE_ONE_LEVEL: u8 = 1;
+
+/// rootDSE attributes we ask for
+const ROOT_DSE_ATTRIBUTES: &[&str] = &[
+    "namingContexts",
+    "defaultNamingContext",
+    "rootDomainNamingContext",
+    "supportedSASLMechanisms",
+    "supportedLDAPVersion",
+    "domainFunctionality",
+    "forestFunctionality",
+    "domainControllerFunctionality",
+    "dnsHostName",
+    "vendorName",
+    "vendorVersion",
+    "objectClass",
+];
+
+/// How many entries the anonymous search may return (and we keep as samples)
+const SEARCH_SIZE_LIMIT: i64 = 10;
+
+/// Largest number of messages read while waiting for a search to finish
+const MAX_SEARCH_MESSAGES: usize = 64;
+
+/// What the LDAP probe learned
+#[derive(Debug, Clone)]
+pub struct LdapProbeResult {
+    /// rootDSE and anonymous-access details
+    pub info: LdapInfo,
+    /// Product identified from the rootDSE, if any
+    pub identity: Option<crate::models::ServiceIdentity>,
+}
+
+/// Runs the LDAP probe
+pub struct LdapProbe {
+    timeout: Duration,
+    egress: Egress,
+    logger: Option<Arc<utils::EnhancedLogger>>,
+}
+
+impl LdapProbe {
+    /// Create a prober with the given per-operation timeout
+    pub fn new(timeout: Duration, egress: Egress, logger: Option<Arc<utils::EnhancedLogger>>) -> Self {
+        Self { timeout, egress, logger }
+    }
+
+    fn log(&self, level: &str, message: &str) {
+        if let Some(logger) = &self.logger {
+            logger.log(level, message);
+        }
+    }
+
+    /// Probe the port if it looks like LDAP
+    ///
+    /// `tls` forces LDAPS; otherwise it is used for the conventional LDAPS
+    /// ports and for services the prober reported as `ssl/ldap`.
+    pub async fn run(&self, target: &str, port: u16, service: Option<&str>, tls: bool) -> Option<LdapProbeResult> {
+        let (is_ldap, use_tls) = match service {
+            Some("ldap") => (true, tls),
+            Some("ssl/ldap") | Some("ldaps") => (true, true),
+            None => (matches!(port, 389 | 636 | 3268 | 3269), tls || matches!(port, 636 | 3269)),
+            _ => (false, false),
+        };
+        if !is_ldap {
+            return None;
+        }
+
+        let stream = match tokio::time::timeout(self.timeout, self.egress.connect_tcp(target, port)).await {
+            Ok(Ok(stream)) => stream,
+            _ => return None,
+        };
+
+        let result = if use_tls {
+            let connector = tokio_rustls::TlsConnector::from(Arc::new(utils::insecure_tls_config()));
+            let domain = rustls::ServerName::try_from(target).ok()?;
+            match tokio::time::timeout(self.timeout, connector.connect(domain, stream)).await {
+                Ok(Ok(tls_stream)) => self.probe(tls_stream, target, port).await,
+                _ => return None,
+            }
+        } else {
+            self.probe(stream, target, port).await
+        };
+
+        match result {
+            Ok(found) => {
+                if found.info.anonymous_search == Some(true) {
+                    self.log("INFO", &format!(
+                        "Anonymous LDAP search on {}:{} returned {} entries",
+                        target, port, found.info.sample_entries.len()
+                    ));
+                }
+                Some(found)
+            }
+            Err(e) => {
+                self.log("DEBUG", &format!("LDAP probe on {}:{} failed: {}", target, port, e));
+                None
+            }
+        }
+    }
+
+    async fn probe<S>(&self, mut stream: S, target: &str, port: u16) -> Result<LdapProbeResult, anyhow::Error>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let mut session = Session { stream: &mut stream, timeout: self.timeout, next_id: 1 };
+
+        // rootDSE: base search of the empty DN, allowed before any bind
+        let root = session.search("", SCOPE_BASE, 0, ROOT_DSE_ATTRIBUTES).await?;
+        let entry = root.entries.into_iter().next().ok_or_else(|| anyhow::anyhow!("no rootDSE returned"))?;
+
+        let mut info = LdapInfo::default();
+        let first = |name: &str| entry.values(name).into_iter().next();
+        info.naming_contexts = entry.values("namingContexts");
+        info.default_naming_context = first("defaultNamingContext")
+            .or_else(|| first("rootDomainNamingContext"))
+            .or_else(|| info.naming_contexts.first().cloned());
+        info.sasl_mechanisms = entry.values("supportedSASLMechanisms");
+        info.ldap_versions = entry.values("supportedLDAPVersion");
+        info.domain_functionality = first("domainFunctionality").map(|level| functionality_level(&level));
+        info.forest_functionality = first("forestFunctionality").map(|level| functionality_level(&level));
+        info.dns_host_name = first("dnsHostName");
+        let identity = root_dse_identity(&entry, &info);
+
+        // Anonymous simple bind, then a one-level search under the base DN
+        match session.anonymous_bind().await {
+            Ok(code) => info.anonymous_bind = Some(code == 0),
+            Err(e) => self.log("DEBUG", &format!("Anonymous bind to {}:{} failed: {}", target, port, e)),
+        }
+
+        if info.anonymous_bind == Some(true) {
+            if let Some(base) = info.default_naming_context.clone() {
+                match session.search(&base, SCOPE_ONE_LEVEL, SEARCH_SIZE_LIMIT, &["1.1"]).await {
+                    Ok(search) => {
+                        // sizeLimitExceeded still means entries were disclosed
+                        let allowed = matches!(search.result_code, Some(0) | Some(4)) && !search.entries.is_empty();
+                        info.anonymous_search = Some(allowed);
+                        info.sample_entries = search.entries.into_iter().map(|e| e.dn).collect();
+                        info.search_diagnostic = search.diagnostic.filter(|d| !d.is_empty());
+                    }
+                    Err(e) => self.log("DEBUG", &format!(
+                        "Anonymous search of {} on {}:{} failed: {}", base, target, port, e
+                    )),
+                }
+            }
+        }
+
+        session.unbind().await;
+        Ok(LdapProbeResult { info, identity })
+    }
+}
+
+/// One LDAP connection with a running message ID
+struct Session<'a, S> {
+    stream: &'a mut S,
+    timeout: Duration,
+    next_id: i64,
+}
+
+/// Collected SearchResultEntry messages and the final result
+struct SearchResult {
+    entries: Vec<Entry>,
+    result_code: Option<i64>,
+    diagnostic: Option<String>,
+}
+
+/// A search result entry
+struct Entry {
+    dn: String,
+    attributes: Vec<(String, Vec<String>)>,
+}
+
+impl Entry {
+    /// Values of an attribute (case-insensitive name)
+    fn values(&self, name: &str) -> Vec<String> {
+        self.attributes
+            .iter()
+            .filter(|(attribute, _)| attribute.eq_ignore_ascii_case(name))
+            .flat_map(|(_, values)| values.iter().cloned())
+            .collect()
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> Session<'_, S> {
+    async fn send(&mut self, op: u8, body: &[u8]) -> Result<i64, anyhow::Error> {
+        let id = self.next_id;
+        self.next_id += 1;
+        let message = tlv(TAG_SEQUENCE, &[tlv(TAG_INTEGER, &crate::ber::encode_integer(id)), tlv(op, body)].concat());
+        self.stream.write_all(&message).await?;
+        self.stream.flush().await?;
+        Ok(id)
+    }
+
+    /// Read the next message, returning (message ID, protocol op tag, op content)
+    async fn receive(&mut self) -> Result<(i64, u8, Vec<u8>), anyhow::Error> {
+        let element = tokio::time::timeout(self.timeout, read_element(&mut *self.stream)).await??;
+        let (tag, message, _) = read_tlv(&element).ok_or_else(|| anyhow::anyhow!("malformed LDAP message"))?;
+        if tag != TAG_SEQUENCE {
+            return Err(anyhow::anyhow!("not an LDAP message"));
+        }
+        let (_, id, rest) = read_tlv(message).ok_or_else(|| anyhow::anyhow!("missing message ID"))?;
+        let (op, body, _) = read_tlv(rest).ok_or_else(|| anyhow::anyhow!("missing protocol op"))?;
+        Ok((decode_integer(id), op, body.to_vec()))
+    }
+
+    async fn search(&mut self, base: &str, scope: u8, size_limit: i64, attributes: &[&str]) -> Result<SearchResult, anyhow::Error> {
+        let attribute_list: Vec<u8> = attributes.iter().flat_map(|a| tlv(TAG_OCTET_STRING, a.as_bytes())).collect();
+        let body = [
+            tlv(TAG_OCTET_STRING, base.as_bytes()),
+            tlv(TAG_ENUMERATED, &[scope]),
+            tlv(TAG_ENUMERATED, &[0]), // neverDerefAliases
+            tlv(TAG_INTEGER, &crate::ber::encode_integer(size_limit)),
+            tlv(TAG_INTEGER, &[0]), // no time limit
+            tlv(TAG_BOOLEAN, &[0]), // typesOnly = false
+            tlv(FILTER_PRESENT, b"objectClass"),
+            tlv(TAG_SEQUENCE, &attribute_list),
+        ]
+        .concat();
+        let id = self.send(OP_SEARCH_REQUEST, &body).await?;
+
+        let mut result = SearchResult { entries: Vec::new(), result_code: None, diagnostic: None };
+        for _ in 0..MAX_SEARCH_MESSAGES {
+            let (message_id, op, body) = self.receive().await?;
+            if message_id != id {
+                continue;
+            }
+            match op {
+                OP_SEARCH_ENTRY => {
+                    if let Some(entry) = parse_entry(&body) {
+                        result.entries.push(entry);
+                    }
+                }
+                OP_SEARCH_REFERENCE => {}
+                OP_SEARCH_DONE => {
+                    let (code, diagnostic) = parse_result(&body);
+                    result.result_code = code;
+                    result.diagnostic = diagnostic;
+                    return Ok(result);
+                }
+                other => return Err(anyhow::anyhow!("unexpected LDAP operation 0x{:02x}", other)),
+            }
+        }
+        Ok(result)
+    }
+
+    /// Simple bind with an empty DN and password; returns the result code
+    async fn anonymous_bind(&mut self) -> Result<i64, anyhow::Error> {
+        let body = [
+            tlv(TAG_INTEGER, &[3]),
+            tlv(TAG_OCTET_STRING, b""),
+            tlv(AUTH_SIMPLE, b""),
+        ]
+        .concat();
+        let id = self.send(OP_BIND_REQUEST, &body).await?;
+        loop {
+            let (message_id, op, body) = self.receive().await?;
+            if message_id == id && op == OP_BIND_RESPONSE {
+                return parse_result(&body).0.ok_or_else(|| anyhow::anyhow!("malformed bind response"));
+            }
+        }
+    }
+
+    async fn unbind(&mut self) {
+        let id = self.next_id;
+        let message = tlv(TAG_SEQUENCE, &[tlv(TAG_INTEGER, &crate::ber::encode_integer(id)), tlv(OP_UNBIND_REQUEST, &[])].concat());
+        let _ = self.stream.write_all(&message).await;
+    }
+}
+
+/// Parse an LDAPResult: resultCode, matchedDN, diagnosticMessage
+fn parse_result(body: &[u8]) -> (Option<i64>, Option<String>) {
+    let code = read_tlv(body).filter(|(tag, _, _)| *tag == TAG_ENUMERATED);
+    let diagnostic = code
+        .and_then(|(_, _, rest)| read_tlv(rest))
+        .and_then(|(_, _, rest)| read_tlv(rest))
+        .map(|(_, message, _)| String::from_utf8_lossy(message).trim_end_matches('\0').to_string());
+    (code.map(|(_, value, _)| decode_integer(value)), diagnostic)
+}
+
+/// Parse a SearchResultEntry body: objectName, then attributes
+fn parse_entry(body: &[u8]) -> Option<Entry> {
+    let (_, dn, rest) = read_tlv(body)?;
+    let (_, mut attributes, _) = read_tlv(rest)?;
+
+    let mut entry = Entry { dn: String::from_utf8_lossy(dn).into_owned(), attributes: Vec::new() };
+    while !attributes.is_empty() {
+        let (_, attribute, rest) = read_tlv(attributes)?;
+        attributes = rest;
+        let (_, name, rest) = read_tlv(attribute)?;
+        let (tag, mut set, _) = read_tlv(rest)?;
+        if tag != TAG_SET {
+            continue;
+        }
+        let mut values = Vec::new();
+        while !set.is_empty() {
+            let (_, value, rest) = read_tlv(set)?;
+            set = rest;
+            values.push(String::from_utf8_lossy(value).into_owned());
+        }
+        entry.attributes.push((String::from_utf8_lossy(name).into_owned(), values));
+    }
+    Some(entry)
+}
+
+/// Active Directory functionality level with its Windows release
+fn functionality_level(level: &str) -> String {
+    let name = match level.trim() {
+        "0" => "Windows 2000",
+        "1" => "Windows Server 2003 interim",
+        "2" => "Windows Server 2003",
+        "3" => "Windows Server 2008",
+        "4" => "Windows Server 2008 R2",
+        "5" => "Windows Server 2012",
+        "6" => "Windows Server 2012 R2",
+        "7" => "Windows Server 2016",
+        "10" => "Windows Server 2025",
+        _ => return level.to_string(),
+    };
+    format!("{} ({})", name, level.trim())
+}
+
+/// Product guess from rootDSE markers
+fn root_dse_identity(entry: &Entry, info: &LdapInfo) -> Option<crate::models::ServiceIdentity> {
+    let vendor = entry.values("vendorName").into_iter().next();
+    let version = entry.values("vendorVersion").into_iter().next();
+    let object_classes = entry.values("objectClass");
+
+    let (product, cpe, os_hint) = if info.domain_functionality.is_some() {
+        ("Microsoft Active Directory LDAP", Some("cpe:/a:microsoft:active_directory"), Some("Windows"))
+    } else if object_classes.iter().any(|c| c.eq_ignore_ascii_case("OpenLDAProotDSE")) {
+        ("OpenLDAP", Some("cpe:/a:openldap:openldap"), None)
+    } else if let Some(vendor) = vendor.as_deref() {
+        return Some(crate::models::ServiceIdentity {
+            product: vendor.to_string(),
+            version: version.clone(),
+            ..Default::default()
+        });
+    } else {
+        return None;
+    };
+
+    Some(crate::models::ServiceIdentity {
+        product: product.to_string(),
+        version,
+        extra_info: info.default_naming_context.clone(),
+        os_hint: os_hint.map(str::to_string),
+        cpe: cpe.map(str::to_string),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ber::{encode_integer, TAG_ENUMERATED};
+
+    /// What a fake directory server publishes and answers
+    #[derive(Clone)]
+    struct Directory {
+        root_dse: Vec<(&'static str, Vec<&'static str>)>,
+        bind_code: i64,
+        entries: Vec<&'static str>,
+        done: (i64, &'static str),
+    }
+
+    fn message(id: i64, op: u8, body: &[u8]) -> Vec<u8> {
+        tlv(TAG_SEQUENCE, &[tlv(TAG_INTEGER, &encode_integer(id)), tlv(op, body)].concat())
+    }
+
+    fn ldap_result(code: i64, diagnostic: &str) -> Vec<u8> {
+        [
+            tlv(TAG_ENUMERATED, &encode_integer(code)),
+            tlv(TAG_OCTET_STRING, b""),
+            tlv(TAG_OCTET_STRING, diagnostic.as_bytes()),
+        ]
+        .concat()
+    }
+
+    fn entry(dn: &str, attributes: &[(&str, Vec<&str>)]) -> Vec<u8> {
+        let attributes: Vec<u8> = attributes.iter().flat_map(|(name, values)| {
+            let set: Vec<u8> = values.iter().flat_map(|v| tlv(TAG_OCTET_STRING, v.as_bytes())).collect();
+            tlv(TAG_SEQUENCE, &[tlv(TAG_OCTET_STRING, name.as_bytes()), tlv(TAG_SET, &set)].concat())
+        }).collect();
+        [tlv(TAG_OCTET_STRING, dn.as_bytes()), tlv(TAG_SEQUENCE, &attributes)].concat()
+    }
+
+    async fn serve<S: AsyncRead + AsyncWrite + Unpin>(mut stream: S, directory: Directory) {
+        while let Ok(element) = read_element(&mut stream).await {
+            let (_, request, _) = read_tlv(&element).unwrap();
+            let (_, id, rest) = read_tlv(request).unwrap();
+            let id = decode_integer(id);
+            let (op, body, _) = read_tlv(rest).unwrap();
+
+            let mut replies = Vec::new();
+            match op {
+                OP_BIND_REQUEST => replies.push(message(id, OP_BIND_RESPONSE, &ldap_result(directory.bind_code, ""))),
+                OP_SEARCH_REQUEST => {
+                    let (_, base, rest) = read_tlv(body).unwrap();
+                    let (_, scope, rest) = read_tlv(rest).unwrap();
+                    let (_, _, rest) = read_tlv(rest).unwrap();
+                    let (_, size_limit, _) = read_tlv(rest).unwrap();
+                    if scope == [SCOPE_BASE] {
+                        assert!(base.is_empty(), "rootDSE is read from the empty DN");
+                        // Unsolicited traffic for another message ID is skipped
+                        replies.push(message(id + 100, OP_SEARCH_ENTRY, &entry("cn=stray", &[])));
+                        if !directory.root_dse.is_empty() {
+                            replies.push(message(id, OP_SEARCH_ENTRY, &entry("", &directory.root_dse)));
+                        }
+                        replies.push(message(id, OP_SEARCH_DONE, &ldap_result(0, "")));
+                    } else {
+                        assert_eq!(scope, [SCOPE_ONE_LEVEL]);
+                        assert_eq!(decode_integer(size_limit), SEARCH_SIZE_LIMIT);
+                        let base = String::from_utf8_lossy(base).into_owned();
+                        for dn in &directory.entries {
+                            replies.push(message(id, OP_SEARCH_ENTRY, &entry(&format!("{},{}", dn, base), &[])));
+                        }
+                        replies.push(message(id, OP_SEARCH_REFERENCE, &tlv(TAG_OCTET_STRING, b"ldap://other/")));
+                        replies.push(message(id, OP_SEARCH_DONE, &ldap_result(directory.done.0, directory.done.1)));
+                    }
+                }
+                OP_UNBIND_REQUEST => break,
+                other => panic!("unexpected operation 0x{:02x}", other),
+            }
+            stream.write_all(&replies.concat()).await.unwrap();
+        }
+    }
+
+    async fn ldap_server(directory: Directory, tls: bool) -> u16 {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let acceptor = crate::remote_desktop::tests::tls_acceptor();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let (acceptor, directory) = (acceptor.clone(), directory.clone());
+                tokio::spawn(async move {
+                    if tls {
+                        serve(acceptor.accept(stream).await.unwrap(), directory).await;
+                    } else {
+                        serve(stream, directory).await;
+                    }
+                });
+            }
+        });
+        port
+    }
+
+    fn probe() -> LdapProbe {
+        LdapProbe::new(Duration::from_secs(2), Egress::default(), None)
+    }
+
+    #[tokio::test]
+    async fn active_directory_allows_anonymous_search() {
+        let port = ldap_server(Directory {
+            root_dse: vec![
+                ("namingContexts", vec!["DC=corp,DC=example,DC=com", "CN=Configuration,DC=corp,DC=example,DC=com"]),
+                ("defaultNamingContext", vec!["DC=corp,DC=example,DC=com"]),
+                ("supportedSASLMechanisms", vec!["GSSAPI", "GSS-SPNEGO", "EXTERNAL"]),
+                ("supportedLDAPVersion", vec!["3", "2"]),
+                ("domainFunctionality", vec!["7"]),
+                ("ForestFunctionality", vec!["10"]),
+                ("dnsHostName", vec!["dc1.corp.example.com"]),
+            ],
+            bind_code: 0,
+            entries: vec!["CN=Users", "OU=Domain Controllers"],
+            done: (4, ""),
+        }, false).await;
+
+        let result = probe().run("127.0.0.1", port, Some("ldap"), false).await.unwrap();
+        let info = &result.info;
+        assert_eq!(info.naming_contexts.len(), 2);
+        assert_eq!(info.default_naming_context.as_deref(), Some("DC=corp,DC=example,DC=com"));
+        assert_eq!(info.sasl_mechanisms, ["GSSAPI", "GSS-SPNEGO", "EXTERNAL"]);
+        assert_eq!(info.ldap_versions, ["3", "2"]);
+        assert_eq!(info.domain_functionality.as_deref(), Some("Windows Server 2016 (7)"));
+        assert_eq!(info.forest_functionality.as_deref(), Some("Windows Server 2025 (10)"), "attribute names are case-insensitive");
+        assert_eq!(info.dns_host_name.as_deref(), Some("dc1.corp.example.com"));
+        assert_eq!(info.anonymous_bind, Some(true));
+        assert_eq!(info.anonymous_search, Some(true), "sizeLimitExceeded still discloses entries");
+        assert_eq!(info.sample_entries, [
+            "CN=Users,DC=corp,DC=example,DC=com",
+            "OU=Domain Controllers,DC=corp,DC=example,DC=com",
+        ]);
+        assert_eq!(info.search_diagnostic, None);
+
+        let identity = result.identity.unwrap();
+        assert_eq!(identity.product, "Microsoft Active Directory LDAP");
+        assert_eq!(identity.os_hint.as_deref(), Some("Windows"));
+        assert_eq!(identity.cpe.as_deref(), Some("cpe:/a:microsoft:active_directory"));
+        assert_eq!(identity.extra_info.as_deref(), Some("DC=corp,DC=example,DC=com"));
+    }
+
+    #[tokio::test]
+    async fn openldap_over_tls_refuses_anonymous_search() {
+        let port = ldap_server(Directory {
+            root_dse: vec![
+                ("objectClass", vec!["top", "OpenLDAProotDSE"]),
+                ("namingContexts", vec!["dc=example,dc=org"]),
+            ],
+            bind_code: 0,
+            entries: Vec::new(),
+            done: (50, "no read access\0"),
+        }, true).await;
+
+        let result = probe().run("127.0.0.1", port, Some("ssl/ldap"), false).await.unwrap();
+        let info = &result.info;
+        assert_eq!(info.default_naming_context.as_deref(), Some("dc=example,dc=org"), "falls back to namingContexts");
+        assert_eq!(info.anonymous_bind, Some(true));
+        assert_eq!(info.anonymous_search, Some(false));
+        assert!(info.sample_entries.is_empty());
+        assert_eq!(info.search_diagnostic.as_deref(), Some("no read access"));
+
+        let identity = result.identity.unwrap();
+        assert_eq!(identity.product, "OpenLDAP");
+        assert_eq!(identity.cpe.as_deref(), Some("cpe:/a:openldap:openldap"));
+        assert_eq!(identity.os_hint, None);
+    }
+
+    #[tokio::test]
+    async fn refused_binds_skip_the_search() {
+        let port = ldap_server(Directory {
+            root_dse: vec![
+                ("namingContexts", vec!["o=example"]),
+                ("vendorName", vec!["389 Project"]),
+                ("vendorVersion", vec!["389-Directory/2.4.5"]),
+            ],
+            bind_code: 48,
+            entries: vec!["ou=people"],
+            done: (0, ""),
+        }, false).await;
+
+        let result = probe().run("127.0.0.1", port, None, false).await;
+        assert!(result.is_none(), "port is not an LDAP port and no service was identified");
+
+        let result = probe().run("127.0.0.1", port, Some("ldap"), false).await.unwrap();
+        assert_eq!(result.info.anonymous_bind, Some(false));
+        assert_eq!(result.info.anonymous_search, None);
+        assert!(result.info.sample_entries.is_empty());
+
+        let identity = result.identity.unwrap();
+        assert_eq!(identity.product, "389 Project");
+        assert_eq!(identity.version.as_deref(), Some("389-Directory/2.4.5"));
+    }
+
+    #[tokio::test]
+    async fn servers_without_a_root_dse_are_ignored() {
+        let port = ldap_server(Directory { root_dse: Vec::new(), bind_code: 0, entries: Vec::new(), done: (0, "") }, false).await;
+        assert!(probe().run("127.0.0.1", port, Some("ldap"), false).await.is_none());
+        assert!(probe().run("127.0.0.1", port, Some("http"), false).await.is_none());
+    }
+
+    #[test]
+    fn results_and_entries_are_parsed() {
+        assert_eq!(parse_result(&ldap_result(49, "80090308: LdapErr: DSID-0C09044E")), (
+            Some(49),
+            Some("80090308: LdapErr: DSID-0C09044E".to_string()),
+        ));
+        assert_eq!(parse_result(&tlv(TAG_ENUMERATED, &[0])), (Some(0), None));
+        assert_eq!(parse_result(&tlv(TAG_INTEGER, &[0])), (None, None), "resultCode is an ENUMERATED");
+        assert_eq!(parse_result(&[]), (None, None));
+
+        let parsed = parse_entry(&entry("cn=admin", &[("mail", vec!["a@example.org", "b@example.org"]), ("cn", vec![])])).unwrap();
+        assert_eq!(parsed.dn, "cn=admin");
+        assert_eq!(parsed.values("MAIL"), ["a@example.org", "b@example.org"]);
+        assert!(parsed.values("cn").is_empty());
+        assert!(parse_entry(&tlv(TAG_OCTET_STRING, b"cn=truncated")).is_none());
+    }
+
+    #[test]
+    fn functionality_levels_name_windows_releases() {
+        assert_eq!(functionality_level("0"), "Windows 2000 (0)");
+        assert_eq!(functionality_level(" 4 "), "Windows Server 2008 R2 (4)");
+        assert_eq!(functionality_level("8"), "8");
+    }
+}
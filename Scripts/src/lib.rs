@@ -0,0 +1,101 @@
+//! Quantum Scanner - A stealth-oriented port and service scanner
+//!
+//! This library exposes the scan engine used by the `quantum_scanner` binary so
+//! that other tools can embed it. Configure a scan with `ScannerBuilder` (or fill
+//! a `ScanConfig` directly), run it, and consume the returned `ScanResults`.
+//!
+//! ```no_run
+//! # async fn example() -> Result<(), quantum_scanner::ScanError> {
+//! use quantum_scanner::{ScanConfig, ScanType};
+//!
+//! let config = ScanConfig {
+//!     target: "10.0.0.5".to_string(),
+//!     ports: vec![21, 22, 80, 443],
+//!     scan_types: vec![ScanType::Syn],
+//!     default_checks: true,
+//!     ..ScanConfig::default()
+//! };
+//!
+//! let results = quantum_scanner::scan(config).await?;
+//! for (port, result) in &results.results {
+//!     println!("{}: {:?}", port, result.service);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod ber;
+pub mod audit;
+pub mod certificates;
+pub mod cluster;
+pub mod config;
+pub mod content_analysis;
+pub mod control;
+pub mod daemon;
+pub mod dashboard;
+pub mod db_probes;
+pub mod default_checks;
+pub mod dns;
+pub mod error;
+pub mod events;
+pub mod filtering;
+pub mod http_client;
+pub mod http_discovery;
+pub mod http_methods;
+pub mod http_security;
+pub mod ldap;
+pub mod models;
+pub mod monitor;
+pub mod ntlm;
+pub mod packet;
+pub mod profiles;
+pub mod remote_desktop;
+pub mod scanner;
+pub mod scope;
+pub mod service_identity;
+pub mod service_probe;
+pub mod simnet;
+pub mod snmp;
+pub mod utils;
+
+// Re-export the main API types for convenience
+pub use crate::config::{FragmentConfig, ScanConfig, ScannerBuilder};
+pub use crate::control::{ScanControl, ScanCounters};
+pub use crate::dns::DnsConfig;
+pub use crate::error::ScanError;
+pub use crate::events::{ScanEvent, ScanEventKind, ScanEventStream};
+pub use crate::http_discovery::HttpDiscoveryConfig;
+pub use crate::models::{
+    ContentFinding, DatabaseInfo, DnsInfo, FilteringClass, FilteringVerdict, Finding, HttpInfo, LdapInfo, NtlmInfo, PortResult, PortStatus,
+    ProbeObservation, RemoteDesktopInfo, ScanResults, ScanType, ServiceIdentity, Severity, SnmpInfo, SslInfo,
+};
+pub use crate::packet::PacketTransport;
+pub use crate::profiles::ProfileSet;
+pub use crate::scanner::QuantumScanner;
+pub use crate::scope::{ScopeConfig, ScopeViolation};
+pub use crate::snmp::SnmpConfig;
+
+/// Library version
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Run a scan with the given configuration and return the results
+///
+/// This is a convenience function for simple use cases. Use `ScannerBuilder`
+/// to attach loggers or reuse a scanner instance.
+pub async fn scan(config: ScanConfig) -> Result<ScanResults, ScanError> {
+    let mut scanner = ScannerBuilder::from_config(config).build().await?;
+    scanner.run().await
+}
+
+/// Start a scan in the background and return its live event stream
+///
+/// The returned handle resolves to the final `ScanResults` once the stream has
+/// delivered `ScanFinished`.
+pub async fn scan_with_events(
+    config: ScanConfig,
+) -> Result<(ScanEventStream, tokio::task::JoinHandle<Result<ScanResults, ScanError>>), ScanError> {
+    let mut scanner = ScannerBuilder::from_config(config).build().await?;
+    let events = scanner.subscribe();
+    let handle = tokio::spawn(async move { scanner.run().await });
+    Ok((events, handle))
+}
@@ -1,6 +1,189 @@
+use std::collections::HashSet;
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use clap::parser::ValueSource;
+use clap::{ArgMatches, CommandFactory, FromArgMatches, Parser};
+use quantum_scanner::certificates;
+use quantum_scanner::audit::AuditLog;
+use quantum_scanner::dashboard;
+use quantum_scanner::dns::{self, DnsConfig};
+use quantum_scanner::events;
+use quantum_scanner::filtering;
+use quantum_scanner::http_discovery::{self, HttpDiscoveryConfig};
+use quantum_scanner::profiles::{self, ProfileSet};
+use quantum_scanner::scope::{self, ScopeConfig};
+use quantum_scanner::snmp::{self, SnmpConfig};
+use quantum_scanner::utils;
+use quantum_scanner::{FragmentConfig, PortStatus, ScanConfig, ScanType, ScannerBuilder, Severity};
+
+/// The 100 most commonly open TCP ports
+const TOP_100_PORTS: [u16; 100] = [
+    7, 9, 13, 21, 22, 23, 25, 26, 37, 53, 79, 80, 81, 88, 106, 110, 111, 113, 119, 135,
+    139, 143, 144, 179, 199, 389, 427, 443, 444, 445, 465, 513, 514, 515, 543, 544, 548, 554, 587, 631,
+    646, 873, 990, 993, 995, 1025, 1026, 1027, 1028, 1029, 1110, 1433, 1720, 1723, 1755, 1900, 2000, 2001, 2049, 2121,
+    2717, 3000, 3128, 3306, 3389, 3986, 4899, 5000, 5009, 5051, 5060, 5101, 5190, 5357, 5432, 5631, 5666, 5800, 5900, 6000,
+    6001, 6646, 7070, 8000, 8008, 8009, 8080, 8081, 8443, 8888, 9100, 9999, 10000, 32768, 49152, 49153, 49154, 49155, 49156, 49157,
+];
+
+/// Set when stdout carries the NDJSON event stream, so nothing else may go there
+static HUMAN_OUTPUT_TO_STDERR: AtomicBool = AtomicBool::new(false);
+
+/// `println!` for human-readable output, which moves to stderr while stdout
+/// carries NDJSON
+macro_rules! say {
+    ($($arg:tt)*) => {
+        if HUMAN_OUTPUT_TO_STDERR.load(Ordering::Relaxed) {
+            eprintln!($($arg)*);
+        } else {
+            println!($($arg)*);
+        }
+    };
+}
+
+/// ANSI colour codes for terminal output (empty when not writing to a terminal)
+struct Colors {
+    green: &'static str,
+    yellow: &'static str,
+    blue: &'static str,
+    reset: &'static str,
+}
+
+impl Colors {
+    fn new(enabled: bool) -> Self {
+        if enabled {
+            Self { green: "\x1b[32m", yellow: "\x1b[33m", blue: "\x1b[34m", reset: "\x1b[0m" }
+        } else {
+            Self { green: "", yellow: "", blue: "", reset: "" }
+        }
+    }
+}
+
+
 #[derive(Parser)]
 #[clap(author, version, about, long_about = None)]
 struct Args {
+    /// Target hostname or IP address
+    target: String,
+    
+    /// Ports to scan, e.g. "22,80,8000-8100"
+    #[clap(short, long)]
+    ports: Option<String>,
+    
+    /// Scan the 100 most common TCP ports (the default when no ports are given)
+    #[clap(long)]
+    top_100: bool,
+    
+    /// Scan techniques: syn, ack, fin, xmas, null, window, udp, ssl, tls-echo, mimic, frag (comma-separated)
+    #[clap(short = 's', long, value_delimiter = ',', default_value = "syn")]
+    scan_types: Vec<String>,
+    
+    /// Maximum number of concurrent probes
+    #[clap(short, long, default_value_t = 100)]
+    concurrency: usize,
+    
+    /// Maximum probes per second (0 = unlimited)
+    #[clap(short, long, default_value_t = 0)]
+    rate: usize,
+    
+    /// Randomise probe order
+    #[clap(short, long)]
+    evasion: bool,
+    
+    /// Mimic another OS's TTLs on raw probes, with jitter
+    #[clap(long)]
+    enhanced_evasion: bool,
+    
+    /// OS to mimic with --enhanced-evasion: windows, linux, macos, cisco or random
+    #[clap(long)]
+    mimic_os: Option<String>,
+    
+    /// Hops the TTL may be lowered by with --enhanced-evasion
+    #[clap(long, default_value_t = 2)]
+    ttl_jitter: u8,
+    
+    /// Protocol whose payload mimic probes carry with --enhanced-evasion
+    #[clap(long)]
+    protocol_variant: Option<String>,
+    
+    /// Verbose output
+    #[clap(short, long)]
+    verbose: bool,
+    
+    /// Prefer IPv6 addresses when resolving the target
+    #[clap(long)]
+    ipv6: bool,
+    
+    /// Write results as JSON (with --output)
+    #[clap(short, long)]
+    json: bool,
+    
+    /// Raw probe timeout in seconds
+    #[clap(long, default_value_t = 5.0)]
+    timeout: f64,
+    
+    /// Connect timeout in seconds
+    #[clap(long, default_value_t = 3.0)]
+    timeout_connect: f64,
+    
+    /// Banner and follow-up timeout in seconds
+    #[clap(long, default_value_t = 3.0)]
+    timeout_banner: f64,
+    
+    /// Protocol mimic probes imitate (HTTP, SSH, FTP, SMTP, IMAP, POP3, MySQL, RDP)
+    #[clap(long, default_value = "HTTP")]
+    mimic_protocol: String,
+    
+    /// Minimum fragment size for fragmented probes
+    #[clap(long, default_value_t = 64)]
+    frag_min_size: u16,
+    
+    /// Maximum fragment size for fragmented probes
+    #[clap(long, default_value_t = 128)]
+    frag_max_size: u16,
+    
+    /// Minimum delay between fragments in seconds
+    #[clap(long, default_value_t = 0.01)]
+    frag_min_delay: f64,
+    
+    /// Maximum delay between fragments in seconds
+    #[clap(long, default_value_t = 0.1)]
+    frag_max_delay: f64,
+    
+    /// Seconds to wait for a reply to fragmented probes
+    #[clap(long, default_value_t = 10)]
+    frag_timeout: u64,
+    
+    /// Minimum size of the first fragment
+    #[clap(long, default_value_t = 64)]
+    frag_first_min_size: u16,
+    
+    /// Split each probe into exactly two fragments
+    #[clap(long)]
+    frag_two_frags: bool,
+    
+    /// Log file, written when the scan finishes
+    #[clap(long, default_value = "scanner.log")]
+    log_file: PathBuf,
+    
+    /// Keep logs in memory only; never write the log file
+    #[clap(long)]
+    memory_only: bool,
+    
+    /// Encrypt log lines in memory (implies never writing them to disk)
+    #[clap(long)]
+    encrypt_logs: bool,
+    
+    /// Reserved for password-protected log files
+    #[clap(long = "log-password", hide = true)]
+    _log_password: Option<String>,
+    
+    /// Write results to this file
+    #[clap(short, long)]
+    output: Option<PathBuf>,
+    
     /// Enable packet capture for detailed network analysis
     #[clap(long, default_value_t = true)]
     packet_capture: bool,
@@ -32,19 +215,452 @@ struct Args {
     /// Analyze HTTP headers
     #[clap(long, default_value_t = true)]
     analyze_http: bool,
+    
+    /// Run content and virtual-host discovery on detected web servers
+    #[clap(long)]
+    http_discovery: bool,
+    
+    /// Wordlist for HTTP path discovery (built-in list if omitted)
+    #[clap(long)]
+    http_wordlist: Option<PathBuf>,
+    
+    /// Extensions to append to each discovery word (comma-separated)
+    #[clap(long, value_delimiter = ',')]
+    http_extensions: Vec<String>,
+    
+    /// Only report these status codes during discovery (comma-separated)
+    #[clap(long, value_delimiter = ',')]
+    http_match_status: Vec<u16>,
+    
+    /// Hide these status codes during discovery (comma-separated)
+    #[clap(long, value_delimiter = ',', default_value = "404")]
+    http_filter_status: Vec<u16>,
+    
+    /// Wordlist of virtual host names to try against web servers
+    #[clap(long)]
+    vhost_wordlist: Option<PathBuf>,
+    
+    /// Domain appended to virtual host words that aren't fully qualified
+    #[clap(long)]
+    vhost_domain: Option<String>,
+    
+    /// Audit HTTP methods on web servers (OPTIONS, TRACE, PROPFIND; PUT/DELETE to a random path)
+    #[clap(long)]
+    http_methods: bool,
+    
+    /// Run read-only checks for anonymous access (FTP, Redis, MongoDB, etc.)
+    #[clap(long)]
+    default_checks: bool,
+    
+    /// Guess SNMP communities and read system/interface info on UDP ports (use with --scan-types udp)
+    #[clap(long)]
+    snmp: bool,
+    
+    /// File with SNMP community strings to try (built-in list if omitted)
+    #[clap(long)]
+    snmp_communities: Option<PathBuf>,
+    
+    /// Also walk the SNMP process table (hrSWRunName)
+    #[clap(long)]
+    snmp_processes: bool,
+    
+    /// Zones to check for DNSSEC and zone transfers on DNS servers (comma-separated)
+    #[clap(long, value_delimiter = ',')]
+    dns_domains: Vec<String>,
+    
+    /// External name used to test DNS servers for open recursion
+    #[clap(long, default_value = "www.example.com")]
+    dns_recursion_name: String,
+    
+    /// Don't attempt zone transfers (AXFR)
+    #[clap(long)]
+    no_axfr: bool,
+    
+    /// Write records from successful zone transfers to this directory as zone files
+    #[clap(long)]
+    axfr_dir: Option<PathBuf>,
+    
+    /// Maximum number of HTTP redirects to follow from a web server (0 = don't follow)
+    #[clap(long, default_value_t = 5)]
+    max_redirects: usize,
+    
+    /// Follow redirects to other hosts (always allowed when --scope is given)
+    #[clap(long)]
+    cross_host_redirects: bool,
+    
+    /// Scope file (CIDRs, host names, exclusions, time windows); anything outside it is refused
+    #[clap(long)]
+    scope: Option<PathBuf>,
+    
+    /// Append a hash-chained record of every probe sent to this file (check with quantum_audit verify-audit)
+    #[clap(long)]
+    audit_log: Option<PathBuf>,
+    
+    /// Write every presented TLS certificate to this directory as <host>_<port>_<n>.pem
+    #[clap(long)]
+    cert_dir: Option<PathBuf>,
+    
+    /// Write a CSV inventory of all TLS certificates seen in the scan
+    #[clap(long)]
+    cert_csv: Option<PathBuf>,
+    
+    /// Stream scan events as newline-delimited JSON to a file ("-" for stdout, which moves all other output to stderr)
+    #[clap(long)]
+    ndjson: Option<PathBuf>,
+    
+    /// Show an interactive terminal dashboard while scanning
+    #[clap(long)]
+    tui: bool,
+    
+    /// Named scan profile to start from; flags given on the command line override it
+    #[clap(long)]
+    profile: Option<String>,
+    
+    /// TOML file with scan profiles (default: ./quantum_scanner.toml, then ~/.config/quantum_scanner/profiles.toml)
+    #[clap(long)]
+    profile_file: Option<PathBuf>,
+    
+    /// Print the effective scan configuration as TOML and exit
+    #[clap(long)]
+    print_config: bool,
+}
+
+/// Parse a port list like "22,80,8000-8100"
+fn parse_ports(spec: &str) -> Result<Vec<u16>, anyhow::Error> {
+    let mut ports = Vec::new();
+    for part in spec.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+        match part.split_once('-') {
+            Some((start, end)) => {
+                let (start, end): (u16, u16) = (start.trim().parse()?, end.trim().parse()?);
+                if start > end {
+                    anyhow::bail!("invalid port range {}", part);
+                }
+                ports.extend(start..=end);
+            }
+            None => ports.push(part.parse()?),
+        }
+    }
+    ports.sort_unstable();
+    ports.dedup();
+    Ok(ports)
+}
+
+/// Parse a scan technique name as given on the command line
+fn parse_scan_type(name: &str) -> Result<ScanType, anyhow::Error> {
+    Ok(match name.trim().to_lowercase().replace('_', "-").as_str() {
+        "syn" => ScanType::Syn,
+        "ack" => ScanType::Ack,
+        "fin" => ScanType::Fin,
+        "xmas" => ScanType::Xmas,
+        "null" => ScanType::Null,
+        "window" => ScanType::Window,
+        "udp" => ScanType::Udp,
+        "ssl" | "tls" => ScanType::Ssl,
+        "tls-echo" | "tlsecho" => ScanType::TlsEcho,
+        "mimic" => ScanType::Mimic,
+        "frag" => ScanType::Frag,
+        other => anyhow::bail!("unknown scan type '{}'", other),
+    })
+}
+
+/// Create the scan log
+///
+/// Lines are buffered in memory while the scan runs and written to `path`
+/// afterwards, unless `memory_only` or `encrypt` keep them off the disk.
+/// The file is created now so an unwritable path fails before scanning.
+fn setup_logging(
+    path: &Path,
+    verbose: bool,
+    memory_only: bool,
+    encrypt: bool,
+    _password: Option<&str>,
+) -> Result<Option<utils::MemoryLogBuffer>, anyhow::Error> {
+    if !memory_only && !encrypt {
+        std::fs::File::create(path)?;
+    }
+    let max_entries = if verbose { 50_000 } else { 10_000 };
+    Ok(Some(utils::MemoryLogBuffer::new(max_entries, encrypt)))
+}
+
+/// Writing scan results to files
+mod output {
+    use std::fmt::Write as _;
+    use std::path::Path;
+    
+    use quantum_scanner::ScanResults;
+    
+    /// Write the results as pretty-printed JSON
+    pub fn save_json_results(results: &ScanResults, path: &Path) -> Result<(), anyhow::Error> {
+        std::fs::write(path, serde_json::to_string_pretty(results)?)?;
+        Ok(())
+    }
+    
+    /// Write a plain-text report of the results
+    pub fn save_text_results(results: &ScanResults, path: &Path) -> Result<(), anyhow::Error> {
+        let mut text = String::new();
+        writeln!(text, "Quantum Scanner results for {} ({})", results.target, results.target_ip)?;
+        if let (Some(start), Some(end)) = (results.start_time, results.end_time) {
+            writeln!(text, "Scanned {} to {}", start.to_rfc3339(), end.to_rfc3339())?;
+        }
+        writeln!(text, "Open ports: {}", results.open_ports.len())?;
+        
+        let mut ports: Vec<_> = results.results.keys().copied().collect();
+        ports.sort_unstable();
+        for port in ports {
+            let result = &results.results[&port];
+            let mut states: Vec<String> = result.tcp_states.iter()
+                .map(|(scan_type, status)| format!("{:?}={:?}", scan_type, status))
+                .collect();
+            states.sort();
+            if let Some(status) = result.udp_state {
+                states.push(format!("Udp={:?}", status));
+            }
+            writeln!(text, "\nPort {}: {}", port, states.join(" "))?;
+            if let Some(service) = &result.service {
+                writeln!(text, "  Service: {}", service)?;
+            }
+            if let Some(version) = &result.version {
+                writeln!(text, "  Version: {}", version)?;
+            }
+            if let Some(verdict) = &result.filtering {
+                writeln!(text, "  Filtering: {}", verdict.class)?;
+            }
+            for vuln in &result.vulns {
+                writeln!(text, "  VULN: {}", vuln)?;
+            }
+            for finding in &result.findings {
+                writeln!(text, "  FINDING: {}", finding)?;
+            }
+        }
+        std::fs::write(path, text)?;
+        Ok(())
+    }
+}
+
+/// HTTP discovery settings from the `--http-*` and `--vhost-*` flags
+fn http_discovery_from_args(args: &Args) -> Result<HttpDiscoveryConfig, anyhow::Error> {
+    let mut discovery = HttpDiscoveryConfig::default();
+    if let Some(path) = &args.http_wordlist {
+        discovery.wordlist = http_discovery::load_wordlist(path)?;
+    }
+    if let Some(path) = &args.vhost_wordlist {
+        discovery.vhost_wordlist = http_discovery::load_wordlist(path)?;
+    }
+    discovery.extensions = args.http_extensions.clone();
+    discovery.match_status = args.http_match_status.clone();
+    discovery.filter_status = args.http_filter_status.clone();
+    discovery.vhost_domain = args.vhost_domain.clone();
+    Ok(discovery)
+}
+
+/// SNMP settings from the `--snmp-*` flags
+fn snmp_from_args(args: &Args) -> Result<SnmpConfig, anyhow::Error> {
+    let mut snmp = SnmpConfig::default();
+    if let Some(path) = &args.snmp_communities {
+        snmp.communities = snmp::load_communities(path)?;
+    }
+    snmp.walk_processes = args.snmp_processes;
+    Ok(snmp)
+}
+
+/// Translate command-line arguments into a library `ScanConfig`
+fn scan_config_from_args(args: &Args, ports: Vec<u16>, scan_types: Vec<ScanType>) -> Result<ScanConfig, anyhow::Error> {
+    let http_discovery = args.http_discovery.then(|| http_discovery_from_args(args)).transpose()?;
+    let snmp = args.snmp.then(|| snmp_from_args(args)).transpose()?;
+    
+    let dns = DnsConfig {
+        domains: args.dns_domains.clone(),
+        recursion_name: args.dns_recursion_name.clone(),
+        axfr: !args.no_axfr,
+        ..DnsConfig::default()
+    };
+    
+    Ok(ScanConfig {
+        target: args.target.clone(),
+        ports,
+        scan_types,
+        concurrency: args.concurrency,
+        rate: args.rate,
+        evasion: args.evasion,
+        enhanced_evasion: args.enhanced_evasion,
+        mimic_os: args.mimic_os.clone().unwrap_or_else(|| "random".to_string()),
+        ttl_jitter: args.ttl_jitter,
+        protocol_variant: args.protocol_variant.clone(),
+        verbose: args.verbose,
+        ipv6: args.ipv6,
+        json_output: args.json,
+        timeout: args.timeout,
+        timeout_connect: args.timeout_connect,
+        timeout_banner: args.timeout_banner,
+        mimic_protocol: args.mimic_protocol.clone(),
+        fragmentation: FragmentConfig {
+            min_size: args.frag_min_size,
+            max_size: args.frag_max_size,
+            min_delay: args.frag_min_delay,
+            max_delay: args.frag_max_delay,
+            timeout: args.frag_timeout,
+            first_min_size: args.frag_first_min_size,
+            two_frags: args.frag_two_frags,
+        },
+        log_file: args.log_file.clone(),
+        http_discovery,
+        default_checks: args.default_checks,
+        snmp,
+        dns,
+        max_redirects: args.max_redirects,
+        cross_host_redirects: args.cross_host_redirects,
+        http_methods: args.http_methods,
+        scope: args.scope.as_deref().map(ScopeConfig::load).transpose()?,
+    })
+}
+
+/// Combine defaults, the selected profile and command-line flags
+///
+/// Only flags the user actually typed (according to `matches`, the parse
+/// `args` came from) override the profile, so clap defaults never clobber
+/// profile settings. The target always comes from the command line.
+fn resolve_scan_config(args: &Args, matches: &ArgMatches) -> Result<ScanConfig, anyhow::Error> {
+    let ports = match &args.ports {
+        Some(spec) if !args.top_100 => parse_ports(spec)?,
+        _ => TOP_100_PORTS.to_vec(),
+    };
+    let scan_types = args.scan_types.iter()
+        .map(|name| parse_scan_type(name))
+        .collect::<Result<Vec<_>, _>>()?;
+    let cli = scan_config_from_args(args, ports, scan_types)?;
+    let name = match &args.profile {
+        Some(name) => name,
+        None => return Ok(cli),
+    };
+    
+    let path = args.profile_file.clone().unwrap_or_else(profiles::default_profile_path);
+    let mut config = ProfileSet::load(&path)?.resolve(name)?;
+    config.target = cli.target.clone();
+    
+    let given: HashSet<&str> = matches.ids()
+        .map(|id| id.as_str())
+        .filter(|id| matches.value_source(id) == Some(ValueSource::CommandLine))
+        .collect();
+    
+    macro_rules! from_cli {
+        ($target:ident <- $source:ident { $($id:literal => $($field:ident).+;)* }) => {
+            $(if given.contains($id) {
+                $target.$($field).+ = $source.$($field).+.clone();
+            })*
+        };
+    }
+    
+    // Any explicit port selection (-p, --top-100, ...) replaces the profile's ports
+    if given.iter().any(|id| *id == "ports" || id.starts_with("top_")) {
+        config.ports = cli.ports.clone();
+    }
+    from_cli! { config <- cli {
+        "scan_types" => scan_types;
+        "concurrency" => concurrency;
+        "rate" => rate;
+        "evasion" => evasion;
+        "enhanced_evasion" => enhanced_evasion;
+        "mimic_os" => mimic_os;
+        "ttl_jitter" => ttl_jitter;
+        "protocol_variant" => protocol_variant;
+        "verbose" => verbose;
+        "ipv6" => ipv6;
+        "json" => json_output;
+        "timeout" => timeout;
+        "timeout_connect" => timeout_connect;
+        "timeout_banner" => timeout_banner;
+        "mimic_protocol" => mimic_protocol;
+        "frag_min_size" => fragmentation.min_size;
+        "frag_max_size" => fragmentation.max_size;
+        "frag_min_delay" => fragmentation.min_delay;
+        "frag_max_delay" => fragmentation.max_delay;
+        "frag_timeout" => fragmentation.timeout;
+        "frag_first_min_size" => fragmentation.first_min_size;
+        "frag_two_frags" => fragmentation.two_frags;
+        "log_file" => log_file;
+        "default_checks" => default_checks;
+        "dns_domains" => dns.domains;
+        "dns_recursion_name" => dns.recursion_name;
+        "no_axfr" => dns.axfr;
+        "max_redirects" => max_redirects;
+        "cross_host_redirects" => cross_host_redirects;
+        "http_methods" => http_methods;
+        "scope" => scope;
+    }}
+    
+    // --http-discovery and --snmp switch a section on; a section the profile
+    // already enabled keeps its settings apart from the flags typed for it
+    if given.contains("http_discovery") && config.http_discovery.is_none() {
+        config.http_discovery = cli.http_discovery.clone();
+    }
+    if let Some(discovery) = config.http_discovery.as_mut() {
+        let flags = match &cli.http_discovery {
+            Some(flags) => flags.clone(),
+            None => http_discovery_from_args(args)?,
+        };
+        from_cli! { discovery <- flags {
+            "http_wordlist" => wordlist;
+            "http_extensions" => extensions;
+            "http_match_status" => match_status;
+            "http_filter_status" => filter_status;
+            "vhost_wordlist" => vhost_wordlist;
+            "vhost_domain" => vhost_domain;
+        }}
+    }
+    if given.contains("snmp") && config.snmp.is_none() {
+        config.snmp = cli.snmp.clone();
+    }
+    if let Some(snmp) = config.snmp.as_mut() {
+        let flags = match &cli.snmp {
+            Some(flags) => flags.clone(),
+            None => snmp_from_args(args)?,
+        };
+        from_cli! { snmp <- flags {
+            "snmp_communities" => communities;
+            "snmp_processes" => walk_processes;
+        }}
+    }
+    
+    Ok(config)
 }
 
 #[tokio::main]
 async fn main() -> Result<(), anyhow::Error> {
+    let matches = Args::command().get_matches();
+    let args = Args::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    let ndjson_to_stdout = args.ndjson.as_deref() == Some(Path::new("-"));
+    if ndjson_to_stdout {
+        if args.tui {
+            anyhow::bail!("--tui draws on stdout and can't be combined with --ndjson -");
+        }
+        HUMAN_OUTPUT_TO_STDERR.store(true, Ordering::Relaxed);
+    }
+    let colors = if ndjson_to_stdout {
+        Colors::new(std::io::stderr().is_terminal())
+    } else {
+        Colors::new(std::io::stdout().is_terminal())
+    };
+    
+    // Resolve the effective configuration: defaults, then profile, then explicit flags
+    let config = resolve_scan_config(&args, &matches)?;
+    if args.print_config {
+        print!("{}", profiles::to_toml(&config)?);
+        return Ok(());
+    }
+    let ports_to_scan = config.ports.clone();
+    let verbose = config.verbose;
+    let json_output = config.json_output;
+    let log_file = config.log_file.clone();
+    
     // Setup memory logger with memory-only option
     let memory_logger = match setup_logging(
-        &args.log_file, 
-        args.verbose, 
+        &config.log_file, 
+        verbose, 
         args.memory_only,
         args.encrypt_logs,
         args._log_password.as_deref()
     ) {
-        Ok(logger) => logger,
+        Ok(logger) => logger.map(Arc::new),
         Err(e) => {
             eprintln!("Warning: Failed to set up logging: {}", e);
             None
@@ -53,7 +669,7 @@ async fn main() -> Result<(), anyhow::Error> {
     
     // Setup enhanced logger for packet capture if enabled
     let enhanced_logger = if args.packet_capture {
-        println!("[{}+{}] Packet capture enabled - collecting detailed network data", 
+        say!("[{}+{}] Packet capture enabled - collecting detailed network data", 
             colors.green, colors.reset);
         Some(Arc::new(utils::EnhancedLogger::new(
             args.max_packet_logs,
@@ -64,118 +680,419 @@ async fn main() -> Result<(), anyhow::Error> {
         None
     };
     
-    // Create scanner instance with enhanced evasion options
-    let mut scanner = QuantumScanner::new(
-        &args.target,
-        ports_to_scan.clone(),
-        scan_types,
-        args.concurrency,
-        args.rate,
-        // Use enhanced evasion if specified
-        args.evasion || args.enhanced_evasion,
-        args.verbose,
-        args.ipv6,
-        args.json,
-        args.timeout,
-        args.timeout_connect,
-        args.timeout_banner,
-        &args.mimic_protocol,
-        args.frag_min_size,
-        args.frag_max_size,
-        args.frag_min_delay,
-        args.frag_max_delay,
-        args.frag_timeout,
-        args.frag_first_min_size,
-        args.frag_two_frags,
-        &args.log_file,
-    ).await?;
-    
-    // Set enhanced evasion options
-    if args.enhanced_evasion {
-        scanner.set_enhanced_evasion(true, args.mimic_os.as_deref().unwrap_or("random"), args.ttl_jitter);
-        scanner.set_protocol_variant(args.protocol_variant.as_deref());
-    }
-    
-    // Set memory logger if available
-    if let Some(logger) = memory_logger.clone() {
-        scanner.set_memory_log(Arc::new(logger));
+    if config.default_checks {
+        say!("[{}+{}] Default-configuration checks enabled (read-only)", 
+            colors.green, colors.reset);
+    }
+    if let Some(snmp) = &config.snmp {
+        say!("[{}+{}] SNMP probing enabled with {} communities", 
+            colors.green, colors.reset, snmp.communities.len());
+        if !config.scan_types.contains(&ScanType::Udp) {
+            say!("[{}!{}] SNMP probing only runs on UDP results; add --scan-types udp", 
+                colors.yellow, colors.reset);
+        }
+    }
+    if let Some(discovery) = &config.http_discovery {
+        say!("[{}+{}] HTTP discovery enabled with {} words", 
+            colors.green, colors.reset, discovery.wordlist.len());
     }
     
-    // Set enhanced logger if available
+    // Start the probe audit trail before anything is sent
+    let audit_log = match &args.audit_log {
+        Some(path) => {
+            let log = Arc::new(AuditLog::open(path)?);
+            say!("[{}+{}] Recording every probe in audit log {}", 
+                colors.green, colors.reset, path.display());
+            Some(log)
+        },
+        None => None,
+    };
+    
+    // Create scanner instance, attaching loggers if available
+    let mut builder = ScannerBuilder::from_config(config);
+    if let Some(logger) = memory_logger.clone() {
+        builder = builder.memory_log(logger);
+    }
     if let Some(logger) = enhanced_logger.clone() {
-        scanner.set_enhanced_logger(logger.clone());
+        builder = builder.enhanced_logger(logger);
+    }
+    if let Some(log) = audit_log.clone() {
+        builder = builder.audit_log(log);
     }
+    let mut scanner = builder.build().await?;
+    let active_scope = scanner.scope();
+    
+    // Stream events as NDJSON while the scan runs
+    let ndjson_writer = match &args.ndjson {
+        Some(path) => {
+            let events = scanner.subscribe();
+            let writer: Box<dyn tokio::io::AsyncWrite + Unpin + Send> = if ndjson_to_stdout {
+                Box::new(tokio::io::stdout())
+            } else {
+                Box::new(tokio::fs::File::create(path).await?)
+            };
+            Some(tokio::spawn(events::write_ndjson(events, writer)))
+        },
+        None => None,
+    };
     
     // Run the scan
-    println!("[{}+{}] Starting scan of {} with {} ports", 
+    say!("[{}+{}] Starting scan of {} with {} ports", 
         colors.green, colors.reset, args.target, ports_to_scan.len());
-    println!("{}════════════════════════════════════════════{}", colors.blue, colors.reset);
+    say!("{}════════════════════════════════════════════{}", colors.blue, colors.reset);
+    
+    let results = if args.tui {
+        // Run the scan in the background and drive the dashboard from its events
+        let events = scanner.subscribe();
+        let control = scanner.control();
+        let logs = dashboard::LogSource {
+            memory_log: memory_logger.clone(),
+            enhanced_logger: enhanced_logger.clone(),
+        };
+        let scan = tokio::spawn(async move { scanner.run().await });
+        
+        dashboard::run_dashboard(events, control, logs, ports_to_scan.len()).await?;
+        if !scan.is_finished() {
+            say!("[{}+{}] Dashboard closed - waiting for the scan to finish", 
+                colors.green, colors.reset);
+        }
+        scan.await??
+    } else {
+        scanner.run().await?
+    };
     
-    let results = scanner.run_scan().await?;
+    // Wait for the NDJSON writer to drain the remaining events
+    if let Some(writer) = ndjson_writer {
+        match writer.await {
+            Ok(Ok(count)) => eprintln!("[{}+{}] Wrote {} events as NDJSON", 
+                colors.green, colors.reset, count),
+            Ok(Err(e)) => eprintln!("[{}!{}] NDJSON output failed: {}", 
+                colors.yellow, colors.reset, e),
+            Err(e) => eprintln!("[{}!{}] NDJSON writer task failed: {}", 
+                colors.yellow, colors.reset, e),
+        }
+    }
     
     // Output results based on mode
-    println!("{}════════════════════════════════════════════{}", colors.blue, colors.reset);
-    println!("[{}+{}] Scan completed. Found {} open ports", 
+    say!("{}════════════════════════════════════════════{}", colors.blue, colors.reset);
+    say!("[{}+{}] Scan completed. Found {} open ports", 
         colors.green, colors.reset, results.open_ports.len());
     
     // Display results
-    for port in results.open_ports.iter().cloned().collect::<Vec<_>>() {
+    for &port in &results.open_ports {
         if let Some(result) = results.results.get(&port) {
             let _status = result.tcp_states.values().next().unwrap_or(&PortStatus::Filtered);
-            println!("Port {}:{} {}", port, colors.green, colors.reset);
+            say!("Port {}:{} {}", port, colors.green, colors.reset);
             
             if let Some(service) = &result.service {
-                println!("  Service: {}", service);
+                say!("  Service: {}", service);
             }
             
             if let Some(version) = &result.version {
-                println!("  Version: {}", version);
+                say!("  Version: {}", version);
+            }
+            
+            if let Some(identity) = &result.service_identity {
+                if let Some(cpe) = &identity.cpe {
+                    say!("  CPE: {}", cpe);
+                }
+                if let Some(os) = &identity.os_hint {
+                    say!("  OS hint: {}", os);
+                }
             }
             
             // Display SSL/TLS details if available and ssl_details enabled
             if args.ssl_details {
                 if let Some(ssl_info) = &result.cert_info {
-                    println!("  SSL/TLS:");
+                    say!("  SSL/TLS:");
                     if let Some(protocol) = &ssl_info.protocol_version {
-                        println!("    Protocol: {}", protocol);
+                        say!("    Protocol: {}", protocol);
                     }
                     if let Some(cipher) = &ssl_info.cipher_suite {
-                        println!("    Cipher: {}", cipher);
+                        say!("    Cipher: {}", cipher);
                     }
                     if let Some(cn) = &ssl_info.cert_cn {
-                        println!("    Subject: {}", cn);
+                        say!("    Subject: {}", cn);
                     }
                     if let Some(issuer) = &ssl_info.cert_issuer {
-                        println!("    Issuer: {}", issuer);
+                        say!("    Issuer: {}", issuer);
                     }
                     if let Some(valid_to) = &ssl_info.cert_valid_to {
-                        println!("    Valid Until: {}", valid_to);
+                        say!("    Valid Until: {}", valid_to);
+                    }
+                }
+            }
+            
+            // Display database handshake details
+            if let Some(db) = &result.database_info {
+                say!("  Database: {} {}", db.engine, db.version.as_deref().unwrap_or("(version hidden)"));
+                if let Some(encryption) = &db.encryption {
+                    say!("    Encryption: {}", encryption);
+                }
+                if !db.auth_methods.is_empty() {
+                    say!("    Auth: {}", db.auth_methods.join(", "));
+                }
+                if let Some(required) = db.authentication_required {
+                    say!("    Authentication required: {}", if required { "yes" } else { "no" });
+                }
+                if verbose {
+                    for (key, value) in &db.details {
+                        say!("    {}: {}", key, value);
+                    }
+                }
+            }
+            
+            // Display remote desktop security details
+            if let Some(rd) = &result.remote_desktop {
+                match &rd.version {
+                    Some(version) => say!("  {}: {}", rd.protocol.to_uppercase(), version),
+                    None => say!("  {}:", rd.protocol.to_uppercase()),
+                }
+                if !rd.security.is_empty() {
+                    say!("    Security: {}", rd.security.join(", "));
+                }
+                if let Some(ntlm) = &rd.ntlm_info {
+                    if let Some(name) = ntlm.dns_computer.as_ref().or(ntlm.netbios_computer.as_ref()) {
+                        say!("    Host: {}", name);
+                    }
+                    if let Some(domain) = ntlm.dns_domain.as_ref().or(ntlm.netbios_domain.as_ref()) {
+                        say!("    Domain: {}", domain);
+                    }
+                    if let Some(os_version) = &ntlm.os_version {
+                        say!("    OS build: {}", os_version);
+                    }
+                }
+            }
+            
+            // Display SNMP agent details
+            if let Some(snmp) = &result.snmp_info {
+                say!("  SNMP {}: community '{}'", snmp.version, snmp.community);
+                if snmp.communities.len() > 1 {
+                    say!("    Also accepts: {}", snmp.communities[1..].join(", "));
+                }
+                if let Some(name) = &snmp.sys_name {
+                    say!("    Name: {}", name);
+                }
+                if let Some(descr) = &snmp.sys_descr {
+                    say!("    Description: {}", descr.lines().next().unwrap_or(descr));
+                }
+                if let Some(contact) = &snmp.sys_contact {
+                    say!("    Contact: {}", contact);
+                }
+                if let Some(location) = &snmp.sys_location {
+                    say!("    Location: {}", location);
+                }
+                if !snmp.interfaces.is_empty() {
+                    say!("    Interfaces: {}", snmp.interfaces.len());
+                    if verbose {
+                        for interface in &snmp.interfaces {
+                            say!("      {} {} {} {}", interface.index, interface.name,
+                                interface.mac.as_deref().unwrap_or("-"),
+                                interface.status.as_deref().unwrap_or("-"));
+                        }
+                    }
+                }
+                if !snmp.processes.is_empty() {
+                    say!("    Processes: {}", snmp.processes.len());
+                    if verbose {
+                        say!("      {}", snmp.processes.join(", "));
+                    }
+                }
+            }
+            
+            // Display DNS server details
+            if let Some(dns) = &result.dns_info {
+                say!("  DNS:");
+                if let Some(version) = &dns.version_bind {
+                    say!("    version.bind: {}", version);
+                }
+                if let Some(hostname) = &dns.hostname_bind {
+                    say!("    hostname.bind: {}", hostname);
+                }
+                if let Some(recursion) = dns.recursion {
+                    say!("    Recursion: {}", if recursion { "open" } else { "refused" });
+                }
+                for zone in &dns.zones {
+                    let dnssec = match zone.dnssec {
+                        Some(true) => "signed",
+                        Some(false) => "unsigned",
+                        None => "unknown",
+                    };
+                    let axfr = match zone.axfr_allowed {
+                        Some(true) => format!("AXFR allowed, {} records", zone.records.len()),
+                        Some(false) => "AXFR refused".to_string(),
+                        None => "AXFR not tried".to_string(),
+                    };
+                    say!("    Zone {}: DNSSEC {}, {}", zone.domain, dnssec, axfr);
+                }
+            }
+            
+            // Display LDAP rootDSE details
+            if let Some(ldap) = &result.ldap_info {
+                say!("  LDAP:");
+                if let Some(host) = &ldap.dns_host_name {
+                    say!("    Host: {}", host);
+                }
+                if !ldap.naming_contexts.is_empty() {
+                    say!("    Naming contexts: {}", ldap.naming_contexts.join("; "));
+                }
+                if !ldap.ldap_versions.is_empty() {
+                    say!("    Versions: {}", ldap.ldap_versions.join(", "));
+                }
+                if !ldap.sasl_mechanisms.is_empty() {
+                    say!("    SASL: {}", ldap.sasl_mechanisms.join(", "));
+                }
+                if let Some(level) = &ldap.domain_functionality {
+                    say!("    Domain functionality: {}", level);
+                }
+                if let Some(level) = &ldap.forest_functionality {
+                    say!("    Forest functionality: {}", level);
+                }
+                if let Some(bind) = ldap.anonymous_bind {
+                    say!("    Anonymous bind: {}", if bind { "allowed" } else { "refused" });
+                }
+                if let Some(search) = ldap.anonymous_search {
+                    say!("    Anonymous search: {}", if search { "returns entries" } else { "no entries" });
+                }
+                if verbose {
+                    for dn in &ldap.sample_entries {
+                        say!("      {}", dn);
                     }
                 }
             }
             
             // Display banner if available and verbose enabled
-            if args.verbose {
+            if verbose {
                 if let Some(banner) = &result.banner {
                     // Limit banner display to first line or 80 chars
                     let first_line = banner.lines().next().unwrap_or(banner).chars().take(80).collect::<String>();
-                    println!("  Banner: {}", first_line);
+                    say!("  Banner: {}", first_line);
                 }
             }
+            
+            // Display confirmed vulnerabilities
+            for vuln in &result.vulns {
+                say!("  {}VULN{}: {}", colors.yellow, colors.reset, vuln);
+            }
+
+            // Display graded findings, most severe first
+            let mut findings: Vec<_> = result.findings.iter().collect();
+            findings.sort_by_key(|f| std::cmp::Reverse(f.severity));
+            for finding in findings {
+                let color = if finding.severity >= Severity::Medium { colors.yellow } else { colors.blue };
+                say!("  {}FINDING{}: {}", color, colors.reset, finding);
+            }
+
+            // Display data the content analysis picked out of responses
+            for finding in &result.content_findings {
+                if verbose {
+                    say!("  Content [{}] {}: {}", finding.source, finding.category, finding.values.join(", "));
+                } else {
+                    say!("  Content [{}] {}: {} match(es)", finding.source, finding.category, finding.values.len());
+                }
+            }
+
+            // Display the filtering classification and what it rests on
+            if let Some(verdict) = &result.filtering {
+                say!("  Filtering: {}", verdict.class);
+                if verbose {
+                    for reason in &verdict.reasons {
+                        say!("    - {}", reason);
+                    }
+                }
+            }
+
+            // Display HTTP discovery findings
+            if let Some(http_info) = &result.http_info {
+                if !http_info.redirects.is_empty() {
+                    say!("  Redirects:");
+                    for hop in &http_info.redirects {
+                        say!("    [{}] {} -> {}{}", hop.status_code, hop.url, hop.location,
+                            if hop.cross_host { " (other host)" } else { "" });
+                    }
+                    if http_info.redirect_loop {
+                        say!("    {}Redirect loop{}", colors.yellow, colors.reset);
+                    }
+                }
+                if let Some(methods) = &http_info.methods {
+                    if !methods.advertised.is_empty() {
+                        say!("  HTTP methods advertised: {}", methods.advertised.join(", "));
+                    }
+                    say!("  HTTP methods accepted: {}", methods.accepted.join(", "));
+                    if methods.webdav {
+                        say!("  WebDAV: enabled{}", methods.dav.as_deref().map(|d| format!(" (DAV: {})", d)).unwrap_or_default());
+                    }
+                }
+                if http_info.wildcard_response == Some(true) {
+                    say!("  HTTP: wildcard/soft-404 responses detected");
+                }
+                if !http_info.discovered_paths.is_empty() {
+                    say!("  Discovered paths:");
+                    for entry in &http_info.discovered_paths {
+                        match &entry.redirect {
+                            Some(location) => say!("    {} [{}] -> {}", entry.path, entry.status_code, location),
+                            None => say!("    {} [{}] ({} bytes)", entry.path, entry.status_code, entry.content_length),
+                        }
+                    }
+                }
+                if !http_info.virtual_hosts.is_empty() {
+                    say!("  Virtual hosts:");
+                    for vhost in &http_info.virtual_hosts {
+                        say!("    {} [{}] {}", vhost.hostname, vhost.status_code,
+                            vhost.title.as_deref().unwrap_or(""));
+                    }
+                }
+            }
+        }
+    }
+    
+    // Summarise the filtering in front of the host, including closed and
+    // filtered ports that aren't listed above
+    if let Some(verdict) = filtering::host_verdict(&results.results) {
+        say!("[{}+{}] Filtering: {}", colors.green, colors.reset, verdict.class);
+        for reason in &verdict.reasons {
+            say!("  - {}", reason);
+        }
+    }
+    
+    // Close the audit session; the final hash lets the log be checked later
+    if let Some(log) = &audit_log {
+        if let Some((failed, error)) = log.failed_writes() {
+            say!("[{}!{}] {} probes could not be written to audit log {}: {}",
+                colors.yellow, colors.reset, failed, log.path().display(), error);
         }
+        let head = log.seal()?;
+        say!("[{}+{}] Audit log sealed, final hash {}", colors.green, colors.reset, head);
     }
     
     // Output to file if requested
     if let Some(output_path) = args.output {
-        if args.json {
+        if json_output {
             output::save_json_results(&results, &output_path)?;
-            println!("[{}+{}] Results saved to {} in JSON format", 
+            say!("[{}+{}] Results saved to {} in JSON format", 
                 colors.green, colors.reset, output_path.display());
         } else {
             output::save_text_results(&results, &output_path)?;
-            println!("[{}+{}] Results saved to {}", 
+            say!("[{}+{}] Results saved to {}", 
                 colors.green, colors.reset, output_path.display());
         }
+        // Record the scope the results were gathered under
+        if let Some(active) = &active_scope {
+            scope::embed_in_results(active, &output_path, json_output)?;
+        }
+    }
+    
+    if let Some(active) = &active_scope {
+        let violations = active.violations();
+        if violations.is_empty() {
+            say!("[{}+{}] No out-of-scope connections attempted", colors.green, colors.reset);
+        } else {
+            say!("\n[{}!{}] Refused {} out-of-scope destinations:", 
+                colors.yellow, colors.reset, violations.len());
+            for violation in &violations {
+                say!("  {} ({})", violation.destination, violation.reason);
+            }
+        }
     }
     
     // Save packet logs if requested
@@ -183,13 +1100,13 @@ async fn main() -> Result<(), anyhow::Error> {
         if let Some(logger) = enhanced_logger {
             match logger.save_packet_logs(&packet_log_path, args.packet_log_password.as_deref()) {
                 Ok(_) => {
-                    println!("[{}+{}] Packet logs saved to {}", 
+                    say!("[{}+{}] Packet logs saved to {}", 
                         colors.green, colors.reset, packet_log_path.display());
-                    println!("[{}+{}] Captured {} network packets", 
+                    say!("[{}+{}] Captured {} network packets", 
                         colors.green, colors.reset, logger.packet_log_count());
                 },
                 Err(e) => {
-                    println!("[{}!{}] Failed to save packet logs: {}", 
+                    say!("[{}!{}] Failed to save packet logs: {}", 
                         colors.yellow, colors.reset, e);
                 }
             }
@@ -197,28 +1114,220 @@ async fn main() -> Result<(), anyhow::Error> {
     } else if args.packet_capture {
         // Display packet log summary if enabled but not saved to file
         if let Some(logger) = enhanced_logger {
-            println!("\n[{}+{}] Captured {} network packets", 
+            say!("\n[{}+{}] Captured {} network packets", 
                 colors.green, colors.reset, logger.packet_log_count());
             
-            if args.verbose {
-                println!("\nPacket Log Summary:");
-                println!("{}", logger.format_packet_logs());
+            if verbose {
+                say!("\nPacket Log Summary:");
+                say!("{}", logger.format_packet_logs());
+            }
+        }
+    }
+    
+    // Export zone transfers
+    if let Some(dir) = &args.axfr_dir {
+        if let Err(e) = std::fs::create_dir_all(dir) {
+            say!("[{}!{}] Failed to create {}: {}", 
+                colors.yellow, colors.reset, dir.display(), e);
+        }
+        for (port, result) in &results.results {
+            let zones = result.dns_info.iter().flat_map(|dns| dns.zones.iter());
+            for zone in zones.filter(|z| !z.records.is_empty()) {
+                let path = dir.join(dns::zone_file_name(&args.target, *port, &zone.domain));
+                match std::fs::write(&path, dns::zone_file(zone)) {
+                    Ok(()) => say!("[{}+{}] Wrote {} records to {}", 
+                        colors.green, colors.reset, zone.records.len(), path.display()),
+                    Err(e) => say!("[{}!{}] Failed to write {}: {}", 
+                        colors.yellow, colors.reset, path.display(), e),
+                }
+            }
+        }
+    }
+    
+    // Export certificate chains
+    if let Some(dir) = &args.cert_dir {
+        for (port, result) in &results.results {
+            if let Some(ssl_info) = &result.cert_info {
+                match certificates::write_pem_files(dir, &args.target, *port, ssl_info) {
+                    Ok(written) if !written.is_empty() => say!("[{}+{}] Wrote {} certificates for port {} to {}", 
+                        colors.green, colors.reset, written.len(), port, dir.display()),
+                    Ok(_) => {}
+                    Err(e) => say!("[{}!{}] Failed to write certificates for port {} to {}: {}", 
+                        colors.yellow, colors.reset, port, dir.display(), e),
+                }
             }
         }
     }
     
+    if let Some(path) = &args.cert_csv {
+        let mut ports: Vec<_> = results.results.keys().copied().collect();
+        ports.sort_unstable();
+        let inventory: Vec<_> = ports.iter()
+            .filter_map(|port| results.results[port].cert_info.as_ref().map(|info| (*port, info)))
+            .flat_map(|(port, info)| certificates::summarize_chain(&args.target, port, info))
+            .collect();
+        match certificates::write_csv(path, &inventory) {
+            Ok(()) => say!("[{}+{}] Wrote {} certificates to {}", 
+                colors.green, colors.reset, inventory.len(), path.display()),
+            Err(e) => say!("[{}!{}] Failed to write {}: {}", 
+                colors.yellow, colors.reset, path.display(), e),
+        }
+    }
+    
     // Print memory log summary if available
-    if let Some(logger) = memory_logger {
-        if args.verbose {
-            println!("\nLog entries: {}", logger.len());
-            println!("Log contents:");
-            println!("{}", logger.format_logs(true));
+    if let Some(logger) = &memory_logger {
+        if verbose {
+            say!("\nLog entries: {}", logger.len());
+            say!("Log contents:");
+            say!("{}", logger.format_logs(true));
         }
     }
     
-    // ... existing cleanup code ...
+    // Write the buffered log now the scan is over
+    if let Some(logger) = &memory_logger {
+        if !args.memory_only && !args.encrypt_logs {
+            std::fs::write(&log_file, logger.format_logs(true))?;
+        }
+    }
     
-    println!("{}Quantum Scanner operation complete{}", colors.green, colors.reset);
+    say!("{}Quantum Scanner operation complete{}", colors.green, colors.reset);
     
     Ok(())
-} 
\ No newline at end of file
+} 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROFILES: &str = r#"
+[base]
+ports = [22, 80]
+rate = 200
+timeout_banner = 6.0
+
+[base.fragmentation]
+two_frags = true
+
+[web]
+inherits = "base"
+ports = [80, 443]
+
+[web.http_discovery]
+wordlist = ["admin", "login"]
+extensions = ["php"]
+match_status = []
+filter_status = [404, 403]
+vhost_wordlist = []
+vhost_domain = "example.com"
+concurrency = 3
+
+[snmp]
+inherits = "base"
+
+[snmp.snmp]
+communities = ["public", "internal"]
+ports = [161]
+walk_processes = false
+max_rows = 64
+retries = 1
+"#;
+
+    /// Resolve a command line (target and profile file are added) against `PROFILES`
+    fn resolve(flags: &[&str]) -> ScanConfig {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("profiles.toml");
+        std::fs::write(&path, PROFILES).unwrap();
+        let mut argv = vec!["quantum_scanner", "192.0.2.1", "--profile-file", path.to_str().unwrap()];
+        argv.extend_from_slice(flags);
+
+        let matches = Args::command().try_get_matches_from(argv).unwrap();
+        let args = Args::from_arg_matches(&matches).unwrap();
+        resolve_scan_config(&args, &matches).unwrap()
+    }
+
+    #[test]
+    fn profile_settings_survive_flag_defaults() {
+        let config = resolve(&["--profile", "web"]);
+        assert_eq!(config.target, "192.0.2.1");
+        assert_eq!(config.ports, [80, 443]);
+        // Inherited from base, not reset by the flags' defaults
+        assert_eq!(config.rate, 200);
+        assert_eq!(config.timeout_banner, 6.0);
+        assert!(config.fragmentation.two_frags);
+        let discovery = config.http_discovery.unwrap();
+        assert_eq!(discovery.filter_status, [404, 403]);
+        assert_eq!(discovery.vhost_domain.as_deref(), Some("example.com"));
+    }
+
+    #[test]
+    fn typed_flags_override_the_profile() {
+        let config = resolve(&["--profile", "web", "-p", "8080", "--rate", "5", "--frag-two-frags", "--timeout-banner", "1.5"]);
+        assert_eq!(config.ports, [8080]);
+        assert_eq!(config.rate, 5);
+        assert_eq!(config.timeout_banner, 1.5);
+        assert!(config.fragmentation.two_frags);
+
+        let config = resolve(&["--profile", "web", "--top-100"]);
+        assert_eq!(config.ports, TOP_100_PORTS);
+    }
+
+    #[test]
+    fn section_flags_override_only_their_field() {
+        let config = resolve(&[
+            "--profile", "web",
+            "--http-extensions", "bak,old",
+            "--http-filter-status", "404",
+            "--http-match-status", "200,301",
+            "--vhost-domain", "example.org",
+        ]);
+        let discovery = config.http_discovery.unwrap();
+        assert_eq!(discovery.extensions, ["bak", "old"]);
+        assert_eq!(discovery.filter_status, [404]);
+        assert_eq!(discovery.match_status, [200, 301]);
+        assert_eq!(discovery.vhost_domain.as_deref(), Some("example.org"));
+        assert_eq!(discovery.wordlist, ["admin", "login"]);
+        assert_eq!(discovery.concurrency, 3);
+
+        let config = resolve(&["--profile", "snmp", "--snmp-processes"]);
+        let snmp = config.snmp.unwrap();
+        assert!(snmp.walk_processes);
+        assert_eq!(snmp.communities, ["public", "internal"]);
+        assert_eq!(snmp.max_rows, 64);
+    }
+
+    #[test]
+    fn wordlist_flags_replace_profile_lists() {
+        let dir = tempfile::tempdir().unwrap();
+        let words = dir.path().join("words.txt");
+        std::fs::write(&words, "backup\nstatus\n").unwrap();
+        let communities = dir.path().join("communities.txt");
+        std::fs::write(&communities, "s3cret\n").unwrap();
+
+        let config = resolve(&["--profile", "web", "--http-wordlist", words.to_str().unwrap()]);
+        assert_eq!(config.http_discovery.unwrap().wordlist, ["backup", "status"]);
+        let config = resolve(&["--profile", "snmp", "--snmp-communities", communities.to_str().unwrap()]);
+        assert_eq!(config.snmp.unwrap().communities, ["s3cret"]);
+    }
+
+    #[test]
+    fn section_switches_enable_what_the_profile_left_off() {
+        let config = resolve(&["--profile", "base", "--http-discovery", "--vhost-domain", "example.net"]);
+        let discovery = config.http_discovery.unwrap();
+        assert_eq!(discovery.vhost_domain.as_deref(), Some("example.net"));
+        assert_eq!(discovery.filter_status, [404]);
+
+        // Section flags without their switch don't turn the section on
+        let config = resolve(&["--profile", "base", "--snmp-processes"]);
+        assert!(config.snmp.is_none());
+        // and the profile's section stays on when the switch is typed again
+        let config = resolve(&["--profile", "snmp", "--snmp"]);
+        assert_eq!(config.snmp.unwrap().communities, ["public", "internal"]);
+    }
+
+    #[test]
+    fn without_a_profile_flags_and_defaults_apply() {
+        let config = resolve(&["-p", "22", "--http-discovery", "--http-extensions", "php"]);
+        assert_eq!(config.ports, [22]);
+        assert_eq!(config.rate, 0);
+        assert_eq!(config.http_discovery.unwrap().extensions, ["php"]);
+    }
+}
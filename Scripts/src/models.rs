@@ -1,3 +1,49 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+/// Scan techniques
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ScanType {
+    /// Half-open SYN scan
+    Syn,
+    /// ACK scan (maps filtering rules)
+    Ack,
+    /// FIN scan
+    Fin,
+    /// XMAS scan (FIN, PSH, URG)
+    Xmas,
+    /// NULL scan (no flags)
+    Null,
+    /// Window scan (ACK scan reading the RST window)
+    Window,
+    /// UDP scan
+    Udp,
+    /// TLS handshake scan
+    Ssl,
+    /// SYN carrying a TLS ClientHello fragment
+    TlsEcho,
+    /// SYN carrying a payload that mimics another protocol
+    Mimic,
+    /// SYN split into IP fragments
+    Frag,
+}
+
+/// State of a port as seen by one scan technique
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum PortStatus {
+    /// Something is listening
+    Open,
+    /// Reachable, nothing listening
+    Closed,
+    /// No reply or an ICMP error; a filter is in the way
+    Filtered,
+    /// No reply to a probe that open ports don't answer either
+    OpenFiltered,
+    /// Reachable, but open or closed can't be told apart (ACK scan)
+    Unfiltered,
+}
+
 /// SSL/TLS certificate and connection information
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct SslInfo {
@@ -49,6 +95,9 @@ pub struct SslInfo {
     pub organization: Option<String>,
     /// Certificate fingerprint (SHA-256)
     pub fingerprint: Option<String>,
+    /// Presented certificate chain as base64 DER, leaf first
+    #[serde(default)]
+    pub cert_chain: Vec<String>,
 }
 
 /// Enum for HTTP security headers
@@ -99,8 +148,362 @@ pub struct HttpInfo {
     pub headers: HashMap<String, String>,
     /// Cookies
     pub cookies: Vec<String>,
-    /// Redirects
-    pub redirects: Vec<String>,
+    /// Redirect hops followed from the first request, in order
+    pub redirects: Vec<RedirectHop>,
+    /// URL of the response described here when redirects were followed
+    pub final_url: Option<String>,
+    /// Redirect chain came back to a URL it had already visited
+    pub redirect_loop: bool,
+    /// Methods advertised by OPTIONS versus those actually accepted
+    pub methods: Option<HttpMethods>,
+    /// Paths found by content discovery
+    pub discovered_paths: Vec<DiscoveredPath>,
+    /// Virtual hosts found by Host header / SNI discovery
+    pub virtual_hosts: Vec<VirtualHost>,
+    /// Server answers every path the same way (wildcard / soft-404)
+    pub wildcard_response: Option<bool>,
+}
+
+/// Result of the HTTP method audit
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HttpMethods {
+    /// Methods listed in the OPTIONS Allow/Public headers
+    pub advertised: Vec<String>,
+    /// Methods the server really carried out when tested
+    pub accepted: Vec<String>,
+    /// Risky methods that were advertised but refused when tested
+    pub refused: Vec<String>,
+    /// DAV header from the OPTIONS response
+    pub dav: Option<String>,
+    /// PROPFIND answered with 207 Multi-Status
+    pub webdav: bool,
+}
+
+/// One hop of an HTTP redirect chain
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RedirectHop {
+    /// URL that was requested
+    pub url: String,
+    /// Redirect status code (301, 302, ...)
+    pub status_code: u16,
+    /// Absolute URL the Location header pointed to
+    pub location: String,
+    /// Location is on a different host than the scanned target
+    pub cross_host: bool,
+}
+
+/// A path found during HTTP content discovery
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiscoveredPath {
+    /// Request path (e.g. "/admin/login.php")
+    pub path: String,
+    /// HTTP response status code
+    pub status_code: u16,
+    /// Response body length in bytes
+    pub content_length: usize,
+    /// Location header for redirects
+    pub redirect: Option<String>,
+}
+
+/// A virtual host found by varying the Host header and TLS SNI
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VirtualHost {
+    /// Hostname that produced a distinct response
+    pub hostname: String,
+    /// HTTP response status code
+    pub status_code: u16,
+    /// Response body length in bytes
+    pub content_length: usize,
+    /// HTML title of the response
+    pub title: Option<String>,
+    /// Hostname was also sent as the TLS SNI value
+    pub via_sni: bool,
+}
+
+/// Product and version identified from a service banner
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ServiceIdentity {
+    /// Product name (e.g. "OpenSSH", "Postfix smtpd")
+    pub product: String,
+    /// Product version
+    pub version: Option<String>,
+    /// Additional details (distribution patch level, protocol version)
+    pub extra_info: Option<String>,
+    /// Operating system suggested by the banner
+    pub os_hint: Option<String>,
+    /// CPE 2.2 name for vulnerability matching
+    pub cpe: Option<String>,
+}
+
+impl std::fmt::Display for ServiceIdentity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.product)?;
+        if let Some(version) = &self.version {
+            write!(f, " {}", version)?;
+        }
+        if let Some(extra) = &self.extra_info {
+            write!(f, " ({})", extra)?;
+        }
+        Ok(())
+    }
+}
+
+/// Details read from a database server's handshake, without authenticating
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DatabaseInfo {
+    /// Database engine ("mysql", "postgresql", "mssql", "redis", "mongodb")
+    pub engine: String,
+    /// Server version reported by the handshake
+    pub version: Option<String>,
+    /// Transport encryption offered by the server (e.g. "supported", "required")
+    pub encryption: Option<String>,
+    /// Authentication methods the server advertised
+    pub auth_methods: Vec<String>,
+    /// Whether the server demanded credentials before answering
+    pub authentication_required: Option<bool>,
+    /// Other protocol details (capability flags, server mode, error messages)
+    pub details: std::collections::BTreeMap<String, String>,
+}
+
+/// Host details from an NTLM CHALLENGE message's target info
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct NtlmInfo {
+    /// Target name from the challenge header
+    pub target_name: Option<String>,
+    /// NetBIOS domain name
+    pub netbios_domain: Option<String>,
+    /// NetBIOS computer name
+    pub netbios_computer: Option<String>,
+    /// DNS domain name
+    pub dns_domain: Option<String>,
+    /// DNS computer name (FQDN)
+    pub dns_computer: Option<String>,
+    /// DNS forest name
+    pub dns_tree: Option<String>,
+    /// Windows version and build (e.g. "10.0.17763")
+    pub os_version: Option<String>,
+}
+
+/// Remote desktop protocol details
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RemoteDesktopInfo {
+    /// Protocol ("rdp" or "vnc")
+    pub protocol: String,
+    /// Protocol version (RFB version for VNC)
+    pub version: Option<String>,
+    /// Security protocols (RDP) or security types (VNC) the server accepts
+    pub security: Vec<String>,
+    /// Host details from the NTLM exchange (RDP with CredSSP)
+    pub ntlm_info: Option<NtlmInfo>,
+}
+
+/// A row of the SNMP interface table
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SnmpInterface {
+    /// ifIndex
+    pub index: u32,
+    /// ifDescr
+    pub name: String,
+    /// ifPhysAddress as a colon-separated MAC
+    pub mac: Option<String>,
+    /// ifOperStatus ("up", "down", ...)
+    pub status: Option<String>,
+}
+
+/// What an SNMP agent revealed with a guessed community
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SnmpInfo {
+    /// Protocol version used ("v1" or "v2c")
+    pub version: String,
+    /// Community used to read the values below
+    pub community: String,
+    /// Every community the agent accepted for that version
+    pub communities: Vec<String>,
+    /// sysDescr
+    pub sys_descr: Option<String>,
+    /// sysName
+    pub sys_name: Option<String>,
+    /// sysContact
+    pub sys_contact: Option<String>,
+    /// sysLocation
+    pub sys_location: Option<String>,
+    /// Interface table
+    pub interfaces: Vec<SnmpInterface>,
+    /// Running processes (hrSWRunName), when walked
+    pub processes: Vec<String>,
+}
+
+/// A resource record from a zone transfer
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DnsRecord {
+    /// Owner name (fully qualified, with trailing dot)
+    pub name: String,
+    /// Record type ("A", "MX", "TYPE65", ...)
+    pub record_type: String,
+    /// Time to live in seconds
+    pub ttl: u32,
+    /// Record data in master-file presentation format
+    pub data: String,
+}
+
+/// Per-zone DNS findings
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DnsZoneInfo {
+    /// Zone name
+    pub domain: String,
+    /// Whether the zone publishes DNSKEY records (None if the query failed)
+    pub dnssec: Option<bool>,
+    /// Whether the server allowed a zone transfer (None if not attempted)
+    pub axfr_allowed: Option<bool>,
+    /// Records returned by the zone transfer
+    pub records: Vec<DnsRecord>,
+}
+
+/// DNS server details
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DnsInfo {
+    /// CHAOS TXT `version.bind`
+    pub version_bind: Option<String>,
+    /// CHAOS TXT `hostname.bind`
+    pub hostname_bind: Option<String>,
+    /// Whether the server resolves external names for us (open resolver)
+    pub recursion: Option<bool>,
+    /// Zones checked for DNSSEC and AXFR
+    pub zones: Vec<DnsZoneInfo>,
+}
+
+/// LDAP rootDSE contents and anonymous access results
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LdapInfo {
+    /// namingContexts
+    pub naming_contexts: Vec<String>,
+    /// Base DN used for the anonymous search (defaultNamingContext if published)
+    pub default_naming_context: Option<String>,
+    /// supportedSASLMechanisms
+    pub sasl_mechanisms: Vec<String>,
+    /// supportedLDAPVersion
+    pub ldap_versions: Vec<String>,
+    /// Active Directory domain functionality level
+    pub domain_functionality: Option<String>,
+    /// Active Directory forest functionality level
+    pub forest_functionality: Option<String>,
+    /// dnsHostName
+    pub dns_host_name: Option<String>,
+    /// Whether an anonymous simple bind succeeded
+    pub anonymous_bind: Option<bool>,
+    /// Whether an anonymous search below the base DN returned entries
+    pub anonymous_search: Option<bool>,
+    /// DNs returned by the anonymous search (at most a handful)
+    pub sample_entries: Vec<String>,
+    /// Diagnostic message the server attached to the search result
+    pub search_diagnostic: Option<String>,
+}
+
+/// Severity grade attached to a finding
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Severity {
+    /// Informational, hardening advice
+    Info,
+    /// Low risk
+    Low,
+    /// Medium risk
+    Medium,
+    /// High risk
+    High,
+}
+
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Severity::Info => "Info",
+            Severity::Low => "Low",
+            Severity::Medium => "Medium",
+            Severity::High => "High",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// A graded configuration weakness, e.g. a missing security header
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Finding {
+    /// Grade of the issue
+    pub severity: Severity,
+    /// Service the finding applies to (e.g. "https")
+    pub service: String,
+    /// Short description of the issue
+    pub title: String,
+    /// Evidence taken from the server's response
+    pub evidence: Option<String>,
+}
+
+impl std::fmt::Display for Finding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[{}] {}: {}", self.service, self.severity, self.title)?;
+        if let Some(evidence) = &self.evidence {
+            write!(f, " ({})", evidence)?;
+        }
+        Ok(())
+    }
+}
+
+/// Data of interest the file analyzer found in content collected from a port
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ContentFinding {
+    /// Where the content came from, e.g. "banner", "http_body" or "tls.cert_san"
+    pub source: String,
+    /// Analyzer category, e.g. "email", "api_key" or "software_versions"
+    pub category: String,
+    /// Matched values, sorted
+    pub values: Vec<String>,
+}
+
+/// A raw probe sent to a port and what came back
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProbeObservation {
+    /// Scan technique of the probe
+    pub scan_type: ScanType,
+    /// State derived from the reply
+    pub status: PortStatus,
+    /// The reply, if anything came back
+    pub reply: Option<crate::packet::Reply>,
+    /// Attempts made (more than one when earlier ones went unanswered)
+    pub attempts: u32,
+}
+
+/// How traffic to a port or host is filtered
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FilteringClass {
+    /// Probes reach the host and it answers for itself
+    Unfiltered,
+    /// A filter that tracks connections and drops packets outside them
+    StatefulFirewall,
+    /// A filter that matches each packet on its own (ports, TCP flags)
+    StatelessAcl,
+    /// Replies are dropped once the host or a device in front of it is busy
+    RateLimited,
+}
+
+impl std::fmt::Display for FilteringClass {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            FilteringClass::Unfiltered => "unfiltered",
+            FilteringClass::StatefulFirewall => "stateful firewall",
+            FilteringClass::StatelessAcl => "stateless ACL",
+            FilteringClass::RateLimited => "rate-limited",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// A filtering classification and the observations it rests on
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FilteringVerdict {
+    /// The classification
+    pub class: FilteringClass,
+    /// Why, one observation or inference per entry
+    pub reasons: Vec<String>,
 }
 
 /// Result information for a single port
@@ -110,14 +513,35 @@ pub struct PortResult {
     pub tcp_states: HashMap<ScanType, PortStatus>,
     /// UDP status
     pub udp_state: Option<PortStatus>,
-    /// Firewall/filtering information
-    pub filtering: Option<String>,
+    /// Firewall/filtering classification
+    pub filtering: Option<FilteringVerdict>,
+    /// Raw probes sent to the port and their replies
+    #[serde(default)]
+    pub probe_observations: Vec<ProbeObservation>,
     /// Service name
     pub service: Option<String>,
     /// Service version
     pub version: Option<String>,
+    /// Structured product/version identity parsed from the banner
+    pub service_identity: Option<ServiceIdentity>,
+    /// Database handshake details
+    pub database_info: Option<DatabaseInfo>,
+    /// RDP/VNC security details
+    pub remote_desktop: Option<RemoteDesktopInfo>,
+    /// SNMP agent details (UDP)
+    pub snmp_info: Option<SnmpInfo>,
+    /// DNS server details
+    pub dns_info: Option<DnsInfo>,
+    /// LDAP rootDSE and anonymous access details
+    pub ldap_info: Option<LdapInfo>,
     /// Known vulnerabilities
     pub vulns: Vec<String>,
+    /// Graded configuration findings
+    #[serde(default)]
+    pub findings: Vec<Finding>,
+    /// Data of interest found in the banner, HTTP bodies and certificate
+    #[serde(default)]
+    pub content_findings: Vec<ContentFinding>,
     /// SSL/TLS certificate info
     pub cert_info: Option<SslInfo>,
     /// Service banner
@@ -128,4 +552,55 @@ pub struct PortResult {
     pub scan_time: chrono::DateTime<chrono::Utc>,
     /// HTTP response info
     pub http_info: Option<HttpInfo>,
-} 
\ No newline at end of file
+}
+
+impl PortResult {
+    /// Empty result for a port that is about to be probed
+    pub fn new() -> Self {
+        Self {
+            tcp_states: HashMap::new(),
+            udp_state: None,
+            filtering: None,
+            probe_observations: Vec::new(),
+            service: None,
+            version: None,
+            service_identity: None,
+            database_info: None,
+            remote_desktop: None,
+            snmp_info: None,
+            dns_info: None,
+            ldap_info: None,
+            vulns: Vec::new(),
+            findings: Vec::new(),
+            content_findings: Vec::new(),
+            cert_info: None,
+            banner: None,
+            os_guess: None,
+            scan_time: chrono::Utc::now(),
+            http_info: None,
+        }
+    }
+}
+
+impl Default for PortResult {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Results of scanning one target
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ScanResults {
+    /// Target as given (hostname or address)
+    pub target: String,
+    /// Address the target resolved to
+    pub target_ip: String,
+    /// Ports found open by any technique, sorted
+    pub open_ports: Vec<u16>,
+    /// Per-port details
+    pub results: HashMap<u16, PortResult>,
+    /// When the scan started
+    pub start_time: Option<chrono::DateTime<chrono::Utc>>,
+    /// When the scan finished
+    pub end_time: Option<chrono::DateTime<chrono::Utc>>,
+}
@@ -0,0 +1,971 @@
+//! Continuous monitoring of a scope ("attack surface watch")
+//!
+//! The monitor rescans a fixed set of hosts on a schedule, stores every run
+//! under `state_dir/runs` and compares it with the run before. Changes that
+//! matter to a defender become `Alert`s:
+//!
+//! * a port that is newly open (or no longer open)
+//! * a service whose product or version changed
+//! * a certificate that entered the expiry window or expired
+//! * a web server that picked up a new security header/cookie finding
+//!
+//! Alerts are delivered to any number of sinks: an HTTP webhook (JSON POST), a
+//! JSON-lines file, or syslog. The first run only sets the baseline, so it
+//! reports nothing but certificate expiry.
+//!
+//! When the template carries an engagement scope, its time windows are
+//! honoured: a run that falls outside them is skipped until the next interval.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::sync::mpsc;
+
+use crate::config::{ScanConfig, ScannerBuilder};
+use crate::error::ScanError;
+use crate::models::{PortResult, ScanResults, Severity, SslInfo};
+
+/// Default certificate expiry window
+pub const DEFAULT_CERT_EXPIRY_DAYS: i64 = 30;
+
+/// Time allowed for delivering one alert
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Syslog facility used for alerts (daemon)
+const SYSLOG_FACILITY: u8 = 3;
+
+/// Where alerts are sent
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AlertSink {
+    /// POST each alert as JSON to an http:// URL
+    Webhook(String),
+    /// Append each alert as one JSON line
+    File(PathBuf),
+    /// Send to syslog: the local socket (/dev/log) or a UDP host:port
+    Syslog(Option<String>),
+}
+
+impl FromStr for AlertSink {
+    type Err = String;
+
+    /// Parse `webhook=URL`, `file=PATH`, `syslog` or `syslog=HOST:PORT`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, value) = match s.split_once('=') {
+            Some((kind, value)) => (kind, Some(value)),
+            None => (s, None),
+        };
+        match (kind.to_lowercase().as_str(), value) {
+            ("webhook", Some(url)) => match crate::http_client::HttpUrl::parse(url) {
+                Some(parsed) if !parsed.tls => Ok(AlertSink::Webhook(url.to_string())),
+                Some(_) => Err("webhook alerts only support http:// URLs".to_string()),
+                None => Err(format!("invalid webhook URL '{}'", url)),
+            },
+            ("file", Some(path)) if !path.is_empty() => Ok(AlertSink::File(PathBuf::from(path))),
+            ("syslog", None) => Ok(AlertSink::Syslog(None)),
+            ("syslog", Some(addr)) if !addr.is_empty() => Ok(AlertSink::Syslog(Some(addr.to_string()))),
+            _ => Err(format!("invalid alert sink '{}' (expected webhook=URL, file=PATH or syslog[=HOST:PORT])", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for AlertSink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AlertSink::Webhook(url) => write!(f, "webhook {}", url),
+            AlertSink::File(path) => write!(f, "file {}", path.display()),
+            AlertSink::Syslog(None) => write!(f, "syslog"),
+            AlertSink::Syslog(Some(addr)) => write!(f, "syslog {}", addr),
+        }
+    }
+}
+
+/// Monitor settings
+#[derive(Debug, Clone)]
+pub struct MonitorConfig {
+    /// Hosts to watch (already expanded, see `cluster::expand_targets`)
+    pub hosts: Vec<String>,
+    /// Scan settings used for every host; the target is replaced
+    pub template: ScanConfig,
+    /// Time from the start of one run to the start of the next
+    pub interval: Duration,
+    /// Directory holding the stored runs
+    pub state_dir: PathBuf,
+    /// Alert when a certificate expires within this many days
+    pub cert_expiry_days: i64,
+    /// Hosts scanned at the same time
+    pub parallel: usize,
+    /// Stored runs to keep (0 = keep all)
+    pub keep_runs: usize,
+    /// Where alerts go
+    pub sinks: Vec<AlertSink>,
+    /// Where progress is reported (dropped when `None`)
+    pub events: Option<mpsc::UnboundedSender<MonitorEvent>>,
+}
+
+/// Progress of the monitor
+#[derive(Debug, Clone)]
+pub enum MonitorEvent {
+    /// A run finished and was stored
+    RunFinished {
+        /// Run identifier
+        id: String,
+        /// Hosts with results
+        hosts: usize,
+        /// Hosts whose scan failed
+        failed: usize,
+        /// Alerts raised
+        alerts: usize,
+    },
+    /// A run could not be completed
+    RunFailed {
+        /// What went wrong
+        error: String,
+    },
+    /// A run was skipped because the scope doesn't allow testing right now
+    RunSkipped {
+        /// Why
+        reason: String,
+    },
+    /// Ctrl-C stopped the monitor between runs
+    Interrupted,
+    /// Scanning one host failed
+    ScanFailed {
+        /// Host
+        host: String,
+        /// What went wrong
+        error: String,
+    },
+    /// A run raised an alert
+    Alert(Alert),
+    /// An alert could not be delivered to a sink
+    DeliveryFailed {
+        /// Sink the alert was meant for
+        sink: String,
+        /// What went wrong
+        error: String,
+    },
+    /// A stored run could not be read and was skipped
+    UnreadableRun {
+        /// The skipped file
+        path: PathBuf,
+    },
+}
+
+impl MonitorEvent {
+    /// Whether the event reports something going wrong
+    pub fn is_problem(&self) -> bool {
+        matches!(self,
+            MonitorEvent::RunFailed { .. }
+            | MonitorEvent::ScanFailed { .. }
+            | MonitorEvent::DeliveryFailed { .. }
+            | MonitorEvent::UnreadableRun { .. })
+    }
+}
+
+impl std::fmt::Display for MonitorEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MonitorEvent::RunFinished { id, hosts, failed, alerts } => write!(f,
+                "run {} done: {} hosts, {} failed, {} alerts", id, hosts, failed, alerts),
+            MonitorEvent::RunFailed { error } => write!(f, "run failed: {}", error),
+            MonitorEvent::RunSkipped { reason } => write!(f, "skipping run: {}", reason),
+            MonitorEvent::Interrupted => write!(f, "interrupted, stopping"),
+            MonitorEvent::ScanFailed { host, error } => write!(f, "scan of {} failed: {}", host, error),
+            MonitorEvent::Alert(alert) => write!(f, "{}", alert),
+            MonitorEvent::DeliveryFailed { sink, error } => write!(f, "delivering alert to {} failed: {}", sink, error),
+            MonitorEvent::UnreadableRun { path } => write!(f, "ignoring unreadable run file {}", path.display()),
+        }
+    }
+}
+
+/// What changed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertKind {
+    /// A port is open that was not open in the previous run
+    NewOpenPort,
+    /// A port that was open is no longer open
+    PortClosed,
+    /// The service or version detected on a port changed
+    ServiceChanged,
+    /// A certificate entered the expiry window or expired
+    CertificateExpiring,
+    /// A web server has a security header or cookie finding it did not have before
+    HeaderRegression,
+}
+
+/// A change worth telling someone about
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Alert {
+    /// When the change was detected
+    pub time: DateTime<Utc>,
+    /// Kind of change
+    pub kind: AlertKind,
+    /// How urgent it is
+    pub severity: Severity,
+    /// Host the change was seen on
+    pub host: String,
+    /// Port the change was seen on
+    pub port: u16,
+    /// One-line description
+    pub message: String,
+    /// State in the previous run, where there was one
+    pub previous: Option<String>,
+    /// State in this run
+    pub current: Option<String>,
+}
+
+impl std::fmt::Display for Alert {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[{}] {}:{} {}", self.severity, self.host, self.port, self.message)
+    }
+}
+
+/// One monitoring run as stored on disk
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MonitorRun {
+    /// Run identifier (start time, sortable)
+    pub id: String,
+    /// When the run started
+    pub started_at: Option<DateTime<Utc>>,
+    /// When the run ended
+    pub finished_at: Option<DateTime<Utc>>,
+    /// Latest known results per host; hosts that failed this run keep the
+    /// results of the last run that reached them
+    pub hosts: BTreeMap<String, ScanResults>,
+    /// Hosts whose scan failed this run, with the error
+    pub failures: BTreeMap<String, String>,
+    /// Alerts raised by comparing this run with the previous one
+    pub alerts: Vec<Alert>,
+}
+
+/// Runs scans on a schedule and alerts on changes
+pub struct Monitor {
+    config: MonitorConfig,
+    previous: Option<MonitorRun>,
+}
+
+impl Monitor {
+    /// Create the monitor and load the most recent stored run as the baseline
+    pub fn new(config: MonitorConfig) -> Result<Self, anyhow::Error> {
+        if config.hosts.is_empty() {
+            return Err(ScanError::MissingTarget.into());
+        }
+        std::fs::create_dir_all(runs_dir(&config.state_dir))?;
+        let (previous, unreadable) = load_latest_run(&config.state_dir)?;
+        let monitor = Self { config, previous };
+        for path in unreadable {
+            monitor.report(MonitorEvent::UnreadableRun { path });
+        }
+        Ok(monitor)
+    }
+
+    /// Pass an event to whoever asked for them
+    fn report(&self, event: MonitorEvent) {
+        if let Some(events) = &self.config.events {
+            let _ = events.send(event);
+        }
+    }
+
+    /// The run new results are compared against
+    pub fn previous(&self) -> Option<&MonitorRun> {
+        self.previous.as_ref()
+    }
+
+    /// Run on the schedule until `runs` runs are done (None = forever) or Ctrl-C
+    pub async fn run(&mut self, runs: Option<usize>) -> Result<(), anyhow::Error> {
+        let mut completed = 0;
+        loop {
+            let started = tokio::time::Instant::now();
+            match self.run_once().await {
+                Ok(Some(run)) => self.report(MonitorEvent::RunFinished {
+                    id: run.id,
+                    hosts: run.hosts.len(),
+                    failed: run.failures.len(),
+                    alerts: run.alerts.len(),
+                }),
+                Ok(None) => {}
+                Err(e) => self.report(MonitorEvent::RunFailed { error: e.to_string() }),
+            }
+
+            completed += 1;
+            if runs.is_some_and(|runs| completed >= runs) {
+                return Ok(());
+            }
+
+            tokio::select! {
+                _ = tokio::time::sleep_until(started + self.config.interval) => {}
+                _ = tokio::signal::ctrl_c() => {
+                    self.report(MonitorEvent::Interrupted);
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    /// Scan every host once, store the run and deliver its alerts
+    ///
+    /// Returns None when the run was skipped because the scope's time windows
+    /// don't allow testing right now.
+    pub async fn run_once(&mut self) -> Result<Option<MonitorRun>, anyhow::Error> {
+        // One scope per run, shared by every host's scanner
+        let scope = match &self.config.template.scope {
+            Some(scope) => {
+                let scope = Arc::new(crate::scope::Scope::new(scope.clone(), None)?);
+                if let Err(reason) = scope.check_time() {
+                    self.report(MonitorEvent::RunSkipped { reason });
+                    return Ok(None);
+                }
+                Some(scope)
+            }
+            None => None,
+        };
+
+        let started_at = Utc::now();
+        let mut run = MonitorRun {
+            id: started_at.format("%Y%m%dT%H%M%SZ").to_string(),
+            started_at: Some(started_at),
+            ..MonitorRun::default()
+        };
+
+        let template = &self.config.template;
+        let mut scans = futures::stream::iter(self.config.hosts.iter().cloned())
+            .map(|host| {
+                let scope = scope.clone();
+                async move {
+                    let config = ScanConfig { target: host.clone(), ..template.clone() };
+                    let mut builder = ScannerBuilder::from_config(config);
+                    if let Some(scope) = scope {
+                        builder = builder.shared_scope(scope);
+                    }
+                    let outcome = match builder.build().await {
+                        Ok(mut scanner) => scanner.run().await,
+                        Err(e) => Err(e),
+                    };
+                    (host, outcome)
+                }
+            })
+            .buffer_unordered(self.config.parallel.max(1));
+
+        while let Some((host, outcome)) = scans.next().await {
+            match outcome {
+                Ok(results) => {
+                    run.hosts.insert(host, results);
+                }
+                Err(e) => {
+                    self.report(MonitorEvent::ScanFailed { host: host.clone(), error: e.to_string() });
+                    if let Some(last) = self.previous.as_ref().and_then(|p| p.hosts.get(&host)) {
+                        run.hosts.insert(host.clone(), last.clone());
+                    }
+                    run.failures.insert(host, e.to_string());
+                }
+            }
+        }
+        drop(scans);
+
+        run.alerts = diff_runs(self.previous.as_ref(), &run, self.config.cert_expiry_days);
+        run.finished_at = Some(Utc::now());
+
+        self.save_run(&run, scope.as_deref())?;
+        for alert in &run.alerts {
+            self.report(MonitorEvent::Alert(alert.clone()));
+            for sink in &self.config.sinks {
+                if let Err(e) = deliver(sink, alert).await {
+                    self.report(MonitorEvent::DeliveryFailed { sink: sink.to_string(), error: e.to_string() });
+                }
+            }
+        }
+
+        self.previous = Some(run.clone());
+        Ok(Some(run))
+    }
+
+    /// Write the run and prune old ones beyond `keep_runs`
+    fn save_run(&self, run: &MonitorRun, scope: Option<&crate::scope::Scope>) -> Result<(), anyhow::Error> {
+        let dir = runs_dir(&self.config.state_dir);
+        let path = dir.join(format!("{}.json", run.id));
+        std::fs::write(&path, serde_json::to_vec(run)?)?;
+        if let Some(scope) = scope {
+            crate::scope::embed_in_results(scope, &path, true)?;
+        }
+
+        if self.config.keep_runs > 0 {
+            let stored = list_runs(&self.config.state_dir)?;
+            let excess = stored.len().saturating_sub(self.config.keep_runs);
+            for path in &stored[..excess] {
+                let _ = std::fs::remove_file(path);
+            }
+        }
+        Ok(())
+    }
+}
+
+fn runs_dir(state_dir: &Path) -> PathBuf {
+    state_dir.join("runs")
+}
+
+/// Stored run files, oldest first
+pub fn list_runs(state_dir: &Path) -> Result<Vec<PathBuf>, anyhow::Error> {
+    let mut runs = Vec::new();
+    for entry in std::fs::read_dir(runs_dir(state_dir))? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == "json") {
+            runs.push(path);
+        }
+    }
+    runs.sort();
+    Ok(runs)
+}
+
+/// Load the most recent readable run, if any, and the newer files that couldn't be read
+pub fn load_latest_run(state_dir: &Path) -> Result<(Option<MonitorRun>, Vec<PathBuf>), anyhow::Error> {
+    let mut unreadable = Vec::new();
+    for path in list_runs(state_dir)?.into_iter().rev() {
+        match std::fs::read(&path).map(|data| serde_json::from_slice::<MonitorRun>(&data)) {
+            Ok(Ok(run)) => return Ok((Some(run), unreadable)),
+            _ => unreadable.push(path),
+        }
+    }
+    Ok((None, unreadable))
+}
+
+/// Parse an interval such as "90s", "15m", "6h", "1d" or plain seconds
+pub fn parse_interval(s: &str) -> Result<Duration, String> {
+    let s = s.trim();
+    let (number, unit) = match s.find(|c: char| !c.is_ascii_digit()) {
+        Some(i) => s.split_at(i),
+        None => (s, "s"),
+    };
+    let number: u64 = number.parse().map_err(|_| format!("invalid interval '{}'", s))?;
+    let multiplier: u64 = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 3600,
+        "d" => 86400,
+        _ => return Err(format!("invalid interval unit in '{}' (use s, m, h or d)", s)),
+    };
+    let seconds = number
+        .checked_mul(multiplier)
+        .ok_or_else(|| format!("interval '{}' is too large", s))?;
+    if seconds == 0 {
+        return Err("interval must be greater than zero".to_string());
+    }
+    Ok(Duration::from_secs(seconds))
+}
+
+/// Compare a run with the one before and list what changed
+///
+/// Hosts that failed in either run are not compared, so an unreachable host
+/// doesn't look like every port closing and then reopening.
+pub fn diff_runs(previous: Option<&MonitorRun>, current: &MonitorRun, cert_expiry_days: i64) -> Vec<Alert> {
+    let now = current.started_at.unwrap_or_else(Utc::now);
+    let mut alerts = Vec::new();
+
+    for (host, results) in &current.hosts {
+        if current.failures.contains_key(host) {
+            continue;
+        }
+        let before = previous
+            .filter(|p| !p.failures.contains_key(host))
+            .and_then(|p| p.hosts.get(host));
+        let mut diff = HostDiff { host, now, alerts: &mut alerts };
+
+        for (port, result) in open_results(results) {
+            let old = before.and_then(|b| open_results(b).remove(&port));
+            if let Some(cert) = &result.cert_info {
+                diff.certificate(port, cert, old.and_then(|o| o.cert_info.as_ref()), cert_expiry_days);
+            }
+            // Without a previous run every port would be "new"
+            let before = match before {
+                Some(before) => before,
+                None => continue,
+            };
+            match old {
+                None => diff.new_port(port, result, before),
+                Some(old) => {
+                    diff.service(port, old, result);
+                    diff.headers(port, old, result);
+                }
+            }
+        }
+
+        if let Some(before) = before {
+            let open = open_results(results);
+            for (port, old) in open_results(before) {
+                if !open.contains_key(&port) {
+                    diff.closed_port(port, old);
+                }
+            }
+        }
+    }
+
+    alerts
+}
+
+/// Ports reported open in a result set, with their details
+fn open_results(results: &ScanResults) -> BTreeMap<u16, &PortResult> {
+    results.open_ports.iter()
+        .filter_map(|port| results.results.get(port).map(|result| (*port, result)))
+        .collect()
+}
+
+/// Collects alerts for one host
+struct HostDiff<'a> {
+    host: &'a str,
+    now: DateTime<Utc>,
+    alerts: &'a mut Vec<Alert>,
+}
+
+impl HostDiff<'_> {
+    fn add(&mut self, kind: AlertKind, severity: Severity, port: u16, message: String, previous: Option<String>, current: Option<String>) {
+        self.alerts.push(Alert {
+            time: self.now,
+            kind,
+            severity,
+            host: self.host.to_string(),
+            port,
+            message,
+            previous,
+            current,
+        });
+    }
+
+    fn new_port(&mut self, port: u16, result: &PortResult, before: &ScanResults) {
+        let service = describe_service(result);
+        let previous = before.results.get(&port)
+            .and_then(|old| old.tcp_states.values().next().copied().or(old.udp_state))
+            .map(|status| format!("{:?}", status).to_lowercase());
+        self.add(
+            AlertKind::NewOpenPort,
+            Severity::Medium,
+            port,
+            format!("port {} is now open ({})", port, service.as_deref().unwrap_or("unknown service")),
+            previous,
+            service,
+        );
+    }
+
+    fn closed_port(&mut self, port: u16, old: &PortResult) {
+        let service = describe_service(old);
+        self.add(
+            AlertKind::PortClosed,
+            Severity::Info,
+            port,
+            format!("port {} is no longer open", port),
+            service,
+            None,
+        );
+    }
+
+    fn service(&mut self, port: u16, old: &PortResult, new: &PortResult) {
+        let (before, after) = (describe_service(old), describe_service(new));
+        // A probe that failed to identify the service this time isn't a change
+        if after.is_none() || before == after {
+            return;
+        }
+        self.add(
+            AlertKind::ServiceChanged,
+            Severity::Low,
+            port,
+            format!("service changed from {} to {}",
+                before.as_deref().unwrap_or("unknown"), after.as_deref().unwrap_or("unknown")),
+            before,
+            after,
+        );
+    }
+
+    fn headers(&mut self, port: u16, old: &PortResult, new: &PortResult) {
+        // Only compare responses that were actually graded both times
+        if old.http_info.is_none() || new.http_info.is_none() {
+            return;
+        }
+        let is_web = |service: &str| service == "http" || service == "https";
+        let known: BTreeSet<&str> = old.findings.iter()
+            .filter(|f| is_web(&f.service))
+            .map(|f| f.title.as_str())
+            .collect();
+        let mut reported = BTreeSet::new();
+        for finding in new.findings.iter().filter(|f| is_web(&f.service)) {
+            if known.contains(finding.title.as_str()) || !reported.insert(finding.title.as_str()) {
+                continue;
+            }
+            self.add(
+                AlertKind::HeaderRegression,
+                finding.severity,
+                port,
+                format!("new web finding: {}", finding.title),
+                None,
+                finding.evidence.clone().or_else(|| Some(finding.title.clone())),
+            );
+        }
+    }
+
+    fn certificate(&mut self, port: u16, cert: &SslInfo, old: Option<&SslInfo>, expiry_days: i64) {
+        let state = CertState::of(cert, self.now, expiry_days);
+        let old_state = old.map(|old| CertState::of(old, self.now, expiry_days));
+        // Alert once when a certificate gets worse, not on every run
+        let same_cert = old.is_some_and(|old| old.fingerprint == cert.fingerprint);
+        if state == CertState::Valid || (same_cert && old_state >= Some(state)) {
+            return;
+        }
+        let subject = cert.cert_cn.as_deref().unwrap_or("certificate");
+        let on = cert.cert_valid_to.as_deref().map(|date| format!(" on {}", date)).unwrap_or_default();
+        let (severity, message) = match state {
+            CertState::Expired => (Severity::High, format!("{} expired{}", subject, on)),
+            _ => (Severity::Medium, format!("{} expires{} (within {} days)", subject, on, expiry_days)),
+        };
+        self.add(
+            AlertKind::CertificateExpiring,
+            severity,
+            port,
+            message,
+            old.and_then(|old| old.cert_valid_to.clone()),
+            cert.cert_valid_to.clone(),
+        );
+    }
+}
+
+/// Service and version as one comparable string
+fn describe_service(result: &PortResult) -> Option<String> {
+    match (&result.service, &result.version) {
+        (Some(service), Some(version)) => Some(format!("{} {}", service, version)),
+        (Some(service), None) => Some(service.clone()),
+        (None, Some(version)) => Some(version.clone()),
+        (None, None) => None,
+    }
+}
+
+/// How close a certificate is to expiry, ordered from best to worst
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum CertState {
+    Valid,
+    Expiring,
+    Expired,
+}
+
+impl CertState {
+    fn of(cert: &SslInfo, now: DateTime<Utc>, expiry_days: i64) -> Self {
+        let not_after = match cert.cert_valid_to.as_deref().and_then(parse_cert_time) {
+            Some(not_after) => not_after,
+            None if cert.is_expired == Some(true) => return CertState::Expired,
+            None if cert.expires_soon == Some(true) => return CertState::Expiring,
+            None => return CertState::Valid,
+        };
+        if not_after <= now {
+            CertState::Expired
+        } else if not_after - now <= chrono::Duration::days(expiry_days) {
+            CertState::Expiring
+        } else {
+            CertState::Valid
+        }
+    }
+}
+
+/// Parse the validity time format `analyze_ssl` stores ("Jan  2 03:04:05 2026 +00:00")
+fn parse_cert_time(value: &str) -> Option<DateTime<Utc>> {
+    let normalized = value.split_whitespace().collect::<Vec<_>>().join(" ");
+    DateTime::parse_from_str(&normalized, "%b %d %H:%M:%S %Y %:z")
+        .ok()
+        .map(|time| time.with_timezone(&Utc))
+}
+
+/// Send one alert to a sink
+pub async fn deliver(sink: &AlertSink, alert: &Alert) -> Result<(), anyhow::Error> {
+    match sink {
+        AlertSink::Webhook(url) => post_webhook(url, alert).await,
+        AlertSink::File(path) => {
+            let mut file = std::fs::OpenOptions::new().create(true).append(true).open(path)?;
+            writeln!(file, "{}", serde_json::to_string(alert)?)?;
+            Ok(())
+        }
+        AlertSink::Syslog(addr) => send_syslog(addr.as_deref(), alert),
+    }
+}
+
+/// POST the alert as JSON and require a 2xx answer
+///
+/// Alerts go to the operator's own infrastructure, so this deliberately uses
+/// a plain socket rather than the scope-checked and audited scan helpers.
+async fn post_webhook(url: &str, alert: &Alert) -> Result<(), anyhow::Error> {
+    let parsed = crate::http_client::HttpUrl::parse(url)
+        .ok_or_else(|| anyhow::anyhow!("invalid webhook URL '{}'", url))?;
+    let body = serde_json::to_vec(alert)?;
+    let host = if parsed.host.contains(':') { format!("[{}]", parsed.host) } else { parsed.host.clone() };
+    let mut request = format!(
+        "POST {} HTTP/1.1\r\nHost: {}:{}\r\nUser-Agent: quantum_monitor/{}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        parsed.path, host, parsed.port, crate::VERSION, body.len()
+    ).into_bytes();
+    request.extend_from_slice(&body);
+
+    let exchange = async {
+        let mut stream = tokio::net::TcpStream::connect((parsed.host.as_str(), parsed.port)).await?;
+        stream.write_all(&request).await?;
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).await?;
+        Ok::<_, anyhow::Error>(response)
+    };
+    let response = tokio::time::timeout(DELIVERY_TIMEOUT, exchange)
+        .await
+        .map_err(|_| anyhow::anyhow!("webhook {} timed out", url))??;
+
+    match crate::http_client::parse_response(&response) {
+        Some(response) if (200..300).contains(&response.status_code) => Ok(()),
+        Some(response) => anyhow::bail!("webhook answered {}", response.status_code),
+        None => anyhow::bail!("webhook sent no HTTP response"),
+    }
+}
+
+/// Send the alert as an RFC 3164 syslog message
+fn send_syslog(addr: Option<&str>, alert: &Alert) -> Result<(), anyhow::Error> {
+    let level = match alert.severity {
+        Severity::High => 3,
+        Severity::Medium => 4,
+        Severity::Low => 5,
+        Severity::Info => 6,
+    };
+    let message = format!(
+        "<{}>quantum_monitor[{}]: {} {}",
+        SYSLOG_FACILITY * 8 + level, std::process::id(), alert, serde_json::to_string(alert)?
+    );
+
+    match addr {
+        Some(addr) => {
+            let socket = std::net::UdpSocket::bind(if addr.starts_with('[') { "[::]:0" } else { "0.0.0.0:0" })?;
+            socket.send_to(message.as_bytes(), addr)?;
+        }
+        #[cfg(unix)]
+        None => {
+            let socket = std::os::unix::net::UnixDatagram::unbound()?;
+            socket.send_to(message.as_bytes(), "/dev/log")?;
+        }
+        #[cfg(not(unix))]
+        None => anyhow::bail!("local syslog is only available on Unix; give syslog=HOST:PORT"),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Finding, HttpInfo, PortStatus, ScanType};
+
+    fn open_port(service: &str, version: Option<&str>) -> PortResult {
+        let mut result = PortResult::new();
+        result.tcp_states.insert(ScanType::Syn, PortStatus::Open);
+        result.service = Some(service.to_string());
+        result.version = version.map(str::to_string);
+        result
+    }
+
+    fn run_with(ports: Vec<(u16, PortResult)>) -> MonitorRun {
+        let mut results = ScanResults { target: "host".to_string(), ..ScanResults::default() };
+        for (port, result) in ports {
+            results.open_ports.push(port);
+            results.results.insert(port, result);
+        }
+        let mut run = MonitorRun { id: "run".to_string(), started_at: Some(Utc::now()), ..MonitorRun::default() };
+        run.hosts.insert("host".to_string(), results);
+        run
+    }
+
+    /// A certificate valid until `days` from now, in the format `analyze_ssl` stores
+    fn cert_valid_for(days: i64, fingerprint: &str) -> SslInfo {
+        let not_after = Utc::now() + chrono::Duration::days(days);
+        SslInfo {
+            cert_cn: Some("www.example.com".to_string()),
+            cert_valid_to: Some(not_after.format("%b %e %H:%M:%S %Y +00:00").to_string()),
+            fingerprint: Some(fingerprint.to_string()),
+            ..SslInfo::default()
+        }
+    }
+
+    fn kinds(alerts: &[Alert]) -> Vec<(AlertKind, u16)> {
+        alerts.iter().map(|alert| (alert.kind, alert.port)).collect()
+    }
+
+    #[test]
+    fn intervals_parse_and_reject_overflow() {
+        assert_eq!(parse_interval("90"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_interval("90s"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_interval("15m"), Ok(Duration::from_secs(900)));
+        assert_eq!(parse_interval(" 6h "), Ok(Duration::from_secs(21600)));
+        assert_eq!(parse_interval("1d"), Ok(Duration::from_secs(86400)));
+        assert!(parse_interval("0").is_err());
+        assert!(parse_interval("0m").is_err());
+        assert!(parse_interval("5x").is_err());
+        assert!(parse_interval("h").is_err());
+        assert!(parse_interval("").is_err());
+        assert!(parse_interval("99999999999999999999").is_err());
+
+        let too_large = parse_interval(&format!("{}d", u64::MAX / 86400 + 1)).unwrap_err();
+        assert!(too_large.contains("too large"), "{}", too_large);
+        assert_eq!(parse_interval(&format!("{}d", u64::MAX / 86400)), Ok(Duration::from_secs(u64::MAX / 86400 * 86400)));
+    }
+
+    #[test]
+    fn alert_sinks_parse() {
+        assert_eq!(
+            "webhook=http://127.0.0.1:8080/hook".parse::<AlertSink>(),
+            Ok(AlertSink::Webhook("http://127.0.0.1:8080/hook".to_string()))
+        );
+        assert_eq!("file=alerts.jsonl".parse::<AlertSink>(), Ok(AlertSink::File(PathBuf::from("alerts.jsonl"))));
+        assert_eq!("syslog".parse::<AlertSink>(), Ok(AlertSink::Syslog(None)));
+        assert_eq!("SYSLOG=10.0.0.5:514".parse::<AlertSink>(), Ok(AlertSink::Syslog(Some("10.0.0.5:514".to_string()))));
+
+        assert!("webhook=https://hooks.example.com/".parse::<AlertSink>().unwrap_err().contains("http://"));
+        assert!("webhook=not a url".parse::<AlertSink>().is_err());
+        assert!("webhook".parse::<AlertSink>().is_err());
+        assert!("file=".parse::<AlertSink>().is_err());
+        assert!("syslog=".parse::<AlertSink>().is_err());
+        assert!("email=me@example.com".parse::<AlertSink>().is_err());
+    }
+
+    #[test]
+    fn certificate_states_follow_the_expiry_window() {
+        let now = Utc::now();
+        assert_eq!(CertState::of(&cert_valid_for(90, "a"), now, 30), CertState::Valid);
+        assert_eq!(CertState::of(&cert_valid_for(10, "a"), now, 30), CertState::Expiring);
+        assert_eq!(CertState::of(&cert_valid_for(10, "a"), now, 7), CertState::Valid);
+        assert_eq!(CertState::of(&cert_valid_for(-1, "a"), now, 30), CertState::Expired);
+
+        // Single-digit days are space padded by OpenSSL
+        let padded = SslInfo { cert_valid_to: Some("Jan  2 03:04:05 2000 +00:00".to_string()), ..SslInfo::default() };
+        assert_eq!(CertState::of(&padded, now, 30), CertState::Expired);
+
+        // Without a parsable date the scanner's own flags decide
+        let flagged = |expired, soon| SslInfo { is_expired: Some(expired), expires_soon: Some(soon), ..SslInfo::default() };
+        assert_eq!(CertState::of(&flagged(true, false), now, 30), CertState::Expired);
+        assert_eq!(CertState::of(&flagged(false, true), now, 30), CertState::Expiring);
+        assert_eq!(CertState::of(&SslInfo::default(), now, 30), CertState::Valid);
+        assert!(CertState::Valid < CertState::Expiring && CertState::Expiring < CertState::Expired);
+    }
+
+    #[test]
+    fn first_run_only_reports_certificates() {
+        let mut https = open_port("https", Some("nginx 1.18.0"));
+        https.cert_info = Some(cert_valid_for(10, "AA"));
+        let run = run_with(vec![(22, open_port("ssh", Some("OpenSSH 8.2"))), (443, https)]);
+
+        let alerts = diff_runs(None, &run, 30);
+        assert_eq!(kinds(&alerts), [(AlertKind::CertificateExpiring, 443)]);
+        assert_eq!(alerts[0].severity, Severity::Medium);
+        assert!(alerts[0].message.contains("within 30 days"), "{}", alerts[0].message);
+    }
+
+    #[test]
+    fn runs_are_diffed_per_port() {
+        let mut https = open_port("https", Some("nginx 1.18.0"));
+        https.http_info = Some(HttpInfo::default());
+        let first = run_with(vec![(22, open_port("ssh", Some("OpenSSH 8.2"))), (443, https.clone())]);
+
+        let mut upgraded = https.clone();
+        upgraded.version = Some("nginx 1.25.3".to_string());
+        let missing_hsts = Finding {
+            severity: Severity::Medium,
+            service: "https".to_string(),
+            title: "Strict-Transport-Security header missing".to_string(),
+            evidence: None,
+        };
+        upgraded.findings = vec![missing_hsts.clone(), missing_hsts];
+        let second = run_with(vec![(443, upgraded.clone()), (8080, open_port("http", None))]);
+
+        let alerts = diff_runs(Some(&first), &second, 30);
+        let mut found = kinds(&alerts);
+        found.sort_by_key(|(_, port)| *port);
+        assert_eq!(found, [
+            (AlertKind::PortClosed, 22),
+            (AlertKind::ServiceChanged, 443),
+            (AlertKind::HeaderRegression, 443),
+            (AlertKind::NewOpenPort, 8080),
+        ]);
+        let changed = alerts.iter().find(|a| a.kind == AlertKind::ServiceChanged).unwrap();
+        assert_eq!(changed.previous.as_deref(), Some("https nginx 1.18.0"));
+        assert_eq!(changed.current.as_deref(), Some("https nginx 1.25.3"));
+
+        // Nothing changed: nothing to report
+        assert!(diff_runs(Some(&second), &second, 30).is_empty());
+
+        // A service that could not be identified this time is not a change
+        let mut unidentified = upgraded;
+        unidentified.service = None;
+        unidentified.version = None;
+        let third = run_with(vec![(443, unidentified), (8080, open_port("http", None))]);
+        assert!(diff_runs(Some(&second), &third, 30).is_empty());
+    }
+
+    #[test]
+    fn failed_hosts_are_not_compared() {
+        let first = run_with(vec![(22, open_port("ssh", None))]);
+        let mut failed = run_with(vec![]);
+        failed.failures.insert("host".to_string(), "timed out".to_string());
+        assert!(diff_runs(Some(&first), &failed, 30).is_empty());
+
+        // Nor is a run against a previous run in which the host failed
+        let back = run_with(vec![(22, open_port("ssh", None)), (80, open_port("http", None))]);
+        assert!(diff_runs(Some(&failed), &back, 30).is_empty());
+    }
+
+    #[test]
+    fn certificate_alerts_fire_once_per_state() {
+        let with_cert = |cert: SslInfo| {
+            let mut https = open_port("https", None);
+            https.cert_info = Some(cert);
+            run_with(vec![(443, https)])
+        };
+
+        let expiring = with_cert(cert_valid_for(10, "AA"));
+        assert!(diff_runs(Some(&expiring), &expiring, 30).is_empty());
+
+        let expired = with_cert(cert_valid_for(-1, "AA"));
+        let alerts = diff_runs(Some(&expiring), &expired, 30);
+        assert_eq!(kinds(&alerts), [(AlertKind::CertificateExpiring, 443)]);
+        assert_eq!(alerts[0].severity, Severity::High);
+        assert!(alerts[0].message.contains("expired"));
+
+        // A replacement certificate that is also close to expiry is reported
+        let replaced = with_cert(cert_valid_for(12, "BB"));
+        assert_eq!(kinds(&diff_runs(Some(&expiring), &replaced, 30)), [(AlertKind::CertificateExpiring, 443)]);
+
+        // A renewed certificate is not
+        let renewed = with_cert(cert_valid_for(365, "CC"));
+        assert!(diff_runs(Some(&expired), &renewed, 30).is_empty());
+    }
+
+    #[test]
+    fn unreadable_runs_are_reported_and_skipped() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(runs_dir(dir.path())).unwrap();
+        let stored = MonitorRun { id: "20260101T000000Z".to_string(), ..MonitorRun::default() };
+        std::fs::write(runs_dir(dir.path()).join("20260101T000000Z.json"), serde_json::to_vec(&stored).unwrap()).unwrap();
+        let broken = runs_dir(dir.path()).join("20260102T000000Z.json");
+        std::fs::write(&broken, b"{truncated").unwrap();
+
+        let (events, mut reports) = mpsc::unbounded_channel();
+        let monitor = Monitor::new(MonitorConfig {
+            hosts: vec!["127.0.0.1".to_string()],
+            template: ScanConfig::default(),
+            interval: Duration::from_secs(60),
+            state_dir: dir.path().to_path_buf(),
+            cert_expiry_days: DEFAULT_CERT_EXPIRY_DAYS,
+            parallel: 1,
+            keep_runs: 0,
+            sinks: Vec::new(),
+            events: Some(events),
+        }).unwrap();
+        assert_eq!(monitor.previous().map(|run| run.id.as_str()), Some("20260101T000000Z"));
+
+        match reports.try_recv() {
+            Ok(MonitorEvent::UnreadableRun { path }) => assert_eq!(path, broken),
+            other => panic!("unexpected report {:?}", other),
+        }
+        assert!(reports.try_recv().is_err());
+    }
+}
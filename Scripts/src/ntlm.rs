@@ -0,0 +1,250 @@
+//! Minimal NTLM message handling
+//!
+//! Only what is needed to learn about a host without credentials: build a
+//! NEGOTIATE message and read the CHALLENGE the server sends back, whose
+//! target info names the computer, its domain and the Windows build. The
+//! exchange is never completed, so no authentication attempt is logged as a
+//! failed login on the server.
+
+use crate::models::NtlmInfo;
+
+/// Signature at the start of every NTLM message
+pub const SIGNATURE: &[u8; 8] = b"NTLMSSP\0";
+
+const NEGOTIATE_UNICODE: u32 = 0x0000_0001;
+const REQUEST_TARGET: u32 = 0x0000_0004;
+const NEGOTIATE_NTLM: u32 = 0x0000_0200;
+const NEGOTIATE_ALWAYS_SIGN: u32 = 0x0000_8000;
+const NEGOTIATE_EXTENDED_SESSIONSECURITY: u32 = 0x0008_0000;
+const NEGOTIATE_TARGET_INFO: u32 = 0x0080_0000;
+const NEGOTIATE_VERSION: u32 = 0x0200_0000;
+const NEGOTIATE_128: u32 = 0x2000_0000;
+const NEGOTIATE_KEY_EXCH: u32 = 0x4000_0000;
+const NEGOTIATE_56: u32 = 0x8000_0000;
+
+/// Build an anonymous NEGOTIATE_MESSAGE (type 1)
+pub fn negotiate_message() -> Vec<u8> {
+    let flags = NEGOTIATE_UNICODE
+        | REQUEST_TARGET
+        | NEGOTIATE_NTLM
+        | NEGOTIATE_ALWAYS_SIGN
+        | NEGOTIATE_EXTENDED_SESSIONSECURITY
+        | NEGOTIATE_TARGET_INFO
+        | NEGOTIATE_VERSION
+        | NEGOTIATE_128
+        | NEGOTIATE_KEY_EXCH
+        | NEGOTIATE_56;
+
+    let mut message = SIGNATURE.to_vec();
+    message.extend_from_slice(&1u32.to_le_bytes());
+    message.extend_from_slice(&flags.to_le_bytes());
+    message.extend_from_slice(&[0u8; 8]); // DomainNameFields
+    message.extend_from_slice(&[0u8; 8]); // WorkstationFields
+    message.extend_from_slice(&[0x0a, 0x00, 0x63, 0x45, 0x00, 0x00, 0x00, 0x0f]); // Version 10.0.17763, NTLMv15
+    message
+}
+
+/// Find and parse a CHALLENGE_MESSAGE (type 2) anywhere in `data`
+///
+/// The challenge usually arrives wrapped in another protocol (CredSSP,
+/// SPNEGO, an HTTP header), so we search for the signature rather than
+/// expecting it at offset 0.
+pub fn find_challenge(data: &[u8]) -> Option<NtlmInfo> {
+    let start = data.windows(SIGNATURE.len()).position(|w| w == SIGNATURE)?;
+    parse_challenge(&data[start..])
+}
+
+/// Parse a CHALLENGE_MESSAGE starting at the signature
+pub fn parse_challenge(message: &[u8]) -> Option<NtlmInfo> {
+    if message.get(..8)? != SIGNATURE || read_u32(message, 8)? != 2 {
+        return None;
+    }
+
+    let mut info = NtlmInfo {
+        target_name: security_buffer(message, 12).map(utf16_string).filter(|s| !s.is_empty()),
+        ..Default::default()
+    };
+
+    let flags = read_u32(message, 20)?;
+    if flags & NEGOTIATE_VERSION != 0 {
+        if let Some(version) = message.get(48..56) {
+            let build = u16::from_le_bytes([version[2], version[3]]);
+            info.os_version = Some(format!("{}.{}.{}", version[0], version[1], build));
+        }
+    }
+
+    // AV_PAIR list: AvId (u16), AvLen (u16), value
+    let target_info = security_buffer(message, 40).unwrap_or_default();
+    let mut pos = 0;
+    while let (Some(id), Some(len)) = (read_u16(target_info, pos), read_u16(target_info, pos + 2)) {
+        let value = match target_info.get(pos + 4..pos + 4 + len as usize) {
+            Some(value) => value,
+            None => break,
+        };
+        let text = || Some(utf16_string(value));
+        match id {
+            0 => break,
+            1 => info.netbios_computer = text(),
+            2 => info.netbios_domain = text(),
+            3 => info.dns_computer = text(),
+            4 => info.dns_domain = text(),
+            5 => info.dns_tree = text(),
+            _ => {}
+        }
+        pos += 4 + len as usize;
+    }
+
+    Some(info)
+}
+
+/// Friendly Windows release name for an NTLM version string
+pub fn windows_release(os_version: &str) -> Option<&'static str> {
+    let mut parts = os_version.split('.');
+    let major: u32 = parts.next()?.parse().ok()?;
+    let minor: u32 = parts.next()?.parse().ok()?;
+    let build: u32 = parts.next()?.parse().ok()?;
+
+    Some(match (major, minor) {
+        (5, 1) => "Windows XP",
+        (5, 2) => "Windows Server 2003",
+        (6, 0) => "Windows Vista / Server 2008",
+        (6, 1) => "Windows 7 / Server 2008 R2",
+        (6, 2) => "Windows 8 / Server 2012",
+        (6, 3) => "Windows 8.1 / Server 2012 R2",
+        (10, 0) if build >= 22000 => "Windows 11 / Server 2022 or later",
+        (10, 0) if build >= 20348 => "Windows Server 2022",
+        (10, 0) if build == 17763 => "Windows 10 / Server 2019",
+        (10, 0) if build == 14393 => "Windows 10 / Server 2016",
+        (10, 0) => "Windows 10 / Server 2016 or later",
+        _ => return None,
+    })
+}
+
+fn read_u16(data: &[u8], pos: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(data.get(pos..pos + 2)?.try_into().ok()?))
+}
+
+fn read_u32(data: &[u8], pos: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(data.get(pos..pos + 4)?.try_into().ok()?))
+}
+
+/// Resolve a (length, max length, offset) security buffer
+fn security_buffer(message: &[u8], pos: usize) -> Option<&[u8]> {
+    let len = read_u16(message, pos)? as usize;
+    let offset = read_u32(message, pos + 4)? as usize;
+    message.get(offset..offset + len)
+}
+
+fn utf16_string(data: &[u8]) -> String {
+    let units: Vec<u16> = data.chunks_exact(2).map(|c| u16::from_le_bytes([c[0], c[1]])).collect();
+    String::from_utf16_lossy(&units)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    fn utf16(text: &str) -> Vec<u8> {
+        text.encode_utf16().flat_map(|unit| unit.to_le_bytes()).collect()
+    }
+
+    /// Build a CHALLENGE_MESSAGE for `target` with the given AV pairs and version
+    pub(crate) fn challenge_message(target: &str, pairs: &[(u16, &str)], version: Option<[u8; 8]>) -> Vec<u8> {
+        let name = utf16(target);
+        let mut target_info = Vec::new();
+        for (id, value) in pairs {
+            let value = utf16(value);
+            target_info.extend_from_slice(&id.to_le_bytes());
+            target_info.extend_from_slice(&(value.len() as u16).to_le_bytes());
+            target_info.extend_from_slice(&value);
+        }
+        target_info.extend_from_slice(&[0; 4]); // MsvAvEOL
+
+        let flags = NEGOTIATE_UNICODE | NEGOTIATE_TARGET_INFO | if version.is_some() { NEGOTIATE_VERSION } else { 0 };
+        let name_offset = 56u32;
+        let info_offset = name_offset + name.len() as u32;
+
+        let mut message = SIGNATURE.to_vec();
+        message.extend_from_slice(&2u32.to_le_bytes());
+        message.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        message.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        message.extend_from_slice(&name_offset.to_le_bytes());
+        message.extend_from_slice(&flags.to_le_bytes());
+        message.extend_from_slice(b"\x01\x23\x45\x67\x89\xab\xcd\xef"); // server challenge
+        message.extend_from_slice(&[0; 8]);
+        message.extend_from_slice(&(target_info.len() as u16).to_le_bytes());
+        message.extend_from_slice(&(target_info.len() as u16).to_le_bytes());
+        message.extend_from_slice(&info_offset.to_le_bytes());
+        message.extend_from_slice(&version.unwrap_or([0; 8]));
+        message.extend_from_slice(&name);
+        message.extend_from_slice(&target_info);
+        message
+    }
+
+    #[test]
+    fn negotiate_asks_for_target_info() {
+        let message = negotiate_message();
+        assert_eq!(&message[..8], SIGNATURE);
+        assert_eq!(read_u32(&message, 8), Some(1));
+        let flags = read_u32(&message, 12).unwrap();
+        assert_ne!(flags & NEGOTIATE_TARGET_INFO, 0);
+        assert_ne!(flags & NEGOTIATE_VERSION, 0);
+        assert_eq!(message.len(), 40);
+    }
+
+    #[test]
+    fn challenge_names_the_host() {
+        let message = challenge_message(
+            "CORP",
+            &[(2, "CORP"), (1, "DC01"), (4, "corp.local"), (3, "dc01.corp.local"), (5, "corp.local"), (7, "ignored")],
+            Some([10, 0, 0x63, 0x45, 0, 0, 0, 15]),
+        );
+
+        // Wrapped in another protocol, as it is in CredSSP or SPNEGO
+        let mut wrapped = vec![0x30, 0x82, 0x01, 0x00, 0xa0, 0x03];
+        wrapped.extend_from_slice(&message);
+        let info = find_challenge(&wrapped).unwrap();
+
+        assert_eq!(info.target_name.as_deref(), Some("CORP"));
+        assert_eq!(info.netbios_domain.as_deref(), Some("CORP"));
+        assert_eq!(info.netbios_computer.as_deref(), Some("DC01"));
+        assert_eq!(info.dns_domain.as_deref(), Some("corp.local"));
+        assert_eq!(info.dns_computer.as_deref(), Some("dc01.corp.local"));
+        assert_eq!(info.dns_tree.as_deref(), Some("corp.local"));
+        assert_eq!(info.os_version.as_deref(), Some("10.0.17763"));
+    }
+
+    #[test]
+    fn version_needs_its_flag() {
+        let info = parse_challenge(&challenge_message("WS", &[(1, "WS")], None)).unwrap();
+        assert_eq!(info.os_version, None);
+        assert_eq!(info.netbios_computer.as_deref(), Some("WS"));
+    }
+
+    #[test]
+    fn malformed_challenges() {
+        assert!(find_challenge(b"no ntlm here").is_none());
+        assert!(parse_challenge(&negotiate_message()).is_none());
+        assert!(parse_challenge(b"NTLMSSP\0\x02\x00").is_none());
+
+        // A target info pair running past the buffer ends the list
+        let mut message = challenge_message("X", &[(1, "HOST"), (3, "host.example.com")], None);
+        let second_pair = 56 + 2 + 4 + 8;
+        message[second_pair + 2..second_pair + 4].copy_from_slice(&0x00ffu16.to_le_bytes());
+        let info = parse_challenge(&message).unwrap();
+        assert_eq!(info.netbios_computer.as_deref(), Some("HOST"));
+        assert_eq!(info.dns_computer, None);
+    }
+
+    #[test]
+    fn windows_releases() {
+        assert_eq!(windows_release("6.1.7601"), Some("Windows 7 / Server 2008 R2"));
+        assert_eq!(windows_release("10.0.14393"), Some("Windows 10 / Server 2016"));
+        assert_eq!(windows_release("10.0.17763"), Some("Windows 10 / Server 2019"));
+        assert_eq!(windows_release("10.0.20348"), Some("Windows Server 2022"));
+        assert_eq!(windows_release("10.0.22631"), Some("Windows 11 / Server 2022 or later"));
+        assert_eq!(windows_release("10.0.19045"), Some("Windows 10 / Server 2016 or later"));
+        assert_eq!(windows_release("4.0.1381"), None);
+        assert_eq!(windows_release("10.0"), None);
+    }
+}
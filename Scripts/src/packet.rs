@@ -0,0 +1,1044 @@
+//! Raw packet layer for the stealth scan types
+//!
+//! SYN, ACK, FIN, Xmas, NULL, window, UDP, mimic, TLS-echo and fragmented SYN
+//! probes are hand-built IPv4 packets whose replies are read off raw sockets.
+//! Everything that touches the wire goes through `PacketTransport`, so the
+//! probe construction, reply matching and port classification here run the
+//! same against `RawSocketTransport` (root required) and against the
+//! in-memory `simnet::SimulatedNetwork` used in tests.
+//!
+//! The codec covers what the probes need: IPv4 with fragmentation and
+//! reassembly, TCP without options, UDP, and ICMP destination-unreachable
+//! messages with the quoted header they carry.
+
+use std::collections::{BTreeMap, HashMap};
+use std::io;
+use std::net::{IpAddr, Ipv4Addr};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use futures::future::BoxFuture;
+use parking_lot::Mutex;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+
+use crate::config::FragmentConfig;
+use crate::models::{PortStatus, ScanType};
+use crate::scope::Egress;
+use crate::utils;
+
+/// IP protocol number of ICMP
+pub const PROTO_ICMP: u8 = 1;
+/// IP protocol number of TCP
+pub const PROTO_TCP: u8 = 6;
+/// IP protocol number of UDP
+pub const PROTO_UDP: u8 = 17;
+
+/// TCP FIN flag
+pub const TCP_FIN: u8 = 0x01;
+/// TCP SYN flag
+pub const TCP_SYN: u8 = 0x02;
+/// TCP RST flag
+pub const TCP_RST: u8 = 0x04;
+/// TCP PSH flag
+pub const TCP_PSH: u8 = 0x08;
+/// TCP ACK flag
+pub const TCP_ACK: u8 = 0x10;
+/// TCP URG flag
+pub const TCP_URG: u8 = 0x20;
+
+/// ICMP destination unreachable
+pub const ICMP_UNREACHABLE: u8 = 3;
+/// Destination-unreachable code for "port unreachable"
+pub const ICMP_PORT_UNREACHABLE: u8 = 3;
+/// Destination-unreachable codes that mean something on the path refused
+/// the packet: network, host and protocol unreachable, and the three
+/// "administratively prohibited" codes. Port unreachable (3) is left out:
+/// for UDP it is the closed port's own answer.
+pub const ICMP_FILTERED_CODES: &[u8] = &[0, 1, 2, 9, 10, 13];
+
+/// How long fragments of a reply are kept while the rest is awaited
+const REPLY_REASSEMBLY_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Payload carried by fragmented SYN probes so there is something to split
+const FRAG_PAYLOAD_LEN: usize = 200;
+
+/// TLS record sent with TLS-echo probes (the start of a ServerHello)
+const TLS_ECHO_PAYLOAD: &[u8] = b"\x16\x03\x03\x00\x2f\x02\x00\x00\x2b\x03\x03";
+
+/// Longest mimic payload sent with a SYN
+const MIMIC_PAYLOAD_LEN: usize = 16;
+
+/// Protocol payloads for mimic probes, as sent by the original Python scanner
+const MIMIC_PAYLOADS: &[(&str, &[u8])] = &[
+    ("HTTP", b"HTTP/1.1 200 OK\r\nServer: Apache\r\nContent-Length: 0\r\n\r\n"),
+    ("SSH", b"SSH-2.0-OpenSSH_8.2p1\r\n"),
+    ("FTP", b"220 FTP Server Ready\r\n"),
+    ("SMTP", b"220 mail.example.com ESMTP Postfix\r\n"),
+    ("IMAP", b"* OK IMAP4rev1 Server Ready\r\n"),
+    ("POP3", b"+OK POP3 server ready\r\n"),
+    ("MySQL", b"\x4a\x00\x00\x00\x0a\x35\x2e\x37\x2e\x33\x39\x00"),
+    ("RDP", b"\x03\x00\x00\x13\x0e\xd0\x00\x00\x12\x34\x00\x02\x01\x08\x00\x01\x00\x00\x00"),
+];
+
+/// Payload a mimic probe carries for `protocol` (empty for unknown protocols)
+pub fn mimic_payload(protocol: &str) -> &'static [u8] {
+    MIMIC_PAYLOADS.iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(protocol))
+        .map(|(_, payload)| &payload[..payload.len().min(MIMIC_PAYLOAD_LEN)])
+        .unwrap_or(b"")
+}
+
+/// Sends and receives raw IPv4 packets
+///
+/// Packets are complete IPv4 datagrams or fragments, header included, in
+/// network byte order. Reading a packet consumes it, so a transport must be
+/// read by one `Prober` at a time; probes through that prober can run
+/// concurrently, as it hands every reply to the probe it answers.
+pub trait PacketTransport: Send + Sync {
+    /// Address probes are sent from
+    fn local_addr(&self) -> Ipv4Addr;
+
+    /// Put one packet on the wire
+    fn send<'a>(&'a self, packet: &'a [u8]) -> BoxFuture<'a, io::Result<()>>;
+
+    /// Wait up to `timeout` for the next incoming packet; `None` on timeout
+    ///
+    /// The packet is returned to this caller only, and may well be
+    /// unrelated traffic.
+    fn recv(&self, timeout: Duration) -> BoxFuture<'_, io::Result<Option<Vec<u8>>>>;
+}
+
+/// Internet checksum (RFC 1071) over `data`
+pub fn checksum(data: &[u8]) -> u16 {
+    let mut sum: u32 = data.chunks(2)
+        .map(|pair| u32::from(u16::from_be_bytes([pair[0], pair.get(1).copied().unwrap_or(0)])))
+        .sum();
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+/// Checksum of a TCP/UDP segment including the IPv4 pseudo-header
+fn transport_checksum(src: Ipv4Addr, dst: Ipv4Addr, protocol: u8, segment: &[u8]) -> u16 {
+    let mut data = Vec::with_capacity(12 + segment.len());
+    data.extend_from_slice(&src.octets());
+    data.extend_from_slice(&dst.octets());
+    data.extend_from_slice(&[0, protocol]);
+    data.extend_from_slice(&(segment.len() as u16).to_be_bytes());
+    data.extend_from_slice(segment);
+    checksum(&data)
+}
+
+/// An IPv4 datagram or fragment
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ipv4Packet {
+    /// Source address
+    pub src: Ipv4Addr,
+    /// Destination address
+    pub dst: Ipv4Addr,
+    /// Identification field (ties fragments together)
+    pub id: u16,
+    /// Time to live
+    pub ttl: u8,
+    /// Protocol of the payload
+    pub protocol: u8,
+    /// Don't Fragment flag
+    pub dont_fragment: bool,
+    /// More Fragments flag
+    pub more_fragments: bool,
+    /// Offset of this fragment's payload in bytes (a multiple of 8)
+    pub fragment_offset: u16,
+    /// Payload after the header
+    pub payload: Vec<u8>,
+}
+
+impl Ipv4Packet {
+    /// Create an unfragmented packet with TTL 64
+    pub fn new(src: Ipv4Addr, dst: Ipv4Addr, protocol: u8, payload: Vec<u8>) -> Self {
+        Self {
+            src,
+            dst,
+            id: 0,
+            ttl: 64,
+            protocol,
+            dont_fragment: false,
+            more_fragments: false,
+            fragment_offset: 0,
+            payload,
+        }
+    }
+
+    /// Whether this is a piece of a larger datagram
+    pub fn is_fragment(&self) -> bool {
+        self.more_fragments || self.fragment_offset > 0
+    }
+
+    /// Encode with a 20-byte header and a correct header checksum
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let total = 20 + self.payload.len();
+        let mut flags_offset = self.fragment_offset / 8;
+        if self.dont_fragment {
+            flags_offset |= 0x4000;
+        }
+        if self.more_fragments {
+            flags_offset |= 0x2000;
+        }
+
+        let mut bytes = Vec::with_capacity(total);
+        bytes.extend_from_slice(&[0x45, 0]);
+        bytes.extend_from_slice(&(total as u16).to_be_bytes());
+        bytes.extend_from_slice(&self.id.to_be_bytes());
+        bytes.extend_from_slice(&flags_offset.to_be_bytes());
+        bytes.extend_from_slice(&[self.ttl, self.protocol, 0, 0]);
+        bytes.extend_from_slice(&self.src.octets());
+        bytes.extend_from_slice(&self.dst.octets());
+        let sum = checksum(&bytes[..20]);
+        bytes[10..12].copy_from_slice(&sum.to_be_bytes());
+        bytes.extend_from_slice(&self.payload);
+        bytes
+    }
+
+    /// Decode a packet, ignoring IP options; `None` if it isn't IPv4
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        if data.len() < 20 || data[0] >> 4 != 4 {
+            return None;
+        }
+        let header_len = usize::from(data[0] & 0x0f) * 4;
+        // Some raw socket implementations hand back a total length that
+        // doesn't match the buffer, so trust whichever is smaller
+        let total = usize::from(u16::from_be_bytes([data[2], data[3]])).min(data.len());
+        if header_len < 20 || total < header_len {
+            return None;
+        }
+        let flags_offset = u16::from_be_bytes([data[6], data[7]]);
+        Some(Self {
+            src: Ipv4Addr::new(data[12], data[13], data[14], data[15]),
+            dst: Ipv4Addr::new(data[16], data[17], data[18], data[19]),
+            id: u16::from_be_bytes([data[4], data[5]]),
+            ttl: data[8],
+            protocol: data[9],
+            dont_fragment: flags_offset & 0x4000 != 0,
+            more_fragments: flags_offset & 0x2000 != 0,
+            fragment_offset: (flags_offset & 0x1fff) * 8,
+            payload: data[header_len..total].to_vec(),
+        })
+    }
+
+    /// Split into fragments whose payloads have the given sizes
+    ///
+    /// Every size but the last is rounded down to a multiple of 8 (at least
+    /// 8), as fragment offsets require; whatever is left over after the sizes
+    /// run out goes into the final fragment.
+    pub fn fragment(&self, sizes: &[usize]) -> Vec<Ipv4Packet> {
+        let mut fragments = Vec::new();
+        let mut offset = 0;
+        for &size in sizes {
+            if offset >= self.payload.len() {
+                break;
+            }
+            let size = (size / 8 * 8).max(8);
+            let end = (offset + size).min(self.payload.len());
+            fragments.push(self.piece(offset, end));
+            offset = end;
+        }
+        if offset < self.payload.len() {
+            fragments.push(self.piece(offset, self.payload.len()));
+        }
+        fragments
+    }
+
+    fn piece(&self, start: usize, end: usize) -> Ipv4Packet {
+        Ipv4Packet {
+            dont_fragment: false,
+            more_fragments: end < self.payload.len() || self.more_fragments,
+            fragment_offset: self.fragment_offset + start as u16,
+            payload: self.payload[start..end].to_vec(),
+            ..self.clone()
+        }
+    }
+}
+
+/// Collects fragments until whole datagrams can be rebuilt
+#[derive(Debug, Default)]
+pub struct Reassembler {
+    pending: HashMap<(Ipv4Addr, Ipv4Addr, u16, u8), PartialDatagram>,
+}
+
+#[derive(Debug)]
+struct PartialDatagram {
+    first_seen: Instant,
+    header: Ipv4Packet,
+    pieces: BTreeMap<usize, Vec<u8>>,
+    total: Option<usize>,
+}
+
+impl Reassembler {
+    /// Add a packet; returns the complete datagram once all of it has arrived
+    ///
+    /// Unfragmented packets are returned as they are. Where fragments
+    /// overlap, the data that arrived first wins.
+    pub fn push(&mut self, packet: Ipv4Packet) -> Option<Ipv4Packet> {
+        if !packet.is_fragment() {
+            return Some(packet);
+        }
+        let key = (packet.src, packet.dst, packet.id, packet.protocol);
+        let partial = self.pending.entry(key).or_insert_with(|| PartialDatagram {
+            first_seen: Instant::now(),
+            header: packet.clone(),
+            pieces: BTreeMap::new(),
+            total: None,
+        });
+        let offset = usize::from(packet.fragment_offset);
+        if !packet.more_fragments {
+            partial.total = Some(offset + packet.payload.len());
+        }
+        if offset == 0 {
+            partial.header = packet.clone();
+        }
+        partial.pieces.entry(offset).or_insert(packet.payload);
+
+        let total = partial.total?;
+        let mut payload = Vec::with_capacity(total);
+        for (&offset, data) in &partial.pieces {
+            if offset > payload.len() {
+                return None;
+            }
+            let skip = payload.len() - offset;
+            if skip < data.len() {
+                payload.extend_from_slice(&data[skip..]);
+            }
+        }
+        if payload.len() < total {
+            return None;
+        }
+        payload.truncate(total);
+
+        let partial = self.pending.remove(&key)?;
+        Some(Ipv4Packet {
+            more_fragments: false,
+            fragment_offset: 0,
+            payload,
+            ..partial.header
+        })
+    }
+
+    /// Drop incomplete datagrams older than `max_age`
+    pub fn expire(&mut self, max_age: Duration) {
+        self.pending.retain(|_, partial| partial.first_seen.elapsed() < max_age);
+    }
+
+    /// Number of datagrams still waiting for fragments
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+}
+
+/// A TCP segment without options
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TcpSegment {
+    /// Source port
+    pub src_port: u16,
+    /// Destination port
+    pub dst_port: u16,
+    /// Sequence number
+    pub seq: u32,
+    /// Acknowledgement number
+    pub ack: u32,
+    /// Flag bits (`TCP_SYN`, `TCP_ACK`, ...)
+    pub flags: u8,
+    /// Advertised window
+    pub window: u16,
+    /// Data after the header
+    pub payload: Vec<u8>,
+}
+
+impl TcpSegment {
+    /// Encode with the checksum computed for the given addresses
+    pub fn to_bytes(&self, src: Ipv4Addr, dst: Ipv4Addr) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(20 + self.payload.len());
+        bytes.extend_from_slice(&self.src_port.to_be_bytes());
+        bytes.extend_from_slice(&self.dst_port.to_be_bytes());
+        bytes.extend_from_slice(&self.seq.to_be_bytes());
+        bytes.extend_from_slice(&self.ack.to_be_bytes());
+        bytes.extend_from_slice(&[5 << 4, self.flags]);
+        bytes.extend_from_slice(&self.window.to_be_bytes());
+        bytes.extend_from_slice(&[0, 0, 0, 0]);
+        bytes.extend_from_slice(&self.payload);
+        let sum = transport_checksum(src, dst, PROTO_TCP, &bytes);
+        bytes[16..18].copy_from_slice(&sum.to_be_bytes());
+        bytes
+    }
+
+    /// Decode a segment (options are skipped)
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        if data.len() < 20 {
+            return None;
+        }
+        let header_len = usize::from(data[12] >> 4) * 4;
+        if header_len < 20 || header_len > data.len() {
+            return None;
+        }
+        Some(Self {
+            src_port: u16::from_be_bytes([data[0], data[1]]),
+            dst_port: u16::from_be_bytes([data[2], data[3]]),
+            seq: u32::from_be_bytes([data[4], data[5], data[6], data[7]]),
+            ack: u32::from_be_bytes([data[8], data[9], data[10], data[11]]),
+            flags: data[13],
+            window: u16::from_be_bytes([data[14], data[15]]),
+            payload: data[header_len..].to_vec(),
+        })
+    }
+}
+
+/// A UDP datagram
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UdpDatagram {
+    /// Source port
+    pub src_port: u16,
+    /// Destination port
+    pub dst_port: u16,
+    /// Data after the header
+    pub payload: Vec<u8>,
+}
+
+impl UdpDatagram {
+    /// Encode with the checksum computed for the given addresses
+    pub fn to_bytes(&self, src: Ipv4Addr, dst: Ipv4Addr) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(8 + self.payload.len());
+        bytes.extend_from_slice(&self.src_port.to_be_bytes());
+        bytes.extend_from_slice(&self.dst_port.to_be_bytes());
+        bytes.extend_from_slice(&((8 + self.payload.len()) as u16).to_be_bytes());
+        bytes.extend_from_slice(&[0, 0]);
+        bytes.extend_from_slice(&self.payload);
+        let sum = match transport_checksum(src, dst, PROTO_UDP, &bytes) {
+            0 => 0xffff,
+            sum => sum,
+        };
+        bytes[6..8].copy_from_slice(&sum.to_be_bytes());
+        bytes
+    }
+
+    /// Decode a datagram
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        if data.len() < 8 {
+            return None;
+        }
+        let len = usize::from(u16::from_be_bytes([data[4], data[5]])).clamp(8, data.len());
+        Some(Self {
+            src_port: u16::from_be_bytes([data[0], data[1]]),
+            dst_port: u16::from_be_bytes([data[2], data[3]]),
+            payload: data[8..len].to_vec(),
+        })
+    }
+}
+
+/// An ICMP message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IcmpMessage {
+    /// Message type
+    pub icmp_type: u8,
+    /// Message code
+    pub code: u8,
+    /// The four bytes after the checksum (unused for unreachables)
+    pub rest: [u8; 4],
+    /// Message body; for errors, the offending packet's header and first 8 bytes
+    pub data: Vec<u8>,
+}
+
+impl IcmpMessage {
+    /// Destination-unreachable error about `original`
+    pub fn unreachable(code: u8, original: &Ipv4Packet) -> Self {
+        let quoted = original.to_bytes();
+        Self {
+            icmp_type: ICMP_UNREACHABLE,
+            code,
+            rest: [0; 4],
+            data: quoted[..quoted.len().min(28)].to_vec(),
+        }
+    }
+
+    /// Encode with checksum
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![self.icmp_type, self.code, 0, 0];
+        bytes.extend_from_slice(&self.rest);
+        bytes.extend_from_slice(&self.data);
+        let sum = checksum(&bytes);
+        bytes[2..4].copy_from_slice(&sum.to_be_bytes());
+        bytes
+    }
+
+    /// Decode a message
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        if data.len() < 8 {
+            return None;
+        }
+        Some(Self {
+            icmp_type: data[0],
+            code: data[1],
+            rest: [data[4], data[5], data[6], data[7]],
+            data: data[8..].to_vec(),
+        })
+    }
+
+    /// The packet quoted by an error message, with the transport ports if present
+    pub fn quoted(&self) -> Option<(Ipv4Packet, Option<(u16, u16)>)> {
+        let packet = Ipv4Packet::parse(&self.data)?;
+        let ports = (packet.payload.len() >= 4 && packet.fragment_offset == 0).then(|| (
+            u16::from_be_bytes([packet.payload[0], packet.payload[1]]),
+            u16::from_be_bytes([packet.payload[2], packet.payload[3]]),
+        ));
+        Some((packet, ports))
+    }
+}
+
+/// What came back for a probe
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Reply {
+    /// A TCP segment from the probed port
+    Tcp {
+        /// Flag bits
+        flags: u8,
+        /// Advertised window
+        window: u16,
+        /// TTL the reply arrived with
+        ttl: u8,
+    },
+    /// A UDP datagram from the probed port
+    Udp {
+        /// TTL the reply arrived with
+        ttl: u8,
+    },
+    /// An ICMP destination-unreachable error about the probe
+    Icmp {
+        /// ICMP code
+        code: u8,
+        /// Router or host that sent the error
+        from: Ipv4Addr,
+        /// TTL the error arrived with
+        ttl: u8,
+    },
+}
+
+impl Reply {
+    /// Flag names of a TCP reply, e.g. "SYN,ACK"
+    pub fn describe(&self) -> String {
+        match self {
+            Reply::Tcp { flags, .. } => flag_names(*flags),
+            Reply::Udp { .. } => "UDP".to_string(),
+            Reply::Icmp { code, .. } => format!("ICMP unreachable code {}", code),
+        }
+    }
+}
+
+/// Readable names of TCP flag bits
+pub fn flag_names(flags: u8) -> String {
+    let names: Vec<&str> = [
+        (TCP_SYN, "SYN"), (TCP_ACK, "ACK"), (TCP_RST, "RST"),
+        (TCP_FIN, "FIN"), (TCP_PSH, "PSH"), (TCP_URG, "URG"),
+    ].iter().filter(|(bit, _)| flags & bit != 0).map(|(_, name)| *name).collect();
+    if names.is_empty() { "none".to_string() } else { names.join(",") }
+}
+
+/// Port state implied by a probe's reply (or silence)
+///
+/// Returns `None` for scan types that don't use raw packets (SSL). The rules
+/// follow the usual conventions: SYN-style probes are open on SYN/ACK and
+/// closed on RST; FIN, Xmas and NULL can only prove a port closed; ACK only
+/// tells filtered from unfiltered; window scans read the RST's window; and
+/// UDP is closed only on ICMP port unreachable. ICMP unreachables with the
+/// filtering codes mean filtered for every type, as does port unreachable
+/// for a TCP probe (the usual firewall "reject"); other ICMP errors prove
+/// nothing and count as silence.
+pub fn classify(scan_type: ScanType, reply: Option<&Reply>) -> Option<PortStatus> {
+    if scan_type == ScanType::Ssl {
+        return None;
+    }
+    let reply = match reply {
+        Some(Reply::Icmp { code, .. }) if scan_type == ScanType::Udp && *code == ICMP_PORT_UNREACHABLE => reply,
+        Some(Reply::Icmp { code, .. }) if ICMP_FILTERED_CODES.contains(code) || *code == ICMP_PORT_UNREACHABLE => {
+            return Some(PortStatus::Filtered);
+        }
+        Some(Reply::Icmp { .. }) => None,
+        _ => reply,
+    };
+    let rst = matches!(reply, Some(Reply::Tcp { flags, .. }) if flags & TCP_RST != 0);
+    let status = match scan_type {
+        ScanType::Syn | ScanType::Mimic | ScanType::Frag | ScanType::TlsEcho => match reply {
+            Some(Reply::Tcp { flags, .. }) if flags & (TCP_SYN | TCP_ACK) == TCP_SYN | TCP_ACK => PortStatus::Open,
+            _ if rst => PortStatus::Closed,
+            _ => PortStatus::Filtered,
+        },
+        ScanType::Ack => if rst { PortStatus::Unfiltered } else { PortStatus::Filtered },
+        ScanType::Fin | ScanType::Xmas | ScanType::Null => if rst { PortStatus::Closed } else { PortStatus::OpenFiltered },
+        ScanType::Window => match reply {
+            Some(Reply::Tcp { window, .. }) if rst => if *window > 0 { PortStatus::Open } else { PortStatus::Closed },
+            _ => PortStatus::Filtered,
+        },
+        ScanType::Udp => match reply {
+            Some(Reply::Udp { .. }) => PortStatus::Open,
+            Some(Reply::Icmp { .. }) => PortStatus::Closed,
+            _ => PortStatus::OpenFiltered,
+        },
+        ScanType::Ssl => return None,
+    };
+    Some(status)
+}
+
+/// Settings for raw probes
+#[derive(Debug, Clone)]
+pub struct ProbeOptions {
+    /// How long to wait for a reply to each attempt
+    pub timeout: Duration,
+    /// Extra attempts when a probe gets no reply (lost packets look like silence)
+    pub retries: u32,
+    /// TTL of outgoing packets
+    pub ttl: u8,
+    /// Fragment sizes and delays for fragmented SYN probes
+    pub fragmentation: FragmentConfig,
+    /// Payload carried by mimic probes
+    pub mimic_payload: Vec<u8>,
+}
+
+impl Default for ProbeOptions {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(2),
+            retries: 2,
+            ttl: 64,
+            fragmentation: FragmentConfig::default(),
+            mimic_payload: mimic_payload("HTTP").to_vec(),
+        }
+    }
+}
+
+/// Result of probing one port with one scan type
+#[derive(Debug, Clone)]
+pub struct ProbeOutcome {
+    /// Port state derived from the reply
+    pub status: PortStatus,
+    /// The reply, if anything came back
+    pub reply: Option<Reply>,
+    /// Attempts made (more than one when earlier ones went unanswered)
+    pub attempts: u32,
+    /// Local port the last raw probe was sent from (None for socket probes)
+    pub local_port: Option<u16>,
+}
+
+/// A probe waiting for its reply
+struct Waiter {
+    target: Ipv4Addr,
+    protocol: u8,
+    src_port: u16,
+    port: u16,
+    replies: mpsc::UnboundedSender<Reply>,
+}
+
+/// Removes a waiter once its probe stops listening, however that happens
+struct WaiterGuard<'a> {
+    waiters: &'a Mutex<HashMap<u64, Waiter>>,
+    id: u64,
+}
+
+impl Drop for WaiterGuard<'_> {
+    fn drop(&mut self) {
+        self.waiters.lock().remove(&self.id);
+    }
+}
+
+/// Sends raw probes over a transport and classifies the replies
+///
+/// Probes can run concurrently: whichever one is waiting reads the
+/// transport for all of them and passes each reply to the probe it answers.
+pub struct Prober {
+    transport: Arc<dyn PacketTransport>,
+    options: ProbeOptions,
+    egress: Egress,
+    logger: Option<Arc<utils::EnhancedLogger>>,
+    reader: tokio::sync::Mutex<Reassembler>,
+    waiters: Mutex<HashMap<u64, Waiter>>,
+    next_waiter: AtomicU64,
+}
+
+impl Prober {
+    /// Create a prober confined to the scope held by `egress`
+    ///
+    /// The prober consumes what `transport` receives, so it should be the
+    /// transport's only reader.
+    pub fn new(transport: Arc<dyn PacketTransport>, options: ProbeOptions, egress: Egress, logger: Option<Arc<utils::EnhancedLogger>>) -> Self {
+        Self {
+            transport,
+            options,
+            egress,
+            logger,
+            reader: tokio::sync::Mutex::new(Reassembler::default()),
+            waiters: Mutex::new(HashMap::new()),
+            next_waiter: AtomicU64::new(0),
+        }
+    }
+
+    /// Address probes are sent from
+    pub fn local_addr(&self) -> Ipv4Addr {
+        self.transport.local_addr()
+    }
+
+    fn log(&self, level: &str, message: &str) {
+        if let Some(logger) = &self.logger {
+            logger.log(level, message);
+        }
+    }
+
+    /// Probe `target:port` with `scan_type`
+    ///
+    /// Returns `Ok(None)` for scan types that don't use raw packets. Fails
+    /// with `PermissionDenied` when the target is outside the prober's scope,
+    /// and with `Interrupted` when the scan control stops the probe.
+    pub async fn probe(&self, target: Ipv4Addr, port: u16, scan_type: ScanType) -> io::Result<Option<ProbeOutcome>> {
+        if classify(scan_type, None).is_none() {
+            return Ok(None);
+        }
+        if !self.egress.permit_probe(&target.to_string()) {
+            return Err(io::Error::new(io::ErrorKind::PermissionDenied, format!("{} is out of scope", target)));
+        }
+
+        let mut attempts = 0;
+        let mut src_port;
+        let reply = loop {
+            attempts += 1;
+            // Every send, retries included, waits for the scan control
+            self.egress.checkpoint(IpAddr::V4(target)).await?;
+            src_port = rand::thread_rng().gen_range(1024..=65535);
+            let seq: u32 = rand::random();
+            let reply = self.attempt(target, port, scan_type, src_port, seq).await?;
+            if reply.is_some() || attempts > self.options.retries {
+                break reply;
+            }
+            self.log("DEBUG", &format!("No reply to {:?} probe of {}:{}, retrying", scan_type, target, port));
+            if let Some(control) = self.egress.control() {
+                control.record_retransmit();
+            }
+        };
+
+        let status = classify(scan_type, reply.as_ref()).unwrap_or(PortStatus::Filtered);
+        Ok(Some(ProbeOutcome { status, reply, attempts, local_port: Some(src_port) }))
+    }
+
+    /// Send one probe and wait for the matching reply
+    async fn attempt(&self, target: Ipv4Addr, port: u16, scan_type: ScanType, src_port: u16, seq: u32) -> io::Result<Option<Reply>> {
+        let local = self.transport.local_addr();
+        let (protocol, segment, flags) = self.build(scan_type, local, target, src_port, port, seq);
+
+        // Listen before sending, in case another probe reads the reply first
+        let (replies, mut inbox) = mpsc::unbounded_channel();
+        let id = self.next_waiter.fetch_add(1, Ordering::Relaxed);
+        self.waiters.lock().insert(id, Waiter { target, protocol, src_port, port, replies });
+        let _waiter = WaiterGuard { waiters: &self.waiters, id };
+
+        let mut packet = Ipv4Packet::new(local, target, protocol, segment);
+        packet.id = rand::random();
+        packet.ttl = self.options.ttl;
+
+        let frag = &self.options.fragmentation;
+        let packets = if scan_type == ScanType::Frag {
+            packet.fragment(&fragment_sizes(frag, packet.payload.len()))
+        } else {
+            vec![packet]
+        };
+
+        let protocol_name = if protocol == PROTO_TCP { "TCP" } else { "UDP" };
+        for (i, fragment) in packets.iter().enumerate() {
+            let bytes = fragment.to_bytes();
+            self.egress.record_probe(&local.to_string(), &format!("{}:{}", target, port), protocol_name, Some(&flags), &bytes);
+            self.transport.send(&bytes).await?;
+            if i + 1 < packets.len() {
+                let delay = rand::thread_rng().gen_range(frag.min_delay..=frag.max_delay.max(frag.min_delay));
+                tokio::time::sleep(Duration::from_secs_f64(delay)).await;
+            }
+        }
+
+        let timeout = if scan_type == ScanType::Frag {
+            Duration::from_secs(frag.timeout).max(self.options.timeout)
+        } else {
+            self.options.timeout
+        };
+        let reply = self.await_reply(&mut inbox, timeout).await?;
+
+        // Tear down the half-open connection so the target doesn't keep retrying
+        if matches!(reply, Some(Reply::Tcp { flags, .. }) if flags & TCP_SYN != 0) {
+            let rst = TcpSegment { src_port, dst_port: port, seq: seq.wrapping_add(1), ack: 0, flags: TCP_RST, window: 0, payload: Vec::new() };
+            let mut packet = Ipv4Packet::new(local, target, PROTO_TCP, rst.to_bytes(local, target));
+            packet.ttl = self.options.ttl;
+            let bytes = packet.to_bytes();
+            self.egress.record_probe(&local.to_string(), &format!("{}:{}", target, port), "TCP", Some("RST"), &bytes);
+            self.transport.send(&bytes).await?;
+        }
+        Ok(reply)
+    }
+
+    /// Transport payload and audit flags of a probe
+    fn build(&self, scan_type: ScanType, local: Ipv4Addr, target: Ipv4Addr, src_port: u16, port: u16, seq: u32) -> (u8, Vec<u8>, String) {
+        if scan_type == ScanType::Udp {
+            let datagram = UdpDatagram { src_port, dst_port: port, payload: Vec::new() };
+            return (PROTO_UDP, datagram.to_bytes(local, target), "UDP".to_string());
+        }
+        let (flags, payload) = match scan_type {
+            ScanType::Ack | ScanType::Window => (TCP_ACK, Vec::new()),
+            ScanType::Fin => (TCP_FIN, Vec::new()),
+            ScanType::Xmas => (TCP_FIN | TCP_PSH | TCP_URG, Vec::new()),
+            ScanType::Null => (0, Vec::new()),
+            ScanType::Mimic => (TCP_SYN, self.options.mimic_payload.clone()),
+            ScanType::TlsEcho => (TCP_SYN, TLS_ECHO_PAYLOAD.to_vec()),
+            ScanType::Frag => (TCP_SYN, vec![b'A'; FRAG_PAYLOAD_LEN]),
+            _ => (TCP_SYN, Vec::new()),
+        };
+        let ack = if flags & TCP_ACK != 0 { rand::random() } else { 0 };
+        let segment = TcpSegment { src_port, dst_port: port, seq, ack, flags, window: 1024, payload };
+        (PROTO_TCP, segment.to_bytes(local, target), flag_names(flags))
+    }
+
+    /// Wait until a reply reaches `inbox` or the time is up
+    ///
+    /// While no other probe is reading the transport this one does, routing
+    /// whatever arrives to the waiting probes, itself included.
+    async fn await_reply(&self, inbox: &mut mpsc::UnboundedReceiver<Reply>, timeout: Duration) -> io::Result<Option<Reply>> {
+        let deadline = tokio::time::Instant::now() + timeout;
+        loop {
+            let remaining = deadline.saturating_duration_since(tokio::time::Instant::now());
+            if remaining.is_zero() {
+                return Ok(None);
+            }
+            tokio::select! {
+                biased;
+                reply = inbox.recv() => return Ok(reply),
+                mut reassembler = self.reader.lock() => {
+                    if let Ok(reply) = inbox.try_recv() {
+                        return Ok(Some(reply));
+                    }
+                    let raw = match self.transport.recv(remaining).await? {
+                        Some(raw) => raw,
+                        None => continue,
+                    };
+                    reassembler.expire(REPLY_REASSEMBLY_TIMEOUT);
+                    if let Some(packet) = Ipv4Packet::parse(&raw).and_then(|packet| reassembler.push(packet)) {
+                        self.route(&packet);
+                    }
+                }
+                _ = tokio::time::sleep(remaining) => return Ok(None),
+            }
+        }
+    }
+
+    /// Hand `packet` to the probe it answers; anything else is dropped
+    fn route(&self, packet: &Ipv4Packet) {
+        let waiters = self.waiters.lock();
+        for waiter in waiters.values() {
+            if let Some(reply) = match_reply(packet, waiter.target, waiter.protocol, waiter.src_port, waiter.port) {
+                let _ = waiter.replies.send(reply);
+                return;
+            }
+        }
+    }
+}
+
+/// The reply `packet` represents for a probe from `src_port` to `target:port`, if any
+pub fn match_reply(packet: &Ipv4Packet, target: Ipv4Addr, protocol: u8, src_port: u16, port: u16) -> Option<Reply> {
+    match packet.protocol {
+        PROTO_TCP if protocol == PROTO_TCP && packet.src == target => {
+            let segment = TcpSegment::parse(&packet.payload)?;
+            (segment.src_port == port && segment.dst_port == src_port)
+                .then_some(Reply::Tcp { flags: segment.flags, window: segment.window, ttl: packet.ttl })
+        }
+        PROTO_UDP if protocol == PROTO_UDP && packet.src == target => {
+            let datagram = UdpDatagram::parse(&packet.payload)?;
+            (datagram.src_port == port && datagram.dst_port == src_port)
+                .then_some(Reply::Udp { ttl: packet.ttl })
+        }
+        PROTO_ICMP => {
+            let message = IcmpMessage::parse(&packet.payload)?;
+            if message.icmp_type != ICMP_UNREACHABLE {
+                return None;
+            }
+            // Errors may come from a router on the way, so match on the quoted probe
+            let (quoted, ports) = message.quoted()?;
+            (quoted.dst == target && quoted.protocol == protocol && ports == Some((src_port, port)))
+                .then_some(Reply::Icmp { code: message.code, from: packet.src, ttl: packet.ttl })
+        }
+        _ => None,
+    }
+}
+
+/// Payload sizes for the fragments of a fragmented SYN probe
+///
+/// With `two_frags` the first fragment holds at least `first_min_size` bytes
+/// and the second the rest; otherwise sizes are drawn between `min_size` and
+/// `max_size`, the first one no smaller than `first_min_size`.
+pub fn fragment_sizes(config: &FragmentConfig, total: usize) -> Vec<usize> {
+    let min = usize::from(config.min_size.max(8));
+    let max = usize::from(config.max_size).max(min);
+    let first_min = usize::from(config.first_min_size);
+    if config.two_frags {
+        return vec![first_min.max(min).min(total)];
+    }
+
+    let mut rng = rand::thread_rng();
+    let mut sizes = Vec::new();
+    let mut covered = 0;
+    while covered < total {
+        let mut size = rng.gen_range(min..=max);
+        if sizes.is_empty() {
+            size = size.max(first_min);
+        }
+        sizes.push(size);
+        covered += size / 8 * 8;
+    }
+    sizes
+}
+
+/// Raw sockets towards `target`, on platforms that have them
+#[cfg(unix)]
+pub fn raw_transport(target: Ipv4Addr) -> io::Result<Arc<dyn PacketTransport>> {
+    Ok(Arc::new(RawSocketTransport::for_target(target)?))
+}
+
+/// Raw sockets towards `target`, on platforms that have them
+#[cfg(not(unix))]
+pub fn raw_transport(_target: Ipv4Addr) -> io::Result<Arc<dyn PacketTransport>> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "raw sockets are not supported on this platform"))
+}
+
+/// Transport over raw IPv4 sockets (root or CAP_NET_RAW required)
+///
+/// Probes go out through an IPPROTO_RAW socket, so the header we build is
+/// sent as is, fragments included. Replies are read from one raw socket per
+/// protocol (TCP, UDP, ICMP); the kernel hands every such packet to each of
+/// them, so unrelated traffic has to be filtered out by the caller.
+#[cfg(unix)]
+pub struct RawSocketTransport {
+    local: Ipv4Addr,
+    sender: socket2::Socket,
+    receivers: Vec<tokio::io::unix::AsyncFd<socket2::Socket>>,
+}
+
+#[cfg(unix)]
+impl RawSocketTransport {
+    /// Open the raw sockets, sending from `local`
+    pub fn new(local: Ipv4Addr) -> io::Result<Self> {
+        use socket2::{Domain, Protocol, Socket, Type};
+
+        // IPPROTO_RAW implies IP_HDRINCL: the kernel sends our header untouched
+        let sender = Socket::new(Domain::IPV4, Type::RAW, Some(Protocol::from(255)))?;
+        let mut receivers = Vec::new();
+        for protocol in [Protocol::TCP, Protocol::UDP, Protocol::ICMPV4] {
+            let socket = Socket::new(Domain::IPV4, Type::RAW, Some(protocol))?;
+            socket.set_nonblocking(true)?;
+            receivers.push(tokio::io::unix::AsyncFd::new(socket)?);
+        }
+        Ok(Self { local, sender, receivers })
+    }
+
+    /// Open the raw sockets, sending from the address that routes to `target`
+    pub fn for_target(target: Ipv4Addr) -> io::Result<Self> {
+        // Connecting a UDP socket sends nothing but makes the kernel pick the source
+        let probe = std::net::UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))?;
+        probe.connect((target, 9))?;
+        match probe.local_addr()?.ip() {
+            std::net::IpAddr::V4(local) => Self::new(local),
+            std::net::IpAddr::V6(_) => Err(io::Error::new(io::ErrorKind::InvalidInput, "no IPv4 route to target")),
+        }
+    }
+}
+
+#[cfg(unix)]
+impl PacketTransport for RawSocketTransport {
+    fn local_addr(&self) -> Ipv4Addr {
+        self.local
+    }
+
+    fn send<'a>(&'a self, packet: &'a [u8]) -> BoxFuture<'a, io::Result<()>> {
+        Box::pin(async move {
+            let dst = Ipv4Packet::parse(packet)
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "not an IPv4 packet"))?
+                .dst;
+            let addr = socket2::SockAddr::from(std::net::SocketAddrV4::new(dst, 0));
+            self.sender.send_to(packet, &addr)?;
+            Ok(())
+        })
+    }
+
+    fn recv(&self, timeout: Duration) -> BoxFuture<'_, io::Result<Option<Vec<u8>>>> {
+        Box::pin(async move {
+            let reads = self.receivers.iter().map(|fd| Box::pin(read_raw(fd)));
+            match tokio::time::timeout(timeout, futures::future::select_all(reads)).await {
+                Ok((result, _, _)) => result.map(Some),
+                Err(_) => Ok(None),
+            }
+        })
+    }
+}
+
+#[cfg(unix)]
+async fn read_raw(fd: &tokio::io::unix::AsyncFd<socket2::Socket>) -> io::Result<Vec<u8>> {
+    use std::io::Read;
+
+    let mut buffer = vec![0u8; 65535];
+    loop {
+        let mut guard = fd.readable().await?;
+        match guard.try_io(|socket| socket.get_ref().read(&mut buffer)) {
+            Ok(result) => {
+                let n = result?;
+                buffer.truncate(n);
+                return Ok(buffer);
+            }
+            Err(_would_block) => continue,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn packets_round_trip_and_reassemble_out_of_order() {
+        let packet = Ipv4Packet { id: 9, ..Ipv4Packet::new(Ipv4Addr::new(10, 0, 0, 1), Ipv4Addr::new(10, 0, 0, 5), PROTO_TCP, vec![7; 100]) };
+        let bytes = packet.to_bytes();
+        assert_eq!(checksum(&bytes[..20]), 0);
+        assert_eq!(Ipv4Packet::parse(&bytes).unwrap(), packet);
+
+        let fragments = packet.fragment(&[20, 30]);
+        assert_eq!(fragments.iter().map(|f| f.payload.len()).collect::<Vec<_>>(), [16, 24, 60]);
+        let mut reassembler = Reassembler::default();
+        let mut whole = None;
+        for fragment in fragments.iter().rev() {
+            whole = reassembler.push(Ipv4Packet::parse(&fragment.to_bytes()).unwrap());
+        }
+        assert_eq!(whole.unwrap().payload, packet.payload);
+        assert_eq!(reassembler.pending(), 0);
+    }
+
+    #[test]
+    fn icmp_codes_are_classified_per_protocol() {
+        let icmp = |code| Reply::Icmp { code, from: Ipv4Addr::new(10, 0, 0, 254), ttl: 64 };
+        for code in ICMP_FILTERED_CODES {
+            assert_eq!(classify(ScanType::Syn, Some(&icmp(*code))), Some(PortStatus::Filtered));
+            assert_eq!(classify(ScanType::Udp, Some(&icmp(*code))), Some(PortStatus::Filtered));
+        }
+        assert_eq!(classify(ScanType::Syn, Some(&icmp(ICMP_PORT_UNREACHABLE))), Some(PortStatus::Filtered));
+        assert_eq!(classify(ScanType::Udp, Some(&icmp(ICMP_PORT_UNREACHABLE))), Some(PortStatus::Closed));
+        // Fragmentation needed says nothing about the port
+        assert_eq!(classify(ScanType::Fin, Some(&icmp(4))), Some(PortStatus::OpenFiltered));
+        assert_eq!(classify(ScanType::Udp, Some(&icmp(4))), Some(PortStatus::OpenFiltered));
+        assert_eq!(classify(ScanType::Ssl, None), None);
+    }
+
+    #[test]
+    fn fragment_sizes_and_mimic_payloads() {
+        assert!(fragment_sizes(&FragmentConfig::default(), 220)[0] >= 64);
+        assert_eq!(mimic_payload("ssh"), b"SSH-2.0-OpenSSH_");
+        assert_eq!(flag_names(TCP_SYN | TCP_ACK), "SYN,ACK");
+    }
+}
@@ -0,0 +1,310 @@
+//! Named scan profiles loaded from TOML
+//!
+//! A profile file is a set of top-level tables, one per profile. Each table may
+//! set any `ScanConfig` field and may name a parent profile with `inherits`:
+//!
+//! ```toml
+//! [quick-external]
+//! ports = [21, 22, 25, 80, 443, 3389, 8080, 8443]
+//! scan_types = ["Syn"]
+//! rate = 500
+//!
+//! [tls-audit]
+//! inherits = "quick-external"
+//! ports = [443, 465, 636, 993, 995, 8443]
+//! timeout_banner = 6.0
+//!
+//! [tls-audit.fragmentation]
+//! two_frags = true
+//! ```
+//!
+//! Profiles are applied on top of `ScanConfig::default()`, parents first, so a
+//! child only needs to list what it changes. Nested tables are merged key by key.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use serde_json::Value;
+
+use crate::config::ScanConfig;
+use crate::error::{Result, ScanError};
+
+/// Key naming the parent profile
+pub const INHERITS_KEY: &str = "inherits";
+
+/// Profile file looked up when none is given explicitly
+pub const DEFAULT_PROFILE_FILE: &str = "quantum_scanner.toml";
+
+/// Longest inheritance chain accepted before assuming a mistake
+const MAX_INHERITANCE_DEPTH: usize = 16;
+
+/// Profiles parsed from a TOML file
+#[derive(Debug, Clone, Default)]
+pub struct ProfileSet {
+    profiles: BTreeMap<String, toml::Table>,
+}
+
+impl ProfileSet {
+    /// Read and parse a profile file
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path).map_err(|source| ScanError::File {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        Self::parse(&text)
+            .map_err(|e| ScanError::InvalidConfig(format!("{}: {}", path.display(), e)))
+    }
+
+    /// Parse profiles from TOML text
+    pub fn parse(text: &str) -> Result<Self> {
+        let table: toml::Table = toml::from_str(text)
+            .map_err(|e| ScanError::InvalidConfig(e.to_string()))?;
+
+        let mut profiles = BTreeMap::new();
+        for (name, value) in table {
+            match value {
+                toml::Value::Table(profile) => {
+                    profiles.insert(name, profile);
+                },
+                _ => return Err(ScanError::InvalidConfig(format!(
+                    "'{}' is not a profile table", name
+                ))),
+            }
+        }
+
+        Ok(Self { profiles })
+    }
+
+    /// Names of all profiles in the file
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.profiles.keys().map(String::as_str)
+    }
+
+    /// Whether a profile with this name exists
+    pub fn contains(&self, name: &str) -> bool {
+        self.profiles.contains_key(name)
+    }
+
+    /// Build the effective configuration for a profile, starting from defaults
+    pub fn resolve(&self, name: &str) -> Result<ScanConfig> {
+        self.apply(name, &ScanConfig::default())
+    }
+
+    /// Apply a profile and all of its ancestors on top of `base`
+    pub fn apply(&self, name: &str, base: &ScanConfig) -> Result<ScanConfig> {
+        let mut config = base.clone();
+        for profile in self.chain(name)?.into_iter().rev() {
+            config = apply_layer(&config, profile, &self.profiles[profile])?;
+        }
+        Ok(config)
+    }
+
+    /// Profile names from `name` up to its root ancestor
+    fn chain<'a>(&'a self, name: &'a str) -> Result<Vec<&'a str>> {
+        let mut chain = Vec::new();
+        let mut current = name;
+
+        loop {
+            let profile = self.profiles.get(current).ok_or_else(|| {
+                ScanError::InvalidConfig(match chain.last() {
+                    Some(child) => format!("profile '{}' inherits unknown profile '{}'", child, current),
+                    None => format!("unknown profile '{}'", current),
+                })
+            })?;
+
+            if chain.contains(&current) {
+                chain.push(current);
+                return Err(ScanError::InvalidConfig(format!(
+                    "profile inheritance cycle: {}", chain.join(" -> ")
+                )));
+            }
+            if chain.len() >= MAX_INHERITANCE_DEPTH {
+                return Err(ScanError::InvalidConfig(format!(
+                    "profile '{}' inherits more than {} levels deep", name, MAX_INHERITANCE_DEPTH
+                )));
+            }
+            chain.push(current);
+
+            match profile.get(INHERITS_KEY) {
+                Some(toml::Value::String(parent)) => current = parent,
+                Some(_) => return Err(ScanError::InvalidConfig(format!(
+                    "'{}' in profile '{}' must be a profile name", INHERITS_KEY, current
+                ))),
+                None => return Ok(chain),
+            }
+        }
+    }
+}
+
+/// Merge one profile table into a configuration
+fn apply_layer(config: &ScanConfig, name: &str, profile: &toml::Table) -> Result<ScanConfig> {
+    let invalid = |e: serde_json::Error| ScanError::InvalidConfig(format!("profile '{}': {}", name, e));
+
+    let mut overlay = profile.clone();
+    overlay.remove(INHERITS_KEY);
+
+    let mut merged = serde_json::to_value(config).map_err(invalid)?;
+    merge_value(&mut merged, serde_json::to_value(overlay).map_err(invalid)?, "")
+        .map_err(|key| ScanError::InvalidConfig(format!("profile '{}': unknown setting '{}'", name, key)))?;
+
+    serde_json::from_value(merged).map_err(invalid)
+}
+
+/// Recursively merge `overlay` into `target`, returning the first unknown key
+///
+/// Objects are merged key by key; anything else (including a `null` optional
+/// section being filled in) is replaced outright.
+fn merge_value(target: &mut Value, overlay: Value, prefix: &str) -> std::result::Result<(), String> {
+    match (target, overlay) {
+        (Value::Object(target), Value::Object(overlay)) => {
+            for (key, value) in overlay {
+                let path = if prefix.is_empty() { key.clone() } else { format!("{}.{}", prefix, key) };
+                match target.get_mut(&key) {
+                    Some(slot) => merge_value(slot, value, &path)?,
+                    None => return Err(path),
+                }
+            }
+            Ok(())
+        },
+        (slot, value) => {
+            *slot = value;
+            Ok(())
+        },
+    }
+}
+
+/// Render a configuration as TOML, e.g. for `--print-config`
+pub fn to_toml(config: &ScanConfig) -> Result<String> {
+    toml::to_string_pretty(config).map_err(|e| ScanError::InvalidConfig(e.to_string()))
+}
+
+/// Profile file used when none is given: `./quantum_scanner.toml`, falling back
+/// to `~/.config/quantum_scanner/profiles.toml`
+pub fn default_profile_path() -> PathBuf {
+    let local = PathBuf::from(DEFAULT_PROFILE_FILE);
+    if local.exists() {
+        return local;
+    }
+    match std::env::var_os("HOME") {
+        Some(home) => Path::new(&home).join(".config").join("quantum_scanner").join("profiles.toml"),
+        None => local,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROFILES: &str = r#"
+[base]
+ports = [22, 80]
+rate = 100
+
+[base.fragmentation]
+two_frags = true
+
+[child]
+inherits = "base"
+rate = 5
+
+[child.fragmentation]
+min_size = 8
+
+[grandchild]
+inherits = "child"
+timeout = 1.5
+
+[loop-a]
+inherits = "loop-b"
+
+[loop-b]
+inherits = "loop-a"
+
+[typo]
+raet = 1
+
+[orphan]
+inherits = "missing"
+"#;
+
+    fn error(profiles: &ProfileSet, name: &str) -> String {
+        profiles.resolve(name).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn children_inherit_and_override_their_parents() {
+        let profiles = ProfileSet::parse(PROFILES).unwrap();
+        let config = profiles.resolve("grandchild").unwrap();
+        assert_eq!(config.ports, [22, 80]);
+        assert_eq!(config.rate, 5);
+        assert_eq!(config.timeout, 1.5);
+        // Nested tables merge key by key down the chain
+        assert!(config.fragmentation.two_frags);
+        assert_eq!(config.fragmentation.min_size, 8);
+        assert_eq!(config.fragmentation.max_size, crate::config::FragmentConfig::default().max_size);
+        // Untouched settings keep their defaults
+        assert_eq!(config.concurrency, ScanConfig::default().concurrency);
+
+        assert_eq!(profiles.resolve("base").unwrap().rate, 100);
+    }
+
+    #[test]
+    fn optional_sections_are_filled_in() {
+        let profiles = ProfileSet::parse(r#"
+[web.http_discovery]
+wordlist = ["admin"]
+extensions = []
+match_status = []
+filter_status = [404]
+vhost_wordlist = []
+concurrency = 4
+
+[web-vhosts]
+inherits = "web"
+
+[web-vhosts.http_discovery]
+vhost_domain = "example.com"
+"#).unwrap();
+        let discovery = profiles.resolve("web-vhosts").unwrap().http_discovery.unwrap();
+        assert_eq!(discovery.concurrency, 4);
+        assert_eq!(discovery.wordlist, ["admin"]);
+        assert_eq!(discovery.vhost_domain.as_deref(), Some("example.com"));
+    }
+
+    #[test]
+    fn apply_starts_from_the_given_base() {
+        let profiles = ProfileSet::parse(PROFILES).unwrap();
+        let base = ScanConfig { target: "192.0.2.1".to_string(), concurrency: 7, ..ScanConfig::default() };
+        let config = profiles.apply("child", &base).unwrap();
+        assert_eq!((config.target.as_str(), config.concurrency, config.rate), ("192.0.2.1", 7, 5));
+    }
+
+    #[test]
+    fn broken_profiles_are_reported() {
+        let profiles = ProfileSet::parse(PROFILES).unwrap();
+        assert!(error(&profiles, "loop-a").contains("profile inheritance cycle: loop-a -> loop-b -> loop-a"));
+        assert!(error(&profiles, "typo").contains("profile 'typo': unknown setting 'raet'"));
+        assert!(error(&profiles, "orphan").contains("profile 'orphan' inherits unknown profile 'missing'"));
+        assert!(error(&profiles, "nothing").contains("unknown profile 'nothing'"));
+
+        let deep: String = (0..20)
+            .map(|i| format!("[p{}]\ninherits = \"p{}\"\n", i, i + 1))
+            .chain(std::iter::once("[p20]\n".to_string()))
+            .collect();
+        let profiles = ProfileSet::parse(&deep).unwrap();
+        assert!(error(&profiles, "p0").contains("more than 16 levels deep"));
+        assert!(profiles.resolve("p10").is_ok());
+
+        assert!(ProfileSet::parse("rate = 5").is_err());
+    }
+
+    #[test]
+    fn rendered_config_reads_back() {
+        let config = ProfileSet::parse(PROFILES).unwrap().resolve("grandchild").unwrap();
+        let text = to_toml(&config).unwrap();
+        let table: toml::Table = toml::from_str(&text).unwrap();
+        let profiles = ProfileSet { profiles: BTreeMap::from([("printed".to_string(), table)]) };
+        let reread = profiles.resolve("printed").unwrap();
+        assert_eq!(serde_json::to_value(&reread).unwrap(), serde_json::to_value(&config).unwrap());
+    }
+}
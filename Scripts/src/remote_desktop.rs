@@ -0,0 +1,478 @@
+//! RDP and VNC security probes
+//!
+//! RDP: an X.224 Connection Request carrying RDP_NEG_REQ is sent once per
+//! security protocol to learn which ones the server accepts. When CredSSP is
+//! accepted we go one step further, start TLS and send an NTLM NEGOTIATE inside
+//! a TSRequest; the CHALLENGE reply names the host, its domain and the Windows
+//! build. We never answer the challenge, so no logon is attempted.
+//!
+//! VNC: the RFB version handshake followed by the list of security types the
+//! server offers. Nothing is selected, so no authentication takes place.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+
+use crate::audit::AuditedStream;
+use crate::ber::{tlv, TAG_INTEGER, TAG_OCTET_STRING, TAG_SEQUENCE};
+use crate::models::RemoteDesktopInfo;
+use crate::ntlm;
+use crate::scope::Egress;
+use crate::utils;
+
+/// RDP security protocols, as `(name, requestedProtocols, selectedProtocol)`
+///
+/// The server answers with one protocol out of the requested set, so CredSSP
+/// is requested together with TLS and counts as supported only when chosen.
+const RDP_PROTOCOLS: &[(&str, u32, u32)] = &[
+    ("Standard RDP Security", 0x0000_0000, 0x0000_0000),
+    ("TLS", 0x0000_0001, 0x0000_0001),
+    ("CredSSP (NLA)", 0x0000_0003, 0x0000_0002),
+    ("RDSTLS", 0x0000_0004, 0x0000_0004),
+    ("CredSSP with Early User Authorization", 0x0000_000b, 0x0000_0008),
+];
+
+/// PROTOCOL_HYBRID | PROTOCOL_HYBRID_EX
+const PROTOCOL_CREDSSP: u32 = 0x0000_000a;
+
+/// What a remote desktop probe learned
+#[derive(Debug, Clone)]
+pub struct RemoteDesktopResult {
+    /// Protocol details
+    pub info: RemoteDesktopInfo,
+    /// Security problems worth reporting as vulnerabilities
+    pub vulns: Vec<String>,
+}
+
+/// Runs the RDP and VNC probes
+pub struct RemoteDesktopProbes {
+    timeout: Duration,
+    egress: Egress,
+    logger: Option<Arc<utils::EnhancedLogger>>,
+}
+
+impl RemoteDesktopProbes {
+    /// Create a prober with the given per-operation timeout
+    pub fn new(timeout: Duration, egress: Egress, logger: Option<Arc<utils::EnhancedLogger>>) -> Self {
+        Self { timeout, egress, logger }
+    }
+
+    fn log(&self, level: &str, message: &str) {
+        if let Some(logger) = &self.logger {
+            logger.log(level, message);
+        }
+    }
+
+    /// Run the probe matching the detected service or port, if any
+    pub async fn run(&self, target: &str, port: u16, service: Option<&str>) -> Option<RemoteDesktopResult> {
+        let is_vnc = matches!(service, Some("vnc")) || (service.is_none() && (5900..=5910).contains(&port));
+        let is_rdp = matches!(service, Some("rdp") | Some("ms-wbt-server")) || (service.is_none() && port == 3389);
+
+        let result = if is_vnc {
+            self.probe_vnc(target, port).await
+        } else if is_rdp {
+            self.probe_rdp(target, port).await
+        } else {
+            return None;
+        };
+
+        match result {
+            Ok(found) => found,
+            Err(e) => {
+                self.log("DEBUG", &format!("Remote desktop probe on {}:{} failed: {}", target, port, e));
+                None
+            }
+        }
+    }
+
+    async fn connect(&self, target: &str, port: u16) -> Result<AuditedStream, anyhow::Error> {
+        Ok(tokio::time::timeout(self.timeout, self.egress.connect_tcp(target, port)).await??)
+    }
+
+    async fn read_exact<S: AsyncRead + Unpin>(&self, stream: &mut S, buffer: &mut [u8]) -> Result<(), anyhow::Error> {
+        tokio::time::timeout(self.timeout, stream.read_exact(buffer)).await??;
+        Ok(())
+    }
+
+    /// Enumerate RDP security protocols, then read NTLM info over CredSSP
+    async fn probe_rdp(&self, target: &str, port: u16) -> Result<Option<RemoteDesktopResult>, anyhow::Error> {
+        let mut info = RemoteDesktopInfo {
+            protocol: "rdp".to_string(),
+            ..Default::default()
+        };
+        let mut answered = false;
+
+        for &(name, requested, expected) in RDP_PROTOCOLS {
+            let mut stream = match self.connect(target, port).await {
+                Ok(stream) => stream,
+                Err(_) => continue,
+            };
+            match self.negotiate(&mut stream, requested).await {
+                Ok(Negotiation::Selected(selected)) => {
+                    answered = true;
+                    if selected == expected {
+                        info.security.push(name.to_string());
+                    }
+                    if selected & PROTOCOL_CREDSSP != 0 && info.ntlm_info.is_none() {
+                        match self.credssp_ntlm(target, stream).await {
+                            Ok(ntlm_info) => info.ntlm_info = ntlm_info,
+                            Err(e) => self.log("DEBUG", &format!(
+                                "CredSSP NTLM exchange with {}:{} failed: {}", target, port, e
+                            )),
+                        }
+                    }
+                }
+                Ok(Negotiation::Legacy) => {
+                    // Pre-negotiation servers only speak standard RDP security
+                    answered = true;
+                    if requested == 0 {
+                        info.security.push(name.to_string());
+                    }
+                }
+                Ok(Negotiation::Failed(code)) => {
+                    answered = true;
+                    self.log("DEBUG", &format!(
+                        "{}:{} refused {}: {}", target, port, name, negotiation_failure(code)
+                    ));
+                }
+                Err(e) => self.log("DEBUG", &format!("{}:{} {} negotiation failed: {}", target, port, name, e)),
+            }
+        }
+
+        if !answered {
+            return Ok(None);
+        }
+        Ok(Some(RemoteDesktopResult { info, vulns: Vec::new() }))
+    }
+
+    /// Send an X.224 Connection Request with RDP_NEG_REQ and read the answer
+    async fn negotiate(&self, stream: &mut AuditedStream, requested: u32) -> Result<Negotiation, anyhow::Error> {
+        let mut request = vec![
+            0x03, 0x00, 0x00, 0x13, // TPKT, length 19
+            0x0e, 0xe0, 0x00, 0x00, 0x00, 0x00, 0x00, // X.224 CR
+            0x01, 0x00, 0x08, 0x00, // RDP_NEG_REQ, flags, length
+        ];
+        request.extend_from_slice(&requested.to_le_bytes());
+        stream.write_all(&request).await?;
+
+        let mut header = [0u8; 4];
+        self.read_exact(stream, &mut header).await?;
+        let length = u16::from_be_bytes([header[2], header[3]]) as usize;
+        if header[0] != 0x03 || !(11..=1024).contains(&length) {
+            return Err(anyhow::anyhow!("not a TPKT response"));
+        }
+        let mut body = vec![0u8; length - 4];
+        self.read_exact(stream, &mut body).await?;
+
+        // body: LI, CC code (0xD0), dst ref, src ref, class, then optional negotiation data
+        if body[1] & 0xf0 != 0xd0 {
+            return Err(anyhow::anyhow!("unexpected X.224 code 0x{:02x}", body[1]));
+        }
+        match body.get(7..15) {
+            Some(neg) if neg[0] == 0x02 => Ok(Negotiation::Selected(u32::from_le_bytes([neg[4], neg[5], neg[6], neg[7]]))),
+            Some(neg) if neg[0] == 0x03 => Ok(Negotiation::Failed(u32::from_le_bytes([neg[4], neg[5], neg[6], neg[7]]))),
+            _ => Ok(Negotiation::Legacy),
+        }
+    }
+
+    /// Start TLS and read the NTLM CHALLENGE from a CredSSP TSRequest
+    async fn credssp_ntlm(&self, target: &str, stream: AuditedStream) -> Result<Option<crate::models::NtlmInfo>, anyhow::Error> {
+        let connector = tokio_rustls::TlsConnector::from(Arc::new(utils::insecure_tls_config()));
+        let domain = rustls::ServerName::try_from(target)?;
+        let mut tls = tokio::time::timeout(self.timeout, connector.connect(domain, stream)).await??;
+
+        tls.write_all(&ts_request(&ntlm::negotiate_message())).await?;
+        tls.flush().await?;
+
+        let mut response = vec![0u8; 4096];
+        let n = tokio::time::timeout(self.timeout, tls.read(&mut response)).await??;
+        Ok(ntlm::find_challenge(&response[..n]))
+    }
+
+    /// RFB version and offered security types
+    async fn probe_vnc(&self, target: &str, port: u16) -> Result<Option<RemoteDesktopResult>, anyhow::Error> {
+        let mut stream = self.connect(target, port).await?;
+
+        let mut greeting = [0u8; 12];
+        self.read_exact(&mut stream, &mut greeting).await?;
+        let (major, minor) = match parse_rfb_version(&greeting) {
+            Some(version) => version,
+            None => return Ok(None),
+        };
+
+        let mut info = RemoteDesktopInfo {
+            protocol: "vnc".to_string(),
+            version: Some(format!("{}.{}", major, minor)),
+            ..Default::default()
+        };
+
+        // Answer with the highest version we both speak
+        let (reply_major, reply_minor) = if (major, minor) >= (3, 8) { (3, 8) } else { (major, minor) };
+        stream.write_all(format!("RFB {:03}.{:03}\n", reply_major, reply_minor).as_bytes()).await?;
+
+        let types: Vec<u8> = if (reply_major, reply_minor) < (3, 7) {
+            // RFB 3.3: the server picks a single type (u32)
+            let mut chosen = [0u8; 4];
+            self.read_exact(&mut stream, &mut chosen).await?;
+            match u32::from_be_bytes(chosen) {
+                0 => Vec::new(),
+                t => vec![t as u8],
+            }
+        } else {
+            let mut count = [0u8; 1];
+            self.read_exact(&mut stream, &mut count).await?;
+            let mut types = vec![0u8; count[0] as usize];
+            self.read_exact(&mut stream, &mut types).await?;
+            types
+        };
+
+        if types.is_empty() {
+            // Connection refused, usually "Too many authentication failures"
+            let mut length = [0u8; 4];
+            if self.read_exact(&mut stream, &mut length).await.is_ok() {
+                let mut reason = vec![0u8; (u32::from_be_bytes(length) as usize).min(1024)];
+                if self.read_exact(&mut stream, &mut reason).await.is_ok() {
+                    self.log("INFO", &format!(
+                        "VNC on {}:{} refused the handshake: {}", target, port, String::from_utf8_lossy(&reason)
+                    ));
+                }
+            }
+        }
+
+        info.security = types.iter().map(|&t| vnc_security_type(t)).collect();
+
+        let mut vulns = Vec::new();
+        if types.contains(&1) {
+            vulns.push(format!(
+                "[vnc] VNC server allows access without authentication (RFB {}.{} offers security type None)",
+                major, minor
+            ));
+        }
+
+        Ok(Some(RemoteDesktopResult { info, vulns }))
+    }
+}
+
+/// Outcome of an RDP_NEG_REQ
+enum Negotiation {
+    /// RDP_NEG_RSP with the selected protocol
+    Selected(u32),
+    /// RDP_NEG_FAILURE with the failure code
+    Failed(u32),
+    /// Connection Confirm without negotiation data (pre-RDP 5.2 servers)
+    Legacy,
+}
+
+fn negotiation_failure(code: u32) -> &'static str {
+    match code {
+        1 => "SSL required by server",
+        2 => "SSL not allowed by server",
+        3 => "SSL certificate not on server",
+        4 => "inconsistent flags",
+        5 => "CredSSP required by server",
+        6 => "SSL with user authentication required by server",
+        _ => "unknown failure",
+    }
+}
+
+/// Wrap an NTLM token in a CredSSP TSRequest (version 2)
+fn ts_request(token: &[u8]) -> Vec<u8> {
+    let version = tlv(0xa0, &tlv(TAG_INTEGER, &[0x02]));
+    let nego_token = tlv(TAG_SEQUENCE, &tlv(TAG_SEQUENCE, &tlv(0xa0, &tlv(TAG_OCTET_STRING, token))));
+    let nego_tokens = tlv(0xa1, &nego_token);
+    tlv(TAG_SEQUENCE, &[version, nego_tokens].concat())
+}
+
+/// Parse "RFB 003.008\n" into (3, 8)
+fn parse_rfb_version(greeting: &[u8]) -> Option<(u32, u32)> {
+    let text = std::str::from_utf8(greeting).ok()?;
+    let version = text.strip_prefix("RFB ")?.trim_end();
+    let (major, minor) = version.split_once('.')?;
+    Some((major.parse().ok()?, minor.parse().ok()?))
+}
+
+/// Name of an RFB security type
+fn vnc_security_type(kind: u8) -> String {
+    match kind {
+        1 => "None".to_string(),
+        2 => "VNC Authentication".to_string(),
+        5 => "RA2".to_string(),
+        6 => "RA2ne".to_string(),
+        16 => "Tight".to_string(),
+        17 => "Ultra".to_string(),
+        18 => "TLS".to_string(),
+        19 => "VeNCrypt".to_string(),
+        20 => "GTK-VNC SASL".to_string(),
+        21 => "MD5 hash".to_string(),
+        22 => "Colin Dean xvp".to_string(),
+        30 => "Apple Remote Desktop".to_string(),
+        other => format!("Unknown ({})", other),
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// How a fake RDP server answers each requestedProtocols value
+    type Policy = fn(u32) -> Option<Result<u32, u32>>;
+
+    pub(crate) fn tls_acceptor() -> tokio_rustls::TlsAcceptor {
+        let cert = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+        let config = rustls::ServerConfig::builder()
+            .with_safe_defaults()
+            .with_no_client_auth()
+            .with_single_cert(
+                vec![rustls::Certificate(cert.serialize_der().unwrap())],
+                rustls::PrivateKey(cert.serialize_private_key_der()),
+            )
+            .unwrap();
+        tokio_rustls::TlsAcceptor::from(Arc::new(config))
+    }
+
+    /// Fake RDP server: `Ok(protocol)` selects, `Err(code)` refuses, `None`
+    /// answers like a server from before protocol negotiation
+    async fn rdp_server(policy: Policy, challenge: Vec<u8>) -> u16 {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let acceptor = tls_acceptor();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let (acceptor, challenge) = (acceptor.clone(), challenge.clone());
+                tokio::spawn(async move {
+                    let mut request = [0u8; 19];
+                    stream.read_exact(&mut request).await.unwrap();
+                    let requested = u32::from_le_bytes([request[15], request[16], request[17], request[18]]);
+
+                    let mut reply = vec![0x03, 0x00, 0x00, 0x13, 0x0e, 0xd0, 0, 0, 0, 0, 0];
+                    let selected = match policy(requested) {
+                        Some(Ok(protocol)) => {
+                            reply.extend_from_slice(&[0x02, 0x00, 0x08, 0x00]);
+                            reply.extend_from_slice(&protocol.to_le_bytes());
+                            Some(protocol)
+                        }
+                        Some(Err(code)) => {
+                            reply.extend_from_slice(&[0x03, 0x00, 0x08, 0x00]);
+                            reply.extend_from_slice(&code.to_le_bytes());
+                            None
+                        }
+                        None => {
+                            reply[3] = 0x0b;
+                            reply[4] = 0x06;
+                            None
+                        }
+                    };
+                    stream.write_all(&reply).await.unwrap();
+
+                    if selected.is_some_and(|p| p & PROTOCOL_CREDSSP != 0) {
+                        let mut tls = acceptor.accept(stream).await.unwrap();
+                        let mut buffer = [0u8; 1024];
+                        let n = tls.read(&mut buffer).await.unwrap();
+                        assert!(buffer[..n].windows(8).any(|w| w == ntlm::SIGNATURE));
+                        tls.write_all(&ts_request(&challenge)).await.unwrap();
+                        tls.flush().await.unwrap();
+                        let _ = tls.read(&mut buffer).await;
+                    }
+                });
+            }
+        });
+        port
+    }
+
+    /// Fake VNC server sending `greeting` and then `security` after the client's version
+    async fn vnc_server(greeting: &'static [u8], security: &'static [u8]) -> u16 {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            stream.write_all(greeting).await.unwrap();
+            let mut version = [0u8; 12];
+            stream.read_exact(&mut version).await.unwrap();
+            stream.write_all(security).await.unwrap();
+            let _ = stream.read(&mut version).await;
+        });
+        port
+    }
+
+    async fn probe(port: u16, service: &str) -> Option<RemoteDesktopResult> {
+        RemoteDesktopProbes::new(Duration::from_secs(2), Egress::default(), None)
+            .run("127.0.0.1", port, Some(service))
+            .await
+    }
+
+    #[tokio::test]
+    async fn nla_server_reveals_ntlm_info() {
+        let challenge = ntlm::tests::challenge_message(
+            "CORP",
+            &[(2, "CORP"), (1, "TS01"), (3, "ts01.corp.local"), (4, "corp.local")],
+            Some([10, 0, 0x7c, 0x4f, 0, 0, 0, 15]),
+        );
+        let port = rdp_server(
+            |requested| match requested {
+                0x3 => Some(Ok(0x2)),
+                0xb => Some(Ok(0x8)),
+                _ => Some(Err(5)),
+            },
+            challenge,
+        )
+        .await;
+
+        let found = probe(port, "rdp").await.unwrap();
+        assert_eq!(found.info.security, ["CredSSP (NLA)", "CredSSP with Early User Authorization"]);
+        let ntlm = found.info.ntlm_info.unwrap();
+        assert_eq!(ntlm.netbios_computer.as_deref(), Some("TS01"));
+        assert_eq!(ntlm.dns_computer.as_deref(), Some("ts01.corp.local"));
+        assert_eq!(ntlm.os_version.as_deref(), Some("10.0.20348"));
+    }
+
+    #[tokio::test]
+    async fn only_chosen_protocols_count() {
+        // Answers TLS whenever TLS is in the requested set
+        let port = rdp_server(|requested| if requested & 1 != 0 { Some(Ok(1)) } else { Some(Err(1)) }, Vec::new()).await;
+        let found = probe(port, "ms-wbt-server").await.unwrap();
+        assert_eq!(found.info.security, ["TLS"]);
+        assert!(found.info.ntlm_info.is_none());
+
+        let port = rdp_server(|_| None, Vec::new()).await;
+        assert_eq!(probe(port, "rdp").await.unwrap().info.security, ["Standard RDP Security"]);
+    }
+
+    #[tokio::test]
+    async fn vnc_security_types() {
+        let port = vnc_server(b"RFB 003.008\n", &[2, 1, 2]).await;
+        let found = probe(port, "vnc").await.unwrap();
+        assert_eq!(found.info.version.as_deref(), Some("3.8"));
+        assert_eq!(found.info.security, ["None", "VNC Authentication"]);
+        assert_eq!(found.vulns.len(), 1);
+        assert!(found.vulns[0].contains("without authentication"));
+
+        // RFB 3.3 servers pick the type themselves
+        let port = vnc_server(b"RFB 003.003\n", &[0, 0, 0, 2]).await;
+        let found = probe(port, "vnc").await.unwrap();
+        assert_eq!(found.info.security, ["VNC Authentication"]);
+        assert!(found.vulns.is_empty());
+
+        // Refused, with a reason
+        let port = vnc_server(b"RFB 003.008\n", b"\x00\x00\x00\x00\x05busy!").await;
+        assert!(probe(port, "vnc").await.unwrap().info.security.is_empty());
+    }
+
+    #[test]
+    fn handshake_helpers() {
+        assert_eq!(parse_rfb_version(b"RFB 003.008\n"), Some((3, 8)));
+        assert_eq!(parse_rfb_version(b"RFB 004.001\n"), Some((4, 1)));
+        assert_eq!(parse_rfb_version(b"SSH-2.0-x\r\n"), None);
+        assert_eq!(vnc_security_type(30), "Apple Remote Desktop");
+        assert_eq!(vnc_security_type(99), "Unknown (99)");
+        assert_eq!(negotiation_failure(5), "CredSSP required by server");
+
+        let request = ts_request(&ntlm::negotiate_message());
+        let (tag, content, rest) = crate::ber::read_tlv(&request).unwrap();
+        assert_eq!(tag, TAG_SEQUENCE);
+        assert!(rest.is_empty());
+        let (tag, version, _) = crate::ber::read_tlv(content).unwrap();
+        assert_eq!((tag, version), (0xa0, &[TAG_INTEGER, 1, 2][..]));
+        assert!(request.ends_with(&ntlm::negotiate_message()));
+    }
+}
@@ -1,21 +1,518 @@
+//! Scan engine
+//!
+//! `QuantumScanner` probes every port with every configured technique, records
+//! the port states and runs the service follow-ups (identification, TLS,
+//! protocol handshakes, discovery) on ports that turn out to be open.
+
+use std::collections::{HashMap, HashSet};
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use rand::seq::SliceRandom;
+
+use crate::config::FragmentConfig;
+use crate::models::{PortResult, PortStatus, ScanResults, ScanType, SslInfo};
+use crate::utils;
+
 pub struct QuantumScanner {
-    // ... existing fields ...
+    target: String,
+    target_ip: String,
+    ports: Vec<u16>,
+    scan_types: Vec<ScanType>,
+    concurrency: usize,
+    evasion: bool,
+    verbose: bool,
+    ipv6: bool,
+    timeout_connect: f64,
+    timeout_banner: f64,
+    results: HashMap<u16, PortResult>,
     memory_log: Option<Arc<utils::MemoryLogBuffer>>,
     enhanced_logger: Option<Arc<utils::EnhancedLogger>>,
-    // ... existing fields ...
+    http_discovery: Option<Arc<crate::http_discovery::HttpDiscoveryConfig>>,
+    default_checks: bool,
+    snmp: Option<Arc<crate::snmp::SnmpConfig>>,
+    dns: Option<Arc<crate::dns::DnsConfig>>,
+    max_redirects: usize,
+    cross_host_redirects: bool,
+    http_methods: bool,
+    events: crate::events::EventBus,
+    host_up_reported: bool,
+    /// Ports whose follow-ups already ran, as (port, over UDP)
+    followed_up: HashSet<(u16, bool)>,
+    control: Arc<crate::control::ScanControl>,
+    packet_transport: Option<Arc<dyn crate::packet::PacketTransport>>,
+    probe_options: crate::packet::ProbeOptions,
+    egress: crate::scope::Egress,
 }
 
 impl QuantumScanner {
-    // ... existing code ...
+    /// Start building a scanner for `target`
+    pub fn builder(target: &str) -> crate::config::ScannerBuilder {
+        crate::config::ScannerBuilder::new(target)
+    }
+    
+    /// Run the configured scan and return the collected results
+    ///
+    /// This is the library entry point; it wraps `run_scan` with the typed
+    /// `ScanError` used by the rest of the public API and classifies the
+    /// filtering in front of each port once all probes are in.
+    pub async fn run(&mut self) -> crate::error::Result<ScanResults> {
+        let start_time = std::time::Instant::now();
+        let mut results = self.run_scan().await;
+        
+        if let Ok(results) = &mut results {
+            if let Some(verdict) = crate::filtering::annotate(&self.target_ip, &mut results.results) {
+                if let Some(logger) = &self.enhanced_logger {
+                    logger.log("INFO", &format!("{} filtering: {} ({})", self.target_ip, verdict.class, verdict.reasons.join("; ")));
+                }
+            }
+            
+            self.emit(crate::events::ScanEventKind::ScanFinished {
+                open_ports: results.open_ports.clone(),
+                duration_ms: start_time.elapsed().as_millis() as u64,
+            });
+        }
+        
+        // Closing the bus ends every subscriber's stream
+        self.events.close();
+        
+        Ok(results?)
+    }
+    
+    /// Subscribe to live scan events
+    ///
+    /// Must be called before `run`; the stream ends when the scan finishes.
+    pub fn subscribe(&mut self) -> crate::events::ScanEventStream {
+        self.events.subscribe()
+    }
+    
+    /// Shared handle for pausing, rate changes and skipping hosts
+    pub fn control(&self) -> Arc<crate::control::ScanControl> {
+        self.control.clone()
+    }
+    
+    /// Replace the control handle (e.g. to share one across several scanners)
+    pub fn set_control(&mut self, control: Arc<crate::control::ScanControl>) {
+        self.egress = self.egress.clone().with_control(control.clone());
+        self.control = control;
+    }
+    
+    /// Confine every connection and probe this scanner makes to `scope`
+    pub fn set_scope(&mut self, scope: Arc<crate::scope::Scope>) {
+        self.egress = crate::scope::Egress::new(Some(scope), self.egress.audit().cloned())
+            .with_control(self.control.clone());
+    }
+    
+    /// The scope this scanner enforces, if one was set
+    pub fn scope(&self) -> Option<Arc<crate::scope::Scope>> {
+        self.egress.scope().cloned()
+    }
+    
+    /// Record every probe this scanner sends in `log`
+    pub fn set_audit_log(&mut self, log: Arc<crate::audit::AuditLog>) {
+        self.egress = crate::scope::Egress::new(self.egress.scope().cloned(), Some(log))
+            .with_control(self.control.clone());
+    }
+    
+    /// Whether to go on probing the target
+    ///
+    /// Refuses destinations outside the scope and waits out a pause; `false`
+    /// once the host is skipped or the scan cancelled. The rate limit and
+    /// probe count apply to each probe as it goes through the egress.
+    async fn checkpoint(&self) -> bool {
+        self.egress.permit_probe(&self.target_ip) && self.control.wait_resumed(&self.target_ip).await
+    }
+    
+    /// Publish an event to all subscribers
+    fn emit(&mut self, kind: crate::events::ScanEventKind) {
+        if self.events.has_subscribers() {
+            let event = crate::events::ScanEvent::new(&self.target_ip, kind);
+            self.events.emit(event);
+        }
+    }
+    
+    /// Create a scanner
+    ///
+    /// The target is resolved when the scan starts. Prefer `ScannerBuilder`,
+    /// which validates the settings and attaches the optional features.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn new(
+        target: &str,
+        ports: Vec<u16>,
+        scan_types: Vec<ScanType>,
+        concurrency: usize,
+        rate: usize,
+        evasion: bool,
+        verbose: bool,
+        ipv6: bool,
+        timeout: f64,
+        timeout_connect: f64,
+        timeout_banner: f64,
+        mimic_protocol: &str,
+        fragmentation: FragmentConfig,
+    ) -> Result<Self, anyhow::Error> {
+        if ports.is_empty() {
+            return Err(anyhow::anyhow!("no ports to scan"));
+        }
+        
+        let probe_options = crate::packet::ProbeOptions {
+            timeout: Duration::from_secs_f64(timeout.max(0.0)),
+            fragmentation,
+            mimic_payload: crate::packet::mimic_payload(mimic_protocol).to_vec(),
+            ..crate::packet::ProbeOptions::default()
+        };
+        let control = Arc::new(crate::control::ScanControl::new(rate));
+        
+        Ok(Self {
+            target: target.to_string(),
+            target_ip: target.to_string(),
+            ports,
+            scan_types,
+            concurrency: concurrency.max(1),
+            evasion,
+            verbose,
+            ipv6,
+            timeout_connect,
+            timeout_banner,
+            results: HashMap::new(),
+            memory_log: None,
+            enhanced_logger: None,
+            http_discovery: None,
+            default_checks: false,
+            snmp: None,
+            dns: None,
+            max_redirects: 5,
+            cross_host_redirects: false,
+            http_methods: false,
+            events: crate::events::EventBus::default(),
+            host_up_reported: false,
+            followed_up: HashSet::new(),
+            control: control.clone(),
+            packet_transport: None,
+            probe_options,
+            egress: crate::scope::Egress::default().with_control(control),
+        })
+    }
+    
+    /// Mimic another operating system's TTLs on raw probes
+    ///
+    /// `mimic_os` is "windows", "linux", "macos" or "random"; the TTL is
+    /// lowered by up to `ttl_jitter` hops so probes don't all look alike.
+    pub fn set_enhanced_evasion(&mut self, enabled: bool, mimic_os: &str, ttl_jitter: u8) {
+        if !enabled {
+            return;
+        }
+        let mut rng = rand::thread_rng();
+        let initial: u8 = match mimic_os.to_lowercase().as_str() {
+            "windows" => 128,
+            "linux" | "macos" | "freebsd" => 64,
+            "cisco" | "solaris" => 255,
+            _ => *[64, 128, 255].choose(&mut rng).unwrap_or(&64),
+        };
+        let jitter = rand::Rng::gen_range(&mut rng, 0..=ttl_jitter);
+        self.probe_options.ttl = initial.saturating_sub(jitter).max(1);
+    }
+    
+    /// Use a protocol's payload for mimic probes instead of the configured one
+    pub fn set_protocol_variant(&mut self, variant: Option<&str>) {
+        if let Some(variant) = variant {
+            let payload = crate::packet::mimic_payload(variant);
+            if !payload.is_empty() {
+                self.probe_options.mimic_payload = payload.to_vec();
+            }
+        }
+    }
+    
+    /// Also write scanner log lines to an in-memory buffer
+    pub fn set_memory_log(&mut self, log: Arc<utils::MemoryLogBuffer>) {
+        self.memory_log = Some(log);
+    }
+    
+    /// Write a line to whichever loggers are attached
+    ///
+    /// DEBUG lines go to the memory log only in verbose mode.
+    fn log(&self, level: &str, message: &str) {
+        if let Some(logger) = &self.enhanced_logger {
+            logger.log(level, message);
+        }
+        if let Some(log) = &self.memory_log {
+            if level != "DEBUG" || self.verbose {
+                log.log(level, message);
+            }
+        }
+    }
     
     /// Set enhanced logger for packet capture and detailed analysis
     pub fn set_enhanced_logger(&mut self, logger: Arc<utils::EnhancedLogger>) {
         self.enhanced_logger = Some(logger);
     }
     
-    // ... existing code ...
+    /// Enable HTTP content and virtual-host discovery on detected web servers
+    pub fn set_http_discovery(&mut self, config: crate::http_discovery::HttpDiscoveryConfig) {
+        self.http_discovery = Some(Arc::new(config));
+    }
+    
+    /// Enable read-only default-configuration checks on open ports
+    pub fn set_default_checks(&mut self, enabled: bool) {
+        self.default_checks = enabled;
+    }
+    
+    /// Enable SNMP community guessing and MIB reads on UDP results
+    pub fn set_snmp(&mut self, config: crate::snmp::SnmpConfig) {
+        self.snmp = Some(Arc::new(config));
+    }
+    
+    /// Set the zones and options used when inspecting DNS servers
+    pub fn set_dns(&mut self, config: crate::dns::DnsConfig) {
+        self.dns = Some(Arc::new(config));
+    }
+    
+    /// Set how many HTTP redirects the banner grab follows
+    pub fn set_max_redirects(&mut self, max: usize) {
+        self.max_redirects = max;
+    }
+    
+    /// Follow redirects to other hosts even when no scope is set
+    ///
+    /// Without a scope only same-host hops are followed; with one, every hop
+    /// is checked against it like any other connection.
+    pub fn set_cross_host_redirects(&mut self, enabled: bool) {
+        self.cross_host_redirects = enabled;
+    }
+    
+    /// Enable the HTTP method and WebDAV audit on detected web servers
+    pub fn set_http_methods(&mut self, enabled: bool) {
+        self.http_methods = enabled;
+    }
+    
+    /// Send raw probes through `transport` instead of opening raw sockets
+    pub fn set_packet_transport(&mut self, transport: Arc<dyn crate::packet::PacketTransport>, options: crate::packet::ProbeOptions) {
+        self.packet_transport = Some(transport);
+        self.probe_options = options;
+    }
+    
+    /// Probe every port with every configured technique and collect the results
+    ///
+    /// Probes go out `concurrency` at a time. Their results are recorded in
+    /// the order the probes were queued, which is also when the follow-up
+    /// analysis of open ports runs.
+    async fn run_scan(&mut self) -> Result<ScanResults, anyhow::Error> {
+        let start_time = chrono::Utc::now();
+        self.target_ip = self.resolve_target().await?;
+        self.results = self.ports.iter().map(|port| (*port, PortResult::new())).collect();
+        self.log("INFO", &format!(
+            "Scanning {} ({}) on {} ports with {:?}", self.target, self.target_ip, self.ports.len(), self.scan_types
+        ));
+        
+        let mut probes: Vec<(u16, ScanType)> = self.ports.iter()
+            .flat_map(|port| self.scan_types.iter().map(move |scan_type| (*port, *scan_type)))
+            .collect();
+        if self.evasion {
+            // Don't walk the port range in order
+            probes.shuffle(&mut rand::thread_rng());
+        }
+        
+        let prober = self.raw_prober();
+        
+        for batch in probes.chunks(self.concurrency) {
+            if self.control.is_cancelled() || self.control.is_skipped(&self.target_ip) {
+                self.log("INFO", &format!("Scan of {} stopped early", self.target_ip));
+                break;
+            }
+            let outcomes = futures::future::join_all(
+                batch.iter().map(|(port, scan_type)| self.probe_state(prober.as_ref(), *port, *scan_type))
+            ).await;
+            for (&(port, scan_type), outcome) in batch.iter().zip(outcomes) {
+                if let Some(outcome) = outcome {
+                    self.record_outcome(port, scan_type, outcome).await;
+                }
+            }
+        }
+        
+        let results = std::mem::take(&mut self.results);
+        let mut open_ports: Vec<u16> = results.iter()
+            .filter(|(_, result)| {
+                result.udp_state == Some(PortStatus::Open)
+                    || result.tcp_states.values().any(|status| *status == PortStatus::Open)
+            })
+            .map(|(port, _)| *port)
+            .collect();
+        open_ports.sort_unstable();
+        
+        Ok(ScanResults {
+            target: self.target.clone(),
+            target_ip: self.target_ip.clone(),
+            open_ports,
+            results,
+            start_time: Some(start_time),
+            end_time: Some(chrono::Utc::now()),
+        })
+    }
+    
+    /// Address to probe: the target itself, or what its name resolves to
+    async fn resolve_target(&self) -> Result<String, anyhow::Error> {
+        if let Ok(ip) = self.target.parse::<IpAddr>() {
+            return Ok(ip.to_string());
+        }
+        let addrs: Vec<IpAddr> = tokio::net::lookup_host((self.target.as_str(), 0)).await?
+            .map(|addr| addr.ip())
+            .collect();
+        addrs.iter()
+            .find(|ip| ip.is_ipv6() == self.ipv6)
+            .or_else(|| addrs.first())
+            .map(IpAddr::to_string)
+            .ok_or_else(|| anyhow::anyhow!("{} did not resolve", self.target))
+    }
+    
+    /// Prober for the raw-packet techniques, if this scan can use one
+    ///
+    /// Uses the transport given to `set_packet_transport`, or else raw
+    /// sockets if the process may open them (root or CAP_NET_RAW). Without
+    /// either, SYN-style probes fall back to full connects and the other
+    /// raw techniques are skipped.
+    fn raw_prober(&self) -> Option<crate::packet::Prober> {
+        if !self.scan_types.iter().any(|scan_type| crate::packet::classify(*scan_type, None).is_some()) {
+            return None;
+        }
+        let target: std::net::Ipv4Addr = self.target_ip.parse().ok()?;
+        let transport = match &self.packet_transport {
+            Some(transport) => transport.clone(),
+            None => match crate::packet::raw_transport(target) {
+                Ok(transport) => transport,
+                Err(e) => {
+                    self.log("DEBUG", &format!("Raw sockets unavailable ({}); using connect probes", e));
+                    return None;
+                }
+            },
+        };
+        Some(crate::packet::Prober::new(transport, self.probe_options.clone(), self.egress.clone(), self.enhanced_logger.clone()))
+    }
+    
+    /// Send one probe and work out the port state from the answer
+    ///
+    /// Raw techniques go through `prober` when there is one. Returns `None`
+    /// when the probe wasn't sent: the scan was paused into cancellation,
+    /// the host skipped, the destination is out of scope or the technique
+    /// needs raw sockets that aren't available.
+    async fn probe_state(&self, prober: Option<&crate::packet::Prober>, port: u16, scan_type: ScanType) -> Option<crate::packet::ProbeOutcome> {
+        if !self.checkpoint().await {
+            return None;
+        }
+        if let (Some(prober), Ok(target)) = (prober, self.target_ip.parse()) {
+            match prober.probe(target, port, scan_type).await {
+                Ok(Some(outcome)) => {
+                    if let Some(reply) = &outcome.reply {
+                        self.log_reply(prober, port, outcome.local_port, reply);
+                    }
+                    return Some(outcome);
+                }
+                // Not a raw technique; probe it over a socket below
+                Ok(None) => {}
+                Err(e) if not_sent(&e) => return None,
+                Err(e) => {
+                    self.log("WARN", &format!("{:?} probe of {}:{} failed: {}", scan_type, self.target_ip, port, e));
+                    return None;
+                }
+            }
+        }
+        
+        let started = std::time::Instant::now();
+        let status = match scan_type {
+            ScanType::Udp => self.udp_probe(port).await,
+            ScanType::Ssl => self.tls_probe(port).await,
+            ScanType::Syn | ScanType::TlsEcho | ScanType::Mimic | ScanType::Frag => self.connect_probe(port).await,
+            ScanType::Ack | ScanType::Fin | ScanType::Xmas | ScanType::Null | ScanType::Window => {
+                self.log("DEBUG", &format!("{:?} probe of {}:{} needs raw sockets; skipped", scan_type, self.target_ip, port));
+                None
+            }
+        }?;
+        // The kernel retransmits an unanswered SYN on its own, so for a connect
+        // the time to the answer is the only sign of how many it took
+        let attempts = match (scan_type, status) {
+            (ScanType::Udp, _) | (_, PortStatus::Filtered) => 1,
+            _ => syn_attempts(started.elapsed()),
+        };
+        Some(crate::packet::ProbeOutcome { status, reply: None, attempts, local_port: None })
+    }
+    
+    /// Log a raw reply with the enhanced logger
+    ///
+    /// Replies come from `port` to the probe's `local_port`. Only their headers
+    /// are kept, so there is no payload to look at for a banner.
+    fn log_reply(&self, prober: &crate::packet::Prober, port: u16, local_port: Option<u16>, reply: &crate::packet::Reply) {
+        let (protocol, flags, ttl) = match reply {
+            crate::packet::Reply::Tcp { ttl, .. } => ("TCP", Some(reply.describe()), *ttl),
+            crate::packet::Reply::Udp { ttl } => ("UDP", None, *ttl),
+            crate::packet::Reply::Icmp { ttl, .. } => ("ICMP", Some(reply.describe()), *ttl),
+        };
+        self.log_packet_response(
+            &self.target_ip,
+            &prober.local_addr().to_string(),
+            protocol,
+            Some(port),
+            local_port,
+            flags.as_deref(),
+            &[],
+            Some(ttl),
+            None
+        );
+    }
+    
+    /// Full TCP connect: open if accepted, closed if refused, filtered otherwise
+    async fn connect_probe(&self, port: u16) -> Option<PortStatus> {
+        let timeout = Duration::from_secs_f64(self.timeout_connect);
+        match tokio::time::timeout(timeout, self.egress.connect_tcp(&self.target_ip, port)).await {
+            Ok(Ok(_)) => Some(PortStatus::Open),
+            Ok(Err(e)) if e.kind() == std::io::ErrorKind::ConnectionRefused => Some(PortStatus::Closed),
+            Ok(Err(e)) if not_sent(&e) => None,
+            _ => Some(PortStatus::Filtered),
+        }
+    }
+    
+    /// TLS handshake probe; the port state comes from the TCP connection
+    async fn tls_probe(&self, port: u16) -> Option<PortStatus> {
+        let timeout = Duration::from_secs_f64(self.timeout_connect);
+        let stream = match tokio::time::timeout(timeout, self.egress.connect_tcp(&self.target_ip, port)).await {
+            Ok(Ok(stream)) => stream,
+            Ok(Err(e)) if e.kind() == std::io::ErrorKind::ConnectionRefused => return Some(PortStatus::Closed),
+            Ok(Err(e)) if not_sent(&e) => return None,
+            _ => return Some(PortStatus::Filtered),
+        };
+        
+        let connector = tokio_rustls::TlsConnector::from(Arc::new(utils::insecure_tls_config()));
+        let domain = rustls::ServerName::try_from(self.target_ip.as_str()).ok()?;
+        let handshake = tokio::time::timeout(Duration::from_secs_f64(self.timeout_banner), connector.connect(domain, stream)).await;
+        self.log("DEBUG", &format!(
+            "{}:{} {}", self.target_ip, port, if matches!(handshake, Ok(Ok(_))) { "completed a TLS handshake" } else { "does not speak TLS" }
+        ));
+        Some(PortStatus::Open)
+    }
+    
+    /// Empty UDP datagram: open on any answer, closed on ICMP port unreachable
+    ///
+    /// Silence is open|filtered; the protocol follow-ups may still prove the
+    /// port open.
+    async fn udp_probe(&self, port: u16) -> Option<PortStatus> {
+        let socket = match self.egress.connect_udp(&self.target_ip, port).await {
+            Ok(socket) => socket,
+            Err(e) if not_sent(&e) => return None,
+            Err(_) => return Some(PortStatus::Filtered),
+        };
+        if socket.send(&[]).await.is_err() {
+            return Some(PortStatus::Filtered);
+        }
+        
+        let mut buffer = [0u8; 512];
+        match tokio::time::timeout(Duration::from_secs_f64(self.timeout_connect), socket.recv(&mut buffer)).await {
+            Ok(Ok(_)) => Some(PortStatus::Open),
+            Ok(Err(e)) if e.kind() == std::io::ErrorKind::ConnectionRefused => Some(PortStatus::Closed),
+            _ => Some(PortStatus::OpenFiltered),
+        }
+    }
     
     /// Process a packet response and log it with the enhanced logger if available
+    #[allow(clippy::too_many_arguments)]
     fn log_packet_response(&self, 
         src_ip: &str, 
         dst_ip: &str, 
@@ -40,23 +537,25 @@ impl QuantumScanner {
                 response_time
             );
             
-            // Check if this might be a service banner and extract version if possible
-            if utils::is_likely_service_banner(payload) {
-                if let Some(port) = dst_port {
+            // Check if this is a recognisable service banner and identify the product;
+            // responses come from the service, so its port is the source port
+            if let Some(service) = crate::service_probe::classify_response(payload) {
+                if let Some(port) = src_port {
                     // This appears to be a service banner response
                     let banner_str = String::from_utf8_lossy(payload);
                     logger.log("INFO", &format!(
-                        "Possible service banner on port {}: {}", 
+                        "Possible {} banner on port {}: {}", 
+                        service,
                         port,
                         banner_str.chars().take(100).collect::<String>() // Limit to first 100 chars for log
                     ));
                     
-                    // Try to extract version information
-                    if let Some(version) = utils::extract_version_info(payload) {
+                    // Try to identify the product and version
+                    if let Some(identity) = crate::service_identity::identify(Some(service), payload) {
                         logger.log("INFO", &format!(
-                            "Detected version on port {}: {}", 
-                            port,
-                            version
+                            "Detected {} on port {}", 
+                            identity,
+                            port
                         ));
                     }
                 }
@@ -64,26 +563,19 @@ impl QuantumScanner {
         }
     }
     
-    // ... existing code ...
-    
     /// Analyze SSL/TLS on an open port
     async fn analyze_ssl(&mut self, target: &str, port: u16) -> Option<SslInfo> {
         let start_time = std::time::Instant::now();
         
-        // Create TLS configuration
-        let config = rustls::ClientConfig::builder()
-            .with_safe_defaults()
-            .with_root_certificates(webpki_roots::TLS_SERVER_ROOTS.clone())
-            .with_no_client_auth();
-            
-        let rc_config = Arc::new(config);
+        // Create TLS configuration; we want the certificate details even when
+        // the chain is self-signed or doesn't match the address we connected to
+        let rc_config = Arc::new(utils::insecure_tls_config());
         
         // Create TLS connector
         let connector = tokio_rustls::TlsConnector::from(rc_config);
         
         // Connect to target
-        let addr = format!("{}:{}", target, port);
-        let stream = match tokio::net::TcpStream::connect(&addr).await {
+        let stream = match self.egress.connect_tcp(target, port).await {
             Ok(s) => s,
             Err(e) => {
                 if let Some(logger) = &self.enhanced_logger {
@@ -129,7 +621,7 @@ impl QuantumScanner {
             cert_valid_from: None,
             cert_valid_to: None,
             cert_serial: None,
-            // ... other fields ...
+            ..SslInfo::default()
         };
         
         // Get connection info
@@ -138,6 +630,7 @@ impl QuantumScanner {
         
         // Process certificate if available
         if let Some(certs) = certs {
+            ssl_info.cert_chain = crate::certificates::encode_chain(&certs);
             if !certs.is_empty() {
                 use x509_parser::prelude::*;
                 // Parse first certificate (leaf cert)
@@ -150,18 +643,16 @@ impl QuantumScanner {
                     }
                     
                     // Extract subject alternative names
-                    if let Some(sans) = cert.subject_alternative_name() {
-                        if let Ok((_, san)) = sans.value.general_names() {
-                            for gn in san {
-                                match gn {
-                                    GeneralName::DNSName(name) => {
-                                        ssl_info.cert_san.push(name.to_string());
-                                    },
-                                    GeneralName::IPAddress(ip) => {
-                                        ssl_info.cert_san.push(format!("IP:{:?}", ip));
-                                    },
-                                    _ => {}
-                                }
+                    if let Ok(Some(sans)) = cert.subject_alternative_name() {
+                        for gn in &sans.value.general_names {
+                            match gn {
+                                GeneralName::DNSName(name) => {
+                                    ssl_info.cert_san.push(name.to_string());
+                                },
+                                GeneralName::IPAddress(ip) => {
+                                    ssl_info.cert_san.push(format!("IP:{:?}", ip));
+                                },
+                                _ => {}
                             }
                         }
                     }
@@ -198,207 +689,1019 @@ impl QuantumScanner {
         Some(ssl_info)
     }
 
-    // ... existing code ...
+    /// Record what a probe found
+    ///
+    /// Every probe leaves an observation for the filtering classification;
+    /// raw probes also keep the reply itself.
+    async fn record_outcome(&mut self, port: u16, scan_type: ScanType, outcome: crate::packet::ProbeOutcome) {
+        self.results.entry(port).or_default().probe_observations.push(crate::models::ProbeObservation {
+            scan_type,
+            status: outcome.status,
+            reply: outcome.reply,
+            attempts: outcome.attempts,
+        });
+        self.update_port_result_enhanced(port, scan_type, outcome.status).await;
+    }
     
     /// Update an enhanced scan status in results
     async fn update_port_result_enhanced(&mut self, port: u16, scan_type: ScanType, status: PortStatus) {
-        // ... existing code ...
+        let result = self.results.entry(port).or_default();
+        result.scan_time = chrono::Utc::now();
+        if scan_type == ScanType::Udp {
+            result.udp_state = Some(status);
+        } else {
+            result.tcp_states.insert(scan_type, status);
+        }
+        if let Some(logger) = &self.enhanced_logger {
+            logger.log("DEBUG", &format!("{}:{} {:?} -> {:?}", self.target_ip, port, scan_type, status));
+        }
         
-        // If the port is open, attempt additional analysis
-        if status == PortStatus::Open {
-            // Check if port is HTTP/HTTPS and grab server banner
-            if port == 80 || port == 443 || port == 8080 || port == 8443 {
-                self.grab_http_banner(&self.target_ip, port).await;
+        // Any definitive answer means the host is up
+        if !self.host_up_reported && (status == PortStatus::Open || status == PortStatus::Closed) {
+            self.host_up_reported = true;
+            self.emit(crate::events::ScanEventKind::HostUp { ip: self.target_ip.clone() });
+        }
+        self.emit(crate::events::ScanEventKind::PortState { port, scan_type, status });
+        
+        if status == PortStatus::Filtered {
+            self.control.record_timeout();
+        }
+        
+        // UDP ports that weren't refused get protocol-specific follow-ups rather
+        // than TCP probes (silence usually means open|filtered for UDP)
+        if scan_type == ScanType::Udp && status != PortStatus::Closed {
+            if !self.followed_up.insert((port, true)) || !self.checkpoint().await {
+                return;
             }
             
-            // Analyze SSL/TLS if appropriate
-            if port == 443 || port == 465 || port == 636 || port == 993 || port == 995 || port == 8443 {
-                if let Some(ssl_info) = self.analyze_ssl(&self.target_ip, port).await {
-                    if let Some(result) = self.results.get_mut(&port) {
-                        result.cert_info = Some(ssl_info);
-                    }
+            if let Some(snmp) = self.snmp.clone() {
+                if snmp.ports.contains(&port) {
+                    self.run_snmp_probe(port, snmp).await;
                 }
             }
+            self.run_dns_probes(port).await;
+            self.analyze_port_content(port);
+            return;
+        }
+        
+        // If the port is open, attempt additional analysis. Several TCP scan
+        // types can report the same port open; the follow-ups run only for
+        // the first of them.
+        if status == PortStatus::Open {
+            if !self.followed_up.insert((port, false)) {
+                return;
+            }
+            
+            // Honour pause / skip requests before sending follow-up probes
+            if !self.checkpoint().await {
+                return;
+            }
             
-            // Try simple banner grabbing for known service ports
-            let known_service_ports = [
-                21, 22, 23, 25, 110, 119, 143, 389, 587
-            ];
+            let identity_before = self.results.get(&port).map(|r| (r.service.clone(), r.version.clone()));
             
-            if known_service_ports.contains(&port) {
-                self.grab_banner(&self.target_ip, port).await;
+            // Identify the service: NULL banner, TLS detection, generic probes
+            self.identify_service(port).await;
+            
+            // Read what database servers volunteer before authentication
+            self.run_database_probes(port).await;
+            
+            // Enumerate RDP security protocols and VNC security types
+            self.run_remote_desktop_probes(port).await;
+            
+            // Ask DNS servers about themselves, recursion and zone transfers
+            self.run_dns_probes(port).await;
+            
+            // Read the LDAP rootDSE and test anonymous searches
+            self.run_ldap_probes(port).await;
+            
+            // Grade security headers and cookies of detected web servers
+            self.grade_http_security(port);
+            
+            // Compare advertised and accepted HTTP methods
+            if self.http_methods {
+                self.run_http_method_audit(port).await;
+            }
+            
+            // Run content/vhost discovery once HTTP and TLS details are known
+            if self.http_discovery.is_some() {
+                self.run_http_discovery(port).await;
+            }
+            
+            // Check for anonymous access once the service is known
+            if self.default_checks {
+                self.run_default_checks(port).await;
+            }
+            
+            // Look for leaked data in what the service sent back
+            self.analyze_port_content(port);
+            
+            // Report the service if analysis identified or refined it
+            if let Some(result) = self.results.get(&port) {
+                let identity = (result.service.clone(), result.version.clone());
+                if identity.0.is_some() && Some(&identity) != identity_before.as_ref() {
+                    let (service, version) = identity;
+                    self.emit(crate::events::ScanEventKind::ServiceIdentified {
+                        port,
+                        service: service.unwrap_or_default(),
+                        version,
+                    });
+                }
             }
         }
     }
     
-    /// Grab HTTP server banner and log it
-    async fn grab_http_banner(&mut self, target: &str, port: u16) {
-        let protocol = if port == 443 || port == 8443 { "https" } else { "http" };
-        let url = format!("{}://{}:{}/", protocol, target, port);
-        
-        // Simulate a very simple HTTP client
-        let request = format!(
-            "GET / HTTP/1.1\r\nHost: {}\r\nUser-Agent: Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/91.0.4472.124 Safari/537.36\r\nAccept: */*\r\nConnection: close\r\n\r\n",
-            target
+    /// Identify the service on an open port with the generic probe pipeline
+    ///
+    /// Runs regardless of the port number. When the port speaks TLS the
+    /// certificate is analysed, and detected web servers get the HTTP banner
+    /// grab over the matching transport.
+    async fn identify_service(&mut self, port: u16) {
+        let target = self.target_ip.clone();
+        let prober = crate::service_probe::ServiceProber::new(
+            &target,
+            port,
+            Duration::from_secs_f64(self.timeout_banner),
+            self.egress.clone(),
+            self.enhanced_logger.clone(),
         );
+        let report = prober.run().await;
         
-        let stream_result = if protocol == "https" {
-            // For HTTPS, we need TLS
-            let config = rustls::ClientConfig::builder()
-                .with_safe_defaults()
-                .with_root_certificates(webpki_roots::TLS_SERVER_ROOTS.clone())
-                .with_no_client_auth();
-                
-            let connector = tokio_rustls::TlsConnector::from(Arc::new(config));
-            let addr = format!("{}:{}", target, port);
-            
-            match tokio::net::TcpStream::connect(&addr).await {
-                Ok(stream) => {
-                    let domain = match rustls::ServerName::try_from(target) {
-                        Ok(d) => d,
-                        Err(_) => return,
-                    };
-                    
-                    match connector.connect(domain, stream).await {
-                        Ok(tls_stream) => {
-                            let (mut reader, mut writer) = tokio::io::split(tls_stream);
-                            
-                            // Write request
-                            if writer.write_all(request.as_bytes()).await.is_err() {
-                                return;
-                            }
-                            
-                            // Read response
-                            let mut buffer = [0; 4096];
-                            match reader.read(&mut buffer).await {
-                                Ok(n) if n > 0 => Some(buffer[..n].to_vec()),
-                                _ => None,
-                            }
-                        },
-                        Err(_) => None,
-                    }
-                },
-                Err(_) => None,
-            }
-        } else {
-            // Regular HTTP
-            let addr = format!("{}:{}", target, port);
-            
-            match tokio::net::TcpStream::connect(&addr).await {
-                Ok(mut stream) => {
-                    // Write request
-                    if stream.write_all(request.as_bytes()).await.is_err() {
-                        return;
-                    }
-                    
-                    // Read response
-                    let mut buffer = [0; 4096];
-                    match stream.read(&mut buffer).await {
-                        Ok(n) if n > 0 => Some(buffer[..n].to_vec()),
-                        _ => None,
-                    }
-                },
-                Err(_) => None,
+        if report.tls {
+            if let Some(ssl_info) = self.analyze_ssl(&target, port).await {
+                self.emit(crate::events::ScanEventKind::TlsInfo { port, info: Box::new(ssl_info.clone()) });
+                if let Some(result) = self.results.get_mut(&port) {
+                    result.cert_info = Some(ssl_info);
+                }
+                // New SAN names may point at zones the DNS server holds
+                self.check_new_dns_zones().await;
             }
-        };
+        }
         
-        // Process the response
-        if let Some(response) = stream_result {
-            // Log the HTTP response with the enhanced logger
+        // Fall back to the conventional service for the port if it answered
+        // with something we couldn't classify
+        let service = report.service().or_else(|| {
+            report.best_response()
+                .and(crate::service_probe::service_for_port(port))
+                .map(str::to_string)
+        });
+        
+        if let Some(response) = report.best_response() {
             self.log_packet_response(
-                target,
+                &target,
                 &utils::get_local_ipv4().unwrap_or_else(|| "127.0.0.1".to_string()),
-                "HTTP",
+                if response.tls { "TLS" } else { "TCP" },
                 Some(port),
                 None,
                 None,
-                &response,
+                &response.data,
                 None,
                 None
             );
             
-            // Update result
+            let identity = crate::service_identity::identify(service.as_deref(), &response.data);
+            if let Some(result) = self.results.get_mut(&port) {
+                let banner = &response.data[..std::cmp::min(response.data.len(), 1024)];
+                result.banner = Some(String::from_utf8_lossy(banner).to_string());
+                if let Some(identity) = identity {
+                    Self::apply_identity(result, identity);
+                }
+            }
+        }
+        
+        if let Some(result) = self.results.get_mut(&port) {
+            if service.is_some() {
+                result.service = service.clone();
+            }
+        }
+        
+        // Web servers get a proper request for their Server header
+        match service.as_deref() {
+            Some("http") => self.grab_http_banner(&target, port, false).await,
+            Some("https") => self.grab_http_banner(&target, port, true).await,
+            _ => {}
+        }
+    }
+    
+    /// Record a parsed service identity on a port result
+    ///
+    /// The human-readable version string is derived from the identity, and its
+    /// OS hint fills in the OS guess if nothing better is known yet.
+    fn apply_identity(result: &mut PortResult, identity: crate::models::ServiceIdentity) {
+        result.version = Some(identity.to_string());
+        if result.os_guess.is_none() {
+            result.os_guess = identity.os_hint.clone();
+        }
+        result.service_identity = Some(identity);
+    }
+    
+    /// Grab HTTP server banner and log it
+    ///
+    /// The response is read in full (up to the client's size cap), so the
+    /// body analysis and header grading see more than the first segment.
+    async fn grab_http_banner(&mut self, target: &str, port: u16, use_tls: bool) {
+        let protocol = if use_tls { "https" } else { "http" };
+        let timeout = Duration::from_secs_f64(self.timeout_banner);
+        let url = crate::http_client::HttpUrl::new(use_tls, &self.scanned_host(), port, "/");
+        let request = Self::http_request(&url, target);
+        
+        let parsed = match crate::http_client::send_request(target, port, use_tls, &request, timeout, &self.egress).await {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                if let Some(logger) = &self.enhanced_logger {
+                    logger.log("DEBUG", &format!("HTTP banner grab on {}:{} failed: {}", target, port, e));
+                }
+                return;
+            }
+        };
+        let response = &parsed.raw;
+        
+        // Log the HTTP response with the enhanced logger
+        self.log_packet_response(
+            target,
+            &utils::get_local_ipv4().unwrap_or_else(|| "127.0.0.1".to_string()),
+            "HTTP",
+            Some(port),
+            None,
+            None,
+            response,
+            None,
+            None
+        );
+        
+        // Update result
+        if let Some(result) = self.results.get_mut(&port) {
+            // Save banner
+            result.banner = Some(String::from_utf8_lossy(&response[..std::cmp::min(response.len(), 1024)]).to_string());
+            
+            // Identify the server from its Server header
+            if let Some(identity) = crate::service_identity::identify(Some(protocol), response) {
+                Self::apply_identity(result, identity);
+            }
+            
+            // Keep the parsed response for header grading and discovery
+            result.http_info = Some(parsed.to_http_info());
+            
+            // Update service
+            result.service = Some(protocol.to_string());
+        }
+        
+        // The body isn't kept on the result, so analyse it now
+        self.analyze_http_body(port, "http_body", &parsed.body);
+        
+        // Follow redirects so the analysis sees the page users land on
+        if parsed.is_redirect() && self.max_redirects > 0 {
+            self.follow_redirects(target, port, use_tls, parsed).await;
+        }
+    }
+    
+    /// Name the user asked to scan, as it appears in URLs
+    fn scanned_host(&self) -> String {
+        self.target.trim_end_matches('.').to_lowercase()
+    }
+    
+    /// GET request for `url`, sent to `address`
+    ///
+    /// When the connection goes to an address other than the URL's host (the
+    /// resolved IP of a scanned hostname), the host name is still named in
+    /// the Host header and SNI so virtual hosts answer as they would for a
+    /// browser.
+    fn http_request(url: &crate::http_client::HttpUrl, address: &str) -> crate::http_client::HttpRequest {
+        let mut request = crate::http_client::HttpRequest::get(&url.path);
+        if url.port != if url.tls { 443 } else { 80 } {
+            request.host_header = Some(format!("{}:{}", url.host, url.port));
+        } else if url.host != address {
+            request.host_header = Some(url.host.clone());
+        }
+        if url.tls && url.host != address {
+            request.sni = Some(url.host.clone());
+        }
+        request
+    }
+    
+    /// Follow a web server's redirect chain and keep the final response
+    ///
+    /// Every hop is recorded on `HttpInfo.redirects`, switching between plain
+    /// HTTP and TLS as the Location headers require. A hop stays on the
+    /// scanned host when it names either the scanned host name or the
+    /// address `target` it resolved to; those hops are requested from
+    /// `target`. Hops to another host are recorded but only requested when a
+    /// scope is set (which then vets the host) or cross-host redirects were
+    /// enabled explicitly. The port's
+    /// banner and identity stay with the first response, since later hops may
+    /// be served by another host; the rest of `HttpInfo` describes the final
+    /// response.
+    async fn follow_redirects(&mut self, target: &str, port: u16, use_tls: bool, first: crate::http_client::HttpResponse) {
+        use crate::http_client::HttpUrl;
+        
+        let timeout = Duration::from_secs_f64(self.timeout_banner);
+        let scanned_host = self.scanned_host();
+        let target_ip = target.to_lowercase();
+        let same_host = |host: &str| host == scanned_host || host == target_ip;
+        let cross_host_allowed = self.cross_host_redirects || self.egress.scope().is_some();
+        let mut url = HttpUrl::new(use_tls, &scanned_host, port, "/");
+        let mut visited = HashSet::from([url.clone()]);
+        let mut response = first;
+        let mut hops = Vec::new();
+        let mut redirect_loop = false;
+        
+        while response.is_redirect() {
+            let next = match response.header("Location").and_then(|location| url.join(location)) {
+                Some(next) => next,
+                None => break,
+            };
+            hops.push(crate::models::RedirectHop {
+                url: url.to_string(),
+                status_code: response.status_code,
+                location: next.to_string(),
+                cross_host: !same_host(&next.host),
+            });
+            
+            if !visited.insert(next.clone()) {
+                redirect_loop = true;
+                break;
+            }
+            if !same_host(&next.host) && !cross_host_allowed {
+                if let Some(logger) = &self.enhanced_logger {
+                    logger.log("DEBUG", &format!(
+                        "Not following redirect from {}:{} to another host ({}) without a scope", target, port, next
+                    ));
+                }
+                break;
+            }
+            if hops.len() > self.max_redirects {
+                if let Some(logger) = &self.enhanced_logger {
+                    logger.log("DEBUG", &format!(
+                        "Stopped following redirects from {}:{} after {} hops", target, port, self.max_redirects
+                    ));
+                }
+                break;
+            }
+            
+            let address = if same_host(&next.host) { target } else { next.host.as_str() };
+            let request = Self::http_request(&next, address);
+            response = match crate::http_client::send_request(address, next.port, next.tls, &request, timeout, &self.egress).await {
+                Ok(response) => response,
+                Err(e) => {
+                    if let Some(logger) = &self.enhanced_logger {
+                        logger.log("DEBUG", &format!("Redirect to {} failed: {}", next, e));
+                    }
+                    break;
+                }
+            };
+            url = next;
+        }
+        
+        if let Some(logger) = &self.enhanced_logger {
+            logger.log("INFO", &format!(
+                "{}:{} redirects {} times to {}{}",
+                target, port, hops.len(), url, if redirect_loop { " (loop)" } else { "" }
+            ));
+        }
+        
+        if !hops.is_empty() {
+            self.analyze_http_body(port, &format!("http_body {}", url), &response.body);
+        }
+        
+        let mut http_info = response.to_http_info();
+        http_info.redirects = hops;
+        http_info.final_url = Some(url.to_string());
+        http_info.redirect_loop = redirect_loop;
+        if let Some(result) = self.results.get_mut(&port) {
+            result.http_info = Some(http_info);
+        }
+    }
+    
+    /// Run the file analyzer over the port's banner and certificate fields
+    ///
+    /// HTTP bodies aren't stored on the result, so they are analysed where they
+    /// are read, in `grab_http_banner` and `follow_redirects`.
+    fn analyze_port_content(&mut self, port: u16) {
+        let found = match self.results.get(&port) {
+            Some(result) => {
+                let mut found = Vec::new();
+                if let Some(banner) = &result.banner {
+                    found.extend(crate::content_analysis::analyze("banner", banner.as_bytes()));
+                }
+                if let Some(cert) = &result.cert_info {
+                    found.extend(crate::content_analysis::analyze_certificate(cert));
+                }
+                found
+            }
+            None => return,
+        };
+        self.record_content_findings(port, |source| source == "banner" || source.starts_with("tls."), found);
+    }
+    
+    /// Run the file analyzer over an HTTP response body read from `port`
+    fn analyze_http_body(&mut self, port: u16, source: &str, body: &[u8]) {
+        let found = crate::content_analysis::analyze(source, body);
+        self.record_content_findings(port, |s| s == source, found);
+    }
+    
+    /// Replace the content findings from the sources matched by `replaces`
+    fn record_content_findings<F>(&mut self, port: u16, replaces: F, found: Vec<crate::models::ContentFinding>)
+    where
+        F: Fn(&str) -> bool,
+    {
+        let target = self.target_ip.clone();
+        if let Some(result) = self.results.get_mut(&port) {
+            if let Some(logger) = &self.enhanced_logger {
+                for finding in &found {
+                    logger.log("INFO", &format!(
+                        "Content analysis of {}:{} {}: {} x{}", target, port, finding.source, finding.category, finding.values.len()
+                    ));
+                }
+            }
+            result.content_findings.retain(|f| !replaces(&f.source));
+            result.content_findings.extend(found);
+        }
+    }
+    
+    /// Grade the stored HTTP response's security headers and cookies
+    fn grade_http_security(&mut self, port: u16) {
+        let target = self.target_ip.clone();
+        let scanned_host = self.scanned_host();
+        if let Some(result) = self.results.get_mut(&port) {
+            let tls = match result.service.as_deref() {
+                Some("http") => false,
+                Some("https") => true,
+                _ => return,
+            };
+            if let Some(http_info) = &result.http_info {
+                // After redirects, judge the final response by where it came from
+                let final_url = http_info.final_url.as_deref().and_then(crate::http_client::HttpUrl::parse);
+                let (host, tls) = match &final_url {
+                    Some(url) => (url.host.as_str(), url.tls),
+                    None => (scanned_host.as_str(), tls),
+                };
+                let findings = crate::http_security::grade(http_info, host, tls);
+                if let Some(logger) = &self.enhanced_logger {
+                    if !findings.is_empty() {
+                        logger.log("INFO", &format!(
+                            "{} HTTP security header/cookie findings on {}:{}", findings.len(), target, port
+                        ));
+                    }
+                }
+                result.findings.retain(|f| f.service != "http" && f.service != "https");
+                result.findings.extend(findings);
+            }
+        }
+    }
+    
+    /// Run the HTTP method and WebDAV audit against a detected web server
+    async fn run_http_method_audit(&mut self, port: u16) {
+        let tls = match self.results.get(&port).and_then(|r| r.service.as_deref()) {
+            Some("http") => false,
+            Some("https") => true,
+            _ => return,
+        };
+        
+        let audit = crate::http_methods::HttpMethodAudit::new(
+            &self.target_ip,
+            port,
+            tls,
+            Duration::from_secs_f64(self.timeout_banner),
+            self.egress.clone(),
+            self.enhanced_logger.clone(),
+        );
+        if let Some(found) = audit.run().await {
             if let Some(result) = self.results.get_mut(&port) {
-                // Save banner
-                result.banner = Some(String::from_utf8_lossy(&response[..std::cmp::min(response.len(), 1024)]).to_string());
-                
-                // Extract server header
-                if let Some(version) = utils::extract_version_info(&response) {
-                    result.version = Some(version);
+                result.findings.extend(found.findings);
+                result.http_info.get_or_insert_with(Default::default).methods = Some(found.methods);
+            }
+        }
+    }
+    
+    /// Run path and virtual-host discovery against a detected web server
+    async fn run_http_discovery(&mut self, port: u16) {
+        let config = match &self.http_discovery {
+            Some(config) => config.clone(),
+            None => return,
+        };
+        
+        // Only follow up on ports where the banner grab found a web server
+        let (use_tls, mut http_info, san_names) = match self.results.get(&port) {
+            Some(result) => match result.service.as_deref() {
+                Some("http") | Some("https") => (
+                    result.service.as_deref() == Some("https"),
+                    result.http_info.clone().unwrap_or_default(),
+                    result.cert_info.as_ref().map(|c| c.cert_san.clone()).unwrap_or_default(),
+                ),
+                _ => return,
+            },
+            None => return,
+        };
+        
+        let discovery = crate::http_discovery::HttpDiscovery::new(
+            &self.target_ip,
+            port,
+            use_tls,
+            config,
+            Duration::from_secs_f64(self.timeout_banner),
+            self.egress.clone(),
+            self.enhanced_logger.clone(),
+        );
+        discovery.run(&mut http_info, &san_names).await;
+        
+        if let Some(result) = self.results.get_mut(&port) {
+            result.http_info = Some(http_info);
+        }
+    }
+    
+    /// Run the pre-authentication handshake probe for database ports
+    async fn run_database_probes(&mut self, port: u16) {
+        let service = self.results.get(&port).and_then(|r| r.service.clone());
+        let probes = crate::db_probes::DatabaseProbes::new(
+            Duration::from_secs_f64(self.timeout_banner),
+            self.egress.clone(),
+            self.enhanced_logger.clone(),
+        );
+        
+        if let Some(found) = probes.run(&self.target_ip, port, service.as_deref()).await {
+            if let Some(result) = self.results.get_mut(&port) {
+                // A successful handshake settles what the port is running
+                result.service = Some(found.info.engine.clone());
+                if let Some(identity) = found.identity {
+                    Self::apply_identity(result, identity);
                 }
-                
-                // Update service
-                if protocol == "https" {
-                    result.service = Some("https".to_string());
-                } else {
-                    result.service = Some("http".to_string());
+                result.database_info = Some(found.info);
+            }
+        }
+    }
+    
+    /// Run the RDP / VNC security negotiation probe
+    async fn run_remote_desktop_probes(&mut self, port: u16) {
+        let service = self.results.get(&port).and_then(|r| r.service.clone());
+        let probes = crate::remote_desktop::RemoteDesktopProbes::new(
+            Duration::from_secs_f64(self.timeout_banner),
+            self.egress.clone(),
+            self.enhanced_logger.clone(),
+        );
+        
+        if let Some(found) = probes.run(&self.target_ip, port, service.as_deref()).await {
+            if let Some(result) = self.results.get_mut(&port) {
+                result.service = Some(found.info.protocol.clone());
+                if found.info.protocol == "rdp" && result.service_identity.is_none() {
+                    Self::apply_identity(result, crate::models::ServiceIdentity {
+                        product: "Microsoft Terminal Services".to_string(),
+                        os_hint: Some("Windows".to_string()),
+                        cpe: Some("cpe:/o:microsoft:windows".to_string()),
+                        ..Default::default()
+                    });
+                }
+                if let Some(release) = found.info.ntlm_info.as_ref()
+                    .and_then(|ntlm| ntlm.os_version.as_deref())
+                    .and_then(crate::ntlm::windows_release)
+                {
+                    result.os_guess = Some(release.to_string());
                 }
+                result.vulns.extend(found.vulns);
+                result.remote_desktop = Some(found.info);
             }
         }
     }
     
-    /// Grab generic service banner and log it
-    async fn grab_banner(&mut self, target: &str, port: u16) {
-        let addr = format!("{}:{}", target, port);
+    /// Guess SNMP communities and read the system and interface tables
+    async fn run_snmp_probe(&mut self, port: u16, config: Arc<crate::snmp::SnmpConfig>) {
+        let probe = crate::snmp::SnmpProbe::new(
+            config,
+            Duration::from_secs_f64(self.timeout_banner),
+            self.egress.clone(),
+            self.enhanced_logger.clone(),
+        );
         
-        match tokio::time::timeout(
+        if let Some(info) = probe.run(&self.target_ip, port).await {
+            if let Some(result) = self.results.get_mut(&port) {
+                // An SNMP answer proves the port is open
+                result.udp_state = Some(PortStatus::Open);
+                result.service = Some("snmp".to_string());
+                if let Some(descr) = &info.sys_descr {
+                    result.banner = Some(descr.clone());
+                }
+                result.vulns.push(format!(
+                    "[snmp] SNMP {} agent readable with community '{}'",
+                    info.version, info.community
+                ));
+                result.snmp_info = Some(info);
+            }
+        }
+    }
+    
+    /// Inspect a DNS server: CHAOS names, recursion, DNSSEC and AXFR
+    async fn run_dns_probes(&mut self, port: u16) {
+        let is_dns = match self.results.get(&port) {
+            Some(result) if result.dns_info.is_some() => return, // already probed over the other transport
+            Some(result) => matches!(result.service.as_deref(), Some("domain") | Some("dns")) || port == 53,
+            None => return,
+        };
+        if !is_dns {
+            return;
+        }
+        
+        let config = self.dns.clone().unwrap_or_default();
+        let zones = self.dns_zones(&config);
+        let probe = crate::dns::DnsProbe::new(
+            config,
             Duration::from_secs_f64(self.timeout_banner),
-            tokio::net::TcpStream::connect(&addr)
-        ).await {
-            Ok(Ok(mut stream)) => {
-                // Some services send a banner immediately upon connection
-                let mut buffer = [0; 2048];
-                
-                // Wait a short time for the banner
-                match tokio::time::timeout(
-                    Duration::from_millis(500),
-                    stream.read(&mut buffer)
-                ).await {
-                    Ok(Ok(n)) if n > 0 => {
-                        let response = &buffer[..n];
-                        
-                        // Log the service response
-                        self.log_packet_response(
-                            target,
-                            &utils::get_local_ipv4().unwrap_or_else(|| "127.0.0.1".to_string()),
-                            "TCP",
-                            Some(port),
-                            None,
-                            None,
-                            response,
-                            None,
-                            None
-                        );
-                        
-                        // Update result with banner
-                        if let Some(result) = self.results.get_mut(&port) {
-                            result.banner = Some(String::from_utf8_lossy(response).to_string());
-                            
-                            // Try to infer service and version
-                            match port {
-                                21 => result.service = Some("ftp".to_string()),
-                                22 => result.service = Some("ssh".to_string()),
-                                23 => result.service = Some("telnet".to_string()),
-                                25 | 587 => result.service = Some("smtp".to_string()),
-                                110 => result.service = Some("pop3".to_string()),
-                                119 => result.service = Some("nntp".to_string()),
-                                143 => result.service = Some("imap".to_string()),
-                                389 => result.service = Some("ldap".to_string()),
-                                _ => {}
-                            }
-                            
-                            // Extract version if possible
-                            if let Some(version) = utils::extract_version_info(response) {
-                                result.version = Some(version);
-                            }
-                        }
-                    },
-                    _ => {}
+            self.egress.clone(),
+            self.enhanced_logger.clone(),
+        );
+        
+        if let Some(info) = probe.run(&self.target_ip, port, &zones).await {
+            if let Some(result) = self.results.get_mut(&port) {
+                result.service = Some("domain".to_string());
+                if let Some(version) = &info.version_bind {
+                    match crate::service_identity::parse_dns_version(version) {
+                        Some(identity) => Self::apply_identity(result, identity),
+                        None => result.version = Some(version.clone()),
+                    }
                 }
-            },
-            _ => {}
+                if info.recursion == Some(true) {
+                    result.vulns.push(
+                        "[dns] Open resolver: recursive queries for external names are answered".to_string()
+                    );
+                }
+                for zone in &info.zones {
+                    result.vulns.extend(Self::dns_zone_vuln(zone));
+                }
+                result.dns_info = Some(info);
+            }
+        }
+    }
+    
+    /// Run zone checks for SAN-derived zones a DNS server hasn't been asked about
+    async fn check_new_dns_zones(&mut self) {
+        let config = self.dns.clone().unwrap_or_default();
+        let zones = self.dns_zones(&config);
+        let pending: Vec<(u16, Vec<String>)> = self.results.iter()
+            .filter_map(|(port, result)| {
+                let info = result.dns_info.as_ref()?;
+                let new: Vec<String> = zones.iter()
+                    .filter(|zone| !info.zones.iter().any(|z| &z.domain == *zone))
+                    .cloned()
+                    .collect();
+                (!new.is_empty()).then_some((*port, new))
+            })
+            .collect();
+        
+        let probe = crate::dns::DnsProbe::new(
+            config,
+            Duration::from_secs_f64(self.timeout_banner),
+            self.egress.clone(),
+            self.enhanced_logger.clone(),
+        );
+        for (port, new_zones) in pending {
+            for zone in new_zones {
+                let zone_info = probe.check_zone(&self.target_ip, port, &zone).await;
+                if let Some(result) = self.results.get_mut(&port) {
+                    result.vulns.extend(Self::dns_zone_vuln(&zone_info));
+                    if let Some(info) = result.dns_info.as_mut() {
+                        info.zones.push(zone_info);
+                    }
+                }
+            }
         }
     }
+    
+    /// Configured zones plus those derived from certificate SANs seen so far
+    fn dns_zones(&self, config: &crate::dns::DnsConfig) -> Vec<String> {
+        let mut zones: Vec<String> = config.domains.iter()
+            .map(|d| d.trim_end_matches('.').to_lowercase())
+            .collect();
+        if config.domains_from_certificates {
+            for result in self.results.values() {
+                let sans = result.cert_info.iter().flat_map(|c| c.cert_san.iter());
+                zones.extend(sans.filter_map(|san| crate::dns::zone_from_san(san))
+                    .filter(|zone| self.egress.permit_name(zone)));
+            }
+        }
+        zones.sort();
+        zones.dedup();
+        zones
+    }
+    
+    /// Vulnerability string for a zone that allowed a transfer
+    fn dns_zone_vuln(zone: &crate::models::DnsZoneInfo) -> Option<String> {
+        (zone.axfr_allowed == Some(true)).then(|| format!(
+            "[dns] Zone transfer (AXFR) allowed for {} ({} records)",
+            zone.domain,
+            zone.records.len()
+        ))
+    }
+    
+    /// Read the rootDSE and check whether the directory can be searched anonymously
+    async fn run_ldap_probes(&mut self, port: u16) {
+        let (service, tls) = match self.results.get(&port) {
+            Some(result) => (result.service.clone(), result.cert_info.is_some()),
+            None => return,
+        };
+        let probe = crate::ldap::LdapProbe::new(
+            Duration::from_secs_f64(self.timeout_banner),
+            self.egress.clone(),
+            self.enhanced_logger.clone(),
+        );
+        
+        if let Some(found) = probe.run(&self.target_ip, port, service.as_deref(), tls).await {
+            if let Some(result) = self.results.get_mut(&port) {
+                result.service = Some(if tls { "ssl/ldap" } else { "ldap" }.to_string());
+                if let Some(identity) = found.identity {
+                    Self::apply_identity(result, identity);
+                }
+                if found.info.anonymous_search == Some(true) {
+                    result.vulns.push(format!(
+                        "[ldap] Anonymous search of {} returns directory entries ({} shown)",
+                        found.info.default_naming_context.as_deref().unwrap_or("the base DN"),
+                        found.info.sample_entries.len()
+                    ));
+                }
+                result.ldap_info = Some(found.info);
+            }
+        }
+    }
+    
+    /// Run the read-only default-configuration check for a port
+    async fn run_default_checks(&mut self, port: u16) {
+        let service = self.results.get(&port).and_then(|r| r.service.clone());
+        let checks = crate::default_checks::DefaultChecks::new(
+            Duration::from_secs_f64(self.timeout_banner),
+            self.egress.clone(),
+            self.enhanced_logger.clone(),
+        );
+        
+        if let Some(finding) = checks.run(&self.target_ip, port, service.as_deref()).await {
+            if let Some(result) = self.results.get_mut(&port) {
+                if result.service.is_none() {
+                    result.service = Some(finding.service.to_string());
+                }
+                result.vulns.push(finding.to_string());
+            }
+        }
+    }
+}
+
+/// Whether a connect failed because the probe was never sent: out of scope,
+/// or stopped by the scan control
+fn not_sent(error: &std::io::Error) -> bool {
+    matches!(error.kind(), std::io::ErrorKind::PermissionDenied | std::io::ErrorKind::Interrupted)
+}
+
+/// Initial retransmission timeout for a SYN (Linux and macOS); it doubles on every retry
+const SYN_INITIAL_RTO: Duration = Duration::from_secs(1);
+
+/// SYNs the kernel had sent when a connect was answered after `elapsed`
+///
+/// Retries go out at 1, 3, 7, 15... seconds, so an answer after 1.2s was to
+/// the second SYN. A path with over a second of round trip counts as a retry
+/// too; that is rare enough not to skew the rate-limit signal.
+fn syn_attempts(elapsed: Duration) -> u32 {
+    let mut attempts = 1;
+    let mut next_retry = SYN_INITIAL_RTO;
+    while elapsed >= next_retry && attempts < 16 {
+        attempts += 1;
+        next_retry = next_retry * 2 + SYN_INITIAL_RTO;
+    }
+    attempts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    #[test]
+    fn connect_time_maps_to_syn_retries() {
+        assert_eq!(syn_attempts(Duration::from_millis(5)), 1);
+        assert_eq!(syn_attempts(Duration::from_millis(999)), 1);
+        assert_eq!(syn_attempts(Duration::from_millis(1020)), 2);
+        assert_eq!(syn_attempts(Duration::from_millis(3100)), 3);
+        assert_eq!(syn_attempts(Duration::from_secs(8)), 4);
+    }
+
+    #[tokio::test]
+    async fn socket_probes_leave_observations() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let open = listener.local_addr().unwrap().port();
+        let closed = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                drop(stream);
+            }
+        });
+
+        // TLS probes always go over a socket, raw sockets or not
+        let mut scanner = crate::config::ScannerBuilder::new("127.0.0.1")
+            .ports(vec![open, closed])
+            .scan_types(vec![ScanType::Ssl])
+            .timeouts(0.2, 0.2, 0.2)
+            .build()
+            .await
+            .unwrap();
+        let results = scanner.run().await.unwrap();
+
+        let observed = |port: u16| results.results[&port].probe_observations.clone();
+        assert_eq!(observed(open), [crate::models::ProbeObservation {
+            scan_type: ScanType::Ssl, status: PortStatus::Open, reply: None, attempts: 1,
+        }]);
+        assert_eq!(observed(closed)[0].status, PortStatus::Closed);
+    }
+
+    /// Serve `200 OK` to every request and count them
+    async fn counting_http_server() -> (u16, Arc<std::sync::atomic::AtomicUsize>) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let hits = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let counter = hits.clone();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                let mut buffer = [0u8; 1024];
+                let _ = stream.read(&mut buffer).await;
+                let _ = stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok").await;
+            }
+        });
+        (port, hits)
+    }
+
+    async fn redirect_scanner(port: u16, builder: impl FnOnce(crate::config::ScannerBuilder) -> crate::config::ScannerBuilder) -> QuantumScanner {
+        let base = crate::config::ScannerBuilder::new("127.0.0.1")
+            .ports(vec![port])
+            .scan_types(vec![ScanType::Ssl])
+            .timeouts(1.0, 1.0, 1.0);
+        let mut scanner = builder(base).build().await.unwrap();
+        scanner.results.insert(port, PortResult::new());
+        scanner
+    }
+
+    fn redirect_to(location: &str) -> crate::http_client::HttpResponse {
+        let raw = format!("HTTP/1.1 302 Found\r\nLocation: {}\r\nContent-Length: 0\r\n\r\n", location);
+        crate::http_client::parse_response(raw.as_bytes()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn redirects_stay_on_the_scanned_host_by_default() {
+        use std::sync::atomic::Ordering;
+        let (port, hits) = counting_http_server().await;
 
-    // ... existing code ...
-} 
\ No newline at end of file
+        // Same host: followed
+        let mut scanner = redirect_scanner(port, |b| b).await;
+        scanner.follow_redirects("127.0.0.1", port, false, redirect_to("/landing")).await;
+        let info = scanner.results[&port].http_info.clone().unwrap();
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+        assert_eq!(info.final_url.as_deref(), Some(format!("http://127.0.0.1:{}/landing", port).as_str()));
+        assert!(!info.redirects[0].cross_host);
+
+        // Another host: recorded but not requested
+        let elsewhere = format!("http://localhost:{}/elsewhere", port);
+        scanner.follow_redirects("127.0.0.1", port, false, redirect_to(&elsewhere)).await;
+        let info = scanner.results[&port].http_info.clone().unwrap();
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+        assert_eq!(info.redirects.len(), 1);
+        assert!(info.redirects[0].cross_host);
+        assert_eq!(info.redirects[0].location, elsewhere);
+        assert_eq!(info.final_url.as_deref(), Some(format!("http://127.0.0.1:{}/", port).as_str()));
+
+        // Explicitly allowed
+        let mut scanner = redirect_scanner(port, |b| b.cross_host_redirects(true)).await;
+        scanner.follow_redirects("127.0.0.1", port, false, redirect_to(&elsewhere)).await;
+        assert_eq!(hits.load(Ordering::SeqCst), 2);
+        let info = scanner.results[&port].http_info.clone().unwrap();
+        assert_eq!(info.final_url.as_deref(), Some(elsewhere.as_str()));
+
+        // Allowed under a scope, which vets the new host
+        let scope = crate::scope::ScopeConfig {
+            include: vec!["127.0.0.1".to_string(), "localhost".to_string()],
+            ..Default::default()
+        };
+        let mut scanner = redirect_scanner(port, |b| b.scope(scope)).await;
+        scanner.follow_redirects("127.0.0.1", port, false, redirect_to(&elsewhere)).await;
+        assert_eq!(hits.load(Ordering::SeqCst), 3);
+
+        let scope = crate::scope::ScopeConfig {
+            include: vec!["127.0.0.1".to_string()],
+            exclude: vec!["localhost".to_string()],
+            ..Default::default()
+        };
+        let mut scanner = redirect_scanner(port, |b| b.scope(scope)).await;
+        scanner.follow_redirects("127.0.0.1", port, false, redirect_to(&elsewhere)).await;
+        assert_eq!(hits.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn redirects_on_a_scanned_hostname_stay_on_the_host() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let hosts = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let seen = hosts.clone();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut buffer = [0u8; 1024];
+                let n = stream.read(&mut buffer).await.unwrap_or(0);
+                let request = String::from_utf8_lossy(&buffer[..n]).to_string();
+                let host = request.lines().find_map(|l| l.strip_prefix("Host: ")).unwrap_or("").to_string();
+                seen.lock().unwrap().push(host);
+                let response = match request.split_whitespace().nth(1).unwrap_or("/") {
+                    "/" => format!("HTTP/1.1 301 Moved\r\nLocation: http://LOCALHOST:{}/home\r\nContent-Length: 0\r\n\r\n", port),
+                    "/home" => format!("HTTP/1.1 302 Found\r\nLocation: http://127.0.0.1:{}/ip\r\nContent-Length: 0\r\n\r\n", port),
+                    _ => "HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok".to_string(),
+                };
+                let _ = stream.write_all(response.as_bytes()).await;
+            }
+        });
+
+        let mut scanner = crate::config::ScannerBuilder::new("localhost")
+            .ports(vec![port])
+            .scan_types(vec![ScanType::Ssl])
+            .timeouts(1.0, 1.0, 1.0)
+            .build()
+            .await
+            .unwrap();
+        scanner.target_ip = "127.0.0.1".to_string();
+        scanner.results.insert(port, PortResult::new());
+        scanner.grab_http_banner("127.0.0.1", port, false).await;
+
+        let info = scanner.results[&port].http_info.clone().unwrap();
+        let hops: Vec<(&str, bool)> = info.redirects.iter().map(|h| (h.url.as_str(), h.cross_host)).collect();
+        assert_eq!(hops, [
+            (format!("http://localhost:{}/", port).as_str(), false),
+            (format!("http://localhost:{}/home", port).as_str(), false),
+        ]);
+        assert_eq!(info.final_url, Some(format!("http://127.0.0.1:{}/ip", port)));
+        assert_eq!(*hosts.lock().unwrap(), [
+            format!("localhost:{}", port),
+            format!("localhost:{}", port),
+            format!("127.0.0.1:{}", port),
+        ], "the scanned name is sent in the Host header");
+    }
+
+    #[tokio::test]
+    async fn follow_ups_run_once_per_port() {
+        use std::sync::atomic::Ordering;
+        let (port, hits) = counting_http_server().await;
+        let mut scanner = redirect_scanner(port, |b| b.http_methods(true)).await;
+        scanner.target_ip = "127.0.0.1".to_string();
+
+        scanner.update_port_result_enhanced(port, ScanType::Syn, PortStatus::Open).await;
+        let after_first = hits.load(Ordering::SeqCst);
+        assert!(after_first > 0);
+        let result = scanner.results[&port].clone();
+        assert_eq!(result.service.as_deref(), Some("http"));
+
+        scanner.update_port_result_enhanced(port, ScanType::Ack, PortStatus::Open).await;
+        scanner.update_port_result_enhanced(port, ScanType::Ssl, PortStatus::Open).await;
+        assert_eq!(hits.load(Ordering::SeqCst), after_first, "no probes are repeated");
+        let again = &scanner.results[&port];
+        assert_eq!(again.tcp_states.len(), 3, "every scan type's state is still recorded");
+        assert_eq!(again.vulns, result.vulns);
+        assert_eq!(again.findings.len(), result.findings.len());
+
+        // UDP follow-ups are tracked separately
+        assert!(!scanner.followed_up.contains(&(port, true)));
+        scanner.update_port_result_enhanced(port, ScanType::Udp, PortStatus::OpenFiltered).await;
+        assert!(scanner.followed_up.contains(&(port, true)));
+    }
+
+    #[tokio::test]
+    async fn http_banner_reads_the_whole_response() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let body = format!("<html><!-- {} --><title>Late title</title></html>", "x".repeat(8000));
+        let size = body.len();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut buffer = [0u8; 1024];
+            let _ = stream.read(&mut buffer).await;
+            let head = format!("HTTP/1.1 200 OK\r\nServer: nginx/1.18.0\r\nContent-Length: {}\r\n\r\n", body.len());
+            stream.write_all(head.as_bytes()).await.unwrap();
+            stream.flush().await.unwrap();
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            stream.write_all(body.as_bytes()).await.unwrap();
+        });
+
+        let mut scanner = redirect_scanner(port, |b| b).await;
+        scanner.grab_http_banner("127.0.0.1", port, false).await;
+
+        let result = &scanner.results[&port];
+        let info = result.http_info.as_ref().unwrap();
+        assert_eq!(info.title.as_deref(), Some("Late title"));
+        assert_eq!(info.response_size, Some(size));
+        assert_eq!(result.service.as_deref(), Some("http"));
+        assert_eq!(result.service_identity.as_ref().unwrap().product, "nginx");
+        assert!(result.banner.as_deref().unwrap().starts_with("HTTP/1.1 200 OK"));
+    }
+
+    #[tokio::test]
+    async fn logged_banners_are_identified() {
+        let logger = Arc::new(utils::EnhancedLogger::new(100, false, true));
+        let scanner = crate::config::ScannerBuilder::new("127.0.0.1")
+            .ports(vec![22])
+            .enhanced_logger(logger.clone())
+            .build()
+            .await
+            .unwrap();
+
+        scanner.log_packet_response("127.0.0.1", "127.0.0.1", "TCP", Some(22), Some(40000), None, b"SSH-2.0-OpenSSH_9.6\r\n", None, None);
+        let logs = logger.buffer().format_logs(false);
+        assert!(logs.contains("Possible ssh banner on port 22"), "{}", logs);
+        assert_eq!(logger.packet_log_count(), 1);
+    }
+}
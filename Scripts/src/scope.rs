@@ -0,0 +1,639 @@
+//! Engagement scope guard
+//!
+//! A scope file lists the networks and host names the scanner may touch, the
+//! ones it must never touch, and optionally the dates and daily time windows
+//! in which testing is allowed. A scanner built with a scope hands an
+//! `Egress` to everything that talks to the network: connections go through
+//! `Egress::connect_tcp` / `connect_udp`, which resolve the destination,
+//! check each address and only then open the socket, and raw probes are
+//! checked with `permit_probe` before they are sent. Names that end up in
+//! requests without being connected to directly (Host headers, SNI, DNS
+//! zones taken from certificate SANs) are checked with `permit_name`.
+//! Refusals are logged and kept so they can be reported with the results.
+//!
+//! ```toml
+//! include = ["10.20.0.0/16", "portal.example.com", "*.dev.example.com"]
+//! exclude = ["10.20.5.0/24", "vpn.example.com"]
+//! not_before = "2026-10-01T00:00:00Z"
+//! not_after = "2026-10-31T23:59:59Z"
+//!
+//! [[windows]]
+//! days = ["mon", "tue", "wed", "thu", "fri"]
+//! start = "09:00"
+//! end = "18:00"
+//! ```
+
+use std::collections::HashSet;
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::path::Path;
+use std::sync::Arc;
+
+use chrono::{DateTime, Datelike, Local, NaiveTime, Timelike, Utc};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+
+use crate::audit::{AuditLog, AuditedStream, AuditedUdpSocket};
+use crate::control::ScanControl;
+use crate::error::{Result, ScanError};
+use crate::utils;
+
+/// Scope definition as written in the scope file
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScopeConfig {
+    /// Networks (CIDR or single address) and host names that may be scanned;
+    /// "*.example.com" matches every name below example.com
+    pub include: Vec<String>,
+    /// Networks and host names that must never be scanned, even if included
+    pub exclude: Vec<String>,
+    /// Testing is not allowed before this time
+    pub not_before: Option<DateTime<Utc>>,
+    /// Testing is not allowed after this time
+    pub not_after: Option<DateTime<Utc>>,
+    /// Daily windows (local time) in which testing is allowed; empty = any time
+    pub windows: Vec<TimeWindow>,
+}
+
+/// A recurring window in which testing is allowed
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TimeWindow {
+    /// Weekdays the window applies to ("mon" .. "sun"); empty = every day
+    pub days: Vec<String>,
+    /// Start time, "HH:MM" local time
+    pub start: String,
+    /// End time, "HH:MM" local time; earlier than `start` wraps past midnight
+    pub end: String,
+}
+
+/// A refused connection or probe
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScopeViolation {
+    /// When the attempt was refused
+    pub time: DateTime<Utc>,
+    /// Destination that was refused (address, name or name:port)
+    pub destination: String,
+    /// Why it is out of scope
+    pub reason: String,
+}
+
+impl ScopeConfig {
+    /// Read and parse a scope file
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path).map_err(|source| ScanError::File {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        let config: Self = toml::from_str(&text)
+            .map_err(|e| ScanError::InvalidConfig(format!("{}: {}", path.display(), e)))?;
+        Scope::new(config.clone(), None)?;
+        Ok(config)
+    }
+}
+
+/// IP network in CIDR form
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Network {
+    addr: IpAddr,
+    prefix: u8,
+}
+
+impl Network {
+    fn parse(value: &str) -> Option<Self> {
+        let (addr, prefix) = match value.split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (value, None),
+        };
+        let addr: IpAddr = addr.trim().trim_start_matches('[').trim_end_matches(']').parse().ok()?;
+        let max = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(p) => p.trim().parse::<u8>().ok().filter(|p| *p <= max)?,
+            None => max,
+        };
+        // Store IPv4-mapped IPv6 networks as the IPv4 network they cover
+        match addr {
+            IpAddr::V6(v6) if prefix >= 96 => match v6.to_ipv4_mapped() {
+                Some(v4) => Some(Self { addr: IpAddr::V4(v4), prefix: prefix - 96 }),
+                None => Some(Self { addr, prefix }),
+            },
+            _ => Some(Self { addr, prefix }),
+        }
+    }
+
+    fn contains(&self, ip: IpAddr) -> bool {
+        // Compare IPv4-mapped IPv6 addresses as IPv4
+        let ip = match ip {
+            IpAddr::V6(v6) => v6.to_ipv4_mapped().map(IpAddr::V4).unwrap_or(ip),
+            v4 => v4,
+        };
+        match (self.addr, ip) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix as u32).unwrap_or(0);
+                u32::from(net) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix as u32).unwrap_or(0);
+                u128::from(net) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Host name, or "*.domain" for every name below a domain
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum NamePattern {
+    Exact(String),
+    Below(String),
+}
+
+impl NamePattern {
+    fn parse(value: &str) -> Option<Self> {
+        let value = value.trim().trim_end_matches('.').to_lowercase();
+        let valid = |name: &str| {
+            !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.' || c == '_')
+        };
+        match value.strip_prefix("*.") {
+            Some(domain) if valid(domain) => Some(Self::Below(domain.to_string())),
+            None if valid(&value) => Some(Self::Exact(value)),
+            _ => None,
+        }
+    }
+
+    fn matches(&self, name: &str) -> bool {
+        match self {
+            Self::Exact(exact) => name == exact,
+            Self::Below(domain) => name.len() > domain.len() + 1
+                && name.ends_with(domain.as_str())
+                && name.as_bytes()[name.len() - domain.len() - 1] == b'.',
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Rules {
+    networks: Vec<Network>,
+    names: Vec<NamePattern>,
+}
+
+impl Rules {
+    fn parse(entries: &[String], what: &str) -> Result<Self> {
+        let mut rules = Self::default();
+        for entry in entries.iter().map(|e| e.trim()).filter(|e| !e.is_empty()) {
+            if let Some(network) = Network::parse(entry) {
+                rules.networks.push(network);
+            } else if let Some(name) = NamePattern::parse(entry) {
+                rules.names.push(name);
+            } else {
+                return Err(ScanError::InvalidConfig(format!("invalid {} scope entry '{}'", what, entry)));
+            }
+        }
+        Ok(rules)
+    }
+
+    fn matches_ip(&self, ip: IpAddr) -> bool {
+        self.networks.iter().any(|n| n.contains(ip))
+    }
+
+    fn matches_name(&self, name: &str) -> bool {
+        self.names.iter().any(|p| p.matches(name))
+    }
+}
+
+#[derive(Debug)]
+struct Window {
+    days: Vec<chrono::Weekday>,
+    start: NaiveTime,
+    end: NaiveTime,
+}
+
+impl Window {
+    fn parse(window: &TimeWindow) -> Result<Self> {
+        let time = |value: &str| {
+            NaiveTime::parse_from_str(value.trim(), "%H:%M")
+                .map_err(|_| ScanError::InvalidConfig(format!("invalid scope window time '{}'", value)))
+        };
+        let days = window.days.iter()
+            .map(|day| day.trim().parse::<chrono::Weekday>()
+                .map_err(|_| ScanError::InvalidConfig(format!("invalid scope window day '{}'", day))))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self { days, start: time(&window.start)?, end: time(&window.end)? })
+    }
+
+    fn contains(&self, now: DateTime<Local>) -> bool {
+        let time = NaiveTime::from_hms_opt(now.hour(), now.minute(), now.second()).unwrap_or_default();
+        let (in_window, day) = if self.start <= self.end {
+            (time >= self.start && time < self.end, now.weekday())
+        } else if time >= self.start {
+            (true, now.weekday())
+        } else {
+            // After midnight the window belongs to the day it started on
+            (time < self.end, now.weekday().pred())
+        };
+        in_window && (self.days.is_empty() || self.days.contains(&day))
+    }
+}
+
+/// Compiled scope with its violation record
+pub struct Scope {
+    config: ScopeConfig,
+    include: Rules,
+    exclude: Rules,
+    windows: Vec<Window>,
+    /// Addresses admitted because the name they were resolved from is included
+    resolved: Mutex<HashSet<IpAddr>>,
+    violations: Mutex<Vec<ScopeViolation>>,
+    logger: Option<Arc<utils::EnhancedLogger>>,
+}
+
+impl Scope {
+    /// Compile a scope definition, rejecting entries that can't be parsed
+    pub fn new(config: ScopeConfig, logger: Option<Arc<utils::EnhancedLogger>>) -> Result<Self> {
+        let include = Rules::parse(&config.include, "include")?;
+        if include.networks.is_empty() && include.names.is_empty() {
+            return Err(ScanError::InvalidConfig("scope includes nothing".to_string()));
+        }
+        let exclude = Rules::parse(&config.exclude, "exclude")?;
+        let windows = config.windows.iter().map(Window::parse).collect::<Result<Vec<_>>>()?;
+        Ok(Self {
+            config,
+            include,
+            exclude,
+            windows,
+            resolved: Mutex::new(HashSet::new()),
+            violations: Mutex::new(Vec::new()),
+            logger,
+        })
+    }
+
+    /// The definition this scope was compiled from
+    pub fn config(&self) -> &ScopeConfig {
+        &self.config
+    }
+
+    /// Every refusal so far
+    pub fn violations(&self) -> Vec<ScopeViolation> {
+        self.violations.lock().clone()
+    }
+
+    /// Whether testing is allowed right now
+    pub fn check_time(&self) -> std::result::Result<(), String> {
+        let now = Utc::now();
+        if self.config.not_before.map(|t| now < t).unwrap_or(false) {
+            return Err("engagement has not started yet".to_string());
+        }
+        if self.config.not_after.map(|t| now > t).unwrap_or(false) {
+            return Err("engagement period has ended".to_string());
+        }
+        if !self.windows.is_empty() && !self.windows.iter().any(|w| w.contains(now.with_timezone(&Local))) {
+            return Err("outside the permitted testing windows".to_string());
+        }
+        Ok(())
+    }
+
+    /// Check an address, reached through `name` if it was resolved from one
+    pub fn check_addr(&self, name: Option<&str>, ip: IpAddr) -> std::result::Result<(), String> {
+        self.check_time()?;
+        let name = name.map(normalize_name);
+        if self.exclude.matches_ip(ip) {
+            return Err(format!("{} is excluded", ip));
+        }
+        if let Some(name) = &name {
+            if self.exclude.matches_name(name) {
+                return Err(format!("{} is excluded", name));
+            }
+        }
+        if self.include.matches_ip(ip) || self.resolved.lock().contains(&ip) {
+            return Ok(());
+        }
+        if name.as_deref().map(|n| self.include.matches_name(n)).unwrap_or(false) {
+            // Raw probes only see the address, so remember where the name led
+            self.resolved.lock().insert(ip);
+            Ok(())
+        } else {
+            Err(format!("{} is not in scope", ip))
+        }
+    }
+
+    /// Check a host name used without connecting to it directly
+    ///
+    /// Excluded names are always refused. When the scope lists host names,
+    /// a name must match one of them; a scope made only of networks leaves
+    /// names to be judged by the addresses they are sent to.
+    pub fn check_name(&self, name: &str) -> std::result::Result<(), String> {
+        self.check_time()?;
+        let name = normalize_name(name);
+        if let Ok(ip) = name.parse::<IpAddr>() {
+            return self.check_addr(None, ip);
+        }
+        if self.exclude.matches_name(&name) {
+            return Err(format!("{} is excluded", name));
+        }
+        if !self.include.names.is_empty() && !self.include.matches_name(&name) {
+            return Err(format!("{} is not in scope", name));
+        }
+        Ok(())
+    }
+
+    /// Check the scan target before anything is sent to it
+    pub async fn check_target(&self, target: &str) -> Result<()> {
+        let name = target.parse::<IpAddr>().is_err().then_some(target);
+        let addrs = tokio::net::lookup_host((target, 0)).await.map_err(|e| ScanError::Resolve {
+            target: target.to_string(),
+            reason: e.to_string(),
+        })?;
+        let mut refusal = None;
+        let mut permitted = false;
+        for addr in addrs {
+            match self.check_addr(name, addr.ip()) {
+                Ok(()) => permitted = true,
+                Err(reason) => refusal = Some(reason),
+            }
+        }
+        match (permitted, refusal) {
+            (true, _) => Ok(()),
+            (false, reason) => {
+                let reason = reason.unwrap_or_else(|| "name did not resolve".to_string());
+                self.refuse(target, &reason);
+                Err(ScanError::OutOfScope { target: target.to_string(), reason })
+            }
+        }
+    }
+
+    /// Record and log a refusal
+    fn refuse(&self, destination: &str, reason: &str) {
+        let mut violations = self.violations.lock();
+        // Repeated attempts at the same destination are recorded once
+        if violations.iter().any(|v| v.destination == destination && v.reason == reason) {
+            return;
+        }
+        if let Some(logger) = &self.logger {
+            logger.log("WARN", &format!("Scope guard refused {}: {}", destination, reason));
+        }
+        violations.push(ScopeViolation {
+            time: Utc::now(),
+            destination: destination.to_string(),
+            reason: reason.to_string(),
+        });
+    }
+}
+
+/// The checks every outbound connection and probe of a scan goes through
+///
+/// Each scanner holds its own `Egress`, so scans running side by side in one
+/// process (daemon jobs, cluster units, monitor targets) are each held to
+/// their own scope and write their own audit log. Without a scope everything
+/// is allowed; without a log nothing is recorded. With a scan control, every
+/// probe also waits out pauses and the rate limit and stops once its host is
+/// skipped. Clones share the scope, its violation record, the log and the
+/// control.
+#[derive(Clone, Default)]
+pub struct Egress {
+    scope: Option<Arc<Scope>>,
+    audit: Option<Arc<AuditLog>>,
+    control: Option<Arc<ScanControl>>,
+}
+
+impl Egress {
+    /// Enforce `scope` and record probes in `audit`; either may be `None`
+    pub fn new(scope: Option<Arc<Scope>>, audit: Option<Arc<AuditLog>>) -> Self {
+        Self { scope, audit, control: None }
+    }
+
+    /// Gate every probe through `control`
+    pub fn with_control(mut self, control: Arc<ScanControl>) -> Self {
+        self.control = Some(control);
+        self
+    }
+
+    /// The scan control probes are gated through, if any
+    pub fn control(&self) -> Option<&Arc<ScanControl>> {
+        self.control.as_ref()
+    }
+
+    /// Wait until the scan control lets a probe to `ip` go
+    ///
+    /// Fails with `Interrupted` when the host was skipped or the scan
+    /// cancelled; the probe must not be sent then.
+    pub async fn checkpoint(&self, ip: IpAddr) -> io::Result<()> {
+        match &self.control {
+            Some(control) if !control.checkpoint(&ip.to_string()).await => Err(io::Error::new(
+                io::ErrorKind::Interrupted,
+                format!("probe to {} stopped: host skipped or scan cancelled", ip),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// The scope being enforced, if any
+    pub fn scope(&self) -> Option<&Arc<Scope>> {
+        self.scope.as_ref()
+    }
+
+    /// The audit log probes are recorded in, if any
+    pub fn audit(&self) -> Option<&Arc<AuditLog>> {
+        self.audit.as_ref()
+    }
+
+    /// Record a raw packet that was put on the wire
+    pub fn record_probe(&self, source: &str, destination: &str, protocol: &str, flags: Option<&str>, payload: &[u8]) {
+        crate::audit::record_probe(self.audit.as_deref(), source, destination, protocol, flags, payload);
+    }
+
+    /// Whether a raw probe to `host` may be sent; refusals are recorded
+    pub fn permit_probe(&self, host: &str) -> bool {
+        let scope = match &self.scope {
+            Some(scope) => scope,
+            None => return true,
+        };
+        let verdict = match host.parse::<IpAddr>() {
+            Ok(ip) => scope.check_addr(None, ip),
+            Err(_) => scope.check_name(host),
+        };
+        match verdict {
+            Ok(()) => true,
+            Err(reason) => {
+                scope.refuse(host, &reason);
+                false
+            }
+        }
+    }
+
+    /// Whether a name may be used in a request (Host header, SNI, DNS query)
+    pub fn permit_name(&self, name: &str) -> bool {
+        let scope = match &self.scope {
+            Some(scope) => scope,
+            None => return true,
+        };
+        match scope.check_name(name) {
+            Ok(()) => true,
+            Err(reason) => {
+                scope.refuse(name, &reason);
+                false
+            }
+        }
+    }
+
+    /// Resolve `host` and keep only the addresses the scope allows
+    async fn permitted_addrs(&self, host: &str, port: u16) -> io::Result<Vec<SocketAddr>> {
+        let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host, port)).await?.collect();
+        let scope = match &self.scope {
+            Some(scope) => scope,
+            None => return Ok(addrs),
+        };
+
+        let name = host.parse::<IpAddr>().is_err().then_some(host);
+        let mut permitted = Vec::new();
+        let mut refusal = None;
+        for addr in addrs {
+            match scope.check_addr(name, addr.ip()) {
+                Ok(()) => permitted.push(addr),
+                Err(reason) => refusal = Some(reason),
+            }
+        }
+        if permitted.is_empty() {
+            let reason = refusal.unwrap_or_else(|| "name did not resolve".to_string());
+            scope.refuse(&format!("{}:{}", host, port), &reason);
+            return Err(io::Error::new(io::ErrorKind::PermissionDenied, format!("out of scope: {}", reason)));
+        }
+        Ok(permitted)
+    }
+
+    /// Open a TCP connection, refusing destinations outside the scope
+    ///
+    /// Each connection attempt passes the scan control's checkpoint first,
+    /// and is recorded in the audit log, if there is one.
+    pub async fn connect_tcp(&self, host: &str, port: u16) -> io::Result<AuditedStream> {
+        let mut last_error = None;
+        for addr in self.permitted_addrs(host, port).await? {
+            self.checkpoint(addr.ip()).await?;
+            match AuditedStream::connect(addr, self.audit.clone()).await {
+                Ok(stream) => return Ok(stream),
+                Err(e) => last_error = Some(e),
+            }
+        }
+        Err(last_error.unwrap_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no address to connect to")))
+    }
+
+    /// Open a connected UDP socket, refusing destinations outside the scope
+    ///
+    /// Opening it passes the scan control's checkpoint; datagrams sent on it
+    /// are recorded in the audit log, if there is one.
+    pub async fn connect_udp(&self, host: &str, port: u16) -> io::Result<AuditedUdpSocket> {
+        let addr = self.permitted_addrs(host, port).await?[0];
+        self.checkpoint(addr.ip()).await?;
+        AuditedUdpSocket::connect(addr, self.audit.clone()).await
+    }
+}
+
+/// Add the scope and its refusals to a results file that has been written
+///
+/// JSON results get a top-level "scope" object; text results get a section
+/// appended.
+pub fn embed_in_results(scope: &Scope, path: &Path, json: bool) -> io::Result<()> {
+    let violations = scope.violations();
+
+    if json {
+        let text = std::fs::read_to_string(path)?;
+        let mut value: serde_json::Value = serde_json::from_str(&text)?;
+        if let Some(object) = value.as_object_mut() {
+            object.insert("scope".to_string(), serde_json::json!({
+                "definition": scope.config(),
+                "violations": violations,
+            }));
+        }
+        std::fs::write(path, serde_json::to_string_pretty(&value)?)?;
+    } else {
+        use std::io::Write;
+        let mut file = std::fs::OpenOptions::new().append(true).open(path)?;
+        writeln!(file, "\nScope")?;
+        writeln!(file, "  Include: {}", scope.config().include.join(", "))?;
+        if !scope.config().exclude.is_empty() {
+            writeln!(file, "  Exclude: {}", scope.config().exclude.join(", "))?;
+        }
+        for window in &scope.config().windows {
+            let days = if window.days.is_empty() { "daily".to_string() } else { window.days.join(",") };
+            writeln!(file, "  Window: {} {}-{}", days, window.start, window.end)?;
+        }
+        writeln!(file, "  Refused: {}", violations.len())?;
+        for violation in &violations {
+            writeln!(file, "    {} {} ({})", violation.time.to_rfc3339(), violation.destination, violation.reason)?;
+        }
+    }
+    Ok(())
+}
+
+fn normalize_name(name: &str) -> String {
+    name.trim().trim_end_matches('.').to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scope(include: &[&str], exclude: &[&str]) -> Arc<Scope> {
+        let config = ScopeConfig {
+            include: include.iter().map(|s| s.to_string()).collect(),
+            exclude: exclude.iter().map(|s| s.to_string()).collect(),
+            ..ScopeConfig::default()
+        };
+        Arc::new(Scope::new(config, None).unwrap())
+    }
+
+    #[test]
+    fn rules_match_networks_and_names() {
+        let egress = Egress::new(Some(scope(&["127.0.0.0/8", "*.example.com"], &["127.0.0.2", "vpn.example.com"])), None);
+        assert!(egress.permit_probe("127.0.0.9"));
+        assert!(!egress.permit_probe("127.0.0.2"));
+        assert!(!egress.permit_probe("10.0.0.1"));
+        assert!(egress.permit_name("a.example.com"));
+        assert!(!egress.permit_name("example.com"));
+        assert!(!egress.permit_name("vpn.example.com"));
+    }
+
+    #[test]
+    fn bad_rules_are_rejected() {
+        let bad = ScopeConfig { include: vec!["10.0.0.0/33".to_string()], ..ScopeConfig::default() };
+        assert!(Scope::new(bad, None).is_err());
+        assert!(Scope::new(ScopeConfig::default(), None).is_err());
+    }
+
+    #[tokio::test]
+    async fn scopes_do_not_leak_between_egresses() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let narrow = scope(&["10.0.0.0/8"], &[]);
+        let confined = Egress::new(Some(narrow.clone()), None);
+        let open = Egress::new(Some(scope(&["127.0.0.1"], &[])), None);
+        let unscoped = Egress::default();
+
+        assert!(confined.connect_tcp("127.0.0.1", port).await.is_err());
+        assert!(open.connect_tcp("127.0.0.1", port).await.is_ok());
+        assert!(unscoped.connect_tcp("127.0.0.1", port).await.is_ok());
+
+        // Only the egress that refused records the refusal
+        assert_eq!(narrow.violations().len(), 1);
+        assert!(open.scope().unwrap().violations().is_empty());
+    }
+
+    #[tokio::test]
+    async fn connections_pass_the_scan_control() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let control = Arc::new(ScanControl::new(0));
+        let egress = Egress::default().with_control(control.clone());
+
+        assert!(egress.connect_tcp("127.0.0.1", port).await.is_ok());
+        assert!(egress.connect_udp("127.0.0.1", 9).await.is_ok());
+        assert_eq!(control.counters().probes, 2);
+
+        control.skip_host("127.0.0.1");
+        let error = egress.connect_tcp("127.0.0.1", port).await.unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::Interrupted);
+        assert_eq!(control.counters().probes, 2);
+        // Other hosts are still probed
+        assert!(egress.connect_udp("127.0.0.2", 9).await.is_ok());
+    }
+}
@@ -0,0 +1,570 @@
+//! Protocol-aware banner parsers
+//!
+//! Each parser understands one protocol's greeting format and turns it into a
+//! `ServiceIdentity` with product, version, extra details, an OS hint and a CPE
+//! name. Parsers only report what the banner actually says; an unrecognised
+//! product yields no identity rather than a guessed version string.
+
+use crate::models::ServiceIdentity;
+
+/// Identify the product behind a banner or probe response
+///
+/// `service` is the detected service name (e.g. "smtp", "ssl/imap") and is
+/// used to pick a parser when the banner format is shared between protocols,
+/// such as the "220" greeting of FTP and SMTP.
+pub fn identify(service: Option<&str>, data: &[u8]) -> Option<ServiceIdentity> {
+    if crate::service_probe::is_mysql_greeting(data) {
+        return parse_mysql(data);
+    }
+
+    let text = String::from_utf8_lossy(&data[..data.len().min(4096)]);
+    let service = service.map(|s| s.trim_start_matches("ssl/"));
+
+    if text.starts_with("SSH-") {
+        parse_ssh(&text)
+    } else if text.starts_with("HTTP/") {
+        http_server_header(&text).and_then(parse_http_server)
+    } else if text.starts_with("RFB ") {
+        parse_vnc(&text)
+    } else if text.starts_with("+OK") {
+        parse_mail_access(&text, "pop3d")
+    } else if text.starts_with("* OK") || text.starts_with("* PREAUTH") {
+        parse_mail_access(&text, "imapd")
+    } else if text.starts_with("220") {
+        match service {
+            Some("smtp") | Some("submission") => parse_smtp(&text),
+            Some("ftp") => parse_ftp(&text),
+            _ => parse_smtp(&text).or_else(|| parse_ftp(&text)),
+        }
+    } else {
+        None
+    }
+}
+
+/// Parse an SSH identification string (`SSH-2.0-OpenSSH_8.9p1 Ubuntu-3`)
+pub fn parse_ssh(banner: &str) -> Option<ServiceIdentity> {
+    let line = banner.lines().next()?.trim();
+    let rest = line.strip_prefix("SSH-")?;
+    let (protocol, rest) = rest.split_once('-')?;
+    let (software, comment) = match rest.split_once(' ') {
+        Some((software, comment)) => (software, Some(comment.trim())),
+        None => (rest, None),
+    };
+
+    let (name, version) = split_name_version(software);
+    let mut identity = match name {
+        "OpenSSH" => match version.and_then(|v| v.strip_prefix("for_Windows_")) {
+            Some(windows_version) => ServiceIdentity {
+                product: "OpenSSH for Windows".to_string(),
+                version: Some(windows_version.to_string()),
+                os_hint: Some("Windows".to_string()),
+                cpe: Some(cpe("openbsd", "openssh", Some(windows_version))),
+                ..Default::default()
+            },
+            None => product("OpenSSH", version, Some(("openbsd", "openssh"))),
+        },
+        "dropbear" => product("Dropbear sshd", version, Some(("matt_johnston", "dropbear_ssh_server"))),
+        "libssh" => product("libssh", version, Some(("libssh", "libssh"))),
+        "Cisco" => ServiceIdentity {
+            os_hint: Some("Cisco IOS".to_string()),
+            ..product("Cisco SSH", version, None)
+        },
+        "ROSSSH" => ServiceIdentity {
+            os_hint: Some("MikroTik RouterOS".to_string()),
+            ..product("MikroTik RouterOS sshd", version, None)
+        },
+        _ => product(name, version, None),
+    };
+
+    let mut extra = Vec::new();
+    if let Some(comment) = comment.filter(|c| !c.is_empty()) {
+        extra.push(comment.to_string());
+        if identity.os_hint.is_none() {
+            identity.os_hint = os_from_text(comment);
+        }
+    }
+    extra.push(format!("protocol {}", protocol));
+    identity.extra_info = Some(extra.join("; "));
+
+    Some(identity)
+}
+
+/// Parse an SMTP 220 greeting
+pub fn parse_smtp(banner: &str) -> Option<ServiceIdentity> {
+    let greeting = greeting(banner, "220")?;
+    let line = greeting.as_str();
+
+    let mut identity = if let Some(version) = word_after(line, "Exim ") {
+        product("Exim smtpd", Some(version), Some(("exim", "exim")))
+    } else if line.contains("Postfix") {
+        product("Postfix smtpd", None, Some(("postfix", "postfix")))
+    } else if let Some(version) = word_after(line, "Sendmail ") {
+        let version = version.split(['/', ';']).next().unwrap_or(version);
+        product("Sendmail", Some(version), Some(("sendmail", "sendmail")))
+    } else if line.contains("OpenSMTPD") {
+        product("OpenSMTPD", None, Some(("openbsd", "opensmtpd")))
+    } else if line.contains("Microsoft ESMTP MAIL Service") {
+        let version = word_after(line, "Version: ").map(|v| v.trim_end_matches(','));
+        ServiceIdentity {
+            os_hint: Some("Windows".to_string()),
+            ..product("Microsoft ESMTP", version, None)
+        }
+    } else if line.contains("Microsoft Exchange") {
+        ServiceIdentity {
+            os_hint: Some("Windows".to_string()),
+            ..product("Microsoft Exchange smtpd", None, Some(("microsoft", "exchange_server")))
+        }
+    } else {
+        return None;
+    };
+
+    if identity.os_hint.is_none() {
+        identity.os_hint = parenthesised(line).and_then(os_from_text);
+    }
+    Some(identity)
+}
+
+/// Parse an FTP 220 greeting
+pub fn parse_ftp(banner: &str) -> Option<ServiceIdentity> {
+    let greeting = greeting(banner, "220")?;
+    let line = greeting.as_str();
+
+    let mut identity = if let Some(version) = word_after(line, "vsFTPd ") {
+        product("vsftpd", Some(version.trim_end_matches(')')), Some(("beasts", "vsftpd")))
+    } else if line.contains("ProFTPD") {
+        let version = word_after(line, "ProFTPD ").filter(|v| starts_with_digit(v));
+        product("ProFTPD", version, Some(("proftpd", "proftpd")))
+    } else if line.contains("Pure-FTPd") {
+        product("Pure-FTPd", None, Some(("pureftpd", "pure-ftpd")))
+    } else if line.contains("FileZilla Server") {
+        let rest = line.split("FileZilla Server").nth(1).unwrap_or("").trim_start();
+        let rest = rest.strip_prefix("version").unwrap_or(rest).trim_start();
+        let mut words = rest.split_whitespace();
+        let version = words.next().filter(|v| starts_with_digit(v));
+        ServiceIdentity {
+            extra_info: version.and(words.next()).filter(|w| w.eq_ignore_ascii_case("beta")).map(str::to_string),
+            os_hint: Some("Windows".to_string()),
+            ..product("FileZilla ftpd", version, Some(("filezilla-project", "filezilla_server")))
+        }
+    } else if line.contains("Microsoft FTP Service") {
+        ServiceIdentity {
+            os_hint: Some("Windows".to_string()),
+            ..product("Microsoft ftpd", None, Some(("microsoft", "ftp_service")))
+        }
+    } else if line.contains("Serv-U FTP Server") {
+        let version = word_after(line, "Serv-U FTP Server v");
+        ServiceIdentity {
+            os_hint: Some("Windows".to_string()),
+            ..product("Serv-U ftpd", version, Some(("serv-u", "serv-u")))
+        }
+    } else {
+        return None;
+    };
+
+    if identity.os_hint.is_none() {
+        identity.os_hint = parenthesised(line).and_then(os_from_text);
+    }
+    Some(identity)
+}
+
+/// Parse a POP3 (`+OK`) or IMAP (`* OK`) greeting
+///
+/// `kind` is "pop3d" or "imapd" and completes the product name.
+pub fn parse_mail_access(banner: &str, kind: &str) -> Option<ServiceIdentity> {
+    let line = banner.lines().next()?.trim();
+
+    let mut identity = if line.contains("Dovecot") {
+        product(&format!("Dovecot {}", kind), None, Some(("dovecot", "dovecot")))
+    } else if line.contains("Courier-IMAP") || (kind == "pop3d" && line.starts_with("+OK Hello there")) {
+        let name = if kind == "imapd" { "Courier Imapd" } else { "Courier pop3d" };
+        product(name, None, Some(("courier-mta", "courier-imap")))
+    } else if line.contains("Cyrus") {
+        // "Cyrus IMAP v2.4.17-Debian-2.4.17 server ready"
+        let full = word_after(line, " v").filter(|v| starts_with_digit(v));
+        let (version, extra) = match full.and_then(|v| v.split_once('-')) {
+            Some((version, extra)) => (Some(version), Some(extra)),
+            None => (full, None),
+        };
+        ServiceIdentity {
+            extra_info: extra.map(str::to_string),
+            os_hint: extra.and_then(os_from_text),
+            ..product(&format!("Cyrus {}", kind), version, Some(("cmu", "cyrus_imap_server")))
+        }
+    } else if line.contains("Microsoft Exchange") {
+        ServiceIdentity {
+            os_hint: Some("Windows".to_string()),
+            ..product(&format!("Microsoft Exchange {}", kind), None, Some(("microsoft", "exchange_server")))
+        }
+    } else {
+        return None;
+    };
+
+    if identity.os_hint.is_none() {
+        identity.os_hint = parenthesised(line).and_then(os_from_text);
+    }
+    Some(identity)
+}
+
+/// Parse an HTTP `Server` header value (`Apache/2.4.41 (Ubuntu) OpenSSL/1.1.1f`)
+pub fn parse_http_server(value: &str) -> Option<ServiceIdentity> {
+    let value = value.trim();
+    if value.is_empty() {
+        return None;
+    }
+
+    // Jetty reports itself as "Jetty(9.4.43.v20210629)"
+    if let Some(version) = value.strip_prefix("Jetty(").and_then(|v| v.split(')').next()) {
+        return Some(product("Jetty", Some(version), Some(("eclipse", "jetty"))));
+    }
+
+    let (first, rest) = value.split_once(' ').unwrap_or((value, ""));
+    let (name, version) = match first.split_once('/') {
+        Some((name, version)) => (name, Some(version).filter(|v| !v.is_empty())),
+        None => (first, None),
+    };
+
+    let mut identity = match name {
+        "Apache" => product("Apache httpd", version, Some(("apache", "http_server"))),
+        "nginx" => product("nginx", version, Some(("igor_sysoev", "nginx"))),
+        "openresty" => product("OpenResty web app server", version, Some(("openresty", "openresty"))),
+        "Microsoft-IIS" => ServiceIdentity {
+            os_hint: Some("Windows".to_string()),
+            ..product("Microsoft IIS httpd", version, Some(("microsoft", "internet_information_services")))
+        },
+        "Microsoft-HTTPAPI" => ServiceIdentity {
+            os_hint: Some("Windows".to_string()),
+            ..product("Microsoft HTTPAPI httpd", version, Some(("microsoft", "http.sys")))
+        },
+        "lighttpd" => product("lighttpd", version, Some(("lighttpd", "lighttpd"))),
+        "LiteSpeed" => product("LiteSpeed httpd", version, Some(("litespeedtech", "litespeed_web_server"))),
+        "Caddy" => product("Caddy httpd", version, Some(("caddyserver", "caddy"))),
+        "gunicorn" => product("Gunicorn", version, Some(("gunicorn", "gunicorn"))),
+        "Werkzeug" => product("Werkzeug httpd", version, Some(("palletsprojects", "werkzeug"))),
+        "SimpleHTTP" => product("SimpleHTTPServer", version, Some(("python", "simplehttpserver"))),
+        "Kestrel" => product("Kestrel httpd", version, Some(("microsoft", "kestrel"))),
+        _ => product(name, version, None),
+    };
+
+    let rest = rest.trim();
+    let comment = parenthesised(rest);
+    if let Some(comment) = comment {
+        if identity.os_hint.is_none() {
+            identity.os_hint = os_from_text(comment);
+        }
+    }
+
+    // Remaining components ("OpenSSL/1.1.1f PHP/7.4.3", "Python/3.11.2")
+    let components: Vec<String> = rest.split_whitespace()
+        .filter(|word| !word.starts_with('(') && !word.ends_with(')'))
+        .map(|word| word.replacen('/', " ", 1))
+        .collect();
+    let extra: Vec<String> = comment.map(str::to_string).into_iter().chain(components).collect();
+    if !extra.is_empty() {
+        identity.extra_info = Some(extra.join("; "));
+    }
+
+    Some(identity)
+}
+
+/// Parse a MySQL/MariaDB initial handshake packet
+pub fn parse_mysql(data: &[u8]) -> Option<ServiceIdentity> {
+    let payload = data.get(4..)?;
+
+    if payload.first() == Some(&0xff) {
+        // Error packet, typically "Host '...' is not allowed to connect"
+        return Some(ServiceIdentity {
+            extra_info: Some("unauthorized".to_string()),
+            ..product("MySQL", None, Some(("mysql", "mysql")))
+        });
+    }
+
+    let end = payload.iter().skip(1).position(|&b| b == 0)? + 1;
+    let raw = std::str::from_utf8(&payload[1..end]).ok()?;
+    Some(identity_from_mysql_version(raw))
+}
+
+/// Build an identity from a MySQL server version string
+///
+/// MariaDB prefixes its real version with "5.5.5-" for old clients.
+pub fn identity_from_mysql_version(raw: &str) -> ServiceIdentity {
+    let is_mariadb = raw.contains("MariaDB");
+    let raw = if is_mariadb { raw.trim_start_matches("5.5.5-") } else { raw };
+    let (version, extra) = match raw.split_once('-') {
+        Some((version, extra)) => (version, Some(extra)),
+        None => (raw, None),
+    };
+
+    let mut identity = if is_mariadb {
+        product("MariaDB", Some(version), Some(("mariadb", "mariadb")))
+    } else {
+        product("MySQL", Some(version), Some(("mysql", "mysql")))
+    };
+    identity.extra_info = extra.map(str::to_string);
+    identity.os_hint = extra.and_then(os_from_text);
+    identity
+}
+
+/// Parse an RFB (VNC) protocol version greeting
+pub fn parse_vnc(banner: &str) -> Option<ServiceIdentity> {
+    let version = banner.strip_prefix("RFB ")?.get(..7)?;
+    let (major, minor) = version.split_once('.')?;
+    let major: u32 = major.parse().ok()?;
+    let minor: u32 = minor.parse().ok()?;
+    Some(ServiceIdentity {
+        extra_info: Some(format!("protocol {}.{}", major, minor)),
+        ..product("VNC", None, None)
+    })
+}
+
+/// Parse a DNS server's `version.bind` answer (`9.16.1-Ubuntu`, `dnsmasq-2.85`)
+pub fn parse_dns_version(version: &str) -> Option<ServiceIdentity> {
+    let version = version.trim();
+    let lower = version.to_lowercase();
+    let first_number = || version.split_whitespace().find(|w| starts_with_digit(w));
+
+    let mut identity = if starts_with_digit(version) {
+        // BIND answers with the bare version, plus a distribution suffix
+        let (number, extra) = match version.split_once(|c: char| c == '-' || c.is_whitespace()) {
+            Some((number, extra)) => (number, Some(extra.trim())),
+            None => (version, None),
+        };
+        ServiceIdentity {
+            extra_info: extra.filter(|e| !e.is_empty()).map(str::to_string),
+            ..product("ISC BIND", Some(number), Some(("isc", "bind")))
+        }
+    } else if lower.starts_with("dnsmasq") {
+        let (_, number) = split_name_version(version);
+        product("dnsmasq", number, Some(("thekelleys", "dnsmasq")))
+    } else if lower.starts_with("unbound") {
+        product("Unbound", first_number(), Some(("nlnetlabs", "unbound")))
+    } else if lower.starts_with("nsd") {
+        product("NSD", first_number(), Some(("nlnetlabs", "nsd")))
+    } else if lower.starts_with("knot") {
+        product("Knot DNS", first_number(), Some(("cz.nic", "knot_dns")))
+    } else if lower.contains("powerdns") {
+        let name = if lower.contains("recursor") { "PowerDNS Recursor" } else { "PowerDNS Authoritative Server" };
+        let cpe_product = if lower.contains("recursor") { "recursor" } else { "authoritative" };
+        product(name, first_number(), Some(("powerdns", cpe_product)))
+    } else if lower.starts_with("microsoft dns") {
+        ServiceIdentity {
+            os_hint: Some("Windows".to_string()),
+            extra_info: parenthesised(version).map(str::to_string),
+            ..product("Microsoft DNS", first_number(), Some(("microsoft", "dns_server")))
+        }
+    } else {
+        return None;
+    };
+
+    if identity.os_hint.is_none() {
+        identity.os_hint = os_from_text(version);
+    }
+    Some(identity)
+}
+
+/// Find the `Server` header in a raw HTTP response
+fn http_server_header(response: &str) -> Option<&str> {
+    let head = response.split("\r\n\r\n").next()?;
+    head.lines().skip(1).find_map(|line| {
+        let (name, value) = line.split_once(':')?;
+        name.trim().eq_ignore_ascii_case("server").then(|| value.trim())
+    })
+}
+
+/// An identity with an optional `(vendor, product)` CPE
+fn product(name: &str, version: Option<&str>, cpe_name: Option<(&str, &str)>) -> ServiceIdentity {
+    ServiceIdentity {
+        product: name.to_string(),
+        version: version.map(str::to_string),
+        extra_info: None,
+        os_hint: None,
+        cpe: cpe_name.map(|(vendor, product)| cpe(vendor, product, version)),
+    }
+}
+
+/// Format a CPE 2.2 application name
+fn cpe(vendor: &str, product: &str, version: Option<&str>) -> String {
+    match version {
+        Some(version) => format!("cpe:/a:{}:{}:{}", vendor, product, version.to_lowercase()),
+        None => format!("cpe:/a:{}:{}", vendor, product),
+    }
+}
+
+/// Split "OpenSSH_8.9p1" / "libssh-0.6.0" into name and version
+fn split_name_version(software: &str) -> (&str, Option<&str>) {
+    if let Some((name, version)) = software.split_once('_') {
+        return (name, Some(version));
+    }
+    match software.find('-') {
+        Some(i) if software[i + 1..].starts_with(|c: char| c.is_ascii_digit()) => {
+            (&software[..i], Some(&software[i + 1..]))
+        },
+        _ => (software, None),
+    }
+}
+
+/// All lines of a (possibly multi-line) reply with the given code, joined
+///
+/// Products often announce themselves on a "220-" continuation line rather
+/// than the final "220 " line.
+fn greeting(banner: &str, code: &str) -> Option<String> {
+    let lines: Vec<&str> = banner.lines()
+        .map(str::trim)
+        .filter(|line| line.starts_with(code))
+        .collect();
+    (!lines.is_empty()).then(|| lines.join(" "))
+}
+
+/// The whitespace-delimited word following `marker`
+fn word_after<'a>(text: &'a str, marker: &str) -> Option<&'a str> {
+    let start = text.find(marker)? + marker.len();
+    text[start..].split_whitespace().next()
+}
+
+/// Contents of the first parenthesised group
+fn parenthesised(text: &str) -> Option<&str> {
+    let start = text.find('(')? + 1;
+    let end = text[start..].find(')')? + start;
+    Some(&text[start..end])
+}
+
+fn starts_with_digit(text: &str) -> bool {
+    text.starts_with(|c: char| c.is_ascii_digit())
+}
+
+/// Operating system implied by a distribution or platform name
+fn os_from_text(text: &str) -> Option<String> {
+    let lower = text.to_lowercase();
+    let os = if ["ubuntu", "debian", "raspbian", "centos", "red hat", "rhel", "fedora", "suse", "alma", "rocky", "linux"]
+        .iter()
+        .any(|name| lower.contains(name))
+    {
+        "Linux"
+    } else if lower.contains("freebsd") {
+        "FreeBSD"
+    } else if lower.contains("openbsd") {
+        "OpenBSD"
+    } else if lower.contains("netbsd") {
+        "NetBSD"
+    } else if lower.contains("win32") || lower.contains("win64") || lower.contains("windows") {
+        "Windows"
+    } else if lower.contains("darwin") || lower.contains("mac os") {
+        "macOS"
+    } else {
+        return None;
+    };
+    Some(os.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Identity as "display | os | cpe" for compact comparisons
+    fn summary(identity: Option<ServiceIdentity>) -> Option<String> {
+        identity.map(|id| {
+            format!("{} | {} | {}", id, id.os_hint.as_deref().unwrap_or("-"), id.cpe.as_deref().unwrap_or("-"))
+        })
+    }
+
+    fn check(cases: &[(Option<&str>, &[u8], Option<&str>)]) {
+        for (service, data, expected) in cases {
+            assert_eq!(
+                summary(identify(*service, data)).as_deref(),
+                *expected,
+                "{:?}",
+                String::from_utf8_lossy(data)
+            );
+        }
+    }
+
+    #[test]
+    fn ssh_banners() {
+        check(&[
+            (None, b"SSH-2.0-OpenSSH_8.9p1 Ubuntu-3ubuntu0.1\r\n",
+                Some("OpenSSH 8.9p1 (Ubuntu-3ubuntu0.1; protocol 2.0) | Linux | cpe:/a:openbsd:openssh:8.9p1")),
+            (None, b"SSH-2.0-OpenSSH_for_Windows_8.1\r\n",
+                Some("OpenSSH for Windows 8.1 (protocol 2.0) | Windows | cpe:/a:openbsd:openssh:8.1")),
+            (None, b"SSH-2.0-dropbear_2020.81\r\n",
+                Some("Dropbear sshd 2020.81 (protocol 2.0) | - | cpe:/a:matt_johnston:dropbear_ssh_server:2020.81")),
+            (None, b"SSH-1.99-Cisco-1.25\r\n", Some("Cisco SSH 1.25 (protocol 1.99) | Cisco IOS | -")),
+        ]);
+    }
+
+    #[test]
+    fn mail_and_ftp_greetings() {
+        check(&[
+            (Some("smtp"), b"220 mail.example.com ESMTP Postfix (Ubuntu)\r\n",
+                Some("Postfix smtpd | Linux | cpe:/a:postfix:postfix")),
+            (None, b"220 mx.example.org ESMTP Exim 4.94.2 Mon, 01 Jan 2024 00:00:00 +0000\r\n",
+                Some("Exim smtpd 4.94.2 | - | cpe:/a:exim:exim:4.94.2")),
+            (None, b"220 host ESMTP Sendmail 8.15.2/8.15.2; Mon\r\n",
+                Some("Sendmail 8.15.2 | - | cpe:/a:sendmail:sendmail:8.15.2")),
+            (None, b"220 host Microsoft ESMTP MAIL Service, Version: 10.0.17763.1 ready\r\n",
+                Some("Microsoft ESMTP 10.0.17763.1 | Windows | -")),
+            (None, b"220 (vsFTPd 3.0.3)\r\n", Some("vsftpd 3.0.3 | - | cpe:/a:beasts:vsftpd:3.0.3")),
+            (Some("ftp"), b"220 ProFTPD 1.3.5e Server (Debian) [::ffff:10.0.0.1]\r\n",
+                Some("ProFTPD 1.3.5e | Linux | cpe:/a:proftpd:proftpd:1.3.5e")),
+            (None, b"220-FileZilla Server 0.9.60 beta\r\n220-written by Tim Kosse\r\n220 Please visit\r\n",
+                Some("FileZilla ftpd 0.9.60 (beta) | Windows | cpe:/a:filezilla-project:filezilla_server:0.9.60")),
+            (None, b"220---------- Welcome to Pure-FTPd [privsep] [TLS] ----------\r\n",
+                Some("Pure-FTPd | - | cpe:/a:pureftpd:pure-ftpd")),
+            (None, b"220 Microsoft FTP Service\r\n", Some("Microsoft ftpd | Windows | cpe:/a:microsoft:ftp_service")),
+            (None, b"+OK Dovecot (Ubuntu) ready.\r\n", Some("Dovecot pop3d | Linux | cpe:/a:dovecot:dovecot")),
+            (Some("ssl/imap"), b"* OK [CAPABILITY IMAP4rev1] Courier-IMAP ready. Copyright 1998\r\n",
+                Some("Courier Imapd | - | cpe:/a:courier-mta:courier-imap")),
+            (None, b"* OK mail Cyrus IMAP v2.4.17-Debian-2.4.17 server ready\r\n",
+                Some("Cyrus imapd 2.4.17 (Debian-2.4.17) | Linux | cpe:/a:cmu:cyrus_imap_server:2.4.17")),
+            // An unknown product is not guessed at
+            (None, b"220 some random greeting\r\n", None),
+        ]);
+    }
+
+    #[test]
+    fn http_server_headers() {
+        check(&[
+            (None, b"HTTP/1.1 200 OK\r\nServer: Apache/2.4.41 (Ubuntu) OpenSSL/1.1.1f\r\n\r\n",
+                Some("Apache httpd 2.4.41 (Ubuntu; OpenSSL 1.1.1f) | Linux | cpe:/a:apache:http_server:2.4.41")),
+            (None, b"HTTP/1.0 200 OK\r\nServer: SimpleHTTP/0.6 Python/3.11.2\r\n\r\n",
+                Some("SimpleHTTPServer 0.6 (Python 3.11.2) | - | cpe:/a:python:simplehttpserver:0.6")),
+            (None, b"HTTP/1.1 200 OK\r\nserver: Microsoft-IIS/10.0\r\n\r\n",
+                Some("Microsoft IIS httpd 10.0 | Windows | cpe:/a:microsoft:internet_information_services:10.0")),
+            (None, b"HTTP/1.1 200 OK\r\nServer: Jetty(9.4.43.v20210629)\r\n\r\n",
+                Some("Jetty 9.4.43.v20210629 | - | cpe:/a:eclipse:jetty:9.4.43.v20210629")),
+            (None, b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n", None),
+        ]);
+    }
+
+    #[test]
+    fn binary_greetings() {
+        check(&[
+            (None, b"RFB 003.008\n", Some("VNC (protocol 3.8) | - | -")),
+            (None, b"\x1c\x00\x00\x00\x0a5.7.33-0ubuntu0.18.04.1\x00abc",
+                Some("MySQL 5.7.33 (0ubuntu0.18.04.1) | Linux | cpe:/a:mysql:mysql:5.7.33")),
+            // MariaDB prefixes its version with 5.5.5- for old clients
+            (None, b"\x2e\x00\x00\x00\x0a5.5.5-10.5.12-MariaDB-1:10.5.12+maria~focal\x00abc",
+                Some("MariaDB 10.5.12 (MariaDB-1:10.5.12+maria~focal) | - | cpe:/a:mariadb:mariadb:10.5.12")),
+        ]);
+    }
+
+    #[test]
+    fn dns_versions() {
+        let bind = parse_dns_version("9.16.1-Ubuntu").unwrap();
+        assert_eq!(bind.product, "ISC BIND");
+        assert_eq!(bind.version.as_deref(), Some("9.16.1"));
+        assert_eq!(bind.extra_info.as_deref(), Some("Ubuntu"));
+        assert_eq!(bind.os_hint.as_deref(), Some("Linux"));
+        assert_eq!(bind.cpe.as_deref(), Some("cpe:/a:isc:bind:9.16.1"));
+
+        let dnsmasq = parse_dns_version("dnsmasq-2.85").unwrap();
+        assert_eq!((dnsmasq.product.as_str(), dnsmasq.version.as_deref()), ("dnsmasq", Some("2.85")));
+        let unbound = parse_dns_version("unbound 1.13.1").unwrap();
+        assert_eq!((unbound.product.as_str(), unbound.version.as_deref()), ("Unbound", Some("1.13.1")));
+        let recursor = parse_dns_version("PowerDNS Recursor 4.5.7").unwrap();
+        assert_eq!(recursor.product, "PowerDNS Recursor");
+        let microsoft = parse_dns_version("Microsoft DNS 10.0.17763 (4563DC06)").unwrap();
+        assert_eq!(microsoft.os_hint.as_deref(), Some("Windows"));
+        assert_eq!(microsoft.extra_info.as_deref(), Some("4563DC06"));
+
+        assert!(parse_dns_version("none of your business").is_none());
+    }
+}
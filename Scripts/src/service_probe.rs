@@ -0,0 +1,417 @@
+//! Generic service detection for open ports
+//!
+//! Runs the same pipeline on every open port regardless of its number:
+//!
+//! 1. NULL probe - connect and wait for the server to speak first (SSH, FTP,
+//!    SMTP, POP3, IMAP, MySQL, VNC ...)
+//! 2. TLS probe - send a ClientHello and check whether the answer is a TLS
+//!    record, so HTTPS on 9443 or IMAPS on an odd port is still recognised
+//! 3. Generic probes - HTTP GET, a bare `\r\n\r\n` and `HELP`, sent inside TLS
+//!    when the port speaks it, with the responses classified by content
+//!
+//! The prober only gathers and classifies responses; the scanner decides what
+//! to store and runs the deeper TLS and HTTP analysis on top.
+
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::audit::AuditedStream;
+use crate::http_client::DEFAULT_USER_AGENT;
+use crate::scope::Egress;
+use crate::utils;
+
+/// Longest we wait for a server to send a banner unprompted
+const NULL_PROBE_WAIT: Duration = Duration::from_millis(1500);
+
+/// Time allowed for further data once a response has started arriving
+const READ_GRACE: Duration = Duration::from_millis(250);
+
+/// Cap on the amount of response data kept per probe
+const MAX_RESPONSE: usize = 8192;
+
+/// A generic request sent to ports that stay silent
+#[derive(Debug, Clone, Copy)]
+pub struct GenericProbe {
+    /// Short name used in logs and results
+    pub name: &'static str,
+    /// Build the payload for a host name
+    pub payload: fn(&str) -> Vec<u8>,
+}
+
+/// Generic probes in the order they are tried
+pub const GENERIC_PROBES: &[GenericProbe] = &[
+    GenericProbe { name: "GetRequest", payload: http_get_payload },
+    GenericProbe { name: "GenericLines", payload: |_| b"\r\n\r\n".to_vec() },
+    GenericProbe { name: "Help", payload: |_| b"HELP\r\n".to_vec() },
+];
+
+fn http_get_payload(host: &str) -> Vec<u8> {
+    format!(
+        "GET / HTTP/1.0\r\nHost: {}\r\nUser-Agent: {}\r\nAccept: */*\r\n\r\n",
+        host, DEFAULT_USER_AGENT
+    ).into_bytes()
+}
+
+/// Response to one probe
+#[derive(Debug, Clone)]
+pub struct ProbeResponse {
+    /// Probe that produced the response ("NULL" for an unprompted banner)
+    pub probe: &'static str,
+    /// Whether the probe was sent inside TLS
+    pub tls: bool,
+    /// Raw response bytes (capped)
+    pub data: Vec<u8>,
+    /// Service the response looks like, if recognised
+    pub service: Option<&'static str>,
+}
+
+/// Everything the pipeline learned about a port
+#[derive(Debug, Clone, Default)]
+pub struct ProbeReport {
+    /// The port answered a ClientHello with a TLS record
+    pub tls: bool,
+    /// Responses in the order they were received
+    pub responses: Vec<ProbeResponse>,
+}
+
+impl ProbeReport {
+    /// First response that could be classified, falling back to the first response
+    pub fn best_response(&self) -> Option<&ProbeResponse> {
+        self.responses.iter()
+            .find(|r| r.service.is_some())
+            .or_else(|| self.responses.first())
+    }
+
+    /// Service name for the port, prefixed for TLS-wrapped protocols
+    ///
+    /// HTTP over TLS is reported as "https" so the HTTP stages pick it up;
+    /// other wrapped protocols become "ssl/<service>", and a TLS port whose
+    /// inner protocol stays unknown is plain "ssl".
+    pub fn service(&self) -> Option<String> {
+        let inner = self.best_response().and_then(|r| r.service);
+        match (self.tls, inner) {
+            (true, Some("http")) => Some("https".to_string()),
+            (true, Some(service)) => Some(format!("ssl/{}", service)),
+            (true, None) => Some("ssl".to_string()),
+            (false, Some(service)) => Some(service.to_string()),
+            (false, None) => None,
+        }
+    }
+}
+
+/// Runs the NULL / TLS / generic probe pipeline against one port
+pub struct ServiceProber {
+    target: String,
+    port: u16,
+    timeout: Duration,
+    egress: Egress,
+    logger: Option<Arc<utils::EnhancedLogger>>,
+}
+
+impl ServiceProber {
+    /// Create a prober; `timeout` bounds each connect and each probe's response
+    pub fn new(target: &str, port: u16, timeout: Duration, egress: Egress, logger: Option<Arc<utils::EnhancedLogger>>) -> Self {
+        Self {
+            target: target.to_string(),
+            port,
+            timeout,
+            egress,
+            logger,
+        }
+    }
+
+    fn log(&self, level: &str, message: &str) {
+        if let Some(logger) = &self.logger {
+            logger.log(level, message);
+        }
+    }
+
+    /// Run the full pipeline
+    ///
+    /// Stops as soon as a response identifies the service, so a chatty SSH
+    /// server costs one connection while a silent web server costs three.
+    pub async fn run(&self) -> ProbeReport {
+        let mut report = ProbeReport::default();
+
+        if let Some(data) = self.null_probe().await {
+            let service = classify_response(&data);
+            report.responses.push(ProbeResponse { probe: "NULL", tls: false, data, service });
+            if service.is_some() {
+                return report;
+            }
+        }
+
+        report.tls = self.speaks_tls().await;
+        if report.tls {
+            self.log("DEBUG", &format!("{}:{} speaks TLS", self.target, self.port));
+        }
+
+        for probe in GENERIC_PROBES {
+            let data = if report.tls {
+                self.send_probe_tls(probe).await
+            } else {
+                self.send_probe(probe).await
+            };
+
+            if let Some(data) = data {
+                let service = classify_response(&data);
+                self.log("DEBUG", &format!(
+                    "{}:{} answered {} probe ({} bytes, {})",
+                    self.target, self.port, probe.name, data.len(), service.unwrap_or("unrecognised")
+                ));
+                report.responses.push(ProbeResponse { probe: probe.name, tls: report.tls, data, service });
+                if service.is_some() {
+                    break;
+                }
+            }
+        }
+
+        report
+    }
+
+    async fn connect(&self) -> Option<AuditedStream> {
+        let addr = format!("{}:{}", self.target, self.port);
+        match tokio::time::timeout(self.timeout, self.egress.connect_tcp(&self.target, self.port)).await {
+            Ok(Ok(stream)) => Some(stream),
+            Ok(Err(e)) => {
+                self.log("DEBUG", &format!("Probe connect to {} failed: {}", addr, e));
+                None
+            },
+            Err(_) => None,
+        }
+    }
+
+    /// Wait for the server to send a banner without being prompted
+    pub async fn null_probe(&self) -> Option<Vec<u8>> {
+        let mut stream = self.connect().await?;
+        read_response(&mut stream, self.timeout.min(NULL_PROBE_WAIT)).await
+    }
+
+    /// Send a ClientHello and check whether the reply is a TLS record
+    ///
+    /// A handshake or alert record both count: either way the port speaks TLS,
+    /// even if it would refuse our cipher suites.
+    pub async fn speaks_tls(&self) -> bool {
+        let mut stream = match self.connect().await {
+            Some(stream) => stream,
+            None => return false,
+        };
+
+        let server_name = self.target.parse::<IpAddr>().is_err().then_some(self.target.as_str());
+        if stream.write_all(&client_hello(server_name)).await.is_err() {
+            return false;
+        }
+
+        let mut header = [0u8; 3];
+        match tokio::time::timeout(self.timeout, stream.read_exact(&mut header)).await {
+            Ok(Ok(_)) => is_tls_record(&header),
+            _ => false,
+        }
+    }
+
+    /// Send a generic probe over plain TCP
+    pub async fn send_probe(&self, probe: &GenericProbe) -> Option<Vec<u8>> {
+        let mut stream = self.connect().await?;
+        exchange(&mut stream, &(probe.payload)(&self.target), self.timeout).await
+    }
+
+    /// Send a generic probe inside a TLS session
+    pub async fn send_probe_tls(&self, probe: &GenericProbe) -> Option<Vec<u8>> {
+        let stream = self.connect().await?;
+        let connector = tokio_rustls::TlsConnector::from(Arc::new(utils::insecure_tls_config()));
+        let domain = rustls::ServerName::try_from(self.target.as_str()).ok()?;
+
+        let mut tls_stream = match tokio::time::timeout(self.timeout, connector.connect(domain, stream)).await {
+            Ok(Ok(s)) => s,
+            Ok(Err(e)) => {
+                self.log("DEBUG", &format!(
+                    "TLS handshake for {} probe on {}:{} failed: {}",
+                    probe.name, self.target, self.port, e
+                ));
+                return None;
+            },
+            Err(_) => return None,
+        };
+
+        exchange(&mut tls_stream, &(probe.payload)(&self.target), self.timeout).await
+    }
+}
+
+/// Write a payload and collect the reply
+async fn exchange<S>(stream: &mut S, payload: &[u8], timeout: Duration) -> Option<Vec<u8>>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    stream.write_all(payload).await.ok()?;
+    stream.flush().await.ok()?;
+    read_response(stream, timeout).await
+}
+
+/// Read until the peer closes, goes quiet or the cap is reached
+async fn read_response<S>(stream: &mut S, first_byte_timeout: Duration) -> Option<Vec<u8>>
+where
+    S: AsyncRead + Unpin,
+{
+    let mut response = Vec::new();
+    let mut buffer = [0u8; 2048];
+    let mut wait = first_byte_timeout;
+
+    while response.len() < MAX_RESPONSE {
+        match tokio::time::timeout(wait, stream.read(&mut buffer)).await {
+            Ok(Ok(n)) if n > 0 => response.extend_from_slice(&buffer[..n]),
+            _ => break,
+        }
+        wait = READ_GRACE;
+    }
+
+    response.truncate(MAX_RESPONSE);
+    (!response.is_empty()).then_some(response)
+}
+
+/// Whether a record header is a TLS handshake or alert record
+fn is_tls_record(header: &[u8]) -> bool {
+    header.len() >= 3 && (header[0] == 0x16 || header[0] == 0x15) && header[1] == 0x03 && header[2] <= 0x04
+}
+
+/// Build a TLS 1.2 ClientHello offering common modern and legacy suites
+pub fn client_hello(server_name: Option<&str>) -> Vec<u8> {
+    const CIPHER_SUITES: &[u16] = &[
+        0x1301, 0x1302, 0x1303, 0xc02b, 0xc02f, 0xc02c, 0xc030, 0xcca9, 0xcca8,
+        0xc013, 0xc014, 0x009c, 0x009d, 0x002f, 0x0035, 0x000a, 0x00ff,
+    ];
+
+    let mut extensions = Vec::new();
+    if let Some(name) = server_name {
+        let name = name.as_bytes();
+        let mut sni = Vec::new();
+        sni.extend_from_slice(&((name.len() + 3) as u16).to_be_bytes());
+        sni.push(0x00);
+        sni.extend_from_slice(&(name.len() as u16).to_be_bytes());
+        sni.extend_from_slice(name);
+        push_extension(&mut extensions, 0x0000, &sni);
+    }
+    // supported_groups: x25519, secp256r1, secp384r1
+    push_extension(&mut extensions, 0x000a, &[0x00, 0x06, 0x00, 0x1d, 0x00, 0x17, 0x00, 0x18]);
+    // ec_point_formats: uncompressed
+    push_extension(&mut extensions, 0x000b, &[0x01, 0x00]);
+    // signature_algorithms
+    let signature_algorithms: &[u16] = &[0x0403, 0x0503, 0x0603, 0x0804, 0x0805, 0x0806, 0x0401, 0x0501, 0x0601, 0x0201];
+    let mut algorithms = ((signature_algorithms.len() * 2) as u16).to_be_bytes().to_vec();
+    for algorithm in signature_algorithms {
+        algorithms.extend_from_slice(&algorithm.to_be_bytes());
+    }
+    push_extension(&mut extensions, 0x000d, &algorithms);
+
+    let mut hello = vec![0x03, 0x03];
+    hello.extend((0..32).map(|_| rand::random::<u8>()));
+    hello.push(0x00); // empty session id
+    hello.extend_from_slice(&((CIPHER_SUITES.len() * 2) as u16).to_be_bytes());
+    for suite in CIPHER_SUITES {
+        hello.extend_from_slice(&suite.to_be_bytes());
+    }
+    hello.extend_from_slice(&[0x01, 0x00]); // null compression only
+    hello.extend_from_slice(&(extensions.len() as u16).to_be_bytes());
+    hello.extend_from_slice(&extensions);
+
+    let mut handshake = vec![0x01];
+    handshake.extend_from_slice(&(hello.len() as u32).to_be_bytes()[1..]);
+    handshake.extend_from_slice(&hello);
+
+    let mut record = vec![0x16, 0x03, 0x01];
+    record.extend_from_slice(&(handshake.len() as u16).to_be_bytes());
+    record.extend_from_slice(&handshake);
+    record
+}
+
+fn push_extension(extensions: &mut Vec<u8>, kind: u16, data: &[u8]) {
+    extensions.extend_from_slice(&kind.to_be_bytes());
+    extensions.extend_from_slice(&(data.len() as u16).to_be_bytes());
+    extensions.extend_from_slice(data);
+}
+
+/// Recognise a service from a banner or probe response
+pub fn classify_response(data: &[u8]) -> Option<&'static str> {
+    if data.is_empty() {
+        return None;
+    }
+    if is_tls_record(data) {
+        return Some("ssl");
+    }
+    if data[0] == 0xff && data.len() > 1 && (0xfb..=0xfe).contains(&data[1]) {
+        return Some("telnet");
+    }
+    if is_mysql_greeting(data) {
+        return Some("mysql");
+    }
+
+    let text = String::from_utf8_lossy(&data[..data.len().min(1024)]);
+    let lower = text.to_lowercase();
+    let starts = |prefix: &str| text.starts_with(prefix);
+
+    if starts("SSH-") {
+        Some("ssh")
+    } else if starts("HTTP/") {
+        Some("http")
+    } else if starts("RTSP/") {
+        Some("rtsp")
+    } else if starts("SIP/2.0") {
+        Some("sip")
+    } else if starts("RFB ") {
+        Some("vnc")
+    } else if starts("@RSYNCD:") {
+        Some("rsync")
+    } else if starts("AMQP") {
+        Some("amqp")
+    } else if starts("+OK") {
+        Some("pop3")
+    } else if starts("* OK") || starts("* PREAUTH") {
+        Some("imap")
+    } else if starts("-ERR") || starts("-NOAUTH") || starts("+PONG") || starts("-DENIED") {
+        Some("redis")
+    } else if text == "ERROR\r\n" {
+        Some("memcached")
+    } else if starts("220") || starts("421") || starts("554") {
+        if lower.contains("smtp") || lower.contains("mail") || lower.contains("postfix") || lower.contains("exim") {
+            Some("smtp")
+        } else if lower.contains("ftp") {
+            Some("ftp")
+        } else {
+            None
+        }
+    } else if (starts("200 ") || starts("201 ")) && lower.contains("nntp") {
+        Some("nntp")
+    } else if starts("<stream:") || (starts("<?xml") && lower.contains("jabber")) {
+        Some("xmpp")
+    } else if lower.starts_with("<!doctype html") || lower.starts_with("<html") {
+        Some("http")
+    } else {
+        None
+    }
+}
+
+/// MySQL/MariaDB servers greet with protocol version 10 or an error packet
+pub(crate) fn is_mysql_greeting(data: &[u8]) -> bool {
+    if data.len() < 5 {
+        return false;
+    }
+    let length = u32::from_le_bytes([data[0], data[1], data[2], 0]) as usize;
+    length + 4 <= data.len() && data[3] == 0 && (data[4] == 0x0a || (data[4] == 0xff && length > 3))
+}
+
+/// Service conventionally found on a port, used when a banner can't be classified
+pub fn service_for_port(port: u16) -> Option<&'static str> {
+    match port {
+        21 => Some("ftp"),
+        22 => Some("ssh"),
+        23 => Some("telnet"),
+        25 | 587 => Some("smtp"),
+        110 => Some("pop3"),
+        119 => Some("nntp"),
+        143 => Some("imap"),
+        389 => Some("ldap"),
+        _ => None,
+    }
+}
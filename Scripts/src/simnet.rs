@@ -0,0 +1,769 @@
+//! Simulated in-memory network
+//!
+//! A `PacketTransport` that answers probes the way real hosts would, so the
+//! raw scan types can be exercised without root or a network. Each simulated
+//! host decides per port whether to accept, reset, silently drop or answer
+//! with an ICMP error, and can sit behind a stateful or stateless firewall or
+//! answer only part of what it receives, as a rate-limited host does. The
+//! network can lose packets at a seeded random rate and reassembles
+//! fragmented probes before the host sees them, as a real IP stack does.
+//! Replies can be fragmented too, to exercise reassembly on the scanner's
+//! side.
+//!
+//! ```no_run
+//! # async fn example() -> std::io::Result<()> {
+//! use std::net::Ipv4Addr;
+//! use std::sync::Arc;
+//! use quantum_scanner::packet::{ProbeOptions, Prober};
+//! use quantum_scanner::scope::Egress;
+//! use quantum_scanner::simnet::{PortBehavior, SimulatedHost, SimulatedNetwork};
+//! use quantum_scanner::ScanType;
+//!
+//! let target = Ipv4Addr::new(10, 0, 0, 5);
+//! let network = SimulatedNetwork::new(Ipv4Addr::new(10, 0, 0, 1))
+//!     .host(target, SimulatedHost::new().tcp(22, PortBehavior::Open));
+//!
+//! let prober = Prober::new(Arc::new(network), ProbeOptions::default(), Egress::default(), None);
+//! let outcome = prober.probe(target, 22, ScanType::Syn).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::net::Ipv4Addr;
+use std::time::Duration;
+
+use futures::future::BoxFuture;
+use parking_lot::Mutex;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use tokio::sync::mpsc;
+
+use crate::packet::{
+    IcmpMessage, Ipv4Packet, PacketTransport, Reassembler, TcpSegment, UdpDatagram, ICMP_PORT_UNREACHABLE, PROTO_ICMP, PROTO_TCP,
+    PROTO_UDP, TCP_ACK, TCP_RST, TCP_SYN,
+};
+
+/// How long the simulated stack keeps incomplete fragmented datagrams
+const REASSEMBLY_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How a simulated port treats incoming packets
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PortBehavior {
+    /// A service is listening: SYN gets SYN/ACK, UDP gets an answer
+    Open,
+    /// Nothing is listening: TCP gets RST, UDP gets ICMP port unreachable
+    Closed,
+    /// A filter drops everything silently
+    Drop,
+    /// A filter rejects with ICMP destination unreachable and this code
+    Unreachable(u8),
+}
+
+/// What a simulated firewall does with packets it blocks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockAction {
+    /// Drop silently
+    Drop,
+    /// Reject with ICMP destination unreachable and this code (13 = administratively prohibited)
+    Prohibit(u8),
+    /// Answer TCP with a RST on the host's behalf (UDP gets ICMP port unreachable)
+    Reset,
+}
+
+/// A firewall in front of a simulated host
+///
+/// A stateless firewall only looks at each packet: it blocks connection
+/// attempts (SYN without ACK) and UDP to ports not allowed and lets every
+/// other packet through. A stateful one also tracks connections and drops
+/// TCP packets that don't belong to one it has seen opened, whatever the
+/// port.
+#[derive(Debug, Clone)]
+pub struct SimulatedFirewall {
+    stateful: bool,
+    allowed: HashSet<u16>,
+    action: BlockAction,
+    address: Option<Ipv4Addr>,
+    ttl: u8,
+    connections: HashSet<(Ipv4Addr, u16, u16)>,
+}
+
+impl SimulatedFirewall {
+    /// A connection-tracking firewall that lets new connections reach `allowed`
+    pub fn stateful(allowed: impl IntoIterator<Item = u16>) -> Self {
+        Self {
+            stateful: true,
+            allowed: allowed.into_iter().collect(),
+            action: BlockAction::Drop,
+            address: None,
+            ttl: 255,
+            connections: HashSet::new(),
+        }
+    }
+
+    /// A per-packet filter that lets new connections reach `allowed`
+    pub fn stateless(allowed: impl IntoIterator<Item = u16>) -> Self {
+        Self { stateful: false, ..Self::stateful(allowed) }
+    }
+
+    /// What to do with blocked packets (dropped by default)
+    pub fn action(mut self, action: BlockAction) -> Self {
+        self.action = action;
+        self
+    }
+
+    /// Send ICMP errors from this address rather than the host's
+    pub fn address(mut self, addr: Ipv4Addr) -> Self {
+        self.address = Some(addr);
+        self
+    }
+
+    /// TTL the firewall's own packets arrive with (255 by default)
+    pub fn ttl(mut self, ttl: u8) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// `None` if `packet` may pass, otherwise what the firewall sends back instead
+    fn filter(&mut self, packet: &Ipv4Packet) -> Option<Vec<Ipv4Packet>> {
+        let (dst_port, segment) = match packet.protocol {
+            PROTO_TCP => {
+                let segment = TcpSegment::parse(&packet.payload)?;
+                (segment.dst_port, Some(segment))
+            }
+            PROTO_UDP => (UdpDatagram::parse(&packet.payload)?.dst_port, None),
+            _ => return None,
+        };
+
+        let segment = match segment {
+            Some(segment) => segment,
+            None if self.allowed.contains(&dst_port) => return None,
+            None => return Some(self.block(packet, None)),
+        };
+        let key = (packet.src, segment.src_port, dst_port);
+        let syn_only = segment.flags & (TCP_SYN | TCP_ACK) == TCP_SYN;
+        if syn_only {
+            if !self.allowed.contains(&dst_port) {
+                return Some(self.block(packet, Some(&segment)));
+            }
+            if self.stateful {
+                self.connections.insert(key);
+            }
+            return None;
+        }
+        if !self.stateful {
+            return None;
+        }
+        if !self.connections.contains(&key) {
+            // Out-of-state packets are dropped whatever the reject action is
+            return Some(Vec::new());
+        }
+        if segment.flags & TCP_RST != 0 {
+            self.connections.remove(&key);
+        }
+        None
+    }
+
+    fn block(&self, packet: &Ipv4Packet, segment: Option<&TcpSegment>) -> Vec<Ipv4Packet> {
+        let icmp = |code| {
+            let from = self.address.unwrap_or(packet.dst);
+            let message = IcmpMessage::unreachable(code, packet).to_bytes();
+            vec![Ipv4Packet { ttl: self.ttl, ..Ipv4Packet::new(from, packet.src, PROTO_ICMP, message) }]
+        };
+        match (self.action, segment) {
+            (BlockAction::Drop, _) => Vec::new(),
+            (BlockAction::Prohibit(code), _) => icmp(code),
+            (BlockAction::Reset, Some(segment)) => {
+                let rst = TcpSegment {
+                    src_port: segment.dst_port,
+                    dst_port: segment.src_port,
+                    seq: 0,
+                    ack: segment.seq.wrapping_add(1 + segment.payload.len() as u32),
+                    flags: TCP_RST | TCP_ACK,
+                    window: 0,
+                    payload: Vec::new(),
+                };
+                let reply = Ipv4Packet::new(packet.dst, packet.src, PROTO_TCP, rst.to_bytes(packet.dst, packet.src));
+                vec![Ipv4Packet { ttl: self.ttl, ..reply }]
+            }
+            (BlockAction::Reset, None) => icmp(ICMP_PORT_UNREACHABLE),
+        }
+    }
+}
+
+/// A host on the simulated network
+#[derive(Debug, Clone)]
+pub struct SimulatedHost {
+    up: bool,
+    tcp: HashMap<u16, PortBehavior>,
+    udp: HashMap<u16, PortBehavior>,
+    default_tcp: PortBehavior,
+    default_udp: PortBehavior,
+    ttl: u8,
+    syn_ack_window: u16,
+    rst_window: u16,
+    icmp_from: Option<Ipv4Addr>,
+    firewall: Option<SimulatedFirewall>,
+    rate_limit: Option<(u32, u32)>,
+    answerable: u32,
+}
+
+impl Default for SimulatedHost {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SimulatedHost {
+    /// A host that is up with every port closed
+    pub fn new() -> Self {
+        Self {
+            up: true,
+            tcp: HashMap::new(),
+            udp: HashMap::new(),
+            default_tcp: PortBehavior::Closed,
+            default_udp: PortBehavior::Closed,
+            ttl: 64,
+            syn_ack_window: 65535,
+            rst_window: 0,
+            icmp_from: None,
+            firewall: None,
+            rate_limit: None,
+            answerable: 0,
+        }
+    }
+
+    /// A host that is down: nothing it is sent gets an answer
+    pub fn down() -> Self {
+        Self { up: false, ..Self::new() }
+    }
+
+    /// Set the behaviour of a TCP port
+    pub fn tcp(mut self, port: u16, behavior: PortBehavior) -> Self {
+        self.tcp.insert(port, behavior);
+        self
+    }
+
+    /// Set the behaviour of a UDP port
+    pub fn udp(mut self, port: u16, behavior: PortBehavior) -> Self {
+        self.udp.insert(port, behavior);
+        self
+    }
+
+    /// Behaviour of TCP ports not set individually
+    pub fn default_tcp(mut self, behavior: PortBehavior) -> Self {
+        self.default_tcp = behavior;
+        self
+    }
+
+    /// Behaviour of UDP ports not set individually
+    pub fn default_udp(mut self, behavior: PortBehavior) -> Self {
+        self.default_udp = behavior;
+        self
+    }
+
+    /// TTL the host's replies arrive with
+    pub fn ttl(mut self, ttl: u8) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Window advertised in RSTs from open ports
+    ///
+    /// Zero on most stacks; some leak a non-zero window, which is what a
+    /// window scan relies on.
+    pub fn rst_window(mut self, window: u16) -> Self {
+        self.rst_window = window;
+        self
+    }
+
+    /// Send ICMP errors from this address (a firewall or router in front of the host)
+    pub fn icmp_from(mut self, addr: Ipv4Addr) -> Self {
+        self.icmp_from = Some(addr);
+        self
+    }
+
+    /// Put a firewall in front of the host
+    pub fn firewall(mut self, firewall: SimulatedFirewall) -> Self {
+        self.firewall = Some(firewall);
+        self
+    }
+
+    /// Send only the first `answered` of every `period` replies, dropping the rest
+    pub fn rate_limit(mut self, answered: u32, period: u32) -> Self {
+        self.rate_limit = Some((answered, period.max(1)));
+        self
+    }
+
+    fn behavior(&self, protocol: u8, port: u16) -> PortBehavior {
+        match protocol {
+            PROTO_TCP => self.tcp.get(&port).copied().unwrap_or(self.default_tcp),
+            _ => self.udp.get(&port).copied().unwrap_or(self.default_udp),
+        }
+    }
+
+    /// Replies to a complete datagram addressed to this host, after the
+    /// firewall and rate limit have had their say
+    fn respond(&mut self, packet: &Ipv4Packet) -> Vec<Ipv4Packet> {
+        if !self.up {
+            return Vec::new();
+        }
+        if let Some(firewall) = &mut self.firewall {
+            if let Some(replies) = firewall.filter(packet) {
+                return replies;
+            }
+        }
+        let replies = self.answer(packet);
+        if let Some((answered, period)) = self.rate_limit.filter(|_| !replies.is_empty()) {
+            self.answerable += 1;
+            if (self.answerable - 1) % period >= answered {
+                return Vec::new();
+            }
+        }
+        replies
+    }
+
+    /// What the host's own stack sends back for `packet`
+    fn answer(&self, packet: &Ipv4Packet) -> Vec<Ipv4Packet> {
+        let reply = |protocol, payload| Ipv4Packet { ttl: self.ttl, ..Ipv4Packet::new(packet.dst, packet.src, protocol, payload) };
+        let unreachable = |code| {
+            let from = self.icmp_from.unwrap_or(packet.dst);
+            let icmp = IcmpMessage::unreachable(code, packet).to_bytes();
+            Ipv4Packet { ttl: self.ttl, ..Ipv4Packet::new(from, packet.src, PROTO_ICMP, icmp) }
+        };
+
+        match packet.protocol {
+            PROTO_TCP => {
+                let segment = match TcpSegment::parse(&packet.payload) {
+                    Some(segment) => segment,
+                    None => return Vec::new(),
+                };
+                // RFC 793: never answer a reset
+                if segment.flags & TCP_RST != 0 {
+                    return Vec::new();
+                }
+                let behavior = self.behavior(PROTO_TCP, segment.dst_port);
+                let answer = |flags, window, seq, ack| {
+                    let tcp = TcpSegment { src_port: segment.dst_port, dst_port: segment.src_port, seq, ack, flags, window, payload: Vec::new() };
+                    reply(PROTO_TCP, tcp.to_bytes(packet.dst, packet.src))
+                };
+                let syn_only = segment.flags & (TCP_SYN | TCP_ACK) == TCP_SYN;
+                let acked = segment.seq.wrapping_add(1 + segment.payload.len() as u32);
+                match behavior {
+                    PortBehavior::Open if syn_only => vec![answer(TCP_SYN | TCP_ACK, self.syn_ack_window, rand::random(), acked)],
+                    // An ACK that belongs to no connection is reset; FIN, Xmas
+                    // and NULL segments to a listening port are dropped
+                    PortBehavior::Open if segment.flags & TCP_ACK != 0 => vec![answer(TCP_RST, self.rst_window, segment.ack, 0)],
+                    PortBehavior::Open => Vec::new(),
+                    PortBehavior::Closed if segment.flags & TCP_ACK != 0 => vec![answer(TCP_RST, 0, segment.ack, 0)],
+                    PortBehavior::Closed => vec![answer(TCP_RST | TCP_ACK, 0, 0, acked)],
+                    PortBehavior::Drop => Vec::new(),
+                    PortBehavior::Unreachable(code) => vec![unreachable(code)],
+                }
+            }
+            PROTO_UDP => {
+                let datagram = match UdpDatagram::parse(&packet.payload) {
+                    Some(datagram) => datagram,
+                    None => return Vec::new(),
+                };
+                match self.behavior(PROTO_UDP, datagram.dst_port) {
+                    PortBehavior::Open => {
+                        let answer = UdpDatagram { src_port: datagram.dst_port, dst_port: datagram.src_port, payload: b"reply".to_vec() };
+                        vec![reply(PROTO_UDP, answer.to_bytes(packet.dst, packet.src))]
+                    }
+                    PortBehavior::Closed => vec![unreachable(ICMP_PORT_UNREACHABLE)],
+                    PortBehavior::Drop => Vec::new(),
+                    PortBehavior::Unreachable(code) => vec![unreachable(code)],
+                }
+            }
+            _ => Vec::new(),
+        }
+    }
+}
+
+struct NetworkState {
+    hosts: HashMap<Ipv4Addr, SimulatedHost>,
+    rng: StdRng,
+    loss: f64,
+    reply_fragment_size: Option<usize>,
+    inbound: Reassembler,
+    sent: Vec<Ipv4Packet>,
+}
+
+impl NetworkState {
+    fn lost(&mut self) -> bool {
+        self.loss > 0.0 && self.rng.gen_bool(self.loss.min(1.0))
+    }
+}
+
+/// An in-memory network of simulated hosts
+pub struct SimulatedNetwork {
+    local: Ipv4Addr,
+    state: Mutex<NetworkState>,
+    replies: mpsc::UnboundedSender<Vec<u8>>,
+    inbox: tokio::sync::Mutex<mpsc::UnboundedReceiver<Vec<u8>>>,
+}
+
+impl SimulatedNetwork {
+    /// An empty network seen from `local`, with no packet loss
+    pub fn new(local: Ipv4Addr) -> Self {
+        let (replies, inbox) = mpsc::unbounded_channel();
+        Self {
+            local,
+            state: Mutex::new(NetworkState {
+                hosts: HashMap::new(),
+                rng: StdRng::seed_from_u64(0),
+                loss: 0.0,
+                reply_fragment_size: None,
+                inbound: Reassembler::default(),
+                sent: Vec::new(),
+            }),
+            replies,
+            inbox: tokio::sync::Mutex::new(inbox),
+        }
+    }
+
+    /// Add a host; addresses without one never answer
+    pub fn host(self, addr: Ipv4Addr, host: SimulatedHost) -> Self {
+        self.state.lock().hosts.insert(addr, host);
+        self
+    }
+
+    /// Lose each packet, in either direction, with probability `rate`
+    ///
+    /// Losses come from a generator seeded with `seed`, so a given sequence
+    /// of probes always loses the same packets.
+    pub fn loss(self, rate: f64, seed: u64) -> Self {
+        {
+            let mut state = self.state.lock();
+            state.loss = rate;
+            state.rng = StdRng::seed_from_u64(seed);
+        }
+        self
+    }
+
+    /// Fragment every reply into pieces carrying at most `size` bytes each
+    pub fn fragment_replies(self, size: usize) -> Self {
+        self.state.lock().reply_fragment_size = Some(size);
+        self
+    }
+
+    /// Every packet that reached the network (before loss), in order
+    pub fn sent(&self) -> Vec<Ipv4Packet> {
+        self.state.lock().sent.clone()
+    }
+}
+
+impl PacketTransport for SimulatedNetwork {
+    fn local_addr(&self) -> Ipv4Addr {
+        self.local
+    }
+
+    fn send<'a>(&'a self, packet: &'a [u8]) -> BoxFuture<'a, io::Result<()>> {
+        Box::pin(async move {
+            let packet = Ipv4Packet::parse(packet)
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "not an IPv4 packet"))?;
+
+            let mut state = self.state.lock();
+            state.sent.push(packet.clone());
+            if state.lost() {
+                return Ok(());
+            }
+            state.inbound.expire(REASSEMBLY_TIMEOUT);
+            let datagram = match state.inbound.push(packet) {
+                Some(datagram) => datagram,
+                None => return Ok(()),
+            };
+            let replies = match state.hosts.get_mut(&datagram.dst) {
+                Some(host) => host.respond(&datagram),
+                None => return Ok(()),
+            };
+
+            for reply in replies {
+                if state.lost() {
+                    continue;
+                }
+                let pieces = match state.reply_fragment_size {
+                    Some(size) => {
+                        let reply = Ipv4Packet { id: state.rng.gen(), ..reply };
+                        reply.fragment(&vec![size; reply.payload.len() / size.max(8) + 1])
+                    }
+                    None => vec![reply],
+                };
+                // Deliver the last piece first, as out-of-order arrival is
+                // what reassembly has to cope with
+                for piece in pieces.iter().rev() {
+                    let _ = self.replies.send(piece.to_bytes());
+                }
+            }
+            Ok(())
+        })
+    }
+
+    fn recv(&self, timeout: Duration) -> BoxFuture<'_, io::Result<Option<Vec<u8>>>> {
+        Box::pin(async move {
+            let mut inbox = self.inbox.lock().await;
+            Ok(tokio::time::timeout(timeout, inbox.recv()).await.ok().flatten())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::config::FragmentConfig;
+    use crate::models::{PortStatus, ScanType};
+    use crate::packet::{ProbeOptions, Prober, Reply, ICMP_FILTERED_CODES};
+    use crate::scope::Egress;
+
+    const LOCAL: Ipv4Addr = Ipv4Addr::new(10, 0, 0, 1);
+    const TARGET: Ipv4Addr = Ipv4Addr::new(10, 0, 0, 5);
+    const ROUTER: Ipv4Addr = Ipv4Addr::new(10, 0, 0, 254);
+
+    /// The SYN-style techniques the review asked to cover
+    const SYN_STYLE: [ScanType; 3] = [ScanType::Syn, ScanType::Frag, ScanType::Mimic];
+
+    fn options() -> ProbeOptions {
+        ProbeOptions {
+            timeout: Duration::from_millis(50),
+            retries: 1,
+            fragmentation: FragmentConfig { timeout: 0, min_delay: 0.0, max_delay: 0.0, ..FragmentConfig::default() },
+            ..ProbeOptions::default()
+        }
+    }
+
+    fn host() -> SimulatedHost {
+        SimulatedHost::new()
+            .tcp(22, PortBehavior::Open)
+            .tcp(23, PortBehavior::Closed)
+            .tcp(24, PortBehavior::Drop)
+            .tcp(25, PortBehavior::Unreachable(13))
+            .tcp(26, PortBehavior::Unreachable(3))
+            .tcp(27, PortBehavior::Unreachable(4))
+            .udp(53, PortBehavior::Open)
+            .udp(54, PortBehavior::Closed)
+            .udp(55, PortBehavior::Drop)
+            .udp(56, PortBehavior::Unreachable(10))
+            .rst_window(512)
+            .icmp_from(ROUTER)
+    }
+
+    fn prober(network: SimulatedNetwork, options: ProbeOptions) -> Prober {
+        Prober::new(Arc::new(network), options, Egress::default(), None)
+    }
+
+    #[tokio::test]
+    async fn syn_style_probes_classify_every_port_state() {
+        let prober = prober(SimulatedNetwork::new(LOCAL).host(TARGET, host()), options());
+        let expected = [
+            (22, PortStatus::Open),
+            (23, PortStatus::Closed),
+            (24, PortStatus::Filtered),
+            (25, PortStatus::Filtered),
+            (26, PortStatus::Filtered),
+            (27, PortStatus::Filtered),
+        ];
+        for scan_type in SYN_STYLE {
+            for (port, status) in expected {
+                let outcome = prober.probe(TARGET, port, scan_type).await.unwrap().unwrap();
+                assert_eq!(outcome.status, status, "{:?} probe of port {}", scan_type, port);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn icmp_errors_are_matched_to_the_probe_they_quote() {
+        let prober = prober(SimulatedNetwork::new(LOCAL).host(TARGET, host()), options());
+        for scan_type in SYN_STYLE {
+            let outcome = prober.probe(TARGET, 25, scan_type).await.unwrap().unwrap();
+            assert!(
+                matches!(outcome.reply, Some(Reply::Icmp { code: 13, from: ROUTER, .. })),
+                "{:?}: {:?}", scan_type, outcome.reply
+            );
+            assert_eq!(outcome.attempts, 1);
+
+            // Other codes prove nothing: the port counts as silent, though
+            // the answer still ends the retries
+            assert!(!ICMP_FILTERED_CODES.contains(&4));
+            let outcome = prober.probe(TARGET, 27, scan_type).await.unwrap().unwrap();
+            assert_eq!((outcome.status, outcome.attempts), (PortStatus::Filtered, 1));
+            assert!(matches!(outcome.reply, Some(Reply::Icmp { code: 4, .. })));
+        }
+    }
+
+    #[tokio::test]
+    async fn other_techniques_classify_every_port_state() {
+        use PortStatus::*;
+
+        let prober = prober(SimulatedNetwork::new(LOCAL).host(TARGET, host()), options());
+        let cases = [
+            (ScanType::Ack, [Unfiltered, Unfiltered, Filtered, Filtered]),
+            (ScanType::Fin, [OpenFiltered, Closed, OpenFiltered, Filtered]),
+            (ScanType::Xmas, [OpenFiltered, Closed, OpenFiltered, Filtered]),
+            (ScanType::Null, [OpenFiltered, Closed, OpenFiltered, Filtered]),
+            (ScanType::Window, [Open, Closed, Filtered, Filtered]),
+            (ScanType::TlsEcho, [Open, Closed, Filtered, Filtered]),
+        ];
+        for (scan_type, statuses) in cases {
+            for (port, status) in [22, 23, 24, 25].into_iter().zip(statuses) {
+                let outcome = prober.probe(TARGET, port, scan_type).await.unwrap().unwrap();
+                assert_eq!(outcome.status, status, "{:?} probe of port {}", scan_type, port);
+            }
+        }
+
+        let mut udp = Vec::new();
+        for port in [53, 54, 55, 56] {
+            udp.push(prober.probe(TARGET, port, ScanType::Udp).await.unwrap().unwrap().status);
+        }
+        assert_eq!(udp, [Open, Closed, OpenFiltered, Filtered]);
+        assert!(prober.probe(TARGET, 22, ScanType::Ssl).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn silent_hosts_are_retried_then_filtered() {
+        let prober = prober(SimulatedNetwork::new(LOCAL).host(TARGET, SimulatedHost::down()), options());
+        for scan_type in SYN_STYLE {
+            let outcome = prober.probe(TARGET, 22, scan_type).await.unwrap().unwrap();
+            assert_eq!((outcome.status, outcome.reply, outcome.attempts), (PortStatus::Filtered, None, 2));
+        }
+    }
+
+    #[tokio::test]
+    async fn every_attempt_passes_the_scan_control() {
+        let control = Arc::new(crate::control::ScanControl::new(0));
+        let network = Arc::new(SimulatedNetwork::new(LOCAL).host(TARGET, SimulatedHost::down()));
+        let prober = Prober::new(network.clone(), options(), Egress::default().with_control(control.clone()), None);
+
+        let outcome = prober.probe(TARGET, 22, ScanType::Syn).await.unwrap().unwrap();
+        assert_eq!(outcome.attempts, 2);
+        let counters = control.counters();
+        assert_eq!((counters.probes, counters.retransmits), (2, 1));
+
+        // Nothing leaves while paused
+        control.pause();
+        let sent = network.sent().len();
+        let paused = tokio::spawn(async move {
+            let outcome = prober.probe(TARGET, 23, ScanType::Syn).await;
+            (prober, outcome)
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(network.sent().len(), sent);
+        control.resume();
+        let (prober, outcome) = paused.await.unwrap();
+        assert!(outcome.unwrap().is_some());
+        assert!(network.sent().len() > sent);
+
+        // or once the host is skipped
+        control.skip_host(&TARGET.to_string());
+        let sent = network.sent().len();
+        let error = prober.probe(TARGET, 24, ScanType::Syn).await.unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::Interrupted);
+        assert_eq!(network.sent().len(), sent);
+    }
+
+    #[tokio::test]
+    async fn lost_packets_are_retransmitted() {
+        for scan_type in SYN_STYLE {
+            let run = || async {
+                // Two fixed-size fragments, so every run sends the same packets
+                let fragmentation = FragmentConfig { two_frags: true, ..options().fragmentation };
+                let options = ProbeOptions { retries: 8, fragmentation, ..options() };
+                let prober = prober(SimulatedNetwork::new(LOCAL).host(TARGET, host()).loss(0.25, 7), options);
+                let mut outcomes = Vec::new();
+                for _ in 0..10 {
+                    let outcome = prober.probe(TARGET, 22, scan_type).await.unwrap().unwrap();
+                    outcomes.push((outcome.status, outcome.attempts));
+                }
+                outcomes
+            };
+            let first = run().await;
+            assert!(first.iter().all(|(status, _)| *status == PortStatus::Open), "{:?}: {:?}", scan_type, first);
+            assert!(first.iter().any(|(_, attempts)| *attempts > 1), "{:?}: {:?}", scan_type, first);
+            // The loss pattern is seeded, so a rerun loses the same packets
+            assert_eq!(first, run().await);
+        }
+    }
+
+    #[tokio::test]
+    async fn fragmented_replies_are_reassembled() {
+        for scan_type in SYN_STYLE {
+            let network = Arc::new(SimulatedNetwork::new(LOCAL).host(TARGET, host()).fragment_replies(8));
+            let prober = Prober::new(network.clone(), options(), Egress::default(), None);
+            assert_eq!(prober.probe(TARGET, 22, scan_type).await.unwrap().unwrap().status, PortStatus::Open);
+            assert_eq!(prober.probe(TARGET, 23, scan_type).await.unwrap().unwrap().status, PortStatus::Closed);
+            let outcome = prober.probe(TARGET, 25, scan_type).await.unwrap().unwrap();
+            assert!(matches!(outcome.reply, Some(Reply::Icmp { code: 13, .. })), "{:?}", scan_type);
+        }
+    }
+
+    #[tokio::test]
+    async fn fragmented_probes_are_reassembled_by_the_host() {
+        let network = Arc::new(SimulatedNetwork::new(LOCAL).host(TARGET, host()));
+        let prober = Prober::new(network.clone(), options(), Egress::default(), None);
+        assert_eq!(prober.probe(TARGET, 22, ScanType::Frag).await.unwrap().unwrap().status, PortStatus::Open);
+
+        let sent = network.sent();
+        assert!(sent.iter().filter(|packet| packet.is_fragment()).count() >= 2);
+        // The half-open connection is torn down afterwards
+        let last = TcpSegment::parse(&sent.last().unwrap().payload).unwrap();
+        assert_eq!(last.flags, TCP_RST);
+    }
+
+    #[tokio::test]
+    async fn concurrent_probes_get_their_own_replies() {
+        let network = SimulatedNetwork::new(LOCAL).host(TARGET, host()).fragment_replies(8);
+        let prober = prober(network, options());
+        let probes = SYN_STYLE.iter().flat_map(|scan_type| [22, 23, 24, 25].map(|port| (*scan_type, port)));
+        let outcomes = futures::future::join_all(
+            probes.clone().map(|(scan_type, port)| prober.probe(TARGET, port, scan_type))
+        ).await;
+
+        for ((scan_type, port), outcome) in probes.zip(outcomes) {
+            let expected = match port {
+                22 => PortStatus::Open,
+                23 => PortStatus::Closed,
+                _ => PortStatus::Filtered,
+            };
+            assert_eq!(outcome.unwrap().unwrap().status, expected, "{:?} probe of port {}", scan_type, port);
+        }
+    }
+
+    #[tokio::test]
+    async fn out_of_scope_targets_are_never_sent_to() {
+        let config = crate::scope::ScopeConfig { include: vec!["10.9.0.0/16".to_string()], ..Default::default() };
+        let egress = Egress::new(Some(Arc::new(crate::scope::Scope::new(config, None).unwrap())), None);
+        let network = Arc::new(SimulatedNetwork::new(LOCAL).host(TARGET, host()));
+        let prober = Prober::new(network.clone(), options(), egress, None);
+
+        let error = prober.probe(TARGET, 22, ScanType::Syn).await.unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::PermissionDenied);
+        assert!(network.sent().is_empty());
+    }
+
+    #[tokio::test]
+    async fn scanner_sends_raw_techniques_through_the_transport() {
+        let target = Ipv4Addr::LOCALHOST;
+        let network = Arc::new(SimulatedNetwork::new(target).host(target, SimulatedHost::new().tcp(24, PortBehavior::Drop)));
+        let mut scanner = crate::config::ScannerBuilder::new(&target.to_string())
+            .ports(vec![23, 24])
+            .scan_types(vec![ScanType::Syn, ScanType::Ack])
+            .timeouts(0.05, 0.05, 0.05)
+            .packet_transport(network.clone())
+            .build()
+            .await
+            .unwrap();
+        let results = scanner.run().await.unwrap();
+
+        let closed = &results.results[&23];
+        assert_eq!(closed.tcp_states[&ScanType::Syn], PortStatus::Closed);
+        assert_eq!(closed.tcp_states[&ScanType::Ack], PortStatus::Unfiltered);
+        assert_eq!(closed.probe_observations.len(), 2);
+        let dropped = &results.results[&24];
+        assert_eq!(dropped.tcp_states[&ScanType::Syn], PortStatus::Filtered);
+        assert_eq!(dropped.tcp_states[&ScanType::Ack], PortStatus::Filtered);
+        assert!(network.sent().len() >= 4);
+    }
+}
@@ -0,0 +1,700 @@
+//! SNMP v1/v2c probing
+//!
+//! Runs on UDP ports the scan found open (or open|filtered). A GetRequest for
+//! sysDescr is sent once per community string and protocol version, all from
+//! the same socket, and the agent's answers tell us which communities it
+//! accepts; a wrong community is normally dropped silently, so trying them in
+//! parallel costs one timeout instead of one per guess. With a working
+//! community we read the system group and walk the interface table (and,
+//! optionally, the running process list) with GetNext requests.
+//!
+//! Only the handful of value types an agent returns for these objects are
+//! understood; the BER primitives live in `ber`.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::audit::AuditedUdpSocket;
+use crate::ber::{
+    decode_integer, decode_oid, decode_unsigned, encode_integer, encode_oid, read_tlv, tlv, TAG_INTEGER, TAG_NULL,
+    TAG_OCTET_STRING, TAG_OID, TAG_SEQUENCE,
+};
+use crate::models::{SnmpInfo, SnmpInterface};
+use crate::scope::Egress;
+use crate::utils;
+
+/// Community strings tried when none are configured
+const DEFAULT_COMMUNITIES: &[&str] = &[
+    "public", "private", "community", "manager", "admin", "snmp", "cisco", "default", "monitor", "secret",
+];
+
+const SYS_DESCR: &str = "1.3.6.1.2.1.1.1.0";
+const SYS_CONTACT: &str = "1.3.6.1.2.1.1.4.0";
+const SYS_NAME: &str = "1.3.6.1.2.1.1.5.0";
+const SYS_LOCATION: &str = "1.3.6.1.2.1.1.6.0";
+const IF_DESCR: &str = "1.3.6.1.2.1.2.2.1.2";
+const IF_PHYS_ADDRESS: &str = "1.3.6.1.2.1.2.2.1.6";
+const IF_OPER_STATUS: &str = "1.3.6.1.2.1.2.2.1.8";
+const HR_SW_RUN_NAME: &str = "1.3.6.1.2.1.25.4.2.1.2";
+
+const TAG_IP_ADDRESS: u8 = 0x40;
+const TAG_COUNTER32: u8 = 0x41;
+const TAG_GAUGE32: u8 = 0x42;
+const TAG_TIMETICKS: u8 = 0x43;
+const TAG_COUNTER64: u8 = 0x46;
+const TAG_NO_SUCH_OBJECT: u8 = 0x80;
+const TAG_NO_SUCH_INSTANCE: u8 = 0x81;
+const TAG_END_OF_MIB_VIEW: u8 = 0x82;
+const PDU_GET: u8 = 0xa0;
+const PDU_GET_NEXT: u8 = 0xa1;
+const PDU_RESPONSE: u8 = 0xa2;
+
+/// Largest datagram we read
+const MAX_DATAGRAM: usize = 65_507;
+
+/// Settings for the SNMP stage
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SnmpConfig {
+    /// Community strings to try
+    pub communities: Vec<String>,
+    /// UDP ports treated as SNMP agents
+    pub ports: Vec<u16>,
+    /// Also walk hrSWRunName (running processes)
+    pub walk_processes: bool,
+    /// Maximum rows read from each table
+    pub max_rows: usize,
+    /// Extra attempts when a request gets no answer
+    pub retries: u32,
+}
+
+impl Default for SnmpConfig {
+    fn default() -> Self {
+        Self {
+            communities: DEFAULT_COMMUNITIES.iter().map(|c| c.to_string()).collect(),
+            ports: vec![161],
+            walk_processes: false,
+            max_rows: 256,
+            retries: 1,
+        }
+    }
+}
+
+/// Load community strings from a file (one per line, `#` comments allowed)
+pub fn load_communities(path: &Path) -> Result<Vec<String>, anyhow::Error> {
+    crate::http_discovery::load_wordlist(path)
+}
+
+/// SNMP protocol version
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SnmpVersion {
+    V1,
+    V2c,
+}
+
+impl SnmpVersion {
+    fn wire(self) -> i64 {
+        match self {
+            SnmpVersion::V1 => 0,
+            SnmpVersion::V2c => 1,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            SnmpVersion::V1 => "v1",
+            SnmpVersion::V2c => "v2c",
+        }
+    }
+}
+
+/// A decoded variable-binding value
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SnmpValue {
+    Integer(i64),
+    OctetString(Vec<u8>),
+    Oid(String),
+    IpAddress([u8; 4]),
+    Counter(u64),
+    TimeTicks(u64),
+    Null,
+    NoSuchObject,
+    NoSuchInstance,
+    EndOfMibView,
+    Other(u8, Vec<u8>),
+}
+
+impl SnmpValue {
+    /// Whether the agent actually returned data for the object
+    pub fn is_value(&self) -> bool {
+        !matches!(self, SnmpValue::Null | SnmpValue::NoSuchObject | SnmpValue::NoSuchInstance | SnmpValue::EndOfMibView)
+    }
+
+    /// Render the value as text (octet strings lossily as UTF-8)
+    pub fn as_text(&self) -> String {
+        match self {
+            SnmpValue::Integer(v) => v.to_string(),
+            SnmpValue::OctetString(bytes) => String::from_utf8_lossy(bytes).trim_end_matches('\0').trim().to_string(),
+            SnmpValue::Oid(oid) => oid.clone(),
+            SnmpValue::IpAddress(ip) => format!("{}.{}.{}.{}", ip[0], ip[1], ip[2], ip[3]),
+            SnmpValue::Counter(v) | SnmpValue::TimeTicks(v) => v.to_string(),
+            SnmpValue::Other(tag, bytes) => format!("[0x{:02x}] {}", tag, bytes.iter().map(|b| format!("{:02x}", b)).collect::<String>()),
+            _ => String::new(),
+        }
+    }
+}
+
+/// A decoded response PDU
+#[derive(Debug, Clone)]
+pub struct SnmpResponse {
+    pub version: i64,
+    pub community: String,
+    pub request_id: i64,
+    pub error_status: i64,
+    pub varbinds: Vec<(String, SnmpValue)>,
+}
+
+/// Runs the SNMP stage against one agent
+pub struct SnmpProbe {
+    config: Arc<SnmpConfig>,
+    timeout: Duration,
+    egress: Egress,
+    logger: Option<Arc<utils::EnhancedLogger>>,
+}
+
+impl SnmpProbe {
+    /// Create a probe with the given per-request timeout
+    pub fn new(config: Arc<SnmpConfig>, timeout: Duration, egress: Egress, logger: Option<Arc<utils::EnhancedLogger>>) -> Self {
+        Self { config, timeout, egress, logger }
+    }
+
+    fn log(&self, level: &str, message: &str) {
+        if let Some(logger) = &self.logger {
+            logger.log(level, message);
+        }
+    }
+
+    /// Find working communities and read the standard objects
+    pub async fn run(&self, target: &str, port: u16) -> Option<SnmpInfo> {
+        match self.probe(target, port).await {
+            Ok(Some(info)) => {
+                self.log("INFO", &format!(
+                    "SNMP {} on {}:{} answers to community '{}'", info.version, target, port, info.community
+                ));
+                Some(info)
+            }
+            Ok(None) => None,
+            Err(e) => {
+                self.log("DEBUG", &format!("SNMP probe on {}:{} failed: {}", target, port, e));
+                None
+            }
+        }
+    }
+
+    async fn probe(&self, target: &str, port: u16) -> Result<Option<SnmpInfo>, anyhow::Error> {
+        let socket = self.egress.connect_udp(target, port).await?;
+
+        let accepted = self.find_communities(&socket).await?;
+        let (version, community) = match accepted.first() {
+            Some(first) => first.clone(),
+            None => return Ok(None),
+        };
+
+        let mut info = SnmpInfo {
+            version: version.name().to_string(),
+            community: community.clone(),
+            communities: accepted.iter()
+                .filter(|(v, _)| *v == version)
+                .map(|(_, c)| c.clone())
+                .collect(),
+            ..Default::default()
+        };
+
+        let session = Session { socket: &socket, version, community: &community, timeout: self.timeout, retries: self.config.retries };
+
+        let system = session.request(PDU_GET, &[SYS_DESCR, SYS_CONTACT, SYS_NAME, SYS_LOCATION]).await?;
+        for (oid, value) in system.varbinds.iter().filter(|(_, v)| v.is_value()) {
+            let text = Some(value.as_text()).filter(|t| !t.is_empty());
+            match oid.as_str() {
+                SYS_DESCR => info.sys_descr = text,
+                SYS_CONTACT => info.sys_contact = text,
+                SYS_NAME => info.sys_name = text,
+                SYS_LOCATION => info.sys_location = text,
+                _ => {}
+            }
+        }
+
+        let names = session.walk(IF_DESCR, self.config.max_rows).await.unwrap_or_default();
+        let macs = session.walk(IF_PHYS_ADDRESS, self.config.max_rows).await.unwrap_or_default();
+        let states = session.walk(IF_OPER_STATUS, self.config.max_rows).await.unwrap_or_default();
+        let macs: BTreeMap<u32, &SnmpValue> = macs.iter().filter_map(|(oid, v)| Some((last_arc(oid)?, v))).collect();
+        let states: BTreeMap<u32, &SnmpValue> = states.iter().filter_map(|(oid, v)| Some((last_arc(oid)?, v))).collect();
+        for (oid, value) in &names {
+            let index = match last_arc(oid) {
+                Some(index) => index,
+                None => continue,
+            };
+            info.interfaces.push(SnmpInterface {
+                index,
+                name: value.as_text(),
+                mac: match macs.get(&index) {
+                    Some(SnmpValue::OctetString(bytes)) if bytes.len() == 6 => Some(format_mac(bytes)),
+                    _ => None,
+                },
+                status: match states.get(&index) {
+                    Some(SnmpValue::Integer(state)) => Some(oper_status(*state).to_string()),
+                    _ => None,
+                },
+            });
+        }
+
+        if self.config.walk_processes {
+            match session.walk(HR_SW_RUN_NAME, self.config.max_rows).await {
+                Ok(rows) => info.processes = rows.iter().map(|(_, v)| v.as_text()).filter(|p| !p.is_empty()).collect(),
+                Err(e) => self.log("DEBUG", &format!("hrSWRunName walk on {}:{} failed: {}", target, port, e)),
+            }
+        }
+
+        Ok(Some(info))
+    }
+
+    /// Send sysDescr requests for every community and version at once
+    ///
+    /// Returns the accepted `(version, community)` pairs, v2c first.
+    async fn find_communities(&self, socket: &AuditedUdpSocket) -> Result<Vec<(SnmpVersion, String)>, anyhow::Error> {
+        let mut candidates = Vec::new();
+        for version in [SnmpVersion::V2c, SnmpVersion::V1] {
+            for community in &self.config.communities {
+                candidates.push((version, community.clone()));
+            }
+        }
+
+        let mut accepted = Vec::new();
+        for _ in 0..=self.config.retries {
+            for (id, (version, community)) in candidates.iter().enumerate() {
+                if accepted.contains(&id) {
+                    continue;
+                }
+                let request = encode_request(*version, community, PDU_GET, id as i64, &[SYS_DESCR])?;
+                socket.send(&request).await?;
+            }
+
+            let deadline = tokio::time::Instant::now() + self.timeout;
+            let mut buffer = vec![0u8; MAX_DATAGRAM];
+            while let Ok(Ok(n)) = tokio::time::timeout_at(deadline, socket.recv(&mut buffer)).await {
+                if let Some(response) = decode_response(&buffer[..n]) {
+                    let id = response.request_id as usize;
+                    if id < candidates.len() && candidates[id].1 == response.community && !accepted.contains(&id) {
+                        accepted.push(id);
+                    }
+                }
+            }
+
+            if !accepted.is_empty() {
+                break;
+            }
+        }
+
+        accepted.sort_unstable();
+        Ok(accepted.into_iter().map(|id| candidates[id].clone()).collect())
+    }
+}
+
+/// Request/response exchange with a known community
+struct Session<'a> {
+    socket: &'a AuditedUdpSocket,
+    version: SnmpVersion,
+    community: &'a str,
+    timeout: Duration,
+    retries: u32,
+}
+
+impl Session<'_> {
+    async fn request(&self, pdu: u8, oids: &[&str]) -> Result<SnmpResponse, anyhow::Error> {
+        let request_id = rand::random::<u16>() as i64;
+        let request = encode_request(self.version, self.community, pdu, request_id, oids)?;
+        let mut buffer = vec![0u8; MAX_DATAGRAM];
+
+        for _ in 0..=self.retries {
+            self.socket.send(&request).await?;
+            let deadline = tokio::time::Instant::now() + self.timeout;
+            while let Ok(Ok(n)) = tokio::time::timeout_at(deadline, self.socket.recv(&mut buffer)).await {
+                match decode_response(&buffer[..n]) {
+                    Some(response) if response.request_id == request_id => return Ok(response),
+                    _ => continue, // late answer to an earlier request
+                }
+            }
+        }
+        Err(anyhow::anyhow!("no response"))
+    }
+
+    /// GetNext walk of a subtree
+    async fn walk(&self, base: &str, max_rows: usize) -> Result<Vec<(String, SnmpValue)>, anyhow::Error> {
+        let prefix = format!("{}.", base);
+        let mut rows = Vec::new();
+        let mut current = base.to_string();
+
+        while rows.len() < max_rows {
+            let response = self.request(PDU_GET_NEXT, &[&current]).await?;
+            if response.error_status != 0 {
+                break; // v1 agents answer noSuchName at the end of the MIB
+            }
+            let (oid, value) = match response.varbinds.into_iter().next() {
+                Some(varbind) => varbind,
+                None => break,
+            };
+            if !oid.starts_with(&prefix) || !value.is_value() || oid == current {
+                break;
+            }
+            current = oid.clone();
+            rows.push((oid, value));
+        }
+        Ok(rows)
+    }
+}
+
+/// Encode a Get/GetNext request with NULL values
+pub fn encode_request(
+    version: SnmpVersion,
+    community: &str,
+    pdu: u8,
+    request_id: i64,
+    oids: &[&str],
+) -> Result<Vec<u8>, anyhow::Error> {
+    let mut varbinds = Vec::new();
+    for oid in oids {
+        let varbind = [tlv(TAG_OID, &encode_oid(oid)?), tlv(TAG_NULL, &[])].concat();
+        varbinds.extend(tlv(TAG_SEQUENCE, &varbind));
+    }
+    let pdu_body = [
+        tlv(TAG_INTEGER, &encode_integer(request_id)),
+        tlv(TAG_INTEGER, &encode_integer(0)),
+        tlv(TAG_INTEGER, &encode_integer(0)),
+        tlv(TAG_SEQUENCE, &varbinds),
+    ]
+    .concat();
+    let message = [
+        tlv(TAG_INTEGER, &encode_integer(version.wire())),
+        tlv(TAG_OCTET_STRING, community.as_bytes()),
+        tlv(pdu, &pdu_body),
+    ]
+    .concat();
+    Ok(tlv(TAG_SEQUENCE, &message))
+}
+
+/// Decode a GetResponse message
+pub fn decode_response(data: &[u8]) -> Option<SnmpResponse> {
+    let (tag, message, _) = read_tlv(data)?;
+    if tag != TAG_SEQUENCE {
+        return None;
+    }
+    let (tag, version, rest) = read_tlv(message)?;
+    if tag != TAG_INTEGER {
+        return None;
+    }
+    let (tag, community, rest) = read_tlv(rest)?;
+    if tag != TAG_OCTET_STRING {
+        return None;
+    }
+    let (tag, pdu, _) = read_tlv(rest)?;
+    if tag != PDU_RESPONSE {
+        return None;
+    }
+
+    let (_, request_id, rest) = read_tlv(pdu)?;
+    let (_, error_status, rest) = read_tlv(rest)?;
+    let (_, _error_index, rest) = read_tlv(rest)?;
+    let (tag, mut list, _) = read_tlv(rest)?;
+    if tag != TAG_SEQUENCE {
+        return None;
+    }
+
+    let mut varbinds = Vec::new();
+    while !list.is_empty() {
+        let (_, varbind, rest) = read_tlv(list)?;
+        list = rest;
+        let (tag, oid, value) = read_tlv(varbind)?;
+        if tag != TAG_OID {
+            return None;
+        }
+        let (tag, content, _) = read_tlv(value)?;
+        varbinds.push((decode_oid(oid)?, decode_value(tag, content)));
+    }
+
+    Some(SnmpResponse {
+        version: decode_integer(version),
+        community: String::from_utf8_lossy(community).into_owned(),
+        request_id: decode_integer(request_id),
+        error_status: decode_integer(error_status),
+        varbinds,
+    })
+}
+
+fn decode_value(tag: u8, content: &[u8]) -> SnmpValue {
+    match tag {
+        TAG_INTEGER => SnmpValue::Integer(decode_integer(content)),
+        TAG_OCTET_STRING => SnmpValue::OctetString(content.to_vec()),
+        TAG_NULL => SnmpValue::Null,
+        TAG_OID => decode_oid(content).map(SnmpValue::Oid).unwrap_or(SnmpValue::Other(tag, content.to_vec())),
+        TAG_IP_ADDRESS if content.len() == 4 => SnmpValue::IpAddress([content[0], content[1], content[2], content[3]]),
+        TAG_COUNTER32 | TAG_GAUGE32 | TAG_COUNTER64 => SnmpValue::Counter(decode_unsigned(content)),
+        TAG_TIMETICKS => SnmpValue::TimeTicks(decode_unsigned(content)),
+        TAG_NO_SUCH_OBJECT => SnmpValue::NoSuchObject,
+        TAG_NO_SUCH_INSTANCE => SnmpValue::NoSuchInstance,
+        TAG_END_OF_MIB_VIEW => SnmpValue::EndOfMibView,
+        _ => SnmpValue::Other(tag, content.to_vec()),
+    }
+}
+
+fn last_arc(oid: &str) -> Option<u32> {
+    oid.rsplit('.').next()?.parse().ok()
+}
+
+fn format_mac(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect::<Vec<_>>().join(":")
+}
+
+/// ifOperStatus names (RFC 2863)
+fn oper_status(state: i64) -> &'static str {
+    match state {
+        1 => "up",
+        2 => "down",
+        3 => "testing",
+        5 => "dormant",
+        6 => "notPresent",
+        7 => "lowerLayerDown",
+        _ => "unknown",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::UdpSocket;
+
+    fn varbind(oid: &str, tag: u8, content: &[u8]) -> Vec<u8> {
+        tlv(TAG_SEQUENCE, &[tlv(TAG_OID, &encode_oid(oid).unwrap()), tlv(tag, content)].concat())
+    }
+
+    fn response(version: i64, community: &str, request_id: i64, error_status: i64, varbinds: &[Vec<u8>]) -> Vec<u8> {
+        let pdu = [
+            tlv(TAG_INTEGER, &encode_integer(request_id)),
+            tlv(TAG_INTEGER, &encode_integer(error_status)),
+            tlv(TAG_INTEGER, &encode_integer(0)),
+            tlv(TAG_SEQUENCE, &varbinds.concat()),
+        ]
+        .concat();
+        let message = [
+            tlv(TAG_INTEGER, &encode_integer(version)),
+            tlv(TAG_OCTET_STRING, community.as_bytes()),
+            tlv(PDU_RESPONSE, &pdu),
+        ]
+        .concat();
+        tlv(TAG_SEQUENCE, &message)
+    }
+
+    /// Split a request into (version, community, pdu, request id, oids)
+    fn parse_request(data: &[u8]) -> (i64, String, u8, i64, Vec<String>) {
+        let (_, message, _) = read_tlv(data).unwrap();
+        let (_, version, rest) = read_tlv(message).unwrap();
+        let (_, community, rest) = read_tlv(rest).unwrap();
+        let (pdu, body, _) = read_tlv(rest).unwrap();
+        let (_, request_id, rest) = read_tlv(body).unwrap();
+        let (_, _, rest) = read_tlv(rest).unwrap();
+        let (_, _, rest) = read_tlv(rest).unwrap();
+        let (_, mut list, _) = read_tlv(rest).unwrap();
+        let mut oids = Vec::new();
+        while !list.is_empty() {
+            let (_, varbind, rest) = read_tlv(list).unwrap();
+            list = rest;
+            let (_, oid, value) = read_tlv(varbind).unwrap();
+            assert_eq!(read_tlv(value).unwrap().0, TAG_NULL, "requests carry NULL values");
+            oids.push(decode_oid(oid).unwrap());
+        }
+        (
+            decode_integer(version),
+            String::from_utf8(community.to_vec()).unwrap(),
+            pdu,
+            decode_integer(request_id),
+            oids,
+        )
+    }
+
+    fn arcs(oid: &str) -> Vec<u32> {
+        oid.split('.').map(|a| a.parse().unwrap()).collect()
+    }
+
+    /// Agent that only answers the given community, serving `mib` (in MIB order)
+    async fn agent(community: &'static str, mib: Vec<(&'static str, u8, Vec<u8>)>) -> u16 {
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let port = socket.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let mut buffer = vec![0u8; MAX_DATAGRAM];
+            loop {
+                let (n, peer) = socket.recv_from(&mut buffer).await.unwrap();
+                let (version, received, pdu, request_id, oids) = parse_request(&buffer[..n]);
+                if received != community {
+                    continue;
+                }
+                let varbinds: Vec<Vec<u8>> = oids.iter().map(|oid| {
+                    let entry = if pdu == PDU_GET_NEXT {
+                        mib.iter().find(|(o, _, _)| arcs(o) > arcs(oid))
+                    } else {
+                        mib.iter().find(|(o, _, _)| o == oid)
+                    };
+                    match entry {
+                        Some((o, tag, content)) => varbind(o, *tag, content),
+                        None if pdu == PDU_GET_NEXT => varbind(oid, TAG_END_OF_MIB_VIEW, &[]),
+                        None => varbind(oid, TAG_NO_SUCH_OBJECT, &[]),
+                    }
+                }).collect();
+                let reply = response(version, &received, request_id, 0, &varbinds);
+                socket.send_to(&reply, peer).await.unwrap();
+            }
+        });
+        port
+    }
+
+    #[test]
+    fn requests_are_well_formed() {
+        let request = encode_request(SnmpVersion::V1, "public", PDU_GET_NEXT, 513, &[SYS_DESCR, IF_DESCR]).unwrap();
+        let (version, community, pdu, request_id, oids) = parse_request(&request);
+        assert_eq!(version, 0);
+        assert_eq!(community, "public");
+        assert_eq!(pdu, PDU_GET_NEXT);
+        assert_eq!(request_id, 513);
+        assert_eq!(oids, [SYS_DESCR, IF_DESCR]);
+
+        let (version, ..) = parse_request(&encode_request(SnmpVersion::V2c, "x", PDU_GET, 1, &[SYS_NAME]).unwrap());
+        assert_eq!(version, 1);
+        assert!(encode_request(SnmpVersion::V2c, "public", PDU_GET, 1, &["sysDescr"]).is_err());
+    }
+
+    #[test]
+    fn responses_are_decoded() {
+        let data = response(1, "private", 42, 0, &[
+            varbind(SYS_DESCR, TAG_OCTET_STRING, b"Linux gw 5.15\0\0"),
+            varbind("1.3.6.1.2.1.1.2.0", TAG_OID, &encode_oid("1.3.6.1.4.1.8072.3.2.10").unwrap()),
+            varbind("1.3.6.1.2.1.1.3.0", TAG_TIMETICKS, &[0x00, 0xd4, 0x31]),
+            varbind("1.3.6.1.2.1.2.2.1.10.1", TAG_COUNTER32, &[0x00, 0xff, 0xff, 0xff, 0xff]),
+            varbind("1.3.6.1.2.1.4.20.1.1.10.0.0.1", TAG_IP_ADDRESS, &[10, 0, 0, 1]),
+            varbind("1.3.6.1.2.1.2.2.1.8.1", TAG_INTEGER, &[1]),
+            varbind(SYS_CONTACT, TAG_NO_SUCH_OBJECT, &[]),
+            varbind(SYS_NAME, TAG_NO_SUCH_INSTANCE, &[]),
+            varbind(SYS_LOCATION, TAG_END_OF_MIB_VIEW, &[]),
+            varbind("1.3.6.1.2.1.1.9.0", 0x44, &[0xde, 0xad]),
+        ]);
+        let decoded = decode_response(&data).unwrap();
+        assert_eq!(decoded.version, 1);
+        assert_eq!(decoded.community, "private");
+        assert_eq!(decoded.request_id, 42);
+        assert_eq!(decoded.error_status, 0);
+
+        let values: Vec<(&str, String, bool)> = decoded.varbinds.iter()
+            .map(|(oid, value)| (oid.as_str(), value.as_text(), value.is_value()))
+            .collect();
+        assert_eq!(values, [
+            (SYS_DESCR, "Linux gw 5.15".to_string(), true),
+            ("1.3.6.1.2.1.1.2.0", "1.3.6.1.4.1.8072.3.2.10".to_string(), true),
+            ("1.3.6.1.2.1.1.3.0", "54321".to_string(), true),
+            ("1.3.6.1.2.1.2.2.1.10.1", "4294967295".to_string(), true),
+            ("1.3.6.1.2.1.4.20.1.1.10.0.0.1", "10.0.0.1".to_string(), true),
+            ("1.3.6.1.2.1.2.2.1.8.1", "1".to_string(), true),
+            (SYS_CONTACT, String::new(), false),
+            (SYS_NAME, String::new(), false),
+            (SYS_LOCATION, String::new(), false),
+            ("1.3.6.1.2.1.1.9.0", "[0x44] dead".to_string(), true),
+        ]);
+        assert_eq!(decoded.varbinds[3].1, SnmpValue::Counter(u32::MAX as u64));
+        assert_eq!(decoded.varbinds[6].1, SnmpValue::NoSuchObject);
+    }
+
+    #[test]
+    fn only_get_responses_are_decoded() {
+        let request = encode_request(SnmpVersion::V2c, "public", PDU_GET, 1, &[SYS_DESCR]).unwrap();
+        assert!(decode_response(&request).is_none(), "a request is not a response");
+
+        let data = response(1, "public", 1, 0, &[varbind(SYS_DESCR, TAG_NULL, &[])]);
+        assert!(decode_response(&data[..data.len() - 1]).is_none(), "truncated");
+        assert!(decode_response(&[TAG_OCTET_STRING, 0]).is_none());
+        assert!(decode_response(b"").is_none());
+
+        let error = decode_response(&response(0, "public", 9, 2, &[])).unwrap();
+        assert_eq!(error.error_status, 2);
+        assert!(error.varbinds.is_empty());
+    }
+
+    #[test]
+    fn interface_helpers() {
+        assert_eq!(last_arc("1.3.6.1.2.1.2.2.1.2.12"), Some(12));
+        assert_eq!(last_arc("1.3.6.1.2.1.2.2.1.2.x"), None);
+        assert_eq!(format_mac(&[0x00, 0x1b, 0x21, 0xaa, 0x0f, 0xff]), "00:1b:21:aa:0f:ff");
+        assert_eq!(oper_status(1), "up");
+        assert_eq!(oper_status(7), "lowerLayerDown");
+        assert_eq!(oper_status(4), "unknown");
+    }
+
+    #[test]
+    fn communities_load_from_a_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("communities.txt");
+        std::fs::write(&path, "# site defaults\npublic\n\n  ops-ro  \n").unwrap();
+        assert_eq!(load_communities(&path).unwrap(), ["public", "ops-ro"]);
+        assert!(load_communities(&dir.path().join("missing.txt")).is_err());
+    }
+
+    #[tokio::test]
+    async fn agent_is_read_with_the_accepted_community() {
+        let port = agent("ops-ro", vec![
+            (SYS_DESCR, TAG_OCTET_STRING, b"Cisco IOS Software, C2960".to_vec()),
+            (SYS_NAME, TAG_OCTET_STRING, b"sw-core-1".to_vec()),
+            (SYS_LOCATION, TAG_OCTET_STRING, b"".to_vec()),
+            ("1.3.6.1.2.1.2.2.1.2.1", TAG_OCTET_STRING, b"Gi0/1".to_vec()),
+            ("1.3.6.1.2.1.2.2.1.2.2", TAG_OCTET_STRING, b"Vlan1".to_vec()),
+            ("1.3.6.1.2.1.2.2.1.6.1", TAG_OCTET_STRING, vec![0x00, 0x1b, 0x21, 0xaa, 0x0f, 0x01]),
+            ("1.3.6.1.2.1.2.2.1.6.2", TAG_OCTET_STRING, vec![]),
+            ("1.3.6.1.2.1.2.2.1.8.1", TAG_INTEGER, vec![1]),
+            ("1.3.6.1.2.1.2.2.1.8.2", TAG_INTEGER, vec![2]),
+            ("1.3.6.1.2.1.25.4.2.1.2.1", TAG_OCTET_STRING, b"init".to_vec()),
+            ("1.3.6.1.2.1.25.4.2.1.2.7", TAG_OCTET_STRING, b"sshd".to_vec()),
+        ]).await;
+
+        let config = SnmpConfig {
+            communities: vec!["public".into(), "ops-ro".into()],
+            walk_processes: true,
+            retries: 0,
+            ..Default::default()
+        };
+        let probe = SnmpProbe::new(Arc::new(config), Duration::from_millis(300), Egress::default(), None);
+        let info = probe.run("127.0.0.1", port).await.unwrap();
+
+        assert_eq!(info.version, "v2c");
+        assert_eq!(info.community, "ops-ro");
+        assert_eq!(info.communities, ["ops-ro"]);
+        assert_eq!(info.sys_descr.as_deref(), Some("Cisco IOS Software, C2960"));
+        assert_eq!(info.sys_name.as_deref(), Some("sw-core-1"));
+        assert_eq!(info.sys_contact, None, "noSuchObject");
+        assert_eq!(info.sys_location, None, "empty strings are dropped");
+
+        let interfaces: Vec<(u32, &str, Option<&str>, Option<&str>)> = info.interfaces.iter()
+            .map(|i| (i.index, i.name.as_str(), i.mac.as_deref(), i.status.as_deref()))
+            .collect();
+        assert_eq!(interfaces, [
+            (1, "Gi0/1", Some("00:1b:21:aa:0f:01"), Some("up")),
+            (2, "Vlan1", None, Some("down")),
+        ]);
+        assert_eq!(info.processes, ["init", "sshd"]);
+    }
+
+    #[tokio::test]
+    async fn silent_agents_yield_nothing() {
+        let port = agent("not-in-the-list", vec![(SYS_DESCR, TAG_OCTET_STRING, b"hidden".to_vec())]).await;
+        let config = SnmpConfig { communities: vec!["public".into()], retries: 1, ..Default::default() };
+        let probe = SnmpProbe::new(Arc::new(config), Duration::from_millis(100), Egress::default(), None);
+        assert!(probe.run("127.0.0.1", port).await.is_none());
+    }
+}
@@ -1,3 +1,104 @@
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+use serde::Serialize;
+
+/// In-memory log buffer
+///
+/// Keeps the most recent entries only. With `encrypt` set, entries are sealed
+/// with a key that lives only in this process, so a memory dump or swap file
+/// doesn't give away what was scanned.
+pub struct MemoryLogBuffer {
+    entries: Mutex<std::collections::VecDeque<LogEntry>>,
+    max_entries: usize,
+    cipher: Option<aes_gcm::Aes256Gcm>,
+}
+
+/// One buffered log line
+struct LogEntry {
+    timestamp: chrono::DateTime<chrono::Utc>,
+    level: String,
+    message: Vec<u8>,
+    nonce: [u8; 12],
+}
+
+impl MemoryLogBuffer {
+    /// Create a buffer holding up to `max_entries` lines
+    pub fn new(max_entries: usize, encrypt: bool) -> Self {
+        use aes_gcm::{Aes256Gcm, KeyInit};
+        
+        Self {
+            entries: Mutex::new(std::collections::VecDeque::new()),
+            max_entries: max_entries.max(1),
+            cipher: encrypt.then(|| Aes256Gcm::new(&Aes256Gcm::generate_key(&mut aes_gcm::aead::OsRng))),
+        }
+    }
+    
+    /// Add a line, dropping the oldest one when the buffer is full
+    pub fn log(&self, level: &str, message: &str) {
+        use aes_gcm::aead::Aead;
+        use rand::Rng;
+        
+        let mut nonce = [0u8; 12];
+        let message = match &self.cipher {
+            Some(cipher) => {
+                rand::thread_rng().fill(&mut nonce);
+                match cipher.encrypt(&nonce.into(), message.as_bytes()) {
+                    Ok(sealed) => sealed,
+                    Err(_) => return,
+                }
+            }
+            None => message.as_bytes().to_vec(),
+        };
+        
+        let mut entries = self.entries.lock();
+        if entries.len() >= self.max_entries {
+            entries.pop_front();
+        }
+        entries.push_back(LogEntry {
+            timestamp: chrono::Utc::now(),
+            level: level.to_string(),
+            message,
+            nonce,
+        });
+    }
+    
+    /// All buffered lines as text, one per line
+    pub fn format_logs(&self, with_timestamps: bool) -> String {
+        use aes_gcm::aead::Aead;
+        
+        let entries = self.entries.lock();
+        let mut result = String::new();
+        for entry in entries.iter() {
+            let message = match &self.cipher {
+                Some(cipher) => match cipher.decrypt(&entry.nonce.into(), entry.message.as_slice()) {
+                    Ok(plain) => plain,
+                    Err(_) => continue,
+                },
+                None => entry.message.clone(),
+            };
+            if with_timestamps {
+                result.push_str(&format!("{} ", entry.timestamp.format("%Y-%m-%d %H:%M:%S%.3f")));
+            }
+            result.push_str(&format!("[{}] {}\n", entry.level, String::from_utf8_lossy(&message)));
+        }
+        result
+    }
+    
+    /// Number of buffered lines
+    pub fn len(&self) -> usize {
+        self.entries.lock().len()
+    }
+    
+    /// Whether nothing has been logged
+    pub fn is_empty(&self) -> bool {
+        self.entries.lock().is_empty()
+    }
+}
+
 /// Enhanced logging module for network responses
 pub struct EnhancedLogger {
     buffer: MemoryLogBuffer,
@@ -7,7 +108,7 @@ pub struct EnhancedLogger {
 }
 
 /// Structure to store packet-level logging information
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct PacketLog {
     pub timestamp: chrono::DateTime<chrono::Utc>,
     pub src_ip: String,
@@ -39,6 +140,7 @@ impl EnhancedLogger {
     }
     
     /// Log a network packet (only if packet capture is enabled)
+    #[allow(clippy::too_many_arguments)]
     pub fn log_packet(&self, 
         src_ip: &str, 
         dst_ip: &str, 
@@ -84,7 +186,7 @@ impl EnhancedLogger {
         packet_logs.push(packet_info);
         
         // Also add a standard log entry for important packets
-        if payload.len() > 0 && (protocol == "TCP" || protocol == "UDP") {
+        if !payload.is_empty() && (protocol == "TCP" || protocol == "UDP") {
             let port_info = match (src_port, dst_port) {
                 (Some(sp), Some(dp)) => format!("{}:{} -> {}:{}", src_ip, sp, dst_ip, dp),
                 _ => format!("{} -> {}", src_ip, dst_ip),
@@ -219,64 +321,43 @@ impl EnhancedLogger {
     }
 }
 
-/// Determine if the content might be a service banner
-pub fn is_likely_service_banner(data: &[u8]) -> bool {
-    if data.len() < 4 {
-        return false;
-    }
-    
-    // Check for common service banner patterns
-    if let Ok(str_data) = std::str::from_utf8(data) {
-        // Common banner prefixes
-        let common_patterns = [
-            "SSH-", "220 ", "HTTP/", "* OK ", "SMTP", "+OK ", 
-            "FTP", "POP3", "IMAP", "NNTP", "PROXY", "MySQL",
-            "220-", "250-", "RFB ", "AUTH", "LMTP", "ESMTP",
-            "VNC ", "EHLO", "HELO", "200 ", "100 ", "101 ", 
-            "302 ", "404 ", "500 ", "Connection: "
-        ];
-        
-        for pattern in &common_patterns {
-            if str_data.starts_with(pattern) {
-                return true;
-            }
-        }
-        
-        // Look for version numbers with common formats
-        if str_data.contains("version") || 
-           str_data.contains("Version") || 
-           str_data.contains(" v") ||
-           (str_data.contains("Server: ")) {
-            return true;
-        }
+/// Certificate verifier that accepts any server certificate
+///
+/// Scan targets routinely present self-signed or mismatched certificates, and we
+/// want to talk to them anyway. Never use this outside of active probing.
+struct AcceptAnyServerCert;
+
+impl rustls::client::ServerCertVerifier for AcceptAnyServerCert {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &rustls::Certificate,
+        _intermediates: &[rustls::Certificate],
+        _server_name: &rustls::ServerName,
+        _scts: &mut dyn Iterator<Item = &[u8]>,
+        _ocsp_response: &[u8],
+        _now: std::time::SystemTime,
+    ) -> Result<rustls::client::ServerCertVerified, rustls::Error> {
+        Ok(rustls::client::ServerCertVerified::assertion())
     }
-    
-    false
 }
 
-/// Extract likely version information from a service banner
-pub fn extract_version_info(data: &[u8]) -> Option<String> {
-    if let Ok(str_data) = std::str::from_utf8(data) {
-        // Try to extract version patterns like "version X.Y.Z" or "vX.Y.Z"
-        let re = regex::Regex::new(r"(?i)(?:version|v)[.\s]+([0-9]+(?:\.[0-9]+)+(?:-\w+)?)")
-            .ok()?;
-            
-        if let Some(captures) = re.captures(str_data) {
-            if let Some(version) = captures.get(1) {
-                return Some(version.as_str().to_string());
-            }
-        }
-        
-        // Try to extract "Server: xxxx" from HTTP headers
-        let re = regex::Regex::new(r"Server: ([^\r\n]+)")
-            .ok()?;
-            
-        if let Some(captures) = re.captures(str_data) {
-            if let Some(server) = captures.get(1) {
-                return Some(server.as_str().to_string());
-            }
-        }
+/// Build a TLS client configuration that skips certificate validation
+pub fn insecure_tls_config() -> rustls::ClientConfig {
+    rustls::ClientConfig::builder()
+        .with_safe_defaults()
+        .with_custom_certificate_verifier(Arc::new(AcceptAnyServerCert))
+        .with_no_client_auth()
+}
+
+/// First non-loopback IPv4 address of this machine
+///
+/// Found by "connecting" a UDP socket towards a public address, which picks
+/// the outgoing interface without sending anything.
+pub fn get_local_ipv4() -> Option<String> {
+    let socket = std::net::UdpSocket::bind("0.0.0.0:0").ok()?;
+    socket.connect("8.8.8.8:53").ok()?;
+    match socket.local_addr().ok()?.ip() {
+        std::net::IpAddr::V4(ip) if !ip.is_loopback() && !ip.is_unspecified() => Some(ip.to_string()),
+        _ => None,
     }
-    
-    None
-} 
\ No newline at end of file
+}